@@ -0,0 +1,170 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The programs of the `userspace` workspace that get packed into the initramfs, in `/bin`.
+const USER_PROGRAMS: &[&str] = &["hello", "init"];
+
+/// The directories of the initramfs that exist even if `initramfs/` has nothing in them.
+const DIRECTORIES: &[&str] = &["bin", "dev", "proc", "tmp"];
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_FILE: u32 = 0o100000;
+const MODE_SYMLINK: u32 = 0o120000;
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let userspace_dir = manifest_dir.join("userspace");
+    let initramfs_dir = manifest_dir.join("initramfs");
+    println!("cargo:rerun-if-changed={}", userspace_dir.display());
+    println!("cargo:rerun-if-changed={}", initramfs_dir.display());
+    // the libraries the user programs are linked with
+    for library in ["rost-lib", "utils"] {
+        println!(
+            "cargo:rerun-if-changed={}",
+            manifest_dir.join(library).display()
+        );
+    }
+
+    let binaries_dir = build_user_programs(&manifest_dir, &userspace_dir, &out_dir);
+
+    // the kernel includes the archive and unpacks it into the root file system at boot
+    let mut archive = CpioWriter::new();
+    for directory in DIRECTORIES {
+        archive.add(directory, MODE_DIRECTORY | 0o755, &[]);
+    }
+    for program in USER_PROGRAMS {
+        let binary = fs::read(binaries_dir.join(program)).unwrap();
+        archive.add(&format!("bin/{}", program), MODE_FILE | 0o755, &binary);
+    }
+    if initramfs_dir.exists() {
+        add_directory(&mut archive, &initramfs_dir, "");
+    }
+    fs::write(out_dir.join("initramfs.cpio"), archive.finish()).unwrap();
+}
+
+/// Adds the contents of the directory to the archive, below `prefix`.
+fn add_directory(archive: &mut CpioWriter, directory: &Path, prefix: &str) {
+    let mut entries: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .collect();
+    // sorted, so the archive is the same on every build
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = format!("{}{}", prefix, entry.file_name().to_str().unwrap());
+        let file_type = entry.file_type().unwrap();
+        if file_type.is_dir() {
+            archive.add(&name, MODE_DIRECTORY | 0o755, &[]);
+            add_directory(archive, &entry.path(), &format!("{}/", name));
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(entry.path()).unwrap();
+            archive.add(
+                &name,
+                MODE_SYMLINK | 0o777,
+                target.to_str().unwrap().as_bytes(),
+            );
+        } else {
+            archive.add(&name, MODE_FILE | 0o644, &fs::read(entry.path()).unwrap());
+        }
+    }
+}
+
+/// Writes a CPIO archive in the `newc` format, the format the kernel's initramfs is unpacked from.
+struct CpioWriter {
+    data: Vec<u8>,
+    next_inode: u32,
+}
+
+impl CpioWriter {
+    fn new() -> Self {
+        CpioWriter {
+            data: Vec::new(),
+            next_inode: 1,
+        }
+    }
+
+    fn add(&mut self, name: &str, mode: u32, contents: &[u8]) {
+        let links = if mode & MODE_TYPE_MASK == MODE_DIRECTORY {
+            2
+        } else {
+            1
+        };
+        // inode, mode, uid, gid, links, modification time, file size, device major and minor,
+        // represented device major and minor, name size including the null byte and checksum
+        let fields = [
+            self.next_inode,
+            mode,
+            0,
+            0,
+            links,
+            0,
+            contents.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+        self.next_inode += 1;
+        self.data.extend_from_slice(b"070701");
+        for field in fields {
+            self.data
+                .extend_from_slice(format!("{:08X}", field).as_bytes());
+        }
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.push(0);
+        self.pad();
+        self.data.extend_from_slice(contents);
+        self.pad();
+    }
+
+    /// Pads the archive to a multiple of 4 bytes, the name and the contents of an entry are aligned.
+    fn pad(&mut self) {
+        while self.data.len() % 4 != 0 {
+            self.data.push(0);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.add("TRAILER!!!", 0, &[]);
+        self.data
+    }
+}
+
+/// Builds the user programs for the kernel's target and returns the directory containing the binaries.
+fn build_user_programs(manifest_dir: &Path, userspace_dir: &Path, out_dir: &Path) -> PathBuf {
+    let release = env::var("PROFILE").unwrap() == "release";
+    let target_dir = out_dir.join("userspace");
+
+    let mut build_cmd = Command::new(env::var("CARGO").unwrap());
+    build_cmd.current_dir(userspace_dir);
+    build_cmd.arg("build");
+    build_cmd
+        .arg("--target")
+        .arg(manifest_dir.join("x86_64-custom.json"));
+    build_cmd
+        .arg("-Zbuild-std=core,alloc")
+        .arg("-Zbuild-std-features=compiler-builtins-mem");
+    build_cmd.arg("--target-dir").arg(&target_dir);
+    if release {
+        build_cmd.arg("--release");
+    }
+    // the flags of the kernel build must not leak into the user programs
+    build_cmd
+        .env_remove("RUSTFLAGS")
+        .env_remove("CARGO_ENCODED_RUSTFLAGS");
+
+    if !build_cmd.status().unwrap().success() {
+        panic!("building the user programs failed");
+    }
+
+    target_dir
+        .join("x86_64-custom")
+        .join(if release { "release" } else { "debug" })
+}
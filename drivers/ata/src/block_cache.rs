@@ -0,0 +1,311 @@
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use kernel::{
+    processes::{sleep, spawn_kernel_thread, SleepMutex},
+    BlockDevice, BlockDeviceError,
+};
+use spin::{Mutex, Once};
+
+use crate::{
+    constants::{SECTOR_SIZE, SYNC_INTERVAL_TICKS},
+    ATADisk, ATAError, PartitionDescriptor,
+};
+
+/// The caches synced by the sync thread and at shutdown.
+static BLOCK_CACHES: Mutex<Vec<Weak<SleepMutex<BlockCache>>>> = Mutex::new(Vec::new());
+static SYNC_THREAD: Once<()> = Once::new();
+
+/// A write-back cache of the sectors of a disk.
+///
+/// Writes only change the cached sectors and mark them dirty, `sync` writes the dirty sectors in runs of
+/// consecutive sectors and flushes the drive. The caches are synced every `SYNC_INTERVAL_TICKS` and at
+/// shutdown. The disk must only be accessed through its cache, otherwise the two can get out of sync.
+///
+/// The cache is shared through `CachedDevice`s, which lock it while a transfer sleeps on the disk.
+pub struct BlockCache {
+    disk: ATADisk,
+    /// The maximum number of cached sectors.
+    capacity: usize,
+    sectors: BTreeMap<u64, CachedSector>,
+    /// Incremented on every access, to find the least recently used sector.
+    clock: u64,
+}
+
+struct CachedSector {
+    data: [u8; SECTOR_SIZE],
+    dirty: bool,
+    last_use: u64,
+}
+
+impl BlockCache {
+    /// Creates a cache holding up to `capacity` sectors of the disk and registers it to be synced.
+    ///
+    /// Returns the whole disk seen through the cache, its partitions are seen through it with
+    /// `CachedDevice::partition`.
+    pub fn new(disk: ATADisk, capacity: usize) -> CachedDevice {
+        let sectors = disk.block_count();
+        let cache = Arc::new(SleepMutex::new(BlockCache {
+            disk,
+            capacity: capacity.max(1),
+            sectors: BTreeMap::new(),
+            clock: 0,
+        }));
+        let mut caches = BLOCK_CACHES.lock();
+        caches.retain(|cache| cache.strong_count() > 0);
+        caches.push(Arc::downgrade(&cache));
+        CachedDevice {
+            cache,
+            start_lba: 0,
+            sectors,
+        }
+    }
+
+    /// Reads the sectors starting at `lba` into the buffer, its length must be a multiple of the sector size.
+    ///
+    /// The sectors that aren't cached yet are read from the disk in runs of consecutive sectors.
+    pub fn read_sectors(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), ATAError> {
+        if buffer.len() % SECTOR_SIZE != 0 {
+            return Err(ATAError::InvalidBufferSize);
+        }
+        let count = buffer.len() / SECTOR_SIZE;
+        let mut index = 0;
+        while index < count {
+            let sector_lba = lba + index as u64;
+            if let Some(data) = self.get(sector_lba) {
+                buffer[index * SECTOR_SIZE..(index + 1) * SECTOR_SIZE].copy_from_slice(&data);
+                index += 1;
+                continue;
+            }
+            let run = (index..count)
+                .take_while(|&i| !self.sectors.contains_key(&(lba + i as u64)))
+                .count();
+            let run_buffer = &mut buffer[index * SECTOR_SIZE..(index + run) * SECTOR_SIZE];
+            self.disk.read_sectors(sector_lba, run_buffer)?;
+            for (offset, sector) in run_buffer.chunks_exact(SECTOR_SIZE).enumerate() {
+                let mut data = [0; SECTOR_SIZE];
+                data.copy_from_slice(sector);
+                self.insert(sector_lba + offset as u64, data, false)?;
+            }
+            index += run;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffer to the cached sectors starting at `lba`, its length must be a multiple of the
+    /// sector size.
+    ///
+    /// The sectors reach the disk with the next `sync`.
+    pub fn write_sectors(&mut self, lba: u64, buffer: &[u8]) -> Result<(), ATAError> {
+        if buffer.len() % SECTOR_SIZE != 0 {
+            return Err(ATAError::InvalidBufferSize);
+        }
+        for (index, sector) in buffer.chunks_exact(SECTOR_SIZE).enumerate() {
+            let mut data = [0; SECTOR_SIZE];
+            data.copy_from_slice(sector);
+            self.insert(lba + index as u64, data, true)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dirty sectors to the disk and flushes the drive's cache.
+    ///
+    /// Sectors that couldn't be written stay dirty, so the next sync tries again.
+    pub fn sync(&mut self) -> Result<(), ATAError> {
+        let dirty: Vec<u64> = self
+            .sectors
+            .iter()
+            .filter(|(_, sector)| sector.dirty)
+            .map(|(&lba, _)| lba)
+            .collect();
+        if dirty.is_empty() {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        let mut buffer = Vec::new();
+        for run in dirty.split_inclusive(|lba| dirty.binary_search(&(lba + 1)).is_err()) {
+            buffer.clear();
+            for lba in run {
+                buffer.extend_from_slice(&self.sectors[lba].data);
+            }
+            match self.disk.write_sectors(run[0], &buffer) {
+                Ok(()) => {
+                    for lba in run {
+                        if let Some(sector) = self.sectors.get_mut(lba) {
+                            sector.dirty = false;
+                        }
+                    }
+                }
+                Err(error) => result = Err(error),
+            }
+        }
+        result.and_then(|_| self.disk.flush())
+    }
+
+    /// Returns `OutOfRange` if the sectors covered by a buffer of the length go past the end of the disk.
+    fn check_range(&self, lba: u64, length: usize) -> Result<(), BlockDeviceError> {
+        let sectors = (length / SECTOR_SIZE) as u64;
+        match lba.checked_add(sectors) {
+            Some(end) if end <= self.disk.block_count() => Ok(()),
+            _ => Err(BlockDeviceError::OutOfRange),
+        }
+    }
+
+    /// Returns the cached data of the sector, if it's cached.
+    fn get(&mut self, lba: u64) -> Option<[u8; SECTOR_SIZE]> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.sectors.get_mut(&lba).map(|sector| {
+            sector.last_use = clock;
+            sector.data
+        })
+    }
+
+    /// Caches the sector, a dirty sector stays dirty until it's synced.
+    ///
+    /// When the cache is full, the least recently used clean sector is evicted, the dirty sectors are
+    /// synced first if there's none.
+    fn insert(&mut self, lba: u64, data: [u8; SECTOR_SIZE], dirty: bool) -> Result<(), ATAError> {
+        self.clock += 1;
+        if let Some(sector) = self.sectors.get_mut(&lba) {
+            sector.data = data;
+            sector.dirty |= dirty;
+            sector.last_use = self.clock;
+            return Ok(());
+        }
+        if self.sectors.len() >= self.capacity {
+            if self.sectors.values().all(|sector| sector.dirty) {
+                self.sync()?;
+            }
+            let evicted = self
+                .sectors
+                .iter()
+                .filter(|(_, sector)| !sector.dirty)
+                .min_by_key(|(_, sector)| sector.last_use)
+                .map(|(&lba, _)| lba);
+            if let Some(evicted) = evicted {
+                self.sectors.remove(&evicted);
+            }
+        }
+        self.sectors.insert(
+            lba,
+            CachedSector {
+                data,
+                dirty,
+                last_use: self.clock,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl BlockDevice for BlockCache {
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.disk.block_count()
+    }
+
+    fn read_blocks(&mut self, block: u64, buffer: &mut [u8]) -> Result<(), BlockDeviceError> {
+        self.check_range(block, buffer.len())?;
+        Ok(self.read_sectors(block, buffer)?)
+    }
+
+    fn write_blocks(&mut self, block: u64, buffer: &[u8]) -> Result<(), BlockDeviceError> {
+        self.check_range(block, buffer.len())?;
+        Ok(self.write_sectors(block, buffer)?)
+    }
+
+    fn flush(&mut self) -> Result<(), BlockDeviceError> {
+        Ok(self.sync()?)
+    }
+}
+
+/// A range of the sectors of a disk, the whole disk or one of its partitions, read and written through
+/// the disk's cache.
+///
+/// Clones share the cache, so every user of the disk sees the sectors written by the others.
+#[derive(Clone)]
+pub struct CachedDevice {
+    cache: Arc<SleepMutex<BlockCache>>,
+    start_lba: u64,
+    sectors: u64,
+}
+
+impl CachedDevice {
+    /// Returns the partition of the disk, seen through the same cache.
+    pub fn partition(&self, descriptor: &PartitionDescriptor) -> CachedDevice {
+        CachedDevice {
+            cache: self.cache.clone(),
+            start_lba: self.start_lba + descriptor.start_lba,
+            sectors: descriptor.sectors,
+        }
+    }
+
+    fn check_range(&self, block: u64, length: usize) -> Result<(), BlockDeviceError> {
+        let sectors = (length / SECTOR_SIZE) as u64;
+        match block.checked_add(sectors) {
+            Some(end) if end <= self.sectors => Ok(()),
+            _ => Err(BlockDeviceError::OutOfRange),
+        }
+    }
+}
+
+impl BlockDevice for CachedDevice {
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.sectors
+    }
+
+    fn read_blocks(&mut self, block: u64, buffer: &mut [u8]) -> Result<(), BlockDeviceError> {
+        self.check_range(block, buffer.len())?;
+        self.cache
+            .lock()
+            .read_blocks(self.start_lba + block, buffer)
+    }
+
+    fn write_blocks(&mut self, block: u64, buffer: &[u8]) -> Result<(), BlockDeviceError> {
+        self.check_range(block, buffer.len())?;
+        self.cache
+            .lock()
+            .write_blocks(self.start_lba + block, buffer)
+    }
+
+    /// Syncs the whole cache, including the sectors of the disk's other partitions.
+    fn flush(&mut self) -> Result<(), BlockDeviceError> {
+        self.cache.lock().flush()
+    }
+}
+
+/// Syncs all the block caches still in use.
+///
+/// Errors are ignored, the sectors that couldn't be written are tried again by the next sync.
+pub fn sync_all() {
+    let caches: Vec<Arc<SleepMutex<BlockCache>>> = BLOCK_CACHES
+        .lock()
+        .iter()
+        .filter_map(|cache| cache.upgrade())
+        .collect();
+    for cache in caches {
+        let _ = cache.lock().sync();
+    }
+}
+
+/// Starts the kernel thread syncing the block caches every `SYNC_INTERVAL_TICKS`
+/// and registers the sync at shutdown, only the first call does anything.
+pub(crate) fn start_sync_thread() {
+    SYNC_THREAD.call_once(|| {
+        spawn_kernel_thread(|| loop {
+            sleep(SYNC_INTERVAL_TICKS);
+            sync_all();
+        });
+        kernel::register_shutdown_hook(sync_all);
+    });
+}
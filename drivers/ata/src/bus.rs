@@ -1,14 +1,21 @@
 use alloc::sync::Arc;
-use spin::Mutex;
 use utils::port_extensions::{PortExtRead, PortExtWrite};
 use x86_64::instructions::{
     interrupts::without_interrupts,
     port::{Port, PortReadOnly, PortWriteOnly},
 };
 
+use kernel::processes::{InterruptEvent, SleepMutex};
+
 use crate::{
-    constants::{ATACommands, ErrorRegisterFlags, StatusRegisterFlags},
-    ATADisk,
+    bus_master::BusMaster,
+    constants::{
+        ATACommands, ErrorRegisterFlags, StatusRegisterFlags, DMA_BUFFER_SIZE,
+        INTERRUPT_TIMEOUT_TICKS, LBA_28_MAX_SECTORS_PER_COMMAND, LBA_28_SECTORS,
+        LBA_48_MAX_SECTORS_PER_COMMAND, LBA_48_SECTORS, POLL_ITERATIONS, SECTOR_SIZE,
+        SET_FEATURES_TRANSFER_MODE, TRANSFER_MODE_UDMA,
+    },
+    ATADisk, ATAError,
 };
 
 use super::{constants::ATAIdentifyError, disk_descriptor::DiskDescriptor};
@@ -31,10 +38,20 @@ pub struct ATABus {
 
     disk_1_descriptor: Option<DiskDescriptor>,
     disk_2_descriptor: Option<DiskDescriptor>,
+
+    /// Signalled by the IRQ of the bus when a command completes or needs data.
+    interrupt: &'static InterruptEvent,
+    /// The I/O port of the bus master registers, if the IDE controller supports DMA.
+    bus_master_port: Option<u16>,
+    bus_master: Option<BusMaster>,
 }
 
 impl ATABus {
-    pub(crate) const fn new(base_port: u16) -> Self {
+    pub(crate) const fn new(
+        base_port: u16,
+        interrupt: &'static InterruptEvent,
+        bus_master_port: Option<u16>,
+    ) -> Self {
         ATABus {
             data_register_rw: Port::new(base_port + 0x00),
             error_register_r: PortReadOnly::new(base_port + 0x01),
@@ -51,6 +68,9 @@ impl ATABus {
             drive_address_register_r: PortReadOnly::new(base_port + 0x207),
             disk_1_descriptor: None,
             disk_2_descriptor: None,
+            interrupt,
+            bus_master_port,
+            bus_master: None,
         }
     }
 
@@ -58,43 +78,74 @@ impl ATABus {
         self.status_register_r.read() != 0xFF
     }
 
+    /// Polls the status register until the flag has the passed state.
+    ///
+    /// Only used for short waits, gives up with `Timeout` after `POLL_ITERATIONS` reads.
     pub unsafe fn wait_for(
         &mut self,
         flag: StatusRegisterFlags,
         should_be_on: bool,
-    ) -> Result<(), ErrorRegisterFlags> {
+    ) -> Result<(), ATAError> {
         let condition = if should_be_on {
             flag
         } else {
             StatusRegisterFlags::empty()
         };
-        loop {
+        for _ in 0..POLL_ITERATIONS {
             let status = StatusRegisterFlags::from_bits_unchecked(self.status_register_r.read());
             if status.intersection(flag) == condition {
-                break;
+                return Ok(());
             }
             if status.contains(StatusRegisterFlags::ERR) {
                 let error = self.error_register_r.read();
                 if error != 0 {
-                    return Err(ErrorRegisterFlags::from_bits_unchecked(error));
+                    return Err(ATAError::Device(ErrorRegisterFlags::from_bits_unchecked(
+                        error,
+                    )));
                 }
             }
         }
-        Ok(())
+        Err(ATAError::Timeout)
     }
 
-    pub unsafe fn wait_400ns(&mut self) -> Result<(), ErrorRegisterFlags> {
+    pub unsafe fn wait_400ns(&mut self) -> Result<(), ATAError> {
         for _ in 0..15 {
             let status = StatusRegisterFlags::from_bits_unchecked(self.status_register_r.read());
             if status.contains(StatusRegisterFlags::ERR) {
                 let error = self.error_register_r.read();
                 if error != 0 {
-                    return Err(ErrorRegisterFlags::from_bits_unchecked(error));
+                    return Err(ATAError::Device(ErrorRegisterFlags::from_bits_unchecked(
+                        error,
+                    )));
                 }
             }
         }
         Ok(())
     }
+
+    /// Sends a command, the bus' interrupt signalled before is forgotten.
+    unsafe fn send_command(&mut self, command: ATACommands) {
+        self.interrupt.reset();
+        self.command_register_w.write(command as u8);
+    }
+
+    /// Sleeps until the device raises the interrupt of the bus, then checks the command's status.
+    ///
+    /// Returns `Timeout` if the interrupt doesn't come within `INTERRUPT_TIMEOUT_TICKS`.
+    unsafe fn wait_for_interrupt(&mut self) -> Result<(), ATAError> {
+        if !self.interrupt.wait(INTERRUPT_TIMEOUT_TICKS) {
+            return Err(ATAError::Timeout);
+        }
+        // reading the status register acknowledges the interrupt
+        let status = StatusRegisterFlags::from_bits_unchecked(self.status_register_r.read());
+        if status.intersects(StatusRegisterFlags::ERR | StatusRegisterFlags::DF) {
+            let error = self.error_register_r.read();
+            return Err(ATAError::Device(ErrorRegisterFlags::from_bits_unchecked(
+                error,
+            )));
+        }
+        Ok(())
+    }
 }
 
 impl ATABus {
@@ -105,10 +156,14 @@ impl ATABus {
         if !master && self.disk_2_descriptor.is_some() {
             return Ok(self.disk_2_descriptor.as_ref().unwrap().clone());
         }
-        unsafe fn handle_identify_error(
-            bus: &mut ATABus,
-            error: ErrorRegisterFlags,
-        ) -> ATAIdentifyError {
+        unsafe fn handle_identify_error(bus: &mut ATABus, error: ATAError) -> ATAIdentifyError {
+            let error = match error {
+                ATAError::Device(error) => error,
+                ATAError::Timeout
+                | ATAError::InvalidBufferSize
+                | ATAError::AddressNotSupported
+                | ATAError::Dma => return ATAIdentifyError::Unknown,
+            };
             if error != ErrorRegisterFlags::ABRT {
                 return ATAIdentifyError::DeviceIsATAPI;
             }
@@ -154,7 +209,10 @@ impl ATABus {
                 }
                 let mut identify_buffer: [u16; 256] = [0; 256];
                 self.data_register_rw.read_to_buffer(&mut identify_buffer);
-                let descriptor = DiskDescriptor::from_bytes(identify_buffer);
+                let mut descriptor = DiskDescriptor::from_bytes(identify_buffer);
+                if self.bus_master_port.is_some() {
+                    self.select_udma_mode(&mut descriptor);
+                }
                 if master {
                     self.disk_1_descriptor = Some(descriptor);
                     Ok(self.disk_1_descriptor.as_ref().unwrap().clone())
@@ -166,67 +224,243 @@ impl ATABus {
         }
     }
 
-    pub(crate) fn read_sector(
-        &mut self,
-        master: bool,
-        lba: u64,
-    ) -> Result<[u8; 512], ErrorRegisterFlags> {
-        if lba > u32::MAX.into() {
-            todo!("LBA48 not supported");
+    /// Selects the fastest UDMA mode of the drive selected by IDENTIFY, if none is selected yet.
+    ///
+    /// The descriptor is updated on success, without a selected mode the drive is used with PIO.
+    unsafe fn select_udma_mode(&mut self, descriptor: &mut DiskDescriptor) {
+        let mode = match descriptor.fastest_udma_mode() {
+            Some(mode) if !descriptor.dma_enabled() => mode,
+            _ => return,
+        };
+        self.features_register_w.write(SET_FEATURES_TRANSFER_MODE);
+        self.sector_count_register_rw
+            .write(TRANSFER_MODE_UDMA | mode);
+        self.send_command(ATACommands::SetFeatures);
+        if self.wait_for(StatusRegisterFlags::BSY, false).is_ok() {
+            descriptor.udma_current_mode = 1 << mode;
         }
-        unsafe {
+    }
+
+    /// Selects the drive and writes the address and sector count of the next command.
+    ///
+    /// LBA48 writes the high bytes first, the registers keep the last two values written to them.
+    unsafe fn select_sectors(&mut self, master: bool, lba_48: bool, lba: u64, count: u32) {
+        if lba_48 {
+            self.drive_head_register_rw
+                .write(if master { 0x40 } else { 0x50 });
+            self.sector_count_register_rw.write((count >> 8) as u8);
+            self.lba_low_register_rw.write((lba >> 24) as u8);
+            self.lba_mid_register_rw.write((lba >> 32) as u8);
+            self.lba_high_register_rw.write((lba >> 40) as u8);
+        } else {
             let slave = if master { 0xE0 } else { 0xF0 };
             self.drive_head_register_rw
                 .write(slave | ((lba >> 24) & 0x0F) as u8);
             self.features_register_w.write(0x00);
-            self.sector_count_register_rw.write(0x01);
-            self.lba_low_register_rw.write(lba as u8);
-            self.lba_mid_register_rw.write((lba >> 8) as u8);
-            self.lba_high_register_rw.write((lba >> 16) as u8);
-            self.command_register_w
-                .write(ATACommands::ReadSectors as u8);
-            self.wait_for(StatusRegisterFlags::BSY, false)?;
-            self.wait_for(StatusRegisterFlags::DRQ, true)?;
-            let mut buffer = [0u8; 512];
-            self.data_register_rw.read_to_buffer(&mut buffer);
-            self.wait_400ns()?;
-            Ok(buffer)
         }
+        // a count of 0 means the maximum, 256 or 65536 sectors
+        self.sector_count_register_rw.write(count as u8);
+        self.lba_low_register_rw.write(lba as u8);
+        self.lba_mid_register_rw.write((lba >> 8) as u8);
+        self.lba_high_register_rw.write((lba >> 16) as u8);
+    }
+
+    /// Returns the bus master of the bus, the memory for its transfers is allocated on the first use.
+    ///
+    /// Returns `None` if the IDE controller doesn't support DMA or the memory can't be allocated.
+    fn bus_master(&mut self) -> Option<&mut BusMaster> {
+        if self.bus_master.is_none() {
+            self.bus_master = BusMaster::new(self.bus_master_port?);
+        }
+        self.bus_master.as_mut()
     }
 
-    pub(crate) fn write_sector(
+    /// Returns the number of sectors a single command transfers and whether the commands use DMA.
+    ///
+    /// DMA is used if the device has a UDMA mode selected and the IDE controller supports it, PIO otherwise.
+    fn sectors_per_command(
+        &mut self,
+        descriptor: &DiskDescriptor,
+        length: usize,
+        lba: u64,
+    ) -> Result<(usize, bool), ATAError> {
+        let sectors = check_transfer(length, lba, descriptor.supports_lba_48)?;
+        if descriptor.dma_enabled() && self.bus_master().is_some() {
+            Ok((sectors.min(DMA_BUFFER_SIZE / SECTOR_SIZE), true))
+        } else {
+            Ok((sectors, false))
+        }
+    }
+
+    /// Reads the sectors starting at `lba` into the buffer, which holds a whole number of sectors.
+    ///
+    /// LBA48 commands are used for the sectors LBA28 can't reach and for long transfers, if the drive
+    /// supports them.
+    pub(crate) fn read_sectors(
         &mut self,
         master: bool,
+        descriptor: &DiskDescriptor,
         lba: u64,
-        buffer: &[u8; 512],
-    ) -> Result<(), ErrorRegisterFlags> {
-        if lba > u32::MAX.into() {
-            todo!("LBA48 not supported");
+        buffer: &mut [u8],
+    ) -> Result<(), ATAError> {
+        let mut lba = lba;
+        let (sectors_per_command, dma) = self.sectors_per_command(descriptor, buffer.len(), lba)?;
+        for chunk in buffer.chunks_mut(sectors_per_command * SECTOR_SIZE) {
+            let count = (chunk.len() / SECTOR_SIZE) as u32;
+            let lba_48 = needs_lba_48(lba, count);
+            unsafe {
+                self.select_sectors(master, lba_48, lba, count);
+                if dma {
+                    self.read_dma(lba_48, chunk)?;
+                } else {
+                    self.read_pio(lba_48, chunk)?;
+                }
+            }
+            lba += count as u64;
         }
+        Ok(())
+    }
+
+    /// Writes the buffer, which holds a whole number of sectors, to the sectors starting at `lba`.
+    ///
+    /// The sectors may stay in the drive's cache until `flush` is called.
+    pub(crate) fn write_sectors(
+        &mut self,
+        master: bool,
+        descriptor: &DiskDescriptor,
+        lba: u64,
+        buffer: &[u8],
+    ) -> Result<(), ATAError> {
+        let mut lba = lba;
+        let (sectors_per_command, dma) = self.sectors_per_command(descriptor, buffer.len(), lba)?;
+        for chunk in buffer.chunks(sectors_per_command * SECTOR_SIZE) {
+            let count = (chunk.len() / SECTOR_SIZE) as u32;
+            let lba_48 = needs_lba_48(lba, count);
+            unsafe {
+                self.select_sectors(master, lba_48, lba, count);
+                if dma {
+                    self.write_dma(lba_48, chunk)?;
+                } else {
+                    self.write_pio(lba_48, chunk)?;
+                }
+            }
+            lba += count as u64;
+        }
+        Ok(())
+    }
+
+    /// Makes the drive write the sectors in its cache to the disk.
+    pub(crate) fn flush(&mut self, master: bool, lba_48: bool) -> Result<(), ATAError> {
         unsafe {
-            let slave = if master { 0xE0 } else { 0xF0 };
             self.drive_head_register_rw
-                .write(slave | ((lba >> 24) & 0x0F) as u8);
-            self.features_register_w.write(0x00);
-            self.sector_count_register_rw.write(0x01);
-            self.lba_low_register_rw.write(lba as u8);
-            self.lba_mid_register_rw.write((lba >> 8) as u8);
-            self.lba_high_register_rw.write((lba >> 16) as u8);
-            self.command_register_w
-                .write(ATACommands::WriteSectors as u8);
-            self.wait_for(StatusRegisterFlags::BSY, false)?;
-            self.wait_for(StatusRegisterFlags::DRQ, true)?;
-            self.data_register_rw.write_from_buffer(buffer);
+                .write(if master { 0xE0 } else { 0xF0 });
             self.wait_400ns()?;
-            self.wait_for(StatusRegisterFlags::DRQ, false)?;
+            self.send_command(if lba_48 {
+                ATACommands::CacheFlushExt
+            } else {
+                ATACommands::CacheFlush
+            });
+            self.wait_for_interrupt()
+        }
+    }
+
+    /// Reads the sectors selected by `select_sectors` through the data register.
+    unsafe fn read_pio(&mut self, lba_48: bool, buffer: &mut [u8]) -> Result<(), ATAError> {
+        self.send_command(if lba_48 {
+            ATACommands::ReadSectorsExt
+        } else {
+            ATACommands::ReadSectors
+        });
+        for sector in buffer.chunks_exact_mut(SECTOR_SIZE) {
+            // the device raises the interrupt once each sector is ready to be read
+            self.wait_for_interrupt()?;
             self.wait_for(StatusRegisterFlags::BSY, false)?;
-            self.command_register_w.write(ATACommands::CacheFlush as u8);
+            self.wait_for(StatusRegisterFlags::DRQ, true)?;
+            self.data_register_rw.read_to_buffer(sector);
+        }
+        self.wait_400ns()
+    }
+
+    /// Writes the sectors selected by `select_sectors` through the data register.
+    unsafe fn write_pio(&mut self, lba_48: bool, buffer: &[u8]) -> Result<(), ATAError> {
+        self.send_command(if lba_48 {
+            ATACommands::WriteSectorsExt
+        } else {
+            ATACommands::WriteSectors
+        });
+        for (index, sector) in buffer.chunks_exact(SECTOR_SIZE).enumerate() {
+            // the device asks for the first sector without raising the interrupt,
+            // it raises it once each sector is written
+            if index > 0 {
+                self.wait_for_interrupt()?;
+            }
             self.wait_for(StatusRegisterFlags::BSY, false)?;
+            self.wait_for(StatusRegisterFlags::DRQ, true)?;
+            self.data_register_rw.write_from_buffer(sector);
+        }
+        self.wait_for_interrupt()
+    }
+
+    /// Reads the sectors selected by `select_sectors` with DMA, at most `DMA_BUFFER_SIZE` bytes.
+    unsafe fn read_dma(&mut self, lba_48: bool, buffer: &mut [u8]) -> Result<(), ATAError> {
+        let mut bus_master = self
+            .bus_master
+            .take()
+            .expect("DMA transfer without a bus master");
+        let command = if lba_48 {
+            ATACommands::ReadDmaExt
+        } else {
+            ATACommands::ReadDma
+        };
+        let result = self.transfer_dma(&mut bus_master, command, true);
+        if result.is_ok() {
+            buffer.copy_from_slice(&bus_master.buffer()[..buffer.len()]);
+        }
+        self.bus_master = Some(bus_master);
+        result
+    }
+
+    /// Writes the sectors selected by `select_sectors` with DMA, at most `DMA_BUFFER_SIZE` bytes.
+    unsafe fn write_dma(&mut self, lba_48: bool, buffer: &[u8]) -> Result<(), ATAError> {
+        let mut bus_master = self
+            .bus_master
+            .take()
+            .expect("DMA transfer without a bus master");
+        bus_master.buffer()[..buffer.len()].copy_from_slice(buffer);
+        let command = if lba_48 {
+            ATACommands::WriteDmaExt
+        } else {
+            ATACommands::WriteDma
+        };
+        let result = self.transfer_dma(&mut bus_master, command, false);
+        self.bus_master = Some(bus_master);
+        result
+    }
+
+    /// Sends the DMA command and sleeps until the device raises the interrupt at the end of the transfer.
+    unsafe fn transfer_dma(
+        &mut self,
+        bus_master: &mut BusMaster,
+        command: ATACommands,
+        read: bool,
+    ) -> Result<(), ATAError> {
+        bus_master.prepare(read);
+        self.send_command(command);
+        bus_master.start();
+        let result = self.wait_for_interrupt();
+        let succeeded = bus_master.stop();
+        result?;
+        if succeeded {
             Ok(())
+        } else {
+            Err(ATAError::Dma)
         }
     }
 
-    pub fn get_disk(this: &Arc<Mutex<Self>>, master: bool) -> Result<ATADisk, ATAIdentifyError> {
+    pub fn get_disk(
+        this: &Arc<SleepMutex<Self>>,
+        master: bool,
+    ) -> Result<ATADisk, ATAIdentifyError> {
         let descriptor = this.lock().identify(master)?;
         Ok(ATADisk {
             bus: this.clone(),
@@ -235,3 +469,31 @@ impl ATABus {
         })
     }
 }
+
+/// Checks that the buffer holds whole sectors and that the drive can address all of them.
+///
+/// Returns the number of sectors transferred per command.
+fn check_transfer(length: usize, lba: u64, lba_48: bool) -> Result<usize, ATAError> {
+    if length % SECTOR_SIZE != 0 {
+        return Err(ATAError::InvalidBufferSize);
+    }
+    let end = lba
+        .checked_add((length / SECTOR_SIZE) as u64)
+        .ok_or(ATAError::AddressNotSupported)?;
+    if lba_48 {
+        if end > LBA_48_SECTORS {
+            return Err(ATAError::AddressNotSupported);
+        }
+        Ok(LBA_48_MAX_SECTORS_PER_COMMAND)
+    } else {
+        if end > LBA_28_SECTORS {
+            return Err(ATAError::AddressNotSupported);
+        }
+        Ok(LBA_28_MAX_SECTORS_PER_COMMAND)
+    }
+}
+
+/// Returns whether a command for the passed sectors can't be sent with LBA28.
+fn needs_lba_48(lba: u64, count: u32) -> bool {
+    lba + count as u64 > LBA_28_SECTORS || count as usize > LBA_28_MAX_SECTORS_PER_COMMAND
+}
@@ -0,0 +1,95 @@
+use kernel::DmaBuffer;
+use x86_64::instructions::port::Port;
+
+use crate::constants::{
+    BusMasterStatusFlags, BUS_MASTER_COMMAND_READ, BUS_MASTER_COMMAND_START, DMA_BUFFER_SIZE,
+    PRD_END_OF_TABLE, PRD_MAX_BYTES, PRD_SIZE,
+};
+
+/// The bus master registers of an IDE channel and the memory its transfers go through.
+///
+/// Transfers are copied through a physically contiguous buffer described by the PRDT,
+/// the physical region descriptor table.
+pub(crate) struct BusMaster {
+    command_register: Port<u8>,
+    status_register: Port<u8>,
+    prdt_register: Port<u32>,
+    prdt: DmaBuffer,
+    buffer: DmaBuffer,
+}
+
+impl BusMaster {
+    /// Returns `None` if the memory for the transfers can't be allocated.
+    pub(crate) fn new(base_port: u16) -> Option<Self> {
+        let mut bus_master = BusMaster {
+            command_register: Port::new(base_port),
+            status_register: Port::new(base_port + 0x02),
+            prdt_register: Port::new(base_port + 0x04),
+            prdt: DmaBuffer::new(PRD_SIZE * (DMA_BUFFER_SIZE / PRD_MAX_BYTES + 1))?,
+            buffer: DmaBuffer::new(DMA_BUFFER_SIZE)?,
+        };
+        bus_master.build_prdt();
+        Some(bus_master)
+    }
+
+    /// Describes the whole buffer in the PRDT, a region can't cross a 64 KiB boundary.
+    fn build_prdt(&mut self) {
+        let mut address = self.buffer.physical_address().as_u64();
+        let end = address + DMA_BUFFER_SIZE as u64;
+        let prdt = self.prdt.as_mut_slice();
+        let mut index = 0;
+        while address < end {
+            let size = (PRD_MAX_BYTES as u64 - (address % PRD_MAX_BYTES as u64)).min(end - address);
+            let flags = if address + size == end {
+                PRD_END_OF_TABLE
+            } else {
+                0
+            };
+            let entry = &mut prdt[index * PRD_SIZE..(index + 1) * PRD_SIZE];
+            entry[0..4].copy_from_slice(&(address as u32).to_le_bytes());
+            // a size of 0 means 64 KiB
+            entry[4..6].copy_from_slice(&(size as u16).to_le_bytes());
+            entry[6..8].copy_from_slice(&flags.to_le_bytes());
+            address += size;
+            index += 1;
+        }
+    }
+
+    /// Returns the memory the transfers go through.
+    pub(crate) fn buffer(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut_slice()[..DMA_BUFFER_SIZE]
+    }
+
+    /// Prepares a transfer, `read` is true if the device writes to the memory.
+    ///
+    /// The transfer starts with `start` once the command was sent to the device.
+    pub(crate) unsafe fn prepare(&mut self, read: bool) {
+        self.command_register.write(0);
+        self.prdt_register
+            .write(self.prdt.physical_address().as_u64() as u32);
+        self.command_register
+            .write(if read { BUS_MASTER_COMMAND_READ } else { 0 });
+        // the error and interrupt bits are cleared by writing them
+        self.status_register
+            .write((BusMasterStatusFlags::ERROR | BusMasterStatusFlags::INTERRUPT).bits());
+    }
+
+    pub(crate) unsafe fn start(&mut self) {
+        let command = self.command_register.read();
+        self.command_register
+            .write(command | BUS_MASTER_COMMAND_START);
+    }
+
+    /// Stops the transfer once the device raised its interrupt.
+    ///
+    /// Returns false if the bus master reported an error.
+    pub(crate) unsafe fn stop(&mut self) -> bool {
+        let command = self.command_register.read();
+        self.command_register
+            .write(command & !BUS_MASTER_COMMAND_START);
+        let status = BusMasterStatusFlags::from_bits_truncate(self.status_register.read());
+        self.status_register
+            .write((BusMasterStatusFlags::ERROR | BusMasterStatusFlags::INTERRUPT).bits());
+        !status.contains(BusMasterStatusFlags::ERROR)
+    }
+}
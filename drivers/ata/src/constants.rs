@@ -1,7 +1,10 @@
 use alloc::sync::Arc;
-use spin::Mutex;
+use kernel::{
+    milliseconds_to_ticks, processes::SleepMutex, BlockDeviceError, ATA_PRIMARY_INTERRUPT,
+    ATA_SECONDARY_INTERRUPT,
+};
 
-use super::bus::ATABus;
+use super::{bus::ATABus, pci};
 use bitflags::bitflags;
 use lazy_static::lazy_static;
 
@@ -45,6 +48,105 @@ bitflags! {
     /// Address Mark Not Found
         const AMNF = 0b10000000;
     }
+
+    #[repr(C)]
+    pub struct BusMasterStatusFlags: u8 {
+        /// The transfer is running
+        const ACTIVE = 0b00000001;
+        /// The transfer failed
+        const ERROR = 0b00000010;
+        /// The device raised its interrupt
+        const INTERRUPT = 0b00000100;
+    }
+}
+
+/// The size of a sector in bytes.
+pub const SECTOR_SIZE: usize = 512;
+/// The number of sectors LBA28 can address.
+pub(crate) const LBA_28_SECTORS: u64 = 1 << 28;
+/// The number of sectors LBA48 can address.
+pub(crate) const LBA_48_SECTORS: u64 = 1 << 48;
+/// The number of sectors a single LBA28 command can transfer.
+pub(crate) const LBA_28_MAX_SECTORS_PER_COMMAND: usize = 256;
+/// The number of sectors a single LBA48 command can transfer.
+pub(crate) const LBA_48_MAX_SECTORS_PER_COMMAND: usize = 65536;
+
+/// The size of the buffer DMA transfers go through, a single command transfers at most this many bytes.
+pub(crate) const DMA_BUFFER_SIZE: usize = 64 * 1024;
+/// The size of an entry of the PRDT in bytes.
+pub(crate) const PRD_SIZE: usize = 8;
+/// The number of bytes a single PRDT entry can describe, its region can't cross a boundary of this size.
+pub(crate) const PRD_MAX_BYTES: usize = 64 * 1024;
+/// Marks the last entry of the PRDT.
+pub(crate) const PRD_END_OF_TABLE: u16 = 0x8000;
+/// Starts the transfer when set in the bus master command register.
+pub(crate) const BUS_MASTER_COMMAND_START: u8 = 0x01;
+/// Makes the device write to the memory when set in the bus master command register.
+pub(crate) const BUS_MASTER_COMMAND_READ: u8 = 0x08;
+
+pub(crate) const PCI_CONFIG_ADDRESS: u16 = 0xCF8;
+pub(crate) const PCI_CONFIG_DATA: u16 = 0xCFC;
+pub(crate) const PCI_CLASS_MASS_STORAGE: u8 = 0x01;
+pub(crate) const PCI_SUBCLASS_IDE: u8 = 0x01;
+/// Set in the programming interface of IDE controllers that support bus mastering.
+pub(crate) const PCI_PROG_IF_BUS_MASTER: u8 = 0x80;
+/// Set in the programming interface if a channel is in native mode, it then doesn't use the legacy ports.
+pub(crate) const PCI_PROG_IF_PRIMARY_NATIVE: u8 = 0x01;
+pub(crate) const PCI_PROG_IF_SECONDARY_NATIVE: u8 = 0x04;
+pub(crate) const PCI_IO_SPACE_ENABLE: u32 = 0x01;
+pub(crate) const PCI_BUS_MASTER_ENABLE: u32 = 0x04;
+
+/// The MBR partition type of the protective partition covering a GPT disk.
+pub(crate) const MBR_PARTITION_TYPE_GPT: u8 = 0xEE;
+pub(crate) const GPT_SIGNATURE: [u8; 8] = *b"EFI PART";
+pub(crate) const GPT_REVISION: u32 = 0x0001_0000;
+/// The number and size of the partition entries of a created GPT, the minimum the specification allows.
+pub(crate) const GPT_ENTRY_COUNT: u32 = 128;
+pub(crate) const GPT_ENTRY_SIZE: u32 = 128;
+/// Created partitions start at a multiple of this many sectors, 1 MiB.
+pub(crate) const PARTITION_ALIGNMENT: u64 = 2048;
+/// The MBR partition types of extended partitions, which hold the EBR chain of the logical partitions.
+pub(crate) const MBR_PARTITION_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+/// The most EBRs followed, longer chains are considered corrupted.
+pub(crate) const MAX_LOGICAL_PARTITIONS: usize = 128;
+/// The geometry CHS addresses are computed with.
+pub(crate) const CHS_HEADS: u64 = 255;
+pub(crate) const CHS_SECTORS_PER_TRACK: u64 = 63;
+pub(crate) const CHS_MAX_CYLINDERS: u64 = 1024;
+
+/// The number of sectors cached per disk, 512 KiB.
+pub(crate) const CACHE_SECTORS: usize = 1024;
+/// The number of timer ticks between two syncs of the block caches.
+pub(crate) const SYNC_INTERVAL_TICKS: u64 = milliseconds_to_ticks(5000);
+
+/// The number of status register reads before polling gives up, a read takes about a microsecond.
+pub(crate) const POLL_ITERATIONS: usize = 1_000_000;
+/// The number of timer ticks to wait for the interrupt of a command.
+pub(crate) const INTERRUPT_TIMEOUT_TICKS: u64 = milliseconds_to_ticks(2000);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ATAError {
+    /// The device reported an error.
+    Device(ErrorRegisterFlags),
+    /// The device didn't respond in time.
+    Timeout,
+    /// The buffer doesn't hold a whole number of sectors.
+    InvalidBufferSize,
+    /// The sectors are beyond what the addressing mode of the device can reach.
+    AddressNotSupported,
+    /// The bus master reported an error during a DMA transfer.
+    Dma,
+}
+
+impl From<ATAError> for BlockDeviceError {
+    fn from(error: ATAError) -> Self {
+        match error {
+            ATAError::Timeout => BlockDeviceError::Timeout,
+            ATAError::InvalidBufferSize => BlockDeviceError::InvalidBufferSize,
+            ATAError::AddressNotSupported => BlockDeviceError::OutOfRange,
+            ATAError::Device(_) | ATAError::Dma => BlockDeviceError::DeviceError,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -64,11 +166,35 @@ pub enum ATAIdentifyError {
 pub enum ATACommands {
     Identify = 0xEC,
     WriteSectors = 0x30,
+    WriteSectorsExt = 0x34,
     ReadSectors = 0x20,
+    ReadSectorsExt = 0x24,
+    ReadDma = 0xC8,
+    ReadDmaExt = 0x25,
+    WriteDma = 0xCA,
+    WriteDmaExt = 0x35,
     CacheFlush = 0xE7,
+    CacheFlushExt = 0xEA,
+    SetFeatures = 0xEF,
 }
 
+/// The SET FEATURES subcommand selecting the transfer mode given in the sector count register.
+pub(crate) const SET_FEATURES_TRANSFER_MODE: u8 = 0x03;
+/// Added to the number of a UDMA mode to select it with `SET_FEATURES_TRANSFER_MODE`.
+pub(crate) const TRANSFER_MODE_UDMA: u8 = 0x40;
+
 lazy_static! {
-    pub static ref PRIMARY_ATA_BUS: Arc<Mutex<ATABus>> = Arc::new(Mutex::new(ATABus::new(0x1F0)));
-    pub static ref SECONDARY_ATA_BUS: Arc<Mutex<ATABus>> = Arc::new(Mutex::new(ATABus::new(0x170)));
+    /// The bus master registers of the primary channel, if an IDE controller supporting DMA was found.
+    static ref BUS_MASTER_PORT: Option<u16> = pci::find_bus_master_port();
+    /// The buses stay locked during transfers, processes waiting for them sleep.
+    pub static ref PRIMARY_ATA_BUS: Arc<SleepMutex<ATABus>> = Arc::new(SleepMutex::new(ATABus::new(
+        0x1F0,
+        &ATA_PRIMARY_INTERRUPT,
+        *BUS_MASTER_PORT
+    )));
+    pub static ref SECONDARY_ATA_BUS: Arc<SleepMutex<ATABus>> = Arc::new(SleepMutex::new(ATABus::new(
+        0x170,
+        &ATA_SECONDARY_INTERRUPT,
+        BUS_MASTER_PORT.map(|port| port + 0x08)
+    )));
 }
@@ -0,0 +1,81 @@
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc};
+use kernel::{
+    devfs::{self, BlockDeviceFile},
+    BlockDevice,
+};
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    constants::CACHE_SECTORS, mbr, ATABus, BlockCache, CachedDevice, PRIMARY_ATA_BUS,
+    SECONDARY_ATA_BUS,
+};
+
+/// The permissions reported for the disk and partition files.
+///
+/// The VFS doesn't check permissions and there are no users yet, so any process can read and
+/// write the raw disks.
+const PERMISSIONS: u16 = 0o600;
+
+lazy_static! {
+    /// The published disks and partitions by their device file names.
+    static ref DEVICES: Mutex<BTreeMap<String, CachedDevice>> = Mutex::new(BTreeMap::new());
+}
+
+/// Returns the disk or partition published with the device file name, like `hda1`, to mount a
+/// file system on it.
+///
+/// It's read and written through the disk's cache, like the device file.
+pub fn block_device(name: &str) -> Option<CachedDevice> {
+    DEVICES.lock().get(name).cloned()
+}
+
+/// Publishes the disks as `hda` to `hdd`, by their position on the buses, and their partitions as
+/// `hda1`, `hda2` and so on, numbered in the order of `get_partitions`.
+///
+/// The extended partition isn't published, its number is skipped so the logical partitions keep
+/// theirs. It only holds the EBR chain, which is changed through the partition table functions.
+///
+/// The files and `block_device` share a cache per disk, a disk published before keeps its cache.
+pub(crate) fn register_device_files() {
+    let positions = [
+        (&*PRIMARY_ATA_BUS, true, 'a'),
+        (&*PRIMARY_ATA_BUS, false, 'b'),
+        (&*SECONDARY_ATA_BUS, true, 'c'),
+        (&*SECONDARY_ATA_BUS, false, 'd'),
+    ];
+    for (bus, master, letter) in positions {
+        let mut disk = match ATABus::get_disk(bus, master) {
+            Ok(disk) => disk,
+            Err(_) => continue,
+        };
+        let name = format!("hd{}", letter);
+        let registered = DEVICES.lock().get(&name).cloned();
+        let mut device = match registered {
+            Some(device) => device,
+            None => BlockCache::new(disk.clone(), CACHE_SECTORS),
+        };
+        // the partition table is read from the disk itself, so cached changes are written first
+        let _ = device.flush();
+        // a disk without a readable partition table is still published
+        let partitions = disk.get_partitions().unwrap_or_default();
+        for (index, partition) in partitions.into_iter().enumerate() {
+            if mbr::is_extended(&partition.descriptor) {
+                continue;
+            }
+            let partition_name = format!("{}{}", name, index + 1);
+            publish(partition_name, device.partition(&partition.descriptor));
+        }
+        publish(name, device);
+    }
+}
+
+fn publish(name: String, device: CachedDevice) {
+    devfs::register_device(
+        &name,
+        PERMISSIONS,
+        Arc::new(BlockDeviceFile::new(device.clone())),
+    )
+    .expect("Invalid device name");
+    DEVICES.lock().insert(name, device);
+}
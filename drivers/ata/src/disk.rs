@@ -1,42 +1,67 @@
 use alloc::{sync::Arc, vec::Vec};
-use spin::Mutex;
-use utils::array_combiner::Combiner;
+use kernel::{processes::SleepMutex, BlockDevice, BlockDeviceError};
 
 use crate::{
-    constants::ErrorRegisterFlags, ATABus, ATAPartition, DiskDescriptor, PartitionDescriptor,
-    PartitionIOError,
+    gpt, mbr::MbrTable, ATABus, ATAError, ATAPartition, DiskDescriptor, Guid, PartitionDescriptor,
+    PartitionIOError, SECTOR_SIZE,
 };
 
 #[derive(Clone)]
 pub struct ATADisk {
-    pub(crate) bus: Arc<Mutex<ATABus>>,
+    pub(crate) bus: Arc<SleepMutex<ATABus>>,
     pub descriptor: DiskDescriptor,
     pub(crate) master: bool,
 }
 
 impl ATADisk {
-    pub fn has_bootloader(&mut self) -> Result<bool, ErrorRegisterFlags> {
+    pub fn has_bootloader(&mut self) -> Result<bool, ATAError> {
         let buffer = self.read_sector(0)?;
         Ok(buffer[510] == 0x55 && buffer[511] == 0xAA)
     }
 
-    pub(crate) fn read_sector(&mut self, lba: u64) -> Result<[u8; 512], ErrorRegisterFlags> {
-        self.bus.lock().read_sector(self.master, lba)
+    /// Reads the sectors starting at `lba` into the buffer, its length must be a multiple of the sector size.
+    pub fn read_sectors(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), ATAError> {
+        self.bus
+            .lock()
+            .read_sectors(self.master, &self.descriptor, lba, buffer)
     }
 
-    pub(crate) fn write_sector(
-        &mut self,
-        lba: u64,
-        buffer: &[u8; 512],
-    ) -> Result<(), ErrorRegisterFlags> {
-        self.bus.lock().write_sector(self.master, lba, buffer)
+    /// Writes the buffer to the sectors starting at `lba`, its length must be a multiple of the sector size.
+    ///
+    /// The sectors may stay in the drive's cache until `flush` is called.
+    pub fn write_sectors(&mut self, lba: u64, buffer: &[u8]) -> Result<(), ATAError> {
+        self.bus
+            .lock()
+            .write_sectors(self.master, &self.descriptor, lba, buffer)
+    }
+
+    /// Makes the drive write the sectors in its cache to the disk.
+    pub fn flush(&mut self) -> Result<(), ATAError> {
+        self.bus
+            .lock()
+            .flush(self.master, self.descriptor.supports_lba_48)
+    }
+
+    pub(crate) fn read_sector(&mut self, lba: u64) -> Result<[u8; 512], ATAError> {
+        let mut buffer = [0u8; 512];
+        self.read_sectors(lba, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    pub(crate) fn write_sector(&mut self, lba: u64, buffer: &[u8; 512]) -> Result<(), ATAError> {
+        self.write_sectors(lba, buffer)
     }
 
-    pub fn get_partitions(&mut self) -> Result<Vec<ATAPartition>, ErrorRegisterFlags> {
-        let mbr = self.read_sector(0)?;
-        let descriptors = mbr[446..510]
-            .chunks(16)
-            .filter_map(PartitionDescriptor::from_bytes);
+    /// Returns the partitions of the disk, read from its GPT if the MBR is a protective one.
+    ///
+    /// The partitions of an MBR are the primary ones, including the extended one, followed by the
+    /// logical ones.
+    pub fn get_partitions(&mut self) -> Result<Vec<ATAPartition>, PartitionIOError> {
+        let descriptors = match MbrTable::read(self) {
+            Ok(table) => table.partitions(),
+            Err(PartitionIOError::InvalidPartitionTable) => gpt::read_partitions(self)?,
+            Err(error) => return Err(error),
+        };
         let mut partitions = Vec::new();
         for descriptor in descriptors {
             partitions.push(ATAPartition {
@@ -47,64 +72,115 @@ impl ATADisk {
         Ok(partitions)
     }
 
+    /// Adds a primary partition to the MBR, in the first gap large enough for it.
+    ///
+    /// An extended partition is created if the type is an extended one, a disk has at most one.
     pub fn create_partition(
         &mut self,
         sectors: u32,
         partition_type: u8,
     ) -> Result<ATAPartition, PartitionIOError> {
-        let mut mbr = self
-            .read_sector(0)
-            .map_err(|err| PartitionIOError::ATAError(err))?;
-        let descriptors: Vec<PartitionDescriptor> = mbr[446..510]
-            .chunks(16)
-            .filter_map(PartitionDescriptor::from_bytes)
-            .collect();
-        if descriptors.len() >= 4 {
-            return Err(PartitionIOError::TooManyPartitions);
-        }
+        let mut table = MbrTable::read(self)?;
+        let descriptor = table.create_primary(sectors, partition_type)?;
+        table.write(self)?;
+        Ok(ATAPartition {
+            disk: self.clone(),
+            descriptor,
+        })
+    }
 
-        let start_sector_bytes = {
-            let start_sector = (descriptors
-                .iter()
-                .map(|d| d.start_lba + d.sectors)
-                .max()
-                .unwrap_or(0)
-                + 1) as u32;
-            u32::to_le_bytes(start_sector)
-        };
+    /// Adds a logical partition to the extended partition of the MBR, in the first gap large enough
+    /// for it and its EBR.
+    pub fn create_logical_partition(
+        &mut self,
+        sectors: u32,
+        partition_type: u8,
+    ) -> Result<ATAPartition, PartitionIOError> {
+        let mut table = MbrTable::read(self)?;
+        let descriptor = table.create_logical(sectors, partition_type)?;
+        table.write(self)?;
+        Ok(ATAPartition {
+            disk: self.clone(),
+            descriptor,
+        })
+    }
+
+    /// Removes the partition from the MBR, the logical partitions are removed with the extended one.
+    pub fn delete_partition(
+        &mut self,
+        descriptor: &PartitionDescriptor,
+    ) -> Result<(), PartitionIOError> {
+        let mut table = MbrTable::read(self)?;
+        table.delete(descriptor.start_lba)?;
+        table.write(self)
+    }
 
-        let sectors_bytes = u32::to_le_bytes(sectors);
+    /// Changes the number of sectors of an MBR partition, it can only grow into free space.
+    pub fn resize_partition(
+        &mut self,
+        descriptor: &PartitionDescriptor,
+        sectors: u32,
+    ) -> Result<ATAPartition, PartitionIOError> {
+        let mut table = MbrTable::read(self)?;
+        let descriptor = table.resize(descriptor.start_lba, sectors)?;
+        table.write(self)?;
+        Ok(ATAPartition {
+            disk: self.clone(),
+            descriptor,
+        })
+    }
 
-        let partition_bytes = Combiner::new()
-            .with(&[0x00, 0xFF, 0xFF, 0xFF])
-            .with(&[partition_type, 0xFF, 0xFF, 0xFF])
-            .with(&start_sector_bytes)
-            .with(&sectors_bytes)
-            .build::<16>()
-            .expect("Wrong number of bytes for a partition descriptor");
+    /// Sets the bootable flag of a primary MBR partition, clearing it on the other ones.
+    pub fn set_bootable(
+        &mut self,
+        descriptor: &PartitionDescriptor,
+        bootable: bool,
+    ) -> Result<(), PartitionIOError> {
+        let mut table = MbrTable::read(self)?;
+        table.set_bootable(descriptor.start_lba, bootable)?;
+        table.write(self)
+    }
 
-        let descriptor = PartitionDescriptor::from_bytes(&partition_bytes);
-        if descriptor.is_none() {
-            return Err(PartitionIOError::Unknown);
-        }
-        let descriptor = descriptor.unwrap();
-        let partition_free_index = mbr[446..510]
-            .chunks(16)
-            .enumerate()
-            .map(|(index, val)| (PartitionDescriptor::from_bytes(val), index))
-            .find(|(val, _)| val.is_none())
-            .map(|(_, i)| i);
-        if partition_free_index.is_none() {
-            return Err(PartitionIOError::Unknown);
-        }
-        let partition_free_index = partition_free_index.unwrap();
-        mbr[446 + partition_free_index * 16..446 + partition_free_index * 16 + 16]
-            .copy_from_slice(&partition_bytes);
-        self.write_sector(0, &mbr)
-            .map_err(|err| PartitionIOError::ATAError(err))?;
+    /// Replaces the partition table with an empty GPT and a protective MBR, the boot code is kept.
+    pub fn create_gpt(&mut self) -> Result<(), PartitionIOError> {
+        gpt::create_table(self)
+    }
+
+    /// Adds a partition to the GPT of the disk, after the last partition.
+    ///
+    /// The name is truncated to the 36 UTF-16 code units GPT stores.
+    pub fn create_gpt_partition(
+        &mut self,
+        sectors: u64,
+        type_guid: Guid,
+        name: &str,
+    ) -> Result<ATAPartition, PartitionIOError> {
+        let descriptor = gpt::create_partition(self, sectors, type_guid, name)?;
         Ok(ATAPartition {
             disk: self.clone(),
             descriptor,
         })
     }
 }
+
+impl BlockDevice for ATADisk {
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.descriptor.sector_count()
+    }
+
+    fn read_blocks(&mut self, block: u64, buffer: &mut [u8]) -> Result<(), BlockDeviceError> {
+        Ok(self.read_sectors(block, buffer)?)
+    }
+
+    fn write_blocks(&mut self, block: u64, buffer: &[u8]) -> Result<(), BlockDeviceError> {
+        Ok(self.write_sectors(block, buffer)?)
+    }
+
+    fn flush(&mut self) -> Result<(), BlockDeviceError> {
+        Ok(ATADisk::flush(self)?)
+    }
+}
@@ -27,6 +27,33 @@ impl DiskDescriptor {
         core::str::from_utf8(&self.model_number_bytes).unwrap()
     }
 
+    /// Returns the number of sectors that can be addressed with the addressing mode of the device.
+    pub fn sector_count(&self) -> u64 {
+        if self.supports_lba_48 {
+            self.lba_48_addressable_sectors
+        } else {
+            self.lba_28_addressable_sectors as u64
+        }
+    }
+
+    /// Returns whether the device supports a UDMA mode.
+    pub fn supports_dma(&self) -> bool {
+        self.udma_available_modes.iter().any(|&available| available)
+    }
+
+    /// Returns whether a UDMA mode is selected, so the device can transfer sectors with DMA.
+    pub fn dma_enabled(&self) -> bool {
+        self.udma_current_mode != 0
+    }
+
+    /// Returns the fastest UDMA mode the device supports.
+    pub(crate) fn fastest_udma_mode(&self) -> Option<u8> {
+        self.udma_available_modes
+            .iter()
+            .rposition(|&available| available)
+            .map(|mode| mode as u8)
+    }
+
     pub(crate) fn from_bytes(buffer: [u16; 256]) -> Self {
         let fixed_device = buffer[0] & 0x0040 != 0;
         let removable_media = buffer[0] & 0x0080 != 0;
@@ -0,0 +1,432 @@
+use alloc::{string::String, vec, vec::Vec};
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use kernel::BlockDevice;
+use utils::{byte_reader::ByteReader, crc32::crc32, get_current_tick};
+
+use crate::{
+    constants::{
+        GPT_ENTRY_COUNT, GPT_ENTRY_SIZE, GPT_REVISION, GPT_SIGNATURE, MBR_PARTITION_TYPE_GPT,
+        PARTITION_ALIGNMENT, SECTOR_SIZE,
+    },
+    PartitionDescriptor, PartitionIOError,
+};
+
+/// The size of the header, the rest of its sector is reserved.
+const HEADER_SIZE: usize = 92;
+/// The largest partition entry array read, larger ones are considered corrupted.
+const MAX_ENTRIES_SIZE: usize = 1024 * 1024;
+/// The offset of the name in a partition entry and its length in UTF-16 code units.
+const NAME_OFFSET: usize = 56;
+const NAME_LENGTH: usize = 36;
+/// Set in the attributes of a partition the legacy BIOS can boot from.
+const ATTRIBUTE_LEGACY_BOOTABLE: u64 = 1 << 2;
+
+/// A globally unique identifier, stored in the mixed endian layout of GPT.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub const UNUSED: Guid = Guid([0; 16]);
+    /// EFI system partition, C12A7328-F81F-11D2-BA4B-00A0C93EC93B
+    pub const EFI_SYSTEM: Guid = Guid([
+        0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11, 0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9,
+        0x3B,
+    ]);
+    /// Microsoft basic data partition, used for FAT, EBD0A0A2-B9E5-4433-87C0-68B6B72699C7
+    pub const BASIC_DATA: Guid = Guid([
+        0xA2, 0xA0, 0xD0, 0xEB, 0xE5, 0xB9, 0x33, 0x44, 0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99,
+        0xC7,
+    ]);
+    /// Linux filesystem data, used for ext2, 0FC63DAF-8483-4772-8E79-3D69D8477DE4
+    pub const LINUX_FILESYSTEM: Guid = Guid([
+        0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47, 0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D,
+        0xE4,
+    ]);
+
+    /// Generates a version 4 GUID.
+    ///
+    /// The bits are derived from the CPU's time stamp counter, they are unique but not suited for cryptography.
+    pub fn generate() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut state =
+            get_current_tick() ^ COUNTER.fetch_add(1, Ordering::Relaxed).rotate_left(32);
+        let mut bytes = [0u8; 16];
+        for half in bytes.chunks_exact_mut(8) {
+            half.copy_from_slice(&split_mix_64(&mut state).to_le_bytes());
+        }
+        bytes[7] = (bytes[7] & 0x0F) | 0x40;
+        bytes[8] = (bytes[8] & 0x3F) | 0x80;
+        Guid(bytes)
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            b[8],
+            b[9]
+        )?;
+        b[10..]
+            .iter()
+            .try_for_each(|byte| write!(f, "{:02X}", byte))
+    }
+}
+
+/// The GPT specific information of a partition.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GptPartitionInfo {
+    pub type_guid: Guid,
+    pub partition_guid: Guid,
+    pub attributes: u64,
+    pub name: String,
+}
+
+/// A GPT header, the primary one is in the second sector of the disk and the backup in the last one.
+#[derive(Clone, Copy)]
+struct GptHeader {
+    current_lba: u64,
+    backup_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    disk_guid: Guid,
+    entries_lba: u64,
+    entry_count: u32,
+    entry_size: u32,
+    entries_crc: u32,
+}
+
+impl GptHeader {
+    /// Parses the header in the sector, returns `None` if its signature or checksum is wrong.
+    fn from_bytes(sector: &[u8; SECTOR_SIZE]) -> Option<Self> {
+        let mut reader = ByteReader::of(sector);
+        if reader.read_slice::<8>() != GPT_SIGNATURE {
+            return None;
+        }
+        let _revision = reader.read_u32();
+        let header_size = reader.read_u32() as usize;
+        let header_crc = reader.read_u32();
+        if !(HEADER_SIZE..=SECTOR_SIZE).contains(&header_size) {
+            return None;
+        }
+        let mut header_bytes = sector[..header_size].to_vec();
+        header_bytes[16..20].fill(0);
+        if crc32(&header_bytes) != header_crc {
+            return None;
+        }
+        let _reserved = reader.read_u32();
+        let header = GptHeader {
+            current_lba: reader.read_u64(),
+            backup_lba: reader.read_u64(),
+            first_usable_lba: reader.read_u64(),
+            last_usable_lba: reader.read_u64(),
+            disk_guid: Guid(reader.read_slice::<16>()),
+            entries_lba: reader.read_u64(),
+            entry_count: reader.read_u32(),
+            entry_size: reader.read_u32(),
+            entries_crc: reader.read_u32(),
+        };
+        let entries_size = header.entries_size();
+        if header.entry_size < GPT_ENTRY_SIZE
+            || header.entry_size % 8 != 0
+            || entries_size > MAX_ENTRIES_SIZE
+        {
+            return None;
+        }
+        Some(header)
+    }
+
+    fn to_bytes(&self) -> [u8; SECTOR_SIZE] {
+        let mut sector = [0u8; SECTOR_SIZE];
+        sector[0..8].copy_from_slice(&GPT_SIGNATURE);
+        sector[8..12].copy_from_slice(&GPT_REVISION.to_le_bytes());
+        sector[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        sector[24..32].copy_from_slice(&self.current_lba.to_le_bytes());
+        sector[32..40].copy_from_slice(&self.backup_lba.to_le_bytes());
+        sector[40..48].copy_from_slice(&self.first_usable_lba.to_le_bytes());
+        sector[48..56].copy_from_slice(&self.last_usable_lba.to_le_bytes());
+        sector[56..72].copy_from_slice(&self.disk_guid.0);
+        sector[72..80].copy_from_slice(&self.entries_lba.to_le_bytes());
+        sector[80..84].copy_from_slice(&self.entry_count.to_le_bytes());
+        sector[84..88].copy_from_slice(&self.entry_size.to_le_bytes());
+        sector[88..92].copy_from_slice(&self.entries_crc.to_le_bytes());
+        let header_crc = crc32(&sector[..HEADER_SIZE]);
+        sector[16..20].copy_from_slice(&header_crc.to_le_bytes());
+        sector
+    }
+
+    /// Returns the size of the partition entry array in bytes.
+    fn entries_size(&self) -> usize {
+        self.entry_count as usize * self.entry_size as usize
+    }
+
+    /// Returns the number of sectors the partition entry array takes.
+    fn entries_sectors(&self) -> u64 {
+        ((self.entries_size() + SECTOR_SIZE - 1) / SECTOR_SIZE) as u64
+    }
+}
+
+/// A header and its partition entry array.
+struct GptTable {
+    header: GptHeader,
+    /// The entries, padded to whole sectors.
+    entries: Vec<u8>,
+}
+
+impl GptTable {
+    fn entries(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries[..self.header.entries_size()].chunks_exact(self.header.entry_size as usize)
+    }
+
+    fn entry_mut(&mut self, index: usize) -> &mut [u8] {
+        let size = self.header.entry_size as usize;
+        &mut self.entries[index * size..(index + 1) * size]
+    }
+
+    /// Returns the descriptors of the used entries.
+    fn partitions(&self) -> Vec<PartitionDescriptor> {
+        self.entries()
+            .filter_map(|entry| parse_entry(entry, &self.header))
+            .collect()
+    }
+}
+
+/// Parses a partition entry, returns `None` if it's unused or its range is corrupted or outside the
+/// usable sectors of the header.
+fn parse_entry(entry: &[u8], header: &GptHeader) -> Option<PartitionDescriptor> {
+    let mut reader = ByteReader::of(entry);
+    let type_guid = Guid(reader.read_slice::<16>());
+    if type_guid == Guid::UNUSED {
+        return None;
+    }
+    let partition_guid = Guid(reader.read_slice::<16>());
+    let first_lba = reader.read_u64();
+    let last_lba = reader.read_u64();
+    let attributes = reader.read_u64();
+    let end_lba = last_lba.checked_add(1)?;
+    if end_lba <= first_lba
+        || first_lba < header.first_usable_lba
+        || last_lba > header.last_usable_lba
+    {
+        return None;
+    }
+    let name_units = entry[NAME_OFFSET..NAME_OFFSET + NAME_LENGTH * 2]
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0);
+    let name = char::decode_utf16(name_units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    Some(PartitionDescriptor {
+        bootable: attributes & ATTRIBUTE_LEGACY_BOOTABLE != 0,
+        file_system: MBR_PARTITION_TYPE_GPT,
+        start_lba: first_lba,
+        sectors: end_lba - first_lba,
+        gpt: Some(GptPartitionInfo {
+            type_guid,
+            partition_guid,
+            attributes,
+            name,
+        }),
+    })
+}
+
+/// Reads the partition table at the passed sector and checks its checksums.
+fn read_table_at<D: BlockDevice>(
+    disk: &mut D,
+    lba: u64,
+) -> Result<Option<GptTable>, PartitionIOError> {
+    let mut sector = [0u8; SECTOR_SIZE];
+    disk.read_blocks(lba, &mut sector)?;
+    let header = match GptHeader::from_bytes(&sector) {
+        Some(header) if header.current_lba == lba => header,
+        _ => return Ok(None),
+    };
+    let mut entries = vec![0u8; header.entries_sectors() as usize * SECTOR_SIZE];
+    disk.read_blocks(header.entries_lba, &mut entries)?;
+    if crc32(&entries[..header.entries_size()]) != header.entries_crc {
+        return Ok(None);
+    }
+    Ok(Some(GptTable { header, entries }))
+}
+
+/// Reads the primary partition table, or the backup one if the primary one is corrupted or can't be read.
+///
+/// Fails only if neither copy can be used, with the first read error if there was one.
+fn read_table<D: BlockDevice>(disk: &mut D) -> Result<GptTable, PartitionIOError> {
+    let primary = match read_table_at(disk, 1) {
+        Ok(Some(table)) => return Ok(table),
+        primary => primary,
+    };
+    let last_lba = disk.block_count().saturating_sub(1);
+    match (primary, read_table_at(disk, last_lba)) {
+        (_, Ok(Some(table))) => Ok(table),
+        (Err(error), _) | (_, Err(error)) => Err(error),
+        _ => Err(PartitionIOError::InvalidPartitionTable),
+    }
+}
+
+/// Writes both copies of the partition table, the backup one first.
+///
+/// Fails with `NotEnoughSpace` if the disk can't hold both copies, and with `InvalidPartitionTable`
+/// if the usable sectors of the table overlap them, e.g. because the disk shrank.
+fn write_table<D: BlockDevice>(disk: &mut D, table: &GptTable) -> Result<(), PartitionIOError> {
+    let entries_sectors = table.header.entries_sectors();
+    // the MBR, both headers and both entry arrays
+    let last_lba = match disk.block_count().checked_sub(1) {
+        Some(last_lba) if last_lba >= 2 + 2 * entries_sectors => last_lba,
+        _ => return Err(PartitionIOError::NotEnoughSpace),
+    };
+    if table.header.first_usable_lba < 2 + entries_sectors
+        || table.header.last_usable_lba >= last_lba - entries_sectors
+    {
+        return Err(PartitionIOError::InvalidPartitionTable);
+    }
+    let entries_crc = crc32(&table.entries[..table.header.entries_size()]);
+    let primary = GptHeader {
+        current_lba: 1,
+        backup_lba: last_lba,
+        entries_lba: 2,
+        entries_crc,
+        ..table.header
+    };
+    let backup = GptHeader {
+        current_lba: last_lba,
+        backup_lba: 1,
+        entries_lba: last_lba - entries_sectors,
+        entries_crc,
+        ..table.header
+    };
+    for header in [backup, primary] {
+        disk.write_blocks(header.entries_lba, &table.entries)?;
+        disk.write_blocks(header.current_lba, &header.to_bytes())?;
+    }
+    Ok(disk.flush()?)
+}
+
+/// Returns the partitions of the GPT of the disk, which must have 512 byte sectors.
+pub fn read_partitions<D: BlockDevice>(
+    disk: &mut D,
+) -> Result<Vec<PartitionDescriptor>, PartitionIOError> {
+    Ok(read_table(disk)?.partitions())
+}
+
+/// Writes an empty GPT and a protective MBR to the disk, the boot code in the first sector is kept.
+pub fn create_table<D: BlockDevice>(disk: &mut D) -> Result<(), PartitionIOError> {
+    let sector_count = disk.block_count();
+    let entries_sectors = (GPT_ENTRY_COUNT * GPT_ENTRY_SIZE) as u64 / SECTOR_SIZE as u64;
+    // the MBR, both headers and both entry arrays, and at least one usable sector
+    if sector_count < 3 + 2 * entries_sectors + 1 {
+        return Err(PartitionIOError::NotEnoughSpace);
+    }
+    let last_lba = sector_count - 1;
+
+    let mut mbr = [0u8; SECTOR_SIZE];
+    disk.read_blocks(0, &mut mbr)?;
+    mbr[446..510].fill(0);
+    let protective_sectors = (sector_count - 1).min(u32::MAX as u64) as u32;
+    mbr[446..462].copy_from_slice(&[
+        0x00,
+        0x00,
+        0x02,
+        0x00,
+        MBR_PARTITION_TYPE_GPT,
+        0xFF,
+        0xFF,
+        0xFF,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        protective_sectors as u8,
+        (protective_sectors >> 8) as u8,
+        (protective_sectors >> 16) as u8,
+        (protective_sectors >> 24) as u8,
+    ]);
+    mbr[510] = 0x55;
+    mbr[511] = 0xAA;
+    disk.write_blocks(0, &mbr)?;
+
+    let table = GptTable {
+        header: GptHeader {
+            current_lba: 1,
+            backup_lba: last_lba,
+            first_usable_lba: 2 + entries_sectors,
+            last_usable_lba: last_lba - entries_sectors - 1,
+            disk_guid: Guid::generate(),
+            entries_lba: 2,
+            entry_count: GPT_ENTRY_COUNT,
+            entry_size: GPT_ENTRY_SIZE,
+            entries_crc: 0,
+        },
+        entries: vec![0u8; entries_sectors as usize * SECTOR_SIZE],
+    };
+    write_table(disk, &table)
+}
+
+/// Adds a partition of `sectors` sectors after the last one, aligned to `PARTITION_ALIGNMENT`.
+///
+/// The name is truncated to the 36 UTF-16 code units an entry can hold.
+pub fn create_partition<D: BlockDevice>(
+    disk: &mut D,
+    sectors: u64,
+    type_guid: Guid,
+    name: &str,
+) -> Result<PartitionDescriptor, PartitionIOError> {
+    let mut table = read_table(disk)?;
+    if sectors == 0 || type_guid == Guid::UNUSED {
+        return Err(PartitionIOError::Unknown);
+    }
+    let index = table
+        .entries()
+        .position(|entry| entry[..16] == Guid::UNUSED.0)
+        .ok_or(PartitionIOError::TooManyPartitions)?;
+    let start_lba = table
+        .partitions()
+        .iter()
+        .map(|partition| partition.start_lba + partition.sectors)
+        .max()
+        .unwrap_or(0)
+        .max(table.header.first_usable_lba);
+    let start_lba =
+        (start_lba + PARTITION_ALIGNMENT - 1) / PARTITION_ALIGNMENT * PARTITION_ALIGNMENT;
+    let last_lba = start_lba + sectors - 1;
+    if last_lba > table.header.last_usable_lba {
+        return Err(PartitionIOError::NotEnoughSpace);
+    }
+
+    let header = table.header;
+    let entry = table.entry_mut(index);
+    entry.fill(0);
+    entry[0..16].copy_from_slice(&type_guid.0);
+    entry[16..32].copy_from_slice(&Guid::generate().0);
+    entry[32..40].copy_from_slice(&start_lba.to_le_bytes());
+    entry[40..48].copy_from_slice(&last_lba.to_le_bytes());
+    for (unit, bytes) in name
+        .encode_utf16()
+        .take(NAME_LENGTH)
+        .zip(entry[NAME_OFFSET..NAME_OFFSET + NAME_LENGTH * 2].chunks_exact_mut(2))
+    {
+        bytes.copy_from_slice(&unit.to_le_bytes());
+    }
+    let descriptor = parse_entry(entry, &header).ok_or(PartitionIOError::Unknown)?;
+    write_table(disk, &table)?;
+    Ok(descriptor)
+}
+
+/// Advances the state and returns the next value of the SplitMix64 generator.
+fn split_mix_64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -7,14 +7,29 @@ use kernel::structures::{driver::Driver, kernel_information::KernelInformation};
 extern crate alloc;
 
 mod constants;
-pub use constants::{ATAIdentifyError, PRIMARY_ATA_BUS, SECONDARY_ATA_BUS};
+pub use constants::{ATAError, ATAIdentifyError, PRIMARY_ATA_BUS, SECONDARY_ATA_BUS, SECTOR_SIZE};
 
 mod bus;
 pub use bus::ATABus;
 
+mod block_cache;
+pub use block_cache::{sync_all, BlockCache, CachedDevice};
+
+mod bus_master;
+
+mod device_files;
+pub use device_files::block_device;
+
+mod pci;
+
 mod disk_descriptor;
 pub use disk_descriptor::DiskDescriptor;
 
+pub mod gpt;
+pub use gpt::{GptPartitionInfo, Guid};
+
+mod mbr;
+
 mod partition_descriptor;
 pub use partition_descriptor::PartitionDescriptor;
 
@@ -30,6 +45,8 @@ mod debug;
 pub extern "C" fn driver_init(_kernel_info: KernelInformation) -> Driver {
     #[cfg(debug_assertions)]
     debug::debug_disks();
+    block_cache::start_sync_thread();
+    device_files::register_device_files();
     Driver {
         signature: [
             0xf0, 0xf1, 0xf2, 0xf3, 0xf0, 0xf1, 0xf2, 0xf3, 0xf0, 0xf1, 0xf2, 0xf3, 0xf0, 0xf1,
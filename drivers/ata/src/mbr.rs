@@ -0,0 +1,401 @@
+use alloc::vec::Vec;
+
+use crate::{
+    constants::{
+        MAX_LOGICAL_PARTITIONS, MBR_PARTITION_TYPES_EXTENDED, MBR_PARTITION_TYPE_GPT,
+        PARTITION_ALIGNMENT, SECTOR_SIZE,
+    },
+    ATADisk, PartitionDescriptor, PartitionIOError,
+};
+
+/// The offset of the partition entries in an MBR or EBR.
+const ENTRIES_OFFSET: usize = 446;
+const ENTRY_SIZE: usize = 16;
+
+/// The partitions of a disk partitioned with an MBR.
+///
+/// The logical partitions are described by a chain of EBRs inside the extended partition. Every EBR
+/// holds the entry of a logical partition, relative to the EBR, and the link to the next EBR, relative
+/// to the extended partition.
+pub(crate) struct MbrTable {
+    /// The first sector of the disk, its boot code is kept when the table is written.
+    mbr: [u8; SECTOR_SIZE],
+    primaries: [Option<PartitionDescriptor>; 4],
+    /// The logical partitions and the sectors of their EBRs, sorted by their address.
+    logicals: Vec<(u64, PartitionDescriptor)>,
+    /// The end of the space partitions can be placed in, the 32 bit LBA of an MBR can't go further.
+    disk_end: u64,
+}
+
+impl MbrTable {
+    /// Reads the MBR and follows the EBR chain of the extended partition.
+    ///
+    /// Returns `InvalidPartitionTable` if the MBR only protects a GPT.
+    pub(crate) fn read(disk: &mut ATADisk) -> Result<Self, PartitionIOError> {
+        let mbr = disk.read_sector(0).map_err(PartitionIOError::ATAError)?;
+        let mut primaries = [None, None, None, None];
+        for (primary, bytes) in primaries.iter_mut().zip(entries(&mbr)) {
+            *primary = PartitionDescriptor::from_bytes(bytes);
+        }
+        let mut table = MbrTable {
+            mbr,
+            primaries,
+            logicals: Vec::new(),
+            disk_end: disk.descriptor.sector_count().min(1 << 32),
+        };
+        if table.is_protective() {
+            return Err(PartitionIOError::InvalidPartitionTable);
+        }
+
+        if let Some(extended) = table.extended().cloned() {
+            let mut ebr_lba = extended.start_lba;
+            for _ in 0..MAX_LOGICAL_PARTITIONS {
+                let ebr = disk
+                    .read_sector(ebr_lba)
+                    .map_err(PartitionIOError::ATAError)?;
+                if ebr[510] != 0x55 || ebr[511] != 0xAA {
+                    break;
+                }
+                let mut ebr_entries = entries(&ebr);
+                let logical = ebr_entries.next().and_then(PartitionDescriptor::from_bytes);
+                if let Some(mut logical) = logical.filter(|logical| logical.file_system != 0) {
+                    logical.start_lba += ebr_lba;
+                    table.logicals.push((ebr_lba, logical));
+                }
+                match ebr_entries.next().and_then(PartitionDescriptor::from_bytes) {
+                    // the links only go forward, anything else would be a loop
+                    Some(link)
+                        if is_extended(&link) && extended.start_lba + link.start_lba > ebr_lba =>
+                    {
+                        ebr_lba = extended.start_lba + link.start_lba;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// Returns whether the MBR only protects a GPT.
+    fn is_protective(&self) -> bool {
+        self.primaries
+            .iter()
+            .flatten()
+            .any(|primary| primary.file_system == MBR_PARTITION_TYPE_GPT)
+    }
+
+    /// Returns the primary partitions, including the extended one, followed by the logical partitions.
+    pub(crate) fn partitions(&self) -> Vec<PartitionDescriptor> {
+        self.primaries
+            .iter()
+            .flatten()
+            .chain(self.logicals.iter().map(|(_, logical)| logical))
+            .cloned()
+            .collect()
+    }
+
+    fn extended(&self) -> Option<&PartitionDescriptor> {
+        self.primaries
+            .iter()
+            .flatten()
+            .find(|primary| is_extended(primary))
+    }
+
+    /// Adds a primary partition in the first large enough gap, an extended one if the type is extended.
+    pub(crate) fn create_primary(
+        &mut self,
+        sectors: u32,
+        partition_type: u8,
+    ) -> Result<PartitionDescriptor, PartitionIOError> {
+        if sectors == 0 {
+            return Err(PartitionIOError::Unknown);
+        }
+        if partition_type == MBR_PARTITION_TYPE_GPT
+            || (MBR_PARTITION_TYPES_EXTENDED.contains(&partition_type) && self.extended().is_some())
+        {
+            return Err(PartitionIOError::InvalidPartitionTable);
+        }
+        let index = self
+            .primaries
+            .iter()
+            .position(|primary| primary.is_none())
+            .ok_or(PartitionIOError::TooManyPartitions)?;
+        let used: Vec<(u64, u64)> = self.primaries.iter().flatten().map(range).collect();
+        let start_lba = first_fit(used, PARTITION_ALIGNMENT, self.disk_end, sectors as u64)
+            .ok_or(PartitionIOError::NotEnoughSpace)?;
+        let descriptor = PartitionDescriptor {
+            bootable: false,
+            file_system: partition_type,
+            start_lba,
+            sectors: sectors as u64,
+            gpt: None,
+        };
+        self.primaries[index] = Some(descriptor.clone());
+        Ok(descriptor)
+    }
+
+    /// Adds a logical partition in the first large enough gap of the extended partition.
+    ///
+    /// The partition starts `PARTITION_ALIGNMENT` sectors after its EBR.
+    pub(crate) fn create_logical(
+        &mut self,
+        sectors: u32,
+        partition_type: u8,
+    ) -> Result<PartitionDescriptor, PartitionIOError> {
+        if sectors == 0 {
+            return Err(PartitionIOError::Unknown);
+        }
+        if partition_type == MBR_PARTITION_TYPE_GPT
+            || MBR_PARTITION_TYPES_EXTENDED.contains(&partition_type)
+        {
+            return Err(PartitionIOError::InvalidPartitionTable);
+        }
+        let extended = self
+            .extended()
+            .cloned()
+            .ok_or(PartitionIOError::InvalidPartitionTable)?;
+        if self.logicals.len() >= MAX_LOGICAL_PARTITIONS {
+            return Err(PartitionIOError::TooManyPartitions);
+        }
+        let used: Vec<(u64, u64)> = self
+            .logicals
+            .iter()
+            .map(|(ebr_lba, logical)| (*ebr_lba, logical.start_lba + logical.sectors))
+            .collect();
+        let (extended_start, extended_end) = range(&extended);
+        let ebr_lba = first_fit(
+            used,
+            extended_start,
+            extended_end,
+            PARTITION_ALIGNMENT + sectors as u64,
+        )
+        .ok_or(PartitionIOError::NotEnoughSpace)?;
+        let descriptor = PartitionDescriptor {
+            bootable: false,
+            file_system: partition_type,
+            start_lba: ebr_lba + PARTITION_ALIGNMENT,
+            sectors: sectors as u64,
+            gpt: None,
+        };
+        let index = self
+            .logicals
+            .iter()
+            .position(|(lba, _)| *lba > ebr_lba)
+            .unwrap_or(self.logicals.len());
+        self.logicals.insert(index, (ebr_lba, descriptor.clone()));
+        Ok(descriptor)
+    }
+
+    /// Removes the partition starting at the passed sector, deleting the extended partition
+    /// deletes its logical partitions too.
+    pub(crate) fn delete(&mut self, start_lba: u64) -> Result<(), PartitionIOError> {
+        if let Some(index) = self.primary_index(start_lba) {
+            if self.primaries[index].as_ref().map_or(false, is_extended) {
+                self.logicals.clear();
+            }
+            self.primaries[index] = None;
+            return Ok(());
+        }
+        let index = self
+            .logical_index(start_lba)
+            .ok_or(PartitionIOError::PartitionNotFound)?;
+        self.logicals.remove(index);
+        Ok(())
+    }
+
+    /// Changes the size of the partition starting at the passed sector, its start stays the same.
+    ///
+    /// The partition can only grow into free space, an extended partition can't shrink below its
+    /// logical partitions.
+    pub(crate) fn resize(
+        &mut self,
+        start_lba: u64,
+        sectors: u32,
+    ) -> Result<PartitionDescriptor, PartitionIOError> {
+        if sectors == 0 {
+            return Err(PartitionIOError::Unknown);
+        }
+        let end = start_lba + sectors as u64;
+        let extended = self.extended().cloned();
+        let (used, region_end, minimum_end): (Vec<(u64, u64)>, u64, u64) =
+            if let Some(index) = self.primary_index(start_lba) {
+                let used = self
+                    .primaries
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .filter_map(|(_, primary)| primary.as_ref().map(range))
+                    .collect();
+                let minimum_end = match &self.primaries[index] {
+                    Some(primary) if is_extended(primary) => self
+                        .logicals
+                        .iter()
+                        .map(|(_, logical)| logical.start_lba + logical.sectors)
+                        .max()
+                        .unwrap_or(start_lba + 1),
+                    _ => 0,
+                };
+                (used, self.disk_end, minimum_end)
+            } else {
+                let extended = extended.ok_or(PartitionIOError::PartitionNotFound)?;
+                let index = self
+                    .logical_index(start_lba)
+                    .ok_or(PartitionIOError::PartitionNotFound)?;
+                let used = self
+                    .logicals
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .map(|(_, (ebr_lba, logical))| (*ebr_lba, logical.start_lba + logical.sectors))
+                    .collect();
+                (used, range(&extended).1, 0)
+            };
+        let overlaps = used
+            .iter()
+            .any(|&(used_start, used_end)| used_start < end && start_lba < used_end);
+        if end > region_end || overlaps {
+            return Err(PartitionIOError::NotEnoughSpace);
+        }
+        if end < minimum_end {
+            return Err(PartitionIOError::InvalidPartitionTable);
+        }
+        let descriptor = self
+            .descriptor_mut(start_lba)
+            .ok_or(PartitionIOError::PartitionNotFound)?;
+        descriptor.sectors = sectors as u64;
+        Ok(descriptor.clone())
+    }
+
+    /// Sets the bootable flag of the partition starting at the passed sector.
+    ///
+    /// Only a single primary partition is bootable, setting the flag clears it on the other ones.
+    pub(crate) fn set_bootable(
+        &mut self,
+        start_lba: u64,
+        bootable: bool,
+    ) -> Result<(), PartitionIOError> {
+        let index = self
+            .primary_index(start_lba)
+            .ok_or(PartitionIOError::PartitionNotFound)?;
+        for (other, primary) in self.primaries.iter_mut().enumerate() {
+            if let Some(primary) = primary {
+                if other == index {
+                    primary.bootable = bootable;
+                } else if bootable {
+                    primary.bootable = false;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the MBR and the EBR chain and flushes the disk.
+    pub(crate) fn write(&mut self, disk: &mut ATADisk) -> Result<(), PartitionIOError> {
+        for (index, primary) in self.primaries.iter().enumerate() {
+            let offset = ENTRIES_OFFSET + index * ENTRY_SIZE;
+            let bytes = primary
+                .as_ref()
+                .map_or([0; ENTRY_SIZE], |primary| primary.to_bytes(0));
+            self.mbr[offset..offset + ENTRY_SIZE].copy_from_slice(&bytes);
+        }
+        self.mbr[510] = 0x55;
+        self.mbr[511] = 0xAA;
+        disk.write_sector(0, &self.mbr)
+            .map_err(PartitionIOError::ATAError)?;
+
+        if let Some(extended) = self.extended().cloned() {
+            // the first EBR is always at the start of the extended partition, even without a partition
+            let mut ebrs: Vec<(u64, Option<&PartitionDescriptor>)> = self
+                .logicals
+                .iter()
+                .map(|(ebr_lba, logical)| (*ebr_lba, Some(logical)))
+                .collect();
+            if ebrs.first().map(|(ebr_lba, _)| *ebr_lba) != Some(extended.start_lba) {
+                ebrs.insert(0, (extended.start_lba, None));
+            }
+            for (index, (ebr_lba, logical)) in ebrs.iter().enumerate() {
+                let mut ebr = [0u8; SECTOR_SIZE];
+                if let Some(logical) = logical {
+                    ebr[ENTRIES_OFFSET..ENTRIES_OFFSET + ENTRY_SIZE]
+                        .copy_from_slice(&logical.to_bytes(*ebr_lba));
+                }
+                if let Some((next_lba, next)) = ebrs.get(index + 1) {
+                    let link = PartitionDescriptor {
+                        bootable: false,
+                        file_system: MBR_PARTITION_TYPES_EXTENDED[0],
+                        start_lba: *next_lba,
+                        sectors: next.map_or(1, |next| next.start_lba + next.sectors - next_lba),
+                        gpt: None,
+                    };
+                    ebr[ENTRIES_OFFSET + ENTRY_SIZE..ENTRIES_OFFSET + 2 * ENTRY_SIZE]
+                        .copy_from_slice(&link.to_bytes(extended.start_lba));
+                }
+                ebr[510] = 0x55;
+                ebr[511] = 0xAA;
+                disk.write_sector(*ebr_lba, &ebr)
+                    .map_err(PartitionIOError::ATAError)?;
+            }
+        }
+        disk.flush().map_err(PartitionIOError::ATAError)
+    }
+
+    fn primary_index(&self, start_lba: u64) -> Option<usize> {
+        self.primaries
+            .iter()
+            .position(|primary| matches!(primary, Some(primary) if primary.start_lba == start_lba))
+    }
+
+    fn logical_index(&self, start_lba: u64) -> Option<usize> {
+        self.logicals
+            .iter()
+            .position(|(_, logical)| logical.start_lba == start_lba)
+    }
+
+    fn descriptor_mut(&mut self, start_lba: u64) -> Option<&mut PartitionDescriptor> {
+        if let Some(index) = self.primary_index(start_lba) {
+            return self.primaries[index].as_mut();
+        }
+        let index = self.logical_index(start_lba)?;
+        Some(&mut self.logicals[index].1)
+    }
+}
+
+/// Returns the partition entries of an MBR or EBR.
+fn entries(sector: &[u8; SECTOR_SIZE]) -> impl Iterator<Item = &[u8]> {
+    sector[ENTRIES_OFFSET..ENTRIES_OFFSET + 4 * ENTRY_SIZE].chunks_exact(ENTRY_SIZE)
+}
+
+/// Returns whether the partition is the container of the logical partitions.
+pub(crate) fn is_extended(descriptor: &PartitionDescriptor) -> bool {
+    MBR_PARTITION_TYPES_EXTENDED.contains(&descriptor.file_system)
+}
+
+/// Returns the sectors the partition covers, the end is exclusive.
+fn range(descriptor: &PartitionDescriptor) -> (u64, u64) {
+    (
+        descriptor.start_lba,
+        descriptor.start_lba + descriptor.sectors,
+    )
+}
+
+/// Returns the first start aligned to `PARTITION_ALIGNMENT` in the region from `start` to `end`
+/// where `size` sectors don't overlap the used ranges.
+fn first_fit(mut used: Vec<(u64, u64)>, start: u64, end: u64, size: u64) -> Option<u64> {
+    used.sort_unstable();
+    let mut candidate = align_up(start);
+    for (used_start, used_end) in used {
+        if candidate + size <= used_start {
+            break;
+        }
+        candidate = candidate.max(align_up(used_end));
+    }
+    if candidate + size <= end {
+        Some(candidate)
+    } else {
+        None
+    }
+}
+
+fn align_up(lba: u64) -> u64 {
+    (lba + PARTITION_ALIGNMENT - 1) / PARTITION_ALIGNMENT * PARTITION_ALIGNMENT
+}
@@ -1,4 +1,6 @@
-use crate::{constants::ErrorRegisterFlags, ATADisk, DiskDescriptor, PartitionDescriptor};
+use kernel::{BlockDevice, BlockDeviceError};
+
+use crate::{ATADisk, ATAError, DiskDescriptor, PartitionDescriptor, SECTOR_SIZE};
 
 #[derive(Clone)]
 pub struct ATAPartition {
@@ -30,12 +32,94 @@ impl ATAPartition {
                 .map_err(|err| PartitionIOError::ATAError(err))
         }
     }
+
+    /// Reads the sectors starting at `lba` into the buffer, its length must be a multiple of the sector size.
+    pub fn read_sectors(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), PartitionIOError> {
+        self.check_range(lba, buffer.len())?;
+        self.disk
+            .read_sectors(lba + self.descriptor.start_lba, buffer)
+            .map_err(|err| PartitionIOError::ATAError(err))
+    }
+
+    /// Writes the buffer to the sectors starting at `lba`, its length must be a multiple of the sector size.
+    pub fn write_sectors(&mut self, lba: u64, buffer: &[u8]) -> Result<(), PartitionIOError> {
+        self.check_range(lba, buffer.len())?;
+        self.disk
+            .write_sectors(lba + self.descriptor.start_lba, buffer)
+            .map_err(|err| PartitionIOError::ATAError(err))
+    }
+
+    /// Makes the drive write the sectors in its cache to the disk.
+    pub fn flush(&mut self) -> Result<(), PartitionIOError> {
+        self.disk
+            .flush()
+            .map_err(|err| PartitionIOError::ATAError(err))
+    }
+
+    fn check_range(&self, lba: u64, length: usize) -> Result<(), PartitionIOError> {
+        let sectors = (length / SECTOR_SIZE) as u64;
+        match lba.checked_add(sectors) {
+            Some(end) if end <= self.descriptor.sectors => Ok(()),
+            _ => Err(PartitionIOError::AddressNotInRange),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PartitionIOError {
-    ATAError(ErrorRegisterFlags),
+    ATAError(ATAError),
+    /// Reading or writing a block device failed, returned by the operations working on any block device.
+    Device(BlockDeviceError),
     AddressNotInRange,
     TooManyPartitions,
+    /// There's no free space large enough for the partition.
+    NotEnoughSpace,
+    /// No partition of the partition table starts at the passed sector.
+    PartitionNotFound,
+    /// The partition table is corrupted or of a different kind than the one the operation works on.
+    InvalidPartitionTable,
     Unknown,
 }
+
+impl From<BlockDeviceError> for PartitionIOError {
+    fn from(error: BlockDeviceError) -> Self {
+        PartitionIOError::Device(error)
+    }
+}
+
+impl From<PartitionIOError> for BlockDeviceError {
+    fn from(error: PartitionIOError) -> Self {
+        match error {
+            PartitionIOError::ATAError(error) => error.into(),
+            PartitionIOError::Device(error) => error,
+            PartitionIOError::AddressNotInRange => BlockDeviceError::OutOfRange,
+            PartitionIOError::TooManyPartitions
+            | PartitionIOError::NotEnoughSpace
+            | PartitionIOError::PartitionNotFound
+            | PartitionIOError::InvalidPartitionTable
+            | PartitionIOError::Unknown => BlockDeviceError::DeviceError,
+        }
+    }
+}
+
+impl BlockDevice for ATAPartition {
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.descriptor.sectors
+    }
+
+    fn read_blocks(&mut self, block: u64, buffer: &mut [u8]) -> Result<(), BlockDeviceError> {
+        Ok(self.read_sectors(block, buffer)?)
+    }
+
+    fn write_blocks(&mut self, block: u64, buffer: &[u8]) -> Result<(), BlockDeviceError> {
+        Ok(self.write_sectors(block, buffer)?)
+    }
+
+    fn flush(&mut self) -> Result<(), BlockDeviceError> {
+        Ok(ATAPartition::flush(self)?)
+    }
+}
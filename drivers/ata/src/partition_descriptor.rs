@@ -1,9 +1,19 @@
+use utils::array_combiner::Combiner;
+
+use crate::{
+    constants::{CHS_HEADS, CHS_MAX_CYLINDERS, CHS_SECTORS_PER_TRACK},
+    GptPartitionInfo,
+};
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct PartitionDescriptor {
     pub bootable: bool,
+    /// The MBR partition type, 0xEE for GPT partitions, which have their type in `gpt`.
     pub file_system: u8,
     pub start_lba: u64,
     pub sectors: u64,
+    /// The GUIDs and name of a GPT partition, `None` for MBR partitions.
+    pub gpt: Option<GptPartitionInfo>,
 }
 
 impl PartitionDescriptor {
@@ -16,6 +26,40 @@ impl PartitionDescriptor {
             file_system: bytes[4],
             start_lba: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as u64,
             sectors: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]) as u64,
+            gpt: None,
         })
     }
+
+    /// Encodes the descriptor as an MBR or EBR entry, whose start is relative to `base_lba`.
+    pub(crate) fn to_bytes(&self, base_lba: u64) -> [u8; 16] {
+        let start_chs = lba_to_chs(self.start_lba);
+        let end_chs = lba_to_chs(self.start_lba + self.sectors.max(1) - 1);
+        let bytes = Combiner::new()
+            .with(&[if self.bootable { 0x80 } else { 0x00 }])
+            .with(&start_chs)
+            .with(&[self.file_system])
+            .with(&end_chs)
+            .with(&((self.start_lba - base_lba) as u32).to_le_bytes())
+            .with(&(self.sectors as u32).to_le_bytes())
+            .build::<16>()
+            .expect("Wrong number of bytes for a partition descriptor");
+        bytes
+    }
+}
+
+/// Converts the address to the CHS address of the usual 255 heads and 63 sectors per track geometry.
+///
+/// Addresses beyond the 1024 cylinders CHS can reach are stored as the maximum, LBA is used for them.
+fn lba_to_chs(lba: u64) -> [u8; 3] {
+    let cylinder = lba / (CHS_HEADS * CHS_SECTORS_PER_TRACK);
+    if cylinder >= CHS_MAX_CYLINDERS {
+        return [0xFE, 0xFF, 0xFF];
+    }
+    let head = (lba / CHS_SECTORS_PER_TRACK) % CHS_HEADS;
+    let sector = lba % CHS_SECTORS_PER_TRACK + 1;
+    [
+        head as u8,
+        (sector as u8 & 0x3F) | ((cylinder >> 2) as u8 & 0xC0),
+        cylinder as u8,
+    ]
 }
@@ -0,0 +1,79 @@
+use x86_64::instructions::port::Port;
+
+use crate::constants::{
+    PCI_BUS_MASTER_ENABLE, PCI_CLASS_MASS_STORAGE, PCI_CONFIG_ADDRESS, PCI_CONFIG_DATA,
+    PCI_IO_SPACE_ENABLE, PCI_PROG_IF_BUS_MASTER, PCI_PROG_IF_PRIMARY_NATIVE,
+    PCI_PROG_IF_SECONDARY_NATIVE, PCI_SUBCLASS_IDE,
+};
+
+/// Reads a dword of the configuration space of a PCI function.
+unsafe fn read_config(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    Port::<u32>::new(PCI_CONFIG_ADDRESS).write(config_address(bus, device, function, offset));
+    Port::<u32>::new(PCI_CONFIG_DATA).read()
+}
+
+/// Writes a dword of the configuration space of a PCI function.
+unsafe fn write_config(bus: u8, device: u8, function: u8, offset: u8, value: u32) {
+    Port::<u32>::new(PCI_CONFIG_ADDRESS).write(config_address(bus, device, function, offset));
+    Port::<u32>::new(PCI_CONFIG_DATA).write(value);
+}
+
+fn config_address(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    1 << 31
+        | (bus as u32) << 16
+        | (device as u32) << 11
+        | (function as u32) << 8
+        | (offset & 0xFC) as u32
+}
+
+/// Looks for an IDE controller that supports bus mastering, like the PIIX one, and enables its bus master.
+///
+/// Only controllers with both channels in compatibility mode are used, since the buses are driven
+/// through the legacy ports. Without one, the transfers fall back to PIO.
+///
+/// Returns the I/O port of the bus master registers of the primary channel, the ones of the secondary
+/// channel follow 8 ports later.
+pub(crate) fn find_bus_master_port() -> Option<u16> {
+    for bus in 0..=255 {
+        for device in 0..32 {
+            for function in 0..8 {
+                unsafe {
+                    let vendor = read_config(bus, device, function, 0x00) & 0xFFFF;
+                    if vendor == 0xFFFF {
+                        if function == 0 {
+                            break;
+                        }
+                        continue;
+                    }
+                    let class = read_config(bus, device, function, 0x08);
+                    let class_code = (class >> 24) as u8;
+                    let subclass = (class >> 16) as u8;
+                    let prog_if = (class >> 8) as u8;
+                    if class_code != PCI_CLASS_MASS_STORAGE
+                        || subclass != PCI_SUBCLASS_IDE
+                        || prog_if & PCI_PROG_IF_BUS_MASTER == 0
+                        || prog_if & (PCI_PROG_IF_PRIMARY_NATIVE | PCI_PROG_IF_SECONDARY_NATIVE)
+                            != 0
+                    {
+                        continue;
+                    }
+                    // BAR4 holds the bus master registers, they are always in the I/O space
+                    let bar = read_config(bus, device, function, 0x20);
+                    if bar & 1 == 0 || bar & 0xFFFC == 0 {
+                        continue;
+                    }
+                    let command = read_config(bus, device, function, 0x04) & 0xFFFF;
+                    write_config(
+                        bus,
+                        device,
+                        function,
+                        0x04,
+                        command | PCI_IO_SPACE_ENABLE | PCI_BUS_MASTER_ENABLE,
+                    );
+                    return Some((bar & 0xFFFC) as u16);
+                }
+            }
+        }
+    }
+    None
+}
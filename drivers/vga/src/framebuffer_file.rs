@@ -0,0 +1,103 @@
+use alloc::sync::Arc;
+use kernel::{
+    devfs::Device,
+    structures::kernel_information::{KernelFrameBuffer, PixelFormat},
+    vfs::VfsError,
+};
+use spin::Mutex;
+use utils::ioctl;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::vga_device::VGADevice;
+
+/// Publishes the framebuffer as a file of its pixels, line after line.
+///
+/// Its geometry and pixel format are queried with the `FRAMEBUFFER` commands. The pixels are
+/// accessed through the device the logger draws on, which owns the framebuffer memory.
+pub(crate) struct FramebufferFile {
+    width: usize,
+    height: usize,
+    stride: usize,
+    bytes_per_pixel: usize,
+    format: PixelFormat,
+    device: Arc<Mutex<VGADevice>>,
+}
+
+impl FramebufferFile {
+    pub(crate) fn new(buffer: &KernelFrameBuffer, device: Arc<Mutex<VGADevice>>) -> Self {
+        FramebufferFile {
+            width: buffer.width,
+            height: buffer.height,
+            stride: buffer.stride,
+            bytes_per_pixel: buffer.bytes_per_pixel,
+            format: buffer.format,
+            device,
+        }
+    }
+
+    fn line_size(&self) -> usize {
+        self.bytes_per_pixel * self.stride
+    }
+
+    /// Runs the function on the memory with the device locked.
+    ///
+    /// The logger draws from interrupt handlers too, so the lock is taken with interrupts
+    /// disabled. Callers copy a line at a time to keep them short.
+    fn with_memory<R>(&self, function: impl FnOnce(&mut [u8]) -> R) -> R {
+        without_interrupts(|| function(self.device.lock().memory()))
+    }
+}
+
+impl Device for FramebufferFile {
+    fn size(&self) -> u64 {
+        (self.line_size() * self.height) as u64
+    }
+
+    fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        if offset >= self.size() {
+            return Ok(0);
+        }
+        let offset = offset as usize;
+        let length = buffer.len().min(self.size() as usize - offset);
+        for (index, chunk) in buffer[..length].chunks_mut(self.line_size()).enumerate() {
+            let start = offset + index * self.line_size();
+            self.with_memory(|memory| {
+                chunk.copy_from_slice(&memory[start..start + chunk.len()]);
+            });
+        }
+        Ok(length)
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if offset >= self.size() {
+            return Err(VfsError::NoSpace);
+        }
+        let offset = offset as usize;
+        let length = data.len().min(self.size() as usize - offset);
+        for (index, chunk) in data[..length].chunks(self.line_size()).enumerate() {
+            let start = offset + index * self.line_size();
+            self.with_memory(|memory| {
+                memory[start..start + chunk.len()].copy_from_slice(chunk);
+            });
+        }
+        Ok(length)
+    }
+
+    fn ioctl(&self, command: u64, _argument: u64) -> Result<u64, VfsError> {
+        match command {
+            ioctl::FRAMEBUFFER_WIDTH => Ok(self.width as u64),
+            ioctl::FRAMEBUFFER_HEIGHT => Ok(self.height as u64),
+            ioctl::FRAMEBUFFER_STRIDE => Ok(self.stride as u64),
+            ioctl::FRAMEBUFFER_BYTES_PER_PIXEL => Ok(self.bytes_per_pixel as u64),
+            ioctl::FRAMEBUFFER_PIXEL_FORMAT => Ok(match self.format {
+                PixelFormat::RGB => ioctl::PIXEL_FORMAT_RGB,
+                PixelFormat::BGR => ioctl::PIXEL_FORMAT_BGR,
+                PixelFormat::U8 => ioctl::PIXEL_FORMAT_U8,
+            }),
+            _ => Err(VfsError::NotSupported),
+        }
+    }
+}
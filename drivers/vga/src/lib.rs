@@ -2,16 +2,20 @@
 #![no_main]
 #![allow(incomplete_features)]
 #![feature(ptr_const_cast, generic_const_exprs, adt_const_params)]
-use alloc::boxed::Box;
+use alloc::{boxed::Box, sync::Arc};
 use core::fmt;
+use framebuffer_file::FramebufferFile;
 use kernel::{
+    devfs,
     logger::Logger,
     structures::{driver::Driver, kernel_information::KernelInformation},
 };
+use spin::Mutex;
 use vga_core::{Clearable, TextDrawable, CHAR_HEIGHT};
 use vga_device::{VGADevice, VGADeviceFactory};
 extern crate alloc;
 
+mod framebuffer_file;
 mod pixel_buffer;
 pub mod point_2d;
 pub mod vga_color;
@@ -22,15 +26,20 @@ struct VGALogger {
     x: u16,
     y: u16,
     start_x: u16,
-    device: VGADevice,
+    /// Shared with the framebuffer file.
+    device: Arc<Mutex<VGADevice>>,
     took_over: bool,
 }
 
 impl VGALogger {
     fn __log(&mut self, text: &str) {
-        let (x, y) =
-            self.device
-                .draw_string(self.x, self.y, vga_color::CHARLOTTE, text, self.start_x);
+        let (x, y) = self.device.lock().draw_string(
+            self.x,
+            self.y,
+            vga_color::CHARLOTTE,
+            text,
+            self.start_x,
+        );
         self.x = x;
         self.y = y;
     }
@@ -39,7 +48,7 @@ impl VGALogger {
 impl Logger for VGALogger {
     fn log(&mut self, text: &str) {
         if !self.took_over {
-            self.device.clear(vga_color::BSOD_BLUE);
+            self.device.lock().clear(vga_color::BSOD_BLUE);
             self.took_over = true;
         }
         self.__log(text);
@@ -63,13 +72,18 @@ impl fmt::Write for VGALogger {
 }
 
 pub extern "C" fn driver_init(kernel_info: KernelInformation) -> Driver {
+    let device = Arc::new(Mutex::new(VGADeviceFactory::from_kernel_info(kernel_info)));
     kernel::LOGGER.lock().replace(Box::new(VGALogger {
         x: 0,
         start_x: 0,
         y: 0,
-        device: VGADeviceFactory::from_kernel_info(kernel_info),
+        device: device.clone(),
         took_over: false,
     }));
+    if let Some(buffer) = kernel_info.framebuffer.as_ref() {
+        devfs::register_device("fb0", 0o660, Arc::new(FramebufferFile::new(buffer, device)))
+            .expect("Invalid framebuffer name");
+    }
     Driver {
         signature: [
             0xf2, 0xf3, 0xf4, 0xf5, 0xf2, 0xf3, 0xf4, 0xf5, 0xf2, 0xf3, 0xf4, 0xf5, 0xf2, 0xf3,
@@ -5,6 +5,8 @@ use crate::vga_color::VGAColor;
 
 pub trait PixelBuffer: Send {
     fn put_pixel(&mut self, index: usize, color: VGAColor<u8>);
+    /// Returns the framebuffer memory, line after line.
+    fn memory(&mut self) -> &mut [u8];
 }
 
 pub(crate) struct BasePixelBuffer<const P: PixelFormat> {
@@ -28,6 +30,10 @@ impl PixelBuffer for BasePixelBuffer<{ PixelFormat::RGB }> {
         self.frame_pointer[index + 2] = result_color.blue;
         self.frame_pointer[index + 3] = result_color.alpha;
     }
+
+    fn memory(&mut self) -> &mut [u8] {
+        self.frame_pointer
+    }
 }
 
 impl PixelBuffer for BasePixelBuffer<{ PixelFormat::BGR }> {
@@ -46,6 +52,10 @@ impl PixelBuffer for BasePixelBuffer<{ PixelFormat::BGR }> {
         self.frame_pointer[index + 0] = result_color.blue;
         self.frame_pointer[index + 3] = result_color.alpha;
     }
+
+    fn memory(&mut self) -> &mut [u8] {
+        self.frame_pointer
+    }
 }
 
 impl PixelBuffer for BasePixelBuffer<{ PixelFormat::U8 }> {
@@ -58,4 +68,8 @@ impl PixelBuffer for BasePixelBuffer<{ PixelFormat::U8 }> {
         let alpha1 = 255 - alpha;
         self.frame_pointer[index] = div_255_fast(gray * alpha1 + color_gray * alpha);
     }
+
+    fn memory(&mut self) -> &mut [u8] {
+        self.frame_pointer
+    }
 }
@@ -260,6 +260,11 @@ impl ImageDrawable for VGADevice {
 }
 
 impl VGADevice {
+    /// Returns the framebuffer memory, for the framebuffer file.
+    pub(crate) fn memory(&mut self) -> &mut [u8] {
+        self.pixel_buffer.memory()
+    }
+
     fn draw_char(&mut self, x: u16, y: u16, char: &BitmapChar, color: VGAColor<u8>) {
         for (iy, row) in char.bitmap().iter().enumerate() {
             for (ix, byte) in row.iter().enumerate() {
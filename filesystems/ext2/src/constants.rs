@@ -0,0 +1,96 @@
+use kernel::BlockDeviceError;
+
+/// The superblock is always 1024 bytes into the volume, whatever the block size is.
+pub(crate) const SUPERBLOCK_OFFSET: usize = 1024;
+pub(crate) const SUPERBLOCK_SIZE: usize = 1024;
+pub(crate) const EXT2_MAGIC: u16 = 0xEF53;
+/// The size of a group descriptor in bytes.
+pub(crate) const GROUP_DESCRIPTOR_SIZE: usize = 32;
+/// The inode size of revision 0 volumes, revision 1 stores it in the superblock.
+pub(crate) const GOOD_OLD_INODE_SIZE: usize = 128;
+/// The first inode usable by files on revision 0 volumes, the ones before are reserved.
+pub(crate) const GOOD_OLD_FIRST_INODE: u32 = 11;
+/// The inode of the root directory.
+pub const ROOT_INODE: u32 = 2;
+
+/// The number of block pointers of an inode, 12 direct ones followed by a single, double and triple
+/// indirect one.
+pub(crate) const INODE_BLOCK_POINTERS: usize = 15;
+pub(crate) const DIRECT_BLOCKS: usize = 12;
+/// Symlink targets shorter than this are stored in the block pointers instead of a block.
+pub(crate) const FAST_SYMLINK_MAX_LENGTH: usize = INODE_BLOCK_POINTERS * 4;
+/// The number of symlinks followed while resolving a path before giving up.
+pub(crate) const MAX_SYMLINKS: usize = 8;
+pub(crate) const MAX_NAME_LENGTH: usize = 255;
+/// `i_blocks` counts 512 byte sectors, whatever the block size is.
+pub(crate) const INODE_BLOCKS_UNIT: usize = 512;
+
+pub(crate) const MODE_TYPE_MASK: u16 = 0xF000;
+pub(crate) const MODE_SOCKET: u16 = 0xC000;
+pub(crate) const MODE_SYMLINK: u16 = 0xA000;
+pub(crate) const MODE_FILE: u16 = 0x8000;
+pub(crate) const MODE_BLOCK_DEVICE: u16 = 0x6000;
+pub(crate) const MODE_DIRECTORY: u16 = 0x4000;
+pub(crate) const MODE_CHARACTER_DEVICE: u16 = 0x2000;
+pub(crate) const MODE_FIFO: u16 = 0x1000;
+/// The permission bits of the mode, including setuid, setgid and sticky.
+pub(crate) const MODE_PERMISSIONS_MASK: u16 = 0o7777;
+
+/// The inode flag of directories indexed by a hash tree, the tree is out of date once entries are
+/// changed without updating it.
+pub(crate) const INODE_FLAG_INDEX: u32 = 0x1000;
+
+/// Directory entries store the file type, revision 1 only.
+pub(crate) const FEATURE_INCOMPAT_FILETYPE: u32 = 0x0002;
+/// The incompatible features the driver supports, volumes with others can't be mounted.
+pub(crate) const SUPPORTED_INCOMPAT_FEATURES: u32 = FEATURE_INCOMPAT_FILETYPE;
+pub(crate) const FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+/// Regular files can be larger than 2 GiB, using the upper 32 bits of the size.
+pub(crate) const FEATURE_RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+pub(crate) const FEATURE_RO_COMPAT_BTREE_DIR: u32 = 0x0004;
+/// The read-only compatible features the driver supports, volumes with others are mounted read-only.
+pub(crate) const SUPPORTED_RO_COMPAT_FEATURES: u32 =
+    FEATURE_RO_COMPAT_SPARSE_SUPER | FEATURE_RO_COMPAT_LARGE_FILE | FEATURE_RO_COMPAT_BTREE_DIR;
+
+pub(crate) const ENTRY_HEADER_SIZE: usize = 8;
+pub(crate) const ENTRY_TYPE_UNKNOWN: u8 = 0;
+pub(crate) const ENTRY_TYPE_FILE: u8 = 1;
+pub(crate) const ENTRY_TYPE_DIRECTORY: u8 = 2;
+pub(crate) const ENTRY_TYPE_CHARACTER_DEVICE: u8 = 3;
+pub(crate) const ENTRY_TYPE_BLOCK_DEVICE: u8 = 4;
+pub(crate) const ENTRY_TYPE_FIFO: u8 = 5;
+pub(crate) const ENTRY_TYPE_SOCKET: u8 = 6;
+pub(crate) const ENTRY_TYPE_SYMLINK: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ext2Error {
+    Device(BlockDeviceError),
+    /// The superblock doesn't describe an ext2 volume, or a structure of the volume is corrupted.
+    InvalidFileSystem,
+    /// The volume uses a feature the driver doesn't support, like extents.
+    UnsupportedFeature,
+    /// The volume was mounted read-only because of a feature the driver can read but not write.
+    ReadOnly,
+    NotFound,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    /// The operation only works on regular files, e.g. writing to a symlink.
+    NotAFile,
+    NotASymlink,
+    DirectoryNotEmpty,
+    /// The name is empty, too long or contains `/`, or the path can't be used.
+    InvalidName,
+    /// More than `MAX_SYMLINKS` symlinks were followed, they probably form a loop.
+    TooManySymlinks,
+    /// There are no free blocks or inodes left.
+    NoSpace,
+    /// The file is larger than the block pointers of an inode can address.
+    FileTooLarge,
+}
+
+impl From<BlockDeviceError> for Ext2Error {
+    fn from(error: BlockDeviceError) -> Self {
+        Ext2Error::Device(error)
+    }
+}
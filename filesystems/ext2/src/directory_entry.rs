@@ -0,0 +1,160 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    constants::{
+        ENTRY_HEADER_SIZE, ENTRY_TYPE_BLOCK_DEVICE, ENTRY_TYPE_CHARACTER_DEVICE,
+        ENTRY_TYPE_DIRECTORY, ENTRY_TYPE_FIFO, ENTRY_TYPE_FILE, ENTRY_TYPE_SOCKET,
+        ENTRY_TYPE_SYMLINK, ENTRY_TYPE_UNKNOWN, MAX_NAME_LENGTH,
+    },
+    Ext2Error, FileType,
+};
+
+/// A file, directory or symlink listed in a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub inode: u32,
+    pub file_type: FileType,
+    /// The offset of the entry in the directory.
+    pub(crate) offset: usize,
+}
+
+/// An entry of a directory block as stored, including the unused ones.
+pub(crate) struct RawEntry {
+    pub(crate) offset: usize,
+    pub(crate) inode: u32,
+    pub(crate) record_length: usize,
+    pub(crate) name_length: usize,
+}
+
+impl RawEntry {
+    /// Returns the bytes the entry needs, the rest of its record can hold other entries.
+    pub(crate) fn used_length(&self) -> usize {
+        if self.inode == 0 {
+            0
+        } else {
+            entry_length(self.name_length)
+        }
+    }
+}
+
+/// Returns the entries of a directory block, an entry never spans blocks.
+///
+/// `has_file_type` tells whether the volume stores the file type in the upper byte of the name length.
+pub(crate) fn parse_block(
+    block: &[u8],
+    block_offset: usize,
+    has_file_type: bool,
+) -> Result<Vec<(RawEntry, Option<DirectoryEntry>)>, Ext2Error> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset < block.len() {
+        if block.len() - offset < ENTRY_HEADER_SIZE {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        let bytes = &block[offset..];
+        let inode = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let record_length = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+        let name_length = if has_file_type {
+            bytes[6] as usize
+        } else {
+            u16::from_le_bytes([bytes[6], bytes[7]]) as usize
+        };
+        if record_length < ENTRY_HEADER_SIZE
+            || record_length % 4 != 0
+            || offset + record_length > block.len()
+            || (inode != 0 && entry_length(name_length) > record_length)
+        {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        let raw = RawEntry {
+            offset: block_offset + offset,
+            inode,
+            record_length,
+            name_length,
+        };
+        let entry = if inode != 0 {
+            let name = &bytes[ENTRY_HEADER_SIZE..ENTRY_HEADER_SIZE + name_length];
+            Some(DirectoryEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                inode,
+                file_type: if has_file_type {
+                    file_type_from_entry(bytes[7])
+                } else {
+                    FileType::Unknown
+                },
+                offset: block_offset + offset,
+            })
+        } else {
+            None
+        };
+        entries.push((raw, entry));
+        offset += record_length;
+    }
+    Ok(entries)
+}
+
+/// Writes an entry at the start of the bytes, which must hold its record.
+pub(crate) fn write_entry(
+    bytes: &mut [u8],
+    inode: u32,
+    record_length: usize,
+    name: &str,
+    file_type: FileType,
+    has_file_type: bool,
+) {
+    bytes[0..4].copy_from_slice(&inode.to_le_bytes());
+    bytes[4..6].copy_from_slice(&(record_length as u16).to_le_bytes());
+    if has_file_type {
+        bytes[6] = name.len() as u8;
+        bytes[7] = entry_type(file_type);
+    } else {
+        bytes[6..8].copy_from_slice(&(name.len() as u16).to_le_bytes());
+    }
+    bytes[ENTRY_HEADER_SIZE..ENTRY_HEADER_SIZE + name.len()].copy_from_slice(name.as_bytes());
+}
+
+/// Returns the bytes an entry with a name of the passed length needs, records are 4 byte aligned.
+pub(crate) fn entry_length(name_length: usize) -> usize {
+    (ENTRY_HEADER_SIZE + name_length + 3) & !3
+}
+
+/// Checks that the name can be stored in a directory entry.
+pub(crate) fn validate_name(name: &str) -> Result<(), Ext2Error> {
+    if name.is_empty()
+        || name.len() > MAX_NAME_LENGTH
+        || name == "."
+        || name == ".."
+        || name.contains(&['/', '\0'][..])
+    {
+        Err(Ext2Error::InvalidName)
+    } else {
+        Ok(())
+    }
+}
+
+fn file_type_from_entry(entry_type: u8) -> FileType {
+    match entry_type {
+        ENTRY_TYPE_FILE => FileType::File,
+        ENTRY_TYPE_DIRECTORY => FileType::Directory,
+        ENTRY_TYPE_CHARACTER_DEVICE => FileType::CharacterDevice,
+        ENTRY_TYPE_BLOCK_DEVICE => FileType::BlockDevice,
+        ENTRY_TYPE_FIFO => FileType::Fifo,
+        ENTRY_TYPE_SOCKET => FileType::Socket,
+        ENTRY_TYPE_SYMLINK => FileType::Symlink,
+        _ => FileType::Unknown,
+    }
+}
+
+fn entry_type(file_type: FileType) -> u8 {
+    match file_type {
+        FileType::File => ENTRY_TYPE_FILE,
+        FileType::Directory => ENTRY_TYPE_DIRECTORY,
+        FileType::CharacterDevice => ENTRY_TYPE_CHARACTER_DEVICE,
+        FileType::BlockDevice => ENTRY_TYPE_BLOCK_DEVICE,
+        FileType::Fifo => ENTRY_TYPE_FIFO,
+        FileType::Socket => ENTRY_TYPE_SOCKET,
+        FileType::Symlink => ENTRY_TYPE_SYMLINK,
+        FileType::Unknown => ENTRY_TYPE_UNKNOWN,
+    }
+}
@@ -0,0 +1,1252 @@
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use kernel::BlockDevice;
+
+use crate::{
+    constants::{
+        DIRECT_BLOCKS, FAST_SYMLINK_MAX_LENGTH, FEATURE_INCOMPAT_FILETYPE,
+        FEATURE_RO_COMPAT_LARGE_FILE, GROUP_DESCRIPTOR_SIZE, INODE_BLOCKS_UNIT, INODE_FLAG_INDEX,
+        MAX_SYMLINKS, MODE_DIRECTORY, MODE_FILE, MODE_PERMISSIONS_MASK, MODE_SYMLINK, ROOT_INODE,
+        SUPERBLOCK_OFFSET, SUPERBLOCK_SIZE, SUPPORTED_INCOMPAT_FEATURES,
+        SUPPORTED_RO_COMPAT_FEATURES,
+    },
+    directory_entry::{entry_length, parse_block, validate_name, write_entry},
+    group_descriptor::GroupDescriptor,
+    inode::Inode,
+    superblock::Superblock,
+    DirectoryEntry, Ext2Error, FileType, Metadata,
+};
+
+/// A mounted ext2 volume on a block device.
+///
+/// Paths are absolute or relative to the root directory, their components are separated by `/`.
+/// Symlinks are followed, except for the last component of the paths passed to `metadata`, `read_link`,
+/// `remove` and `rename`. The methods ending in `_at` take inode numbers instead, for callers resolving
+/// paths themselves. The superblock and group descriptors are only written by `sync`, the other
+/// changes may stay in the device's cache until then.
+pub struct Ext2<D: BlockDevice> {
+    device: D,
+    superblock: Superblock,
+    groups: Vec<GroupDescriptor>,
+    /// The number of device blocks making up a block of the volume.
+    device_blocks_per_block: u64,
+    /// Set if the volume uses a feature that can be read but not written.
+    read_only: bool,
+    /// Whether the superblock or group descriptors changed since the last sync.
+    dirty: bool,
+}
+
+impl<D: BlockDevice> Ext2<D> {
+    /// Mounts the ext2 volume stored on the device.
+    ///
+    /// Volumes using read-only compatible features the driver doesn't know are mounted read-only,
+    /// volumes using unknown incompatible features, like the extents of ext4, aren't mounted at all.
+    pub fn mount(mut device: D) -> Result<Self, Ext2Error> {
+        let superblock = Superblock::from_bytes(&read_superblock(&mut device)?)?;
+        let block_size = superblock.block_size;
+        if block_size % device.block_size() != 0 {
+            return Err(Ext2Error::UnsupportedFeature);
+        }
+        if superblock.feature_incompat & !SUPPORTED_INCOMPAT_FEATURES != 0 {
+            return Err(Ext2Error::UnsupportedFeature);
+        }
+        let device_blocks_per_block = (block_size / device.block_size()) as u64;
+        if superblock.block_count as u64 * device_blocks_per_block > device.block_count() {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+
+        // the group descriptor table follows the superblock's block
+        let group_count = superblock.group_count() as usize;
+        let table_blocks = (group_count * GROUP_DESCRIPTOR_SIZE + block_size - 1) / block_size;
+        let mut table = vec![0; table_blocks * block_size];
+        device.read_blocks(
+            (superblock.first_data_block as u64 + 1) * device_blocks_per_block,
+            &mut table,
+        )?;
+        let groups = table
+            .chunks_exact(GROUP_DESCRIPTOR_SIZE)
+            .take(group_count)
+            .map(GroupDescriptor::from_bytes)
+            .collect();
+
+        Ok(Ext2 {
+            device,
+            read_only: superblock.feature_ro_compat & !SUPPORTED_RO_COMPAT_FEATURES != 0,
+            superblock,
+            groups,
+            device_blocks_per_block,
+            dirty: false,
+        })
+    }
+
+    /// Returns the size of a block in bytes, the unit space is allocated in.
+    pub fn block_size(&self) -> usize {
+        self.superblock.block_size
+    }
+
+    /// Returns the free space of the volume in bytes.
+    pub fn free_space(&self) -> u64 {
+        self.superblock.free_block_count as u64 * self.superblock.block_size as u64
+    }
+
+    /// Returns whether the volume was mounted read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns the entries of the directory, without `.` and `..`.
+    pub fn read_dir(&mut self, path: &str) -> Result<Vec<DirectoryEntry>, Ext2Error> {
+        let number = self.resolve(path, true)?;
+        self.read_dir_at(number)
+    }
+
+    /// Returns the entries of the directory with the inode number, without `.` and `..`.
+    pub fn read_dir_at(&mut self, number: u32) -> Result<Vec<DirectoryEntry>, Ext2Error> {
+        let inode = self.read_inode(number)?;
+        let mut entries = self.directory_entries(&inode)?;
+        entries.retain(|entry| entry.name != "." && entry.name != "..");
+        // without the file type feature, the type is only stored in the inode
+        for entry in entries.iter_mut() {
+            if entry.file_type == FileType::Unknown {
+                entry.file_type = self.read_inode(entry.inode)?.file_type();
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Returns the metadata of the file, directory or symlink at the path.
+    pub fn metadata(&mut self, path: &str) -> Result<Metadata, Ext2Error> {
+        let number = self.resolve(path, false)?;
+        self.metadata_at(number)
+    }
+
+    /// Returns the metadata of the inode with the number.
+    pub fn metadata_at(&mut self, number: u32) -> Result<Metadata, Ext2Error> {
+        Ok(self.read_inode(number)?.metadata(number))
+    }
+
+    /// Returns the inode number of the entry with the name in the directory, symlinks aren't followed.
+    pub fn lookup_at(&mut self, directory: u32, name: &str) -> Result<u32, Ext2Error> {
+        let directory = self.read_inode(directory)?;
+        Ok(self.find_entry(&directory, name)?.inode)
+    }
+
+    /// Reads the file from `offset` into the buffer, returns the number of bytes read.
+    ///
+    /// Fewer bytes than the buffer holds are read at the end of the file, none at or after it.
+    /// Holes in sparse files read as zeros.
+    pub fn read(
+        &mut self,
+        path: &str,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Ext2Error> {
+        let number = self.resolve(path, true)?;
+        self.read_at(number, offset, buffer)
+    }
+
+    /// Reads the file with the inode number like `read`.
+    pub fn read_at(
+        &mut self,
+        number: u32,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Ext2Error> {
+        let inode = self.read_inode(number)?;
+        if inode.file_type() == FileType::Directory {
+            return Err(Ext2Error::IsADirectory);
+        }
+        let size = inode.size() as usize;
+        if offset >= size || buffer.is_empty() {
+            return Ok(0);
+        }
+        let length = buffer.len().min(size - offset);
+        let block_size = self.superblock.block_size;
+        let mut block_data = vec![0; block_size];
+        let mut position = offset;
+        let mut done = 0;
+        while done < length {
+            let start = position % block_size;
+            let part = (block_size - start).min(length - done);
+            let block = self.get_block(&inode, (position / block_size) as u64)?;
+            let target = &mut buffer[done..done + part];
+            if block == 0 {
+                target.fill(0);
+            } else if part == block_size {
+                self.read_block(block, target)?;
+            } else {
+                self.read_block(block, &mut block_data)?;
+                target.copy_from_slice(&block_data[start..start + part]);
+            }
+            position += part;
+            done += part;
+        }
+        Ok(length)
+    }
+
+    /// Reads the whole file.
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, Ext2Error> {
+        let number = self.resolve(path, true)?;
+        let size = self.read_inode(number)?.size() as usize;
+        let mut data = vec![0; size];
+        let read = self.read(path, 0, &mut data)?;
+        data.truncate(read);
+        Ok(data)
+    }
+
+    /// Writes the data to the file at `offset`, returns the number of bytes written.
+    ///
+    /// Writing past the end extends the file, the blocks between the old end and `offset` are left as a hole.
+    pub fn write(&mut self, path: &str, offset: usize, data: &[u8]) -> Result<usize, Ext2Error> {
+        let number = self.resolve(path, true)?;
+        self.write_at(number, offset, data)
+    }
+
+    /// Writes the data to the file with the inode number like `write`.
+    pub fn write_at(
+        &mut self,
+        number: u32,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<usize, Ext2Error> {
+        self.check_writable()?;
+        let mut inode = self.read_inode(number)?;
+        // the block pointers of other inodes may hold data, e.g. the target of a fast symlink
+        check_regular_file(&inode)?;
+        let end = offset
+            .checked_add(data.len())
+            .ok_or(Ext2Error::FileTooLarge)?;
+        self.check_file_size(end as u64)?;
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        // the inode is written even if the volume runs out of space, it references the allocated blocks
+        let result = self.write_data(number, &mut inode, offset, data);
+        self.write_inode(number, &inode)?;
+        result.map(|_| data.len())
+    }
+
+    /// Writes the data to the end of the file, returns the number of bytes written.
+    pub fn append(&mut self, path: &str, data: &[u8]) -> Result<usize, Ext2Error> {
+        let number = self.resolve(path, true)?;
+        let size = self.read_inode(number)?.size() as usize;
+        self.write(path, size, data)
+    }
+
+    /// Sets the size of the file, freeing the blocks past the new end.
+    ///
+    /// Growing the file leaves the new part as a hole, which reads as zeros.
+    pub fn truncate(&mut self, path: &str, size: usize) -> Result<(), Ext2Error> {
+        let number = self.resolve(path, true)?;
+        self.truncate_at(number, size)
+    }
+
+    /// Sets the size of the file with the inode number like `truncate`.
+    pub fn truncate_at(&mut self, number: u32, size: usize) -> Result<(), Ext2Error> {
+        self.check_writable()?;
+        let mut inode = self.read_inode(number)?;
+        // the block pointers of other inodes may hold data, e.g. the target of a fast symlink
+        check_regular_file(&inode)?;
+        self.check_file_size(size as u64)?;
+        let block_size = self.superblock.block_size;
+        if (size as u64) < inode.size() {
+            self.free_blocks_from(&mut inode, ((size + block_size - 1) / block_size) as u64)?;
+            // the rest of the last block is zeroed, so it reads as zeros if the file grows again
+            if size % block_size != 0 {
+                let block = self.get_block(&inode, (size / block_size) as u64)?;
+                if block != 0 {
+                    let mut data = vec![0; block_size];
+                    self.read_block(block, &mut data)?;
+                    data[size % block_size..].fill(0);
+                    self.write_block(block, &data)?;
+                }
+            }
+        }
+        inode.set_size(size as u64);
+        self.write_inode(number, &inode)
+    }
+
+    /// Creates an empty file with the permission bits, its parent directory must exist.
+    pub fn create_file(&mut self, path: &str, permissions: u16) -> Result<(), Ext2Error> {
+        let (parent, name) = self.resolve_parent(path)?;
+        self.create_file_at(parent, name, permissions).map(|_| ())
+    }
+
+    /// Creates an empty file in the directory with the inode number, returns the new inode's number.
+    pub fn create_file_at(
+        &mut self,
+        directory: u32,
+        name: &str,
+        permissions: u16,
+    ) -> Result<u32, Ext2Error> {
+        let mut inode = Inode::new(MODE_FILE | (permissions & MODE_PERMISSIONS_MASK));
+        inode.links = 1;
+        self.create(directory, name, inode, |_, _, _, _| Ok(()))
+    }
+
+    /// Creates an empty directory with the permission bits, its parent directory must exist.
+    pub fn create_directory(&mut self, path: &str, permissions: u16) -> Result<(), Ext2Error> {
+        let (parent, name) = self.resolve_parent(path)?;
+        self.create_directory_at(parent, name, permissions)
+            .map(|_| ())
+    }
+
+    /// Creates an empty directory in the directory with the inode number, returns the new inode's number.
+    pub fn create_directory_at(
+        &mut self,
+        directory: u32,
+        name: &str,
+        permissions: u16,
+    ) -> Result<u32, Ext2Error> {
+        let mut inode = Inode::new(MODE_DIRECTORY | (permissions & MODE_PERMISSIONS_MASK));
+        // the entry in the parent and the directory's own `.` entry
+        inode.links = 2;
+        self.create(
+            directory,
+            name,
+            inode,
+            |file_system, parent, number, inode| {
+                let block_size = file_system.superblock.block_size;
+                let has_file_type = file_system.has_file_type();
+                let block = file_system.map_block(number, inode, 0)?;
+                let mut data = vec![0; block_size];
+                let dot_length = entry_length(1);
+                write_entry(
+                    &mut data,
+                    number,
+                    dot_length,
+                    ".",
+                    FileType::Directory,
+                    has_file_type,
+                );
+                write_entry(
+                    &mut data[dot_length..],
+                    parent,
+                    block_size - dot_length,
+                    "..",
+                    FileType::Directory,
+                    has_file_type,
+                );
+                file_system.write_block(block, &data)?;
+                inode.set_size(block_size as u64);
+                Ok(())
+            },
+        )
+    }
+
+    /// Creates a symlink pointing to the target, which is stored as it is and doesn't have to exist.
+    pub fn create_symlink(&mut self, path: &str, target: &str) -> Result<(), Ext2Error> {
+        let (parent, name) = self.resolve_parent(path)?;
+        self.create_symlink_at(parent, name, target).map(|_| ())
+    }
+
+    /// Creates a symlink in the directory with the inode number, returns the new inode's number.
+    pub fn create_symlink_at(
+        &mut self,
+        directory: u32,
+        name: &str,
+        target: &str,
+    ) -> Result<u32, Ext2Error> {
+        if target.is_empty() || target.len() >= self.superblock.block_size {
+            return Err(Ext2Error::InvalidName);
+        }
+        let mut inode = Inode::new(MODE_SYMLINK | 0o777);
+        inode.links = 1;
+        self.create(directory, name, inode, |file_system, _, number, inode| {
+            // short targets are stored in the block pointers, without a block
+            if target.len() < FAST_SYMLINK_MAX_LENGTH {
+                inode.set_fast_symlink_target(target.as_bytes());
+            } else {
+                let block = file_system.map_block(number, inode, 0)?;
+                let mut data = vec![0; file_system.superblock.block_size];
+                data[..target.len()].copy_from_slice(target.as_bytes());
+                file_system.write_block(block, &data)?;
+            }
+            inode.set_size(target.len() as u64);
+            Ok(())
+        })
+    }
+
+    /// Returns the target of the symlink.
+    pub fn read_link(&mut self, path: &str) -> Result<String, Ext2Error> {
+        let number = self.resolve(path, false)?;
+        self.read_link_at(number)
+    }
+
+    /// Returns the target of the symlink with the inode number.
+    pub fn read_link_at(&mut self, number: u32) -> Result<String, Ext2Error> {
+        let inode = self.read_inode(number)?;
+        if inode.file_type() != FileType::Symlink {
+            return Err(Ext2Error::NotASymlink);
+        }
+        self.symlink_target(&inode)
+    }
+
+    /// Removes the file, symlink or empty directory.
+    ///
+    /// The inode and its blocks are freed once no directory entry refers to it anymore.
+    pub fn remove(&mut self, path: &str) -> Result<(), Ext2Error> {
+        let (parent, name) = self.resolve_parent(path)?;
+        self.remove_at(parent, name).map(|_| ())
+    }
+
+    /// Removes the entry with the name from the directory with the inode number like `remove`.
+    ///
+    /// Returns the number of the entry's inode if it was freed, since it may be reused from then on.
+    pub fn remove_at(&mut self, parent_number: u32, name: &str) -> Result<Option<u32>, Ext2Error> {
+        self.check_writable()?;
+        validate_name(name)?;
+        let mut parent = self.read_inode(parent_number)?;
+        let entry = self.find_entry(&parent, name)?;
+        let number = entry.inode;
+        let mut inode = self.read_inode(number)?;
+        let is_directory = inode.file_type() == FileType::Directory;
+        if is_directory {
+            let entries = self.directory_entries(&inode)?;
+            if entries
+                .iter()
+                .any(|entry| entry.name != "." && entry.name != "..")
+            {
+                return Err(Ext2Error::DirectoryNotEmpty);
+            }
+            // the directory's `..` entry no longer refers to the parent
+            parent.links = parent.links.saturating_sub(1);
+            inode.links = 0;
+        } else {
+            inode.links = inode.links.saturating_sub(1);
+        }
+        self.remove_entry(parent_number, &mut parent, &entry)?;
+
+        if inode.links > 0 {
+            return self.write_inode(number, &inode).map(|_| None);
+        }
+        if !self.is_fast_symlink(&inode) {
+            self.free_blocks_from(&mut inode, 0)?;
+        }
+        // e2fsck takes deletion times below the inode count for links of the orphan list
+        inode.deleted = self.timestamp().max(self.superblock.inode_count);
+        self.write_inode(number, &inode)?;
+        self.free_inode(number, is_directory)?;
+        Ok(Some(number))
+    }
+
+    /// Renames or moves the file, symlink or directory, the destination must not exist.
+    ///
+    /// A directory can't be moved into itself or one of its subdirectories.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), Ext2Error> {
+        self.check_writable()?;
+        let (source_number, source_name) = self.resolve_parent(from)?;
+        validate_name(source_name)?;
+        let (destination_number, destination_name) = self.resolve_parent(to)?;
+        validate_name(destination_name)?;
+        let source = self.read_inode(source_number)?;
+        let entry = self.find_entry(&source, source_name)?;
+        let mut destination = self.read_inode(destination_number)?;
+        if destination.file_type() != FileType::Directory {
+            return Err(Ext2Error::NotADirectory);
+        }
+        match self.find_entry(&destination, destination_name) {
+            Ok(_) => return Err(Ext2Error::AlreadyExists),
+            Err(Ext2Error::NotFound) => {}
+            Err(error) => return Err(error),
+        }
+        let file_type = self.read_inode(entry.inode)?.file_type();
+        let is_directory = file_type == FileType::Directory;
+        if is_directory && self.is_inside(destination_number, entry.inode)? {
+            return Err(Ext2Error::InvalidName);
+        }
+
+        // the new entry is added first, so the file isn't lost if removing the old one fails
+        let moved = is_directory && source_number != destination_number;
+        if moved {
+            destination.links += 1;
+        }
+        self.add_entry(
+            destination_number,
+            &mut destination,
+            destination_name,
+            entry.inode,
+            file_type,
+        )?;
+        // the source may be the destination, so it's read again after adding the entry
+        let mut source = self.read_inode(source_number)?;
+        let entry = self.find_entry(&source, source_name)?;
+        if moved {
+            source.links = source.links.saturating_sub(1);
+        }
+        self.remove_entry(source_number, &mut source, &entry)?;
+
+        if moved {
+            let directory = self.read_inode(entry.inode)?;
+            let dot_dot = self.find_entry(&directory, "..")?;
+            self.set_entry_inode(&directory, &dot_dot, destination_number)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the permission bits, including setuid, setgid and sticky.
+    pub fn set_permissions(&mut self, path: &str, permissions: u16) -> Result<(), Ext2Error> {
+        self.check_writable()?;
+        let number = self.resolve(path, true)?;
+        let mut inode = self.read_inode(number)?;
+        inode.mode = (inode.mode & !MODE_PERMISSIONS_MASK) | (permissions & MODE_PERMISSIONS_MASK);
+        self.write_inode(number, &inode)
+    }
+
+    /// Sets the user and group owning the file or directory.
+    pub fn set_owner(&mut self, path: &str, uid: u32, gid: u32) -> Result<(), Ext2Error> {
+        self.check_writable()?;
+        let number = self.resolve(path, true)?;
+        let mut inode = self.read_inode(number)?;
+        inode.uid = uid;
+        inode.gid = gid;
+        self.write_inode(number, &inode)
+    }
+
+    /// Writes the superblock and the group descriptors if they changed and flushes the device.
+    pub fn sync(&mut self) -> Result<(), Ext2Error> {
+        if self.dirty {
+            let mut superblock = read_superblock(&mut self.device)?;
+            self.superblock.write_to(&mut superblock);
+            write_superblock(&mut self.device, &superblock)?;
+
+            let block_size = self.superblock.block_size;
+            let table_blocks =
+                (self.groups.len() * GROUP_DESCRIPTOR_SIZE + block_size - 1) / block_size;
+            let mut table = vec![0; table_blocks * block_size];
+            let table_block = self.superblock.first_data_block + 1;
+            for index in 0..table_blocks {
+                self.read_block(
+                    table_block + index as u32,
+                    &mut table[index * block_size..(index + 1) * block_size],
+                )?;
+            }
+            for (group, bytes) in self
+                .groups
+                .iter()
+                .zip(table.chunks_exact_mut(GROUP_DESCRIPTOR_SIZE))
+            {
+                group.write_to(bytes);
+            }
+            for index in 0..table_blocks {
+                self.write_block(
+                    table_block + index as u32,
+                    &table[index * block_size..(index + 1) * block_size],
+                )?;
+            }
+            self.dirty = false;
+        }
+        Ok(self.device.flush()?)
+    }
+
+    fn check_writable(&self) -> Result<(), Ext2Error> {
+        if self.read_only {
+            Err(Ext2Error::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the time stored in new inodes.
+    ///
+    /// There's no real time clock, so the last time the volume was written by a system with one is used.
+    fn timestamp(&self) -> u32 {
+        self.superblock.write_time
+    }
+
+    fn has_file_type(&self) -> bool {
+        self.superblock.feature_incompat & FEATURE_INCOMPAT_FILETYPE != 0
+    }
+
+    /// Checks that a regular file can have the size, files of 2 GiB and more need the large file feature.
+    fn check_file_size(&mut self, size: u64) -> Result<(), Ext2Error> {
+        let block_size = self.superblock.block_size as u64;
+        if size > 0 {
+            self.block_path((size - 1) / block_size)?;
+        }
+        if size > i32::MAX as u64
+            && self.superblock.feature_ro_compat & FEATURE_RO_COMPAT_LARGE_FILE == 0
+        {
+            // revision 0 volumes have no feature flags
+            if self.superblock.revision == 0 {
+                return Err(Ext2Error::FileTooLarge);
+            }
+            self.superblock.feature_ro_compat |= FEATURE_RO_COMPAT_LARGE_FILE;
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Creates an inode with the name in the parent directory, `initialize` sets up its content and gets
+    /// the parent's and the new inode's numbers. Returns the new inode's number.
+    ///
+    /// The inode is allocated in the parent's group, everything allocated is freed again if a step fails.
+    fn create(
+        &mut self,
+        parent_number: u32,
+        name: &str,
+        mut inode: Inode,
+        initialize: impl FnOnce(&mut Self, u32, u32, &mut Inode) -> Result<(), Ext2Error>,
+    ) -> Result<u32, Ext2Error> {
+        self.check_writable()?;
+        validate_name(name)?;
+        let mut parent = self.read_inode(parent_number)?;
+        if parent.file_type() != FileType::Directory {
+            return Err(Ext2Error::NotADirectory);
+        }
+        match self.find_entry(&parent, name) {
+            Ok(_) => return Err(Ext2Error::AlreadyExists),
+            Err(Ext2Error::NotFound) => {}
+            Err(error) => return Err(error),
+        }
+
+        let file_type = inode.file_type();
+        let is_directory = file_type == FileType::Directory;
+        inode.accessed = self.timestamp();
+        inode.changed = self.timestamp();
+        inode.modified = self.timestamp();
+        let number = self.allocate_inode(self.group_of(parent_number), is_directory)?;
+        let result = initialize(self, parent_number, number, &mut inode)
+            .and_then(|_| self.write_inode(number, &inode))
+            .and_then(|_| {
+                if is_directory {
+                    parent.links += 1;
+                }
+                self.add_entry(parent_number, &mut parent, name, number, file_type)
+            });
+        if let Err(error) = result {
+            if !self.is_fast_symlink(&inode) {
+                self.free_blocks_from(&mut inode, 0)?;
+            }
+            self.free_inode(number, is_directory)?;
+            return Err(error);
+        }
+        Ok(number)
+    }
+
+    /// Resolves the path to an inode number, following symlinks.
+    ///
+    /// A symlink in the last component is only followed if `follow_last` is set.
+    fn resolve(&mut self, path: &str, follow_last: bool) -> Result<u32, Ext2Error> {
+        let mut pending: Vec<String> = components(path).rev().map(String::from).collect();
+        let mut current = ROOT_INODE;
+        let mut symlinks = 0;
+        while let Some(component) = pending.pop() {
+            let directory = self.read_inode(current)?;
+            if directory.file_type() != FileType::Directory {
+                return Err(Ext2Error::NotADirectory);
+            }
+            let entry = self.find_entry(&directory, &component)?;
+            let inode = self.read_inode(entry.inode)?;
+            if inode.file_type() == FileType::Symlink && (follow_last || !pending.is_empty()) {
+                symlinks += 1;
+                if symlinks > MAX_SYMLINKS {
+                    return Err(Ext2Error::TooManySymlinks);
+                }
+                // relative targets start at the directory holding the symlink
+                let target = self.symlink_target(&inode)?;
+                if target.starts_with('/') {
+                    current = ROOT_INODE;
+                }
+                pending.extend(components(&target).rev().map(String::from));
+                continue;
+            }
+            current = entry.inode;
+        }
+        Ok(current)
+    }
+
+    /// Returns the inode number of the parent directory of the path and the last component.
+    fn resolve_parent<'a>(&mut self, path: &'a str) -> Result<(u32, &'a str), Ext2Error> {
+        let name = components(path).last().ok_or(Ext2Error::InvalidName)?;
+        let parent_path = &path[..path.rfind(name).unwrap_or(0)];
+        Ok((self.resolve(parent_path, true)?, name))
+    }
+
+    /// Returns whether the directory is the one with the `ancestor` inode or inside it, by following the
+    /// `..` entries up to the root directory.
+    fn is_inside(&mut self, directory: u32, ancestor: u32) -> Result<bool, Ext2Error> {
+        let mut current = directory;
+        for _ in 0..self.superblock.inode_count {
+            if current == ancestor {
+                return Ok(true);
+            }
+            if current == ROOT_INODE {
+                return Ok(false);
+            }
+            let inode = self.read_inode(current)?;
+            current = self.find_entry(&inode, "..")?.inode;
+        }
+        Err(Ext2Error::InvalidFileSystem)
+    }
+
+    fn symlink_target(&mut self, inode: &Inode) -> Result<String, Ext2Error> {
+        let size = inode.size() as usize;
+        if self.is_fast_symlink(inode) {
+            let target = inode.fast_symlink_target();
+            let target = target.get(..size).ok_or(Ext2Error::InvalidFileSystem)?;
+            return Ok(String::from_utf8_lossy(target).to_string());
+        }
+        let block = self.get_block(inode, 0)?;
+        if block == 0 || size > self.superblock.block_size {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        let mut data = vec![0; self.superblock.block_size];
+        self.read_block(block, &mut data)?;
+        Ok(String::from_utf8_lossy(&data[..size]).to_string())
+    }
+
+    /// Returns whether the inode is a symlink storing its target in the block pointers.
+    fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        let attribute_sectors = if inode.file_acl != 0 {
+            (self.superblock.block_size / INODE_BLOCKS_UNIT) as u32
+        } else {
+            0
+        };
+        inode.file_type() == FileType::Symlink && inode.sectors == attribute_sectors
+    }
+
+    /// Returns the entries of the directory, including `.` and `..`.
+    fn directory_entries(&mut self, directory: &Inode) -> Result<Vec<DirectoryEntry>, Ext2Error> {
+        if directory.file_type() != FileType::Directory {
+            return Err(Ext2Error::NotADirectory);
+        }
+        let block_size = self.superblock.block_size;
+        let has_file_type = self.has_file_type();
+        let mut entries = Vec::new();
+        let mut data = vec![0; block_size];
+        for index in 0..directory.size() / block_size as u64 {
+            // directories have no holes
+            let block = self.get_block(directory, index)?;
+            if block == 0 {
+                return Err(Ext2Error::InvalidFileSystem);
+            }
+            self.read_block(block, &mut data)?;
+            let block_entries = parse_block(&data, index as usize * block_size, has_file_type)?;
+            entries.extend(block_entries.into_iter().filter_map(|(_, entry)| entry));
+        }
+        Ok(entries)
+    }
+
+    fn find_entry(&mut self, directory: &Inode, name: &str) -> Result<DirectoryEntry, Ext2Error> {
+        self.directory_entries(directory)?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .ok_or(Ext2Error::NotFound)
+    }
+
+    /// Adds an entry to the directory and writes its inode.
+    ///
+    /// The entry goes into the unused part of an existing record if one is large enough,
+    /// otherwise a block is added to the directory.
+    fn add_entry(
+        &mut self,
+        number: u32,
+        directory: &mut Inode,
+        name: &str,
+        inode: u32,
+        file_type: FileType,
+    ) -> Result<(), Ext2Error> {
+        let block_size = self.superblock.block_size;
+        let has_file_type = self.has_file_type();
+        let needed = entry_length(name.len());
+        // a hash tree index doesn't know about the new entry, the directory is used as a linear one
+        directory.flags &= !INODE_FLAG_INDEX;
+
+        let mut data = vec![0; block_size];
+        let block_count = directory.size() / block_size as u64;
+        for index in 0..block_count {
+            let block = self.get_block(directory, index)?;
+            if block == 0 {
+                return Err(Ext2Error::InvalidFileSystem);
+            }
+            self.read_block(block, &mut data)?;
+            let free_record = parse_block(&data, 0, has_file_type)?
+                .into_iter()
+                .map(|(raw, _)| raw)
+                .find(|raw| raw.record_length - raw.used_length() >= needed);
+            if let Some(raw) = free_record {
+                let used = raw.used_length();
+                if used > 0 {
+                    data[raw.offset + 4..raw.offset + 6]
+                        .copy_from_slice(&(used as u16).to_le_bytes());
+                }
+                let offset = raw.offset + used;
+                write_entry(
+                    &mut data[offset..],
+                    inode,
+                    raw.record_length - used,
+                    name,
+                    file_type,
+                    has_file_type,
+                );
+                self.write_block(block, &data)?;
+                return self.write_inode(number, directory);
+            }
+        }
+
+        let block = self.map_block(number, directory, block_count)?;
+        data.fill(0);
+        write_entry(&mut data, inode, block_size, name, file_type, has_file_type);
+        self.write_block(block, &data)?;
+        directory.set_size((block_count + 1) * block_size as u64);
+        self.write_inode(number, directory)
+    }
+
+    /// Removes the entry from the directory and writes the directory's inode.
+    ///
+    /// The entry's record is merged into the previous one in its block, or marked unused if it's the first.
+    fn remove_entry(
+        &mut self,
+        number: u32,
+        directory: &mut Inode,
+        entry: &DirectoryEntry,
+    ) -> Result<(), Ext2Error> {
+        let block_size = self.superblock.block_size;
+        let block = self.get_block(directory, (entry.offset / block_size) as u64)?;
+        let mut data = vec![0; block_size];
+        self.read_block(block, &mut data)?;
+        let offset = entry.offset % block_size;
+        let records: Vec<_> = parse_block(&data, 0, self.has_file_type())?
+            .into_iter()
+            .map(|(raw, _)| raw)
+            .collect();
+        let record = records
+            .iter()
+            .find(|raw| raw.offset == offset)
+            .ok_or(Ext2Error::InvalidFileSystem)?;
+        match records
+            .iter()
+            .find(|raw| raw.offset + raw.record_length == offset)
+        {
+            Some(previous) => {
+                let merged = (previous.record_length + record.record_length) as u16;
+                data[previous.offset + 4..previous.offset + 6]
+                    .copy_from_slice(&merged.to_le_bytes());
+            }
+            None => data[offset..offset + 4].fill(0),
+        }
+        self.write_block(block, &data)?;
+        directory.flags &= !INODE_FLAG_INDEX;
+        self.write_inode(number, directory)
+    }
+
+    /// Changes the inode an entry refers to.
+    fn set_entry_inode(
+        &mut self,
+        directory: &Inode,
+        entry: &DirectoryEntry,
+        inode: u32,
+    ) -> Result<(), Ext2Error> {
+        let block_size = self.superblock.block_size;
+        let block = self.get_block(directory, (entry.offset / block_size) as u64)?;
+        let mut data = vec![0; block_size];
+        self.read_block(block, &mut data)?;
+        let offset = entry.offset % block_size;
+        data[offset..offset + 4].copy_from_slice(&inode.to_le_bytes());
+        self.write_block(block, &data)
+    }
+
+    /// Writes the data to the file's blocks from `offset` on, allocating the missing ones.
+    ///
+    /// The size grows with every written block, so it's correct if the volume runs out of space.
+    fn write_data(
+        &mut self,
+        number: u32,
+        inode: &mut Inode,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<(), Ext2Error> {
+        let block_size = self.superblock.block_size;
+        let mut block_data = vec![0; block_size];
+        let mut position = offset;
+        let mut done = 0;
+        while done < data.len() {
+            let start = position % block_size;
+            let part = (block_size - start).min(data.len() - done);
+            let block = self.map_block(number, inode, (position / block_size) as u64)?;
+            if part == block_size {
+                self.write_block(block, &data[done..done + part])?;
+            } else {
+                self.read_block(block, &mut block_data)?;
+                block_data[start..start + part].copy_from_slice(&data[done..done + part]);
+                self.write_block(block, &block_data)?;
+            }
+            position += part;
+            done += part;
+            if position as u64 > inode.size() {
+                inode.set_size(position as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn pointers_per_block(&self) -> u64 {
+        (self.superblock.block_size / 4) as u64
+    }
+
+    fn block_path(&self, index: u64) -> Result<Vec<usize>, Ext2Error> {
+        block_path(self.superblock.block_size, index)
+    }
+
+    /// Returns the data block with the index, 0 if it's a hole.
+    fn get_block(&mut self, inode: &Inode, index: u64) -> Result<u32, Ext2Error> {
+        let path = self.block_path(index)?;
+        let mut block = inode.blocks[path[0]];
+        let mut data = vec![0; self.superblock.block_size];
+        for &offset in &path[1..] {
+            if block == 0 {
+                return Ok(0);
+            }
+            self.read_block(block, &mut data)?;
+            block = read_u32(&data, offset * 4);
+        }
+        Ok(block)
+    }
+
+    /// Returns the data block with the index, allocating it and the indirect blocks leading to it
+    /// if they're missing. The inode must be written afterwards.
+    fn map_block(&mut self, number: u32, inode: &mut Inode, index: u64) -> Result<u32, Ext2Error> {
+        let path = self.block_path(index)?;
+        let group = self.group_of(number);
+        let sectors_per_block = (self.superblock.block_size / INODE_BLOCKS_UNIT) as u32;
+        if inode.blocks[path[0]] == 0 {
+            inode.blocks[path[0]] = self.allocate_block(group)?;
+            inode.sectors += sectors_per_block;
+        }
+        let mut block = inode.blocks[path[0]];
+        let mut data = vec![0; self.superblock.block_size];
+        for &offset in &path[1..] {
+            self.read_block(block, &mut data)?;
+            let mut next = read_u32(&data, offset * 4);
+            if next == 0 {
+                next = self.allocate_block(group)?;
+                inode.sectors += sectors_per_block;
+                data[offset * 4..offset * 4 + 4].copy_from_slice(&next.to_le_bytes());
+                self.write_block(block, &data)?;
+            }
+            block = next;
+        }
+        Ok(block)
+    }
+
+    /// Frees the data blocks from the index on and the indirect blocks no longer needed.
+    /// The inode must be written afterwards.
+    fn free_blocks_from(&mut self, inode: &mut Inode, first: u64) -> Result<(), Ext2Error> {
+        let sectors_per_block = (self.superblock.block_size / INODE_BLOCKS_UNIT) as u32;
+        for index in (first as usize).min(DIRECT_BLOCKS)..DIRECT_BLOCKS {
+            if inode.blocks[index] != 0 {
+                self.free_block(inode.blocks[index])?;
+                inode.blocks[index] = 0;
+                inode.sectors = inode.sectors.saturating_sub(sectors_per_block);
+            }
+        }
+        let mut start = DIRECT_BLOCKS as u64;
+        let mut span = self.pointers_per_block();
+        for level in 1..=3 {
+            let pointer = DIRECT_BLOCKS + level - 1;
+            let block = inode.blocks[pointer];
+            if block != 0
+                && first < start + span
+                && self.free_tree(inode, block, level, first.saturating_sub(start))?
+            {
+                inode.blocks[pointer] = 0;
+            }
+            start += span;
+            span *= self.pointers_per_block();
+        }
+        Ok(())
+    }
+
+    /// Frees the data blocks from the index `first` on in the tree below the indirect block of the depth.
+    ///
+    /// Returns whether the whole tree, including the block itself, was freed.
+    fn free_tree(
+        &mut self,
+        inode: &mut Inode,
+        block: u32,
+        depth: usize,
+        first: u64,
+    ) -> Result<bool, Ext2Error> {
+        let sectors_per_block = (self.superblock.block_size / INODE_BLOCKS_UNIT) as u32;
+        if depth > 0 {
+            let pointers_per_block = self.pointers_per_block();
+            let span = pointers_per_block.pow(depth as u32 - 1);
+            let mut data = vec![0; self.superblock.block_size];
+            self.read_block(block, &mut data)?;
+            let mut changed = false;
+            for index in 0..pointers_per_block {
+                let child = read_u32(&data, index as usize * 4);
+                if child == 0 || (index + 1) * span <= first {
+                    continue;
+                }
+                if self.free_tree(inode, child, depth - 1, first.saturating_sub(index * span))? {
+                    data[index as usize * 4..index as usize * 4 + 4].fill(0);
+                    changed = true;
+                }
+            }
+            if first > 0 {
+                if changed {
+                    self.write_block(block, &data)?;
+                }
+                return Ok(false);
+            }
+        } else if first > 0 {
+            return Ok(false);
+        }
+        self.free_block(block)?;
+        inode.sectors = inode.sectors.saturating_sub(sectors_per_block);
+        Ok(true)
+    }
+
+    fn group_of(&self, inode: u32) -> u32 {
+        (inode - 1) / self.superblock.inodes_per_group
+    }
+
+    /// Allocates a zeroed block, preferring the group.
+    fn allocate_block(&mut self, group: u32) -> Result<u32, Ext2Error> {
+        let block = self.allocate(group, false)?;
+        self.write_block(block, &vec![0; self.superblock.block_size])?;
+        Ok(block)
+    }
+
+    /// Allocates an inode, preferring the group.
+    fn allocate_inode(&mut self, group: u32, is_directory: bool) -> Result<u32, Ext2Error> {
+        let inode = self.allocate(group, true)?;
+        if is_directory {
+            let group = self.group_of(inode) as usize;
+            self.groups[group].directory_count += 1;
+        }
+        Ok(inode)
+    }
+
+    /// Finds a free block or inode in the bitmaps, starting with the group, and marks it as used.
+    fn allocate(&mut self, first_group: u32, inode: bool) -> Result<u32, Ext2Error> {
+        let group_count = self.groups.len() as u32;
+        let mut bitmap = vec![0; self.superblock.block_size];
+        for offset in 0..group_count {
+            let group = (first_group + offset) % group_count;
+            let descriptor = &self.groups[group as usize];
+            let (free, bitmap_block) = if inode {
+                (descriptor.free_inode_count, descriptor.inode_bitmap)
+            } else {
+                (descriptor.free_block_count, descriptor.block_bitmap)
+            };
+            if free == 0 {
+                continue;
+            }
+            self.read_block(bitmap_block, &mut bitmap)?;
+            let found = (0..self.group_size(group, inode)).find(|&bit| {
+                bitmap[bit as usize / 8] & (1 << (bit % 8)) == 0
+                    && (!inode
+                        || group * self.superblock.inodes_per_group + bit + 1
+                            >= self.superblock.first_inode)
+            });
+            if let Some(bit) = found {
+                bitmap[bit as usize / 8] |= 1 << (bit % 8);
+                self.write_block(bitmap_block, &bitmap)?;
+                let descriptor = &mut self.groups[group as usize];
+                self.dirty = true;
+                return Ok(if inode {
+                    descriptor.free_inode_count -= 1;
+                    self.superblock.free_inode_count -= 1;
+                    group * self.superblock.inodes_per_group + bit + 1
+                } else {
+                    descriptor.free_block_count -= 1;
+                    self.superblock.free_block_count -= 1;
+                    self.superblock.first_data_block
+                        + group * self.superblock.blocks_per_group
+                        + bit
+                });
+            }
+        }
+        Err(Ext2Error::NoSpace)
+    }
+
+    /// Returns the number of blocks or inodes of the group, the last group may have fewer blocks.
+    fn group_size(&self, group: u32, inode: bool) -> u32 {
+        if inode {
+            self.superblock.inodes_per_group
+        } else {
+            let first_block =
+                self.superblock.first_data_block + group * self.superblock.blocks_per_group;
+            self.superblock
+                .blocks_per_group
+                .min(self.superblock.block_count - first_block)
+        }
+    }
+
+    fn free_block(&mut self, block: u32) -> Result<(), Ext2Error> {
+        if block < self.superblock.first_data_block || block >= self.superblock.block_count {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        let index = block - self.superblock.first_data_block;
+        let group = index / self.superblock.blocks_per_group;
+        self.clear_bit(group, index % self.superblock.blocks_per_group, false)?;
+        self.groups[group as usize].free_block_count += 1;
+        self.superblock.free_block_count += 1;
+        Ok(())
+    }
+
+    fn free_inode(&mut self, inode: u32, is_directory: bool) -> Result<(), Ext2Error> {
+        let group = self.group_of(inode);
+        self.clear_bit(group, (inode - 1) % self.superblock.inodes_per_group, true)?;
+        let descriptor = &mut self.groups[group as usize];
+        descriptor.free_inode_count += 1;
+        if is_directory {
+            descriptor.directory_count = descriptor.directory_count.saturating_sub(1);
+        }
+        self.superblock.free_inode_count += 1;
+        Ok(())
+    }
+
+    fn clear_bit(&mut self, group: u32, bit: u32, inode: bool) -> Result<(), Ext2Error> {
+        let descriptor = self
+            .groups
+            .get(group as usize)
+            .ok_or(Ext2Error::InvalidFileSystem)?;
+        let bitmap_block = if inode {
+            descriptor.inode_bitmap
+        } else {
+            descriptor.block_bitmap
+        };
+        let mut bitmap = vec![0; self.superblock.block_size];
+        self.read_block(bitmap_block, &mut bitmap)?;
+        bitmap[bit as usize / 8] &= !(1 << (bit % 8));
+        self.write_block(bitmap_block, &bitmap)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the block of the inode table holding the inode and the inode's offset in it.
+    fn inode_position(&self, inode: u32) -> Result<(u32, usize), Ext2Error> {
+        if inode == 0 || inode > self.superblock.inode_count {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        let group = self.group_of(inode) as usize;
+        let offset =
+            ((inode - 1) % self.superblock.inodes_per_group) as usize * self.superblock.inode_size;
+        let block_size = self.superblock.block_size;
+        let table = self
+            .groups
+            .get(group)
+            .ok_or(Ext2Error::InvalidFileSystem)?
+            .inode_table;
+        Ok((table + (offset / block_size) as u32, offset % block_size))
+    }
+
+    fn read_inode(&mut self, inode: u32) -> Result<Inode, Ext2Error> {
+        let (block, offset) = self.inode_position(inode)?;
+        let mut data = vec![0; self.superblock.block_size];
+        self.read_block(block, &mut data)?;
+        Ok(Inode::from_bytes(
+            &data[offset..offset + self.superblock.inode_size],
+        ))
+    }
+
+    fn write_inode(&mut self, number: u32, inode: &Inode) -> Result<(), Ext2Error> {
+        let (block, offset) = self.inode_position(number)?;
+        let mut data = vec![0; self.superblock.block_size];
+        self.read_block(block, &mut data)?;
+        inode.write_to(&mut data[offset..offset + self.superblock.inode_size]);
+        self.write_block(block, &data)
+    }
+
+    fn read_block(&mut self, block: u32, buffer: &mut [u8]) -> Result<(), Ext2Error> {
+        if block >= self.superblock.block_count {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        Ok(self
+            .device
+            .read_blocks(block as u64 * self.device_blocks_per_block, buffer)?)
+    }
+
+    fn write_block(&mut self, block: u32, data: &[u8]) -> Result<(), Ext2Error> {
+        if block >= self.superblock.block_count {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        Ok(self
+            .device
+            .write_blocks(block as u64 * self.device_blocks_per_block, data)?)
+    }
+}
+
+/// Returns the way to the data block with the index: the index of the inode's block pointer,
+/// followed by the index in each indirect block.
+///
+/// Returns `FileTooLarge` if the index is beyond the triply indirect blocks.
+pub fn block_path(block_size: usize, index: u64) -> Result<Vec<usize>, Ext2Error> {
+    if index < DIRECT_BLOCKS as u64 {
+        return Ok(vec![index as usize]);
+    }
+    let pointers_per_block = (block_size / 4) as u64;
+    let mut index = index - DIRECT_BLOCKS as u64;
+    // the number of data blocks reachable through the indirect block of the level
+    let mut span = pointers_per_block;
+    for level in 1..=3 {
+        if index < span {
+            let mut path = vec![DIRECT_BLOCKS + level - 1];
+            let mut sub_span = span;
+            for _ in 0..level {
+                sub_span /= pointers_per_block;
+                path.push((index / sub_span % pointers_per_block) as usize);
+            }
+            return Ok(path);
+        }
+        index -= span;
+        span *= pointers_per_block;
+    }
+    Err(Ext2Error::FileTooLarge)
+}
+
+/// Returns an error unless the inode is a regular file.
+fn check_regular_file(inode: &Inode) -> Result<(), Ext2Error> {
+    match inode.file_type() {
+        FileType::File => Ok(()),
+        FileType::Directory => Err(Ext2Error::IsADirectory),
+        _ => Err(Ext2Error::NotAFile),
+    }
+}
+
+/// Returns the non-empty components of the path.
+fn components(path: &str) -> impl DoubleEndedIterator<Item = &str> {
+    path.split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+}
+
+/// Returns the device blocks covering the superblock and the superblock's offset in them.
+fn superblock_range<D: BlockDevice>(device: &D) -> (u64, usize, usize) {
+    let block_size = device.block_size();
+    let first_block = SUPERBLOCK_OFFSET / block_size;
+    let offset = SUPERBLOCK_OFFSET % block_size;
+    let length = (offset + SUPERBLOCK_SIZE + block_size - 1) / block_size * block_size;
+    (first_block as u64, offset, length)
+}
+
+fn read_superblock<D: BlockDevice>(device: &mut D) -> Result<Vec<u8>, Ext2Error> {
+    let (first_block, offset, length) = superblock_range(device);
+    let mut data = vec![0; length];
+    device.read_blocks(first_block, &mut data)?;
+    Ok(data[offset..offset + SUPERBLOCK_SIZE].to_vec())
+}
+
+fn write_superblock<D: BlockDevice>(device: &mut D, superblock: &[u8]) -> Result<(), Ext2Error> {
+    let (first_block, offset, length) = superblock_range(device);
+    let mut data = vec![0; length];
+    device.read_blocks(first_block, &mut data)?;
+    data[offset..offset + SUPERBLOCK_SIZE].copy_from_slice(superblock);
+    Ok(device.write_blocks(first_block, &data)?)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
@@ -0,0 +1,33 @@
+use utils::byte_reader::ByteReader;
+
+/// The descriptor of a block group, locating its bitmaps and inode table.
+#[derive(Debug, Clone)]
+pub(crate) struct GroupDescriptor {
+    pub(crate) block_bitmap: u32,
+    pub(crate) inode_bitmap: u32,
+    pub(crate) inode_table: u32,
+    pub(crate) free_block_count: u16,
+    pub(crate) free_inode_count: u16,
+    pub(crate) directory_count: u16,
+}
+
+impl GroupDescriptor {
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        let mut reader = ByteReader::of(bytes);
+        GroupDescriptor {
+            block_bitmap: reader.read_u32(),
+            inode_bitmap: reader.read_u32(),
+            inode_table: reader.read_u32(),
+            free_block_count: reader.read_u16(),
+            free_inode_count: reader.read_u16(),
+            directory_count: reader.read_u16(),
+        }
+    }
+
+    /// Writes the counters the driver changes into the bytes of the descriptor.
+    pub(crate) fn write_to(&self, bytes: &mut [u8]) {
+        bytes[12..14].copy_from_slice(&self.free_block_count.to_le_bytes());
+        bytes[14..16].copy_from_slice(&self.free_inode_count.to_le_bytes());
+        bytes[16..18].copy_from_slice(&self.directory_count.to_le_bytes());
+    }
+}
@@ -0,0 +1,217 @@
+use utils::byte_reader::ByteReader;
+
+use crate::constants::{
+    INODE_BLOCK_POINTERS, MODE_BLOCK_DEVICE, MODE_CHARACTER_DEVICE, MODE_DIRECTORY, MODE_FIFO,
+    MODE_FILE, MODE_PERMISSIONS_MASK, MODE_SOCKET, MODE_SYMLINK, MODE_TYPE_MASK,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    CharacterDevice,
+    BlockDevice,
+    Fifo,
+    Socket,
+    Unknown,
+}
+
+impl FileType {
+    pub(crate) fn from_mode(mode: u16) -> Self {
+        match mode & MODE_TYPE_MASK {
+            MODE_FILE => FileType::File,
+            MODE_DIRECTORY => FileType::Directory,
+            MODE_SYMLINK => FileType::Symlink,
+            MODE_CHARACTER_DEVICE => FileType::CharacterDevice,
+            MODE_BLOCK_DEVICE => FileType::BlockDevice,
+            MODE_FIFO => FileType::Fifo,
+            MODE_SOCKET => FileType::Socket,
+            _ => FileType::Unknown,
+        }
+    }
+}
+
+/// The metadata of a file, directory or symlink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub inode: u32,
+    pub file_type: FileType,
+    /// The permission bits, including setuid, setgid and sticky.
+    pub permissions: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// The number of directory entries referring to the inode.
+    pub links: u16,
+    /// The access, modification and inode change times in seconds since the Unix epoch.
+    pub accessed: u32,
+    pub modified: u32,
+    pub changed: u32,
+}
+
+/// The fields of an inode the driver uses.
+///
+/// Like the superblock, the inode is written back by patching its fields into the bytes on the volume.
+#[derive(Debug, Clone)]
+pub(crate) struct Inode {
+    pub(crate) mode: u16,
+    pub(crate) uid: u32,
+    pub(crate) gid: u32,
+    /// The lower 32 bits of the size, regular files store the upper ones in `size_high`.
+    pub(crate) size: u32,
+    pub(crate) size_high: u32,
+    pub(crate) accessed: u32,
+    pub(crate) changed: u32,
+    pub(crate) modified: u32,
+    pub(crate) deleted: u32,
+    pub(crate) links: u16,
+    /// The number of 512 byte sectors allocated for the data and the indirect blocks.
+    pub(crate) sectors: u32,
+    pub(crate) flags: u32,
+    pub(crate) blocks: [u32; INODE_BLOCK_POINTERS],
+    /// The block holding the extended attributes, 0 if there's none.
+    pub(crate) file_acl: u32,
+}
+
+impl Inode {
+    /// Creates an unused inode of the passed mode, the type bits included.
+    pub(crate) fn new(mode: u16) -> Self {
+        Inode {
+            mode,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            size_high: 0,
+            accessed: 0,
+            changed: 0,
+            modified: 0,
+            deleted: 0,
+            links: 0,
+            sectors: 0,
+            flags: 0,
+            blocks: [0; INODE_BLOCK_POINTERS],
+            file_acl: 0,
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        let mut reader = ByteReader::of(bytes);
+        let mode = reader.read_u16();
+        let uid_low = reader.read_u16();
+        let size = reader.read_u32();
+        let accessed = reader.read_u32();
+        let changed = reader.read_u32();
+        let modified = reader.read_u32();
+        let deleted = reader.read_u32();
+        let gid_low = reader.read_u16();
+        let links = reader.read_u16();
+        let sectors = reader.read_u32();
+        let flags = reader.read_u32();
+        let _os_specific = reader.read_u32();
+        let mut blocks = [0; INODE_BLOCK_POINTERS];
+        for block in blocks.iter_mut() {
+            *block = reader.read_u32();
+        }
+        let _generation = reader.read_u32();
+        let file_acl = reader.read_u32();
+        let size_high = reader.read_u32();
+        // the upper 16 bits of the IDs are stored in the Linux specific part
+        let uid_high = u16::from_le_bytes([bytes[120], bytes[121]]);
+        let gid_high = u16::from_le_bytes([bytes[122], bytes[123]]);
+        Inode {
+            mode,
+            uid: (uid_high as u32) << 16 | uid_low as u32,
+            gid: (gid_high as u32) << 16 | gid_low as u32,
+            size,
+            size_high,
+            accessed,
+            changed,
+            modified,
+            deleted,
+            links,
+            sectors,
+            flags,
+            blocks,
+            file_acl,
+        }
+    }
+
+    /// Writes the fields into the bytes of the inode on the volume.
+    pub(crate) fn write_to(&self, bytes: &mut [u8]) {
+        bytes[0..2].copy_from_slice(&self.mode.to_le_bytes());
+        bytes[2..4].copy_from_slice(&(self.uid as u16).to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.size.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.accessed.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.changed.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.modified.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.deleted.to_le_bytes());
+        bytes[24..26].copy_from_slice(&(self.gid as u16).to_le_bytes());
+        bytes[26..28].copy_from_slice(&self.links.to_le_bytes());
+        bytes[28..32].copy_from_slice(&self.sectors.to_le_bytes());
+        bytes[32..36].copy_from_slice(&self.flags.to_le_bytes());
+        for (index, block) in self.blocks.iter().enumerate() {
+            bytes[40 + index * 4..44 + index * 4].copy_from_slice(&block.to_le_bytes());
+        }
+        bytes[108..112].copy_from_slice(&self.size_high.to_le_bytes());
+        bytes[120..122].copy_from_slice(&((self.uid >> 16) as u16).to_le_bytes());
+        bytes[122..124].copy_from_slice(&((self.gid >> 16) as u16).to_le_bytes());
+    }
+
+    pub(crate) fn file_type(&self) -> FileType {
+        FileType::from_mode(self.mode)
+    }
+
+    /// Returns the size, the upper 32 bits are only used by regular files.
+    pub(crate) fn size(&self) -> u64 {
+        if self.file_type() == FileType::File {
+            (self.size_high as u64) << 32 | self.size as u64
+        } else {
+            self.size as u64
+        }
+    }
+
+    pub(crate) fn set_size(&mut self, size: u64) {
+        self.size = size as u32;
+        if self.file_type() == FileType::File {
+            self.size_high = (size >> 32) as u32;
+        }
+    }
+
+    /// Returns the target of a fast symlink, stored in the block pointers.
+    pub(crate) fn fast_symlink_target(&self) -> [u8; INODE_BLOCK_POINTERS * 4] {
+        let mut target = [0; INODE_BLOCK_POINTERS * 4];
+        for (index, block) in self.blocks.iter().enumerate() {
+            target[index * 4..index * 4 + 4].copy_from_slice(&block.to_le_bytes());
+        }
+        target
+    }
+
+    pub(crate) fn set_fast_symlink_target(&mut self, target: &[u8]) {
+        let mut bytes = [0; INODE_BLOCK_POINTERS * 4];
+        bytes[..target.len()].copy_from_slice(target);
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            *block = u32::from_le_bytes([
+                bytes[index * 4],
+                bytes[index * 4 + 1],
+                bytes[index * 4 + 2],
+                bytes[index * 4 + 3],
+            ]);
+        }
+    }
+
+    pub(crate) fn metadata(&self, inode: u32) -> Metadata {
+        Metadata {
+            inode,
+            file_type: self.file_type(),
+            permissions: self.mode & MODE_PERMISSIONS_MASK,
+            uid: self.uid,
+            gid: self.gid,
+            size: self.size(),
+            links: self.links,
+            accessed: self.accessed,
+            modified: self.modified,
+            changed: self.changed,
+        }
+    }
+}
@@ -0,0 +1,22 @@
+#![no_std] // no standard library
+#![no_main]
+extern crate alloc;
+
+mod constants;
+pub use constants::{Ext2Error, ROOT_INODE};
+
+mod superblock;
+
+mod group_descriptor;
+
+mod inode;
+pub use inode::{FileType, Metadata};
+
+mod directory_entry;
+pub use directory_entry::DirectoryEntry;
+
+mod file_system;
+pub use file_system::{block_path, Ext2};
+
+mod vfs;
+pub use vfs::Ext2FileSystem;
@@ -0,0 +1,126 @@
+use utils::byte_reader::ByteReader;
+
+use crate::{
+    constants::{EXT2_MAGIC, GOOD_OLD_FIRST_INODE, GOOD_OLD_INODE_SIZE, SUPERBLOCK_SIZE},
+    Ext2Error,
+};
+
+/// The fields of the superblock the driver uses.
+///
+/// The superblock is written back by patching the changed fields into its bytes,
+/// so the fields the driver doesn't know about are kept.
+#[derive(Debug, Clone)]
+pub(crate) struct Superblock {
+    pub(crate) inode_count: u32,
+    pub(crate) block_count: u32,
+    pub(crate) free_block_count: u32,
+    pub(crate) free_inode_count: u32,
+    pub(crate) first_data_block: u32,
+    pub(crate) block_size: usize,
+    pub(crate) blocks_per_group: u32,
+    pub(crate) inodes_per_group: u32,
+    /// The last time the volume was written, in seconds since the Unix epoch.
+    pub(crate) write_time: u32,
+    pub(crate) revision: u32,
+    pub(crate) first_inode: u32,
+    pub(crate) inode_size: usize,
+    pub(crate) feature_incompat: u32,
+    pub(crate) feature_ro_compat: u32,
+}
+
+impl Superblock {
+    /// Parses the superblock, returns `InvalidFileSystem` if it doesn't describe an ext2 volume.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, Ext2Error> {
+        if bytes.len() < SUPERBLOCK_SIZE {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        let mut reader = ByteReader::of(bytes);
+        let inode_count = reader.read_u32();
+        let block_count = reader.read_u32();
+        let _reserved_block_count = reader.read_u32();
+        let free_block_count = reader.read_u32();
+        let free_inode_count = reader.read_u32();
+        let first_data_block = reader.read_u32();
+        let log_block_size = reader.read_u32();
+        let _log_fragment_size = reader.read_u32();
+        let blocks_per_group = reader.read_u32();
+        let _fragments_per_group = reader.read_u32();
+        let inodes_per_group = reader.read_u32();
+        let _mount_time = reader.read_u32();
+        let write_time = reader.read_u32();
+        let _mount_count = reader.read_u16();
+        let _max_mount_count = reader.read_u16();
+        let magic = reader.read_u16();
+        let _state = reader.read_u16();
+        let _errors = reader.read_u16();
+        let _minor_revision = reader.read_u16();
+        let _last_check = reader.read_u32();
+        let _check_interval = reader.read_u32();
+        let _creator_os = reader.read_u32();
+        let revision = reader.read_u32();
+        let _default_reserved_uid = reader.read_u16();
+        let _default_reserved_gid = reader.read_u16();
+
+        // the dynamic revision has variable inode sizes and feature flags
+        let (first_inode, inode_size, feature_incompat, feature_ro_compat) = if revision == 0 {
+            (GOOD_OLD_FIRST_INODE, GOOD_OLD_INODE_SIZE, 0, 0)
+        } else {
+            let first_inode = reader.read_u32();
+            let inode_size = reader.read_u16() as usize;
+            let _block_group = reader.read_u16();
+            let _feature_compat = reader.read_u32();
+            (
+                first_inode,
+                inode_size,
+                reader.read_u32(),
+                reader.read_u32(),
+            )
+        };
+
+        let valid = magic == EXT2_MAGIC
+            && log_block_size <= 6
+            && blocks_per_group > 0
+            && inodes_per_group > 0
+            && block_count > first_data_block
+            && inode_size >= GOOD_OLD_INODE_SIZE
+            && inode_size.is_power_of_two();
+        if !valid {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        let block_size = 1024 << log_block_size;
+        if inode_size > block_size {
+            return Err(Ext2Error::InvalidFileSystem);
+        }
+        Ok(Superblock {
+            inode_count,
+            block_count,
+            free_block_count,
+            free_inode_count,
+            first_data_block,
+            block_size,
+            blocks_per_group,
+            inodes_per_group,
+            write_time,
+            revision,
+            first_inode,
+            inode_size,
+            feature_incompat,
+            feature_ro_compat,
+        })
+    }
+
+    /// Writes the fields the driver changes into the bytes of the superblock.
+    pub(crate) fn write_to(&self, bytes: &mut [u8]) {
+        bytes[12..16].copy_from_slice(&self.free_block_count.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.free_inode_count.to_le_bytes());
+        if self.revision != 0 {
+            bytes[100..104].copy_from_slice(&self.feature_ro_compat.to_le_bytes());
+        }
+    }
+
+    /// Returns the number of block groups.
+    pub(crate) fn group_count(&self) -> u32 {
+        let data_blocks = self.block_count - self.first_data_block;
+        (data_blocks + self.blocks_per_group - 1) / self.blocks_per_group
+    }
+}
@@ -0,0 +1,238 @@
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+use kernel::{
+    processes::SleepMutex,
+    vfs::{self, DirectoryEntry, FileStat, FileSystem, Inode, VfsError},
+    BlockDevice,
+};
+
+use crate::{Ext2, Ext2Error, FileType, ROOT_INODE};
+
+impl From<Ext2Error> for VfsError {
+    fn from(error: Ext2Error) -> Self {
+        match error {
+            Ext2Error::Device(_) | Ext2Error::InvalidFileSystem => VfsError::IoError,
+            Ext2Error::UnsupportedFeature => VfsError::NotSupported,
+            Ext2Error::ReadOnly => VfsError::ReadOnly,
+            Ext2Error::NotFound => VfsError::NotFound,
+            Ext2Error::AlreadyExists => VfsError::AlreadyExists,
+            Ext2Error::NotADirectory => VfsError::NotADirectory,
+            Ext2Error::IsADirectory => VfsError::IsADirectory,
+            Ext2Error::NotAFile | Ext2Error::NotASymlink => VfsError::InvalidArgument,
+            Ext2Error::DirectoryNotEmpty => VfsError::DirectoryNotEmpty,
+            Ext2Error::InvalidName => VfsError::InvalidName,
+            Ext2Error::TooManySymlinks => VfsError::TooManySymlinks,
+            Ext2Error::NoSpace => VfsError::NoSpace,
+            Ext2Error::FileTooLarge => VfsError::FileTooLarge,
+        }
+    }
+}
+
+impl From<FileType> for vfs::FileType {
+    fn from(file_type: FileType) -> Self {
+        match file_type {
+            FileType::File => vfs::FileType::File,
+            FileType::Directory => vfs::FileType::Directory,
+            FileType::Symlink => vfs::FileType::Symlink,
+            FileType::CharacterDevice => vfs::FileType::CharacterDevice,
+            FileType::BlockDevice => vfs::FileType::BlockDevice,
+            FileType::Fifo => vfs::FileType::Fifo,
+            FileType::Socket => vfs::FileType::Socket,
+            FileType::Unknown => vfs::FileType::Unknown,
+        }
+    }
+}
+
+/// An ext2 volume that can be mounted in the virtual file system.
+pub struct Ext2FileSystem<D: BlockDevice> {
+    volume: Arc<SleepMutex<Volume<D>>>,
+}
+
+impl<D: BlockDevice> Ext2FileSystem<D> {
+    pub fn new(ext2: Ext2<D>) -> Self {
+        Ext2FileSystem {
+            volume: Arc::new(SleepMutex::new(Volume {
+                ext2,
+                inodes: BTreeMap::new(),
+            })),
+        }
+    }
+}
+
+impl<D: BlockDevice + 'static> FileSystem for Ext2FileSystem<D> {
+    fn name(&self) -> &str {
+        "ext2"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(Ext2Inode {
+            volume: self.volume.clone(),
+            number: ROOT_INODE,
+            removed: AtomicBool::new(false),
+        })
+    }
+
+    fn sync(&self) -> Result<(), VfsError> {
+        Ok(self.volume.lock().ext2.sync()?)
+    }
+}
+
+/// A mounted volume and the inodes handed out for it, behind a sleeping lock since it's held across disk I/O.
+struct Volume<D: BlockDevice> {
+    ext2: Ext2<D>,
+    /// The inodes in use by their number.
+    inodes: BTreeMap<u32, Weak<Ext2Inode<D>>>,
+}
+
+impl<D: BlockDevice> Volume<D> {
+    /// Marks the inode in use for the number as removed, since the number may be reused from now on.
+    fn detach(&mut self, number: u32) {
+        if let Some(inode) = self
+            .inodes
+            .remove(&number)
+            .and_then(|inode| inode.upgrade())
+        {
+            inode.removed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A file, directory or symlink of a mounted volume, identified by its inode number.
+///
+/// There's one per inode in use, so an open file keeps referring to its inode after being removed
+/// instead of to a new file reusing the number. It fails with `NotFound` once the inode was freed.
+struct Ext2Inode<D: BlockDevice> {
+    volume: Arc<SleepMutex<Volume<D>>>,
+    number: u32,
+    /// Only changed with the volume locked.
+    removed: AtomicBool,
+}
+
+impl<D: BlockDevice + 'static> Ext2Inode<D> {
+    /// Returns the inode number, unless the inode was freed. The volume must be locked.
+    fn number(&self) -> Result<u32, VfsError> {
+        if self.removed.load(Ordering::Relaxed) {
+            Err(VfsError::NotFound)
+        } else {
+            Ok(self.number)
+        }
+    }
+
+    /// Returns the inode in use for the number, or a new one.
+    fn inode(&self, volume: &mut Volume<D>, number: u32) -> Arc<dyn Inode> {
+        if let Some(inode) = volume.inodes.get(&number).and_then(Weak::upgrade) {
+            return inode;
+        }
+        let inode = Arc::new(Ext2Inode {
+            volume: self.volume.clone(),
+            number,
+            removed: AtomicBool::new(false),
+        });
+        volume.inodes.retain(|_, inode| inode.strong_count() > 0);
+        volume.inodes.insert(number, Arc::downgrade(&inode));
+        inode
+    }
+}
+
+impl<D: BlockDevice + 'static> Inode for Ext2Inode<D> {
+    fn stat(&self) -> Result<FileStat, VfsError> {
+        let mut volume = self.volume.lock();
+        let metadata = volume.ext2.metadata_at(self.number()?)?;
+        Ok(FileStat {
+            inode: metadata.inode as u64,
+            file_type: metadata.file_type.into(),
+            permissions: metadata.permissions,
+            links: metadata.links as u32,
+            uid: metadata.uid,
+            gid: metadata.gid,
+            size: metadata.size,
+            accessed: metadata.accessed as u64,
+            modified: metadata.modified as u64,
+            changed: metadata.changed as u64,
+        })
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        let mut volume = self.volume.lock();
+        Ok(volume
+            .ext2
+            .read_at(self.number()?, offset as usize, buffer)?)
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        let mut volume = self.volume.lock();
+        Ok(volume
+            .ext2
+            .write_at(self.number()?, offset as usize, data)?)
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), VfsError> {
+        let mut volume = self.volume.lock();
+        Ok(volume.ext2.truncate_at(self.number()?, size as usize)?)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, VfsError> {
+        let mut volume = self.volume.lock();
+        let number = volume.ext2.lookup_at(self.number()?, name)?;
+        Ok(self.inode(&mut volume, number))
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirectoryEntry>, VfsError> {
+        let mut volume = self.volume.lock();
+        let entries = volume.ext2.read_dir_at(self.number()?)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| DirectoryEntry {
+                name: entry.name,
+                inode: entry.inode as u64,
+                file_type: entry.file_type.into(),
+            })
+            .collect())
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: vfs::FileType,
+        permissions: u16,
+    ) -> Result<Arc<dyn Inode>, VfsError> {
+        let mut volume = self.volume.lock();
+        let directory = self.number()?;
+        let number = match file_type {
+            vfs::FileType::File => volume.ext2.create_file_at(directory, name, permissions)?,
+            vfs::FileType::Directory => {
+                volume
+                    .ext2
+                    .create_directory_at(directory, name, permissions)?
+            }
+            _ => return Err(VfsError::NotSupported),
+        };
+        Ok(self.inode(&mut volume, number))
+    }
+
+    fn create_symlink(&self, name: &str, target: &str) -> Result<(), VfsError> {
+        let mut volume = self.volume.lock();
+        volume
+            .ext2
+            .create_symlink_at(self.number()?, name, target)?;
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<(), VfsError> {
+        let mut volume = self.volume.lock();
+        if let Some(number) = volume.ext2.remove_at(self.number()?, name)? {
+            volume.detach(number);
+        }
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<String, VfsError> {
+        let mut volume = self.volume.lock();
+        Ok(volume.ext2.read_link_at(self.number()?)?)
+    }
+}
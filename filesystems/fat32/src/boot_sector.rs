@@ -0,0 +1,121 @@
+use utils::byte_reader::ByteReader;
+
+use crate::{constants::MEDIA_FIXED_DISK, FatError};
+
+/// The BIOS parameter block of a FAT32 volume, in its first sector.
+#[derive(Debug, Clone)]
+pub(crate) struct BootSector {
+    pub(crate) bytes_per_sector: u16,
+    pub(crate) sectors_per_cluster: u8,
+    pub(crate) reserved_sectors: u16,
+    pub(crate) fat_count: u8,
+    pub(crate) total_sectors: u32,
+    /// The number of sectors of a single FAT.
+    pub(crate) fat_size: u32,
+    pub(crate) root_cluster: u32,
+    pub(crate) fs_info_sector: u16,
+    pub(crate) backup_boot_sector: u16,
+    pub(crate) volume_id: u32,
+    pub(crate) volume_label: [u8; 11],
+}
+
+impl BootSector {
+    /// Parses the boot sector, returns `InvalidFileSystem` if it doesn't describe a FAT32 volume.
+    pub(crate) fn from_bytes(sector: &[u8]) -> Result<Self, FatError> {
+        if sector.len() < 512 || sector[510] != 0x55 || sector[511] != 0xAA {
+            return Err(FatError::InvalidFileSystem);
+        }
+        let mut reader = ByteReader::of(&sector[11..]);
+        let bytes_per_sector = reader.read_u16();
+        let sectors_per_cluster = reader.read_u8();
+        let reserved_sectors = reader.read_u16();
+        let fat_count = reader.read_u8();
+        let root_entry_count = reader.read_u16();
+        let total_sectors_16 = reader.read_u16();
+        let _media = reader.read_u8();
+        let fat_size_16 = reader.read_u16();
+        let _sectors_per_track = reader.read_u16();
+        let _heads = reader.read_u16();
+        let _hidden_sectors = reader.read_u32();
+        let total_sectors_32 = reader.read_u32();
+        let fat_size = reader.read_u32();
+        let _extended_flags = reader.read_u16();
+        let _version = reader.read_u16();
+        let root_cluster = reader.read_u32();
+        let fs_info_sector = reader.read_u16();
+        let backup_boot_sector = reader.read_u16();
+
+        // FAT12 and FAT16 have a fixed root directory and their FAT size in the 16 bit field
+        let valid = bytes_per_sector.is_power_of_two()
+            && (512..=4096).contains(&bytes_per_sector)
+            && sectors_per_cluster.is_power_of_two()
+            && reserved_sectors > 0
+            && fat_count > 0
+            && root_entry_count == 0
+            && fat_size_16 == 0
+            && fat_size > 0
+            && root_cluster >= 2;
+        if !valid {
+            return Err(FatError::InvalidFileSystem);
+        }
+        let mut volume_label = [0u8; 11];
+        volume_label.copy_from_slice(&sector[71..82]);
+        Ok(BootSector {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors,
+            fat_count,
+            total_sectors: if total_sectors_16 != 0 {
+                total_sectors_16 as u32
+            } else {
+                total_sectors_32
+            },
+            fat_size,
+            root_cluster,
+            fs_info_sector,
+            backup_boot_sector,
+            volume_id: u32::from_le_bytes([sector[67], sector[68], sector[69], sector[70]]),
+            volume_label,
+        })
+    }
+
+    pub(crate) fn to_bytes(&self) -> [u8; 512] {
+        let mut sector = [0u8; 512];
+        // a jump over the parameter block, as x86 would execute it
+        sector[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        sector[3..11].copy_from_slice(b"ROSTOS  ");
+        sector[11..13].copy_from_slice(&self.bytes_per_sector.to_le_bytes());
+        sector[13] = self.sectors_per_cluster;
+        sector[14..16].copy_from_slice(&self.reserved_sectors.to_le_bytes());
+        sector[16] = self.fat_count;
+        sector[21] = MEDIA_FIXED_DISK;
+        // the usual geometry, only used by the BIOS
+        sector[24..26].copy_from_slice(&63u16.to_le_bytes());
+        sector[26..28].copy_from_slice(&255u16.to_le_bytes());
+        sector[32..36].copy_from_slice(&self.total_sectors.to_le_bytes());
+        sector[36..40].copy_from_slice(&self.fat_size.to_le_bytes());
+        sector[44..48].copy_from_slice(&self.root_cluster.to_le_bytes());
+        sector[48..50].copy_from_slice(&self.fs_info_sector.to_le_bytes());
+        sector[50..52].copy_from_slice(&self.backup_boot_sector.to_le_bytes());
+        sector[64] = 0x80;
+        // the extended boot signature, the volume ID, label and type follow
+        sector[66] = 0x29;
+        sector[67..71].copy_from_slice(&self.volume_id.to_le_bytes());
+        sector[71..82].copy_from_slice(&self.volume_label);
+        sector[82..90].copy_from_slice(b"FAT32   ");
+        sector[510] = 0x55;
+        sector[511] = 0xAA;
+        sector
+    }
+
+    /// Returns the first sector of the data region, where the cluster 2 starts.
+    pub(crate) fn first_data_sector(&self) -> u64 {
+        self.reserved_sectors as u64 + self.fat_count as u64 * self.fat_size as u64
+    }
+
+    /// Returns the number of clusters of the data region.
+    pub(crate) fn cluster_count(&self) -> u32 {
+        ((self.total_sectors as u64).saturating_sub(self.first_data_sector())
+            / self.sectors_per_cluster as u64) as u32
+    }
+}
@@ -0,0 +1,72 @@
+use kernel::BlockDeviceError;
+
+/// The size of a directory entry in bytes.
+pub(crate) const ENTRY_SIZE: usize = 32;
+/// The number of UTF-16 code units of a long name stored in a single long name entry.
+pub(crate) const LONG_NAME_UNITS_PER_ENTRY: usize = 13;
+/// The longest long name in UTF-16 code units.
+pub(crate) const MAX_LONG_NAME_UNITS: usize = 255;
+
+pub(crate) const ATTRIBUTE_READ_ONLY: u8 = 0x01;
+pub(crate) const ATTRIBUTE_HIDDEN: u8 = 0x02;
+pub(crate) const ATTRIBUTE_SYSTEM: u8 = 0x04;
+pub(crate) const ATTRIBUTE_VOLUME_ID: u8 = 0x08;
+pub(crate) const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+pub(crate) const ATTRIBUTE_ARCHIVE: u8 = 0x20;
+/// The attributes marking a long name entry.
+pub(crate) const ATTRIBUTE_LONG_NAME: u8 =
+    ATTRIBUTE_READ_ONLY | ATTRIBUTE_HIDDEN | ATTRIBUTE_SYSTEM | ATTRIBUTE_VOLUME_ID;
+
+/// The first byte of a free directory entry.
+pub(crate) const ENTRY_FREE: u8 = 0xE5;
+/// The first byte of the entry ending a directory, the entries after it are free too.
+pub(crate) const ENTRY_END: u8 = 0x00;
+/// Set in the ordinal of the last long name entry of a name, which is stored first.
+pub(crate) const LAST_LONG_NAME_ENTRY: u8 = 0x40;
+
+/// FAT entries only use the lower 28 bits.
+pub(crate) const FAT_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+/// Marks the last cluster of a chain, values from `FAT_END_OF_CHAIN_MIN` on are all treated as the end.
+pub(crate) const FAT_END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+pub(crate) const FAT_END_OF_CHAIN_MIN: u32 = 0x0FFF_FFF8;
+pub(crate) const FAT_BAD_CLUSTER: u32 = 0x0FFF_FFF7;
+/// The first cluster of the data region, the first two FAT entries are reserved.
+pub(crate) const FIRST_CLUSTER: u32 = 2;
+/// The fewest clusters a FAT32 volume has, smaller ones are FAT12 or FAT16 by definition.
+pub(crate) const MIN_CLUSTERS: u32 = 65525;
+
+pub(crate) const MEDIA_FIXED_DISK: u8 = 0xF8;
+/// The date of the entries, 1980-01-01, there's no real time clock to get the date from.
+pub(crate) const DEFAULT_DATE: u16 = (1 << 5) | 1;
+
+pub(crate) const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+pub(crate) const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+pub(crate) const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+/// The free cluster count of the FSInfo sector if it isn't known.
+pub(crate) const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatError {
+    Device(BlockDeviceError),
+    /// The boot sector doesn't describe a FAT32 volume, or a structure of the volume is corrupted.
+    InvalidFileSystem,
+    /// The volume is too small for the minimum number of clusters of FAT32.
+    VolumeTooSmall,
+    NotFound,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    /// The name contains characters FAT doesn't allow, is too long, or the path can't be used.
+    InvalidName,
+    /// There are no free clusters left.
+    NoSpace,
+    /// Files are limited to 4 GiB.
+    FileTooLarge,
+}
+
+impl From<BlockDeviceError> for FatError {
+    fn from(error: BlockDeviceError) -> Self {
+        FatError::Device(error)
+    }
+}
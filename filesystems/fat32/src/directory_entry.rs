@@ -0,0 +1,325 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    constants::{
+        ATTRIBUTE_DIRECTORY, ATTRIBUTE_LONG_NAME, ATTRIBUTE_VOLUME_ID, DEFAULT_DATE, ENTRY_END,
+        ENTRY_FREE, ENTRY_SIZE, LAST_LONG_NAME_ENTRY, LONG_NAME_UNITS_PER_ENTRY,
+        MAX_LONG_NAME_UNITS,
+    },
+    FatError,
+};
+
+/// A file or directory listed in a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    /// The long name if there's one, the short 8.3 name otherwise.
+    pub name: String,
+    pub is_directory: bool,
+    /// The size of a file in bytes, 0 for directories.
+    pub size: u32,
+    pub(crate) attributes: u8,
+    /// The first cluster of the data, 0 for empty files.
+    pub(crate) first_cluster: u32,
+    /// The 8.3 name as stored, padded with spaces.
+    pub(crate) short_name: [u8; 11],
+    /// The indices of the entry's slots in its directory, the long name ones followed by the short one.
+    pub(crate) slots: Vec<usize>,
+}
+
+/// Where the short entry of a file or directory is stored, which identifies it since FAT has no inode numbers.
+///
+/// It stays the same until the entry is removed or renamed, then its slot may be reused by another entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EntryLocation {
+    /// The first cluster of the directory holding the entry.
+    pub(crate) directory: u32,
+    pub(crate) slot: usize,
+}
+
+impl EntryLocation {
+    /// The root directory, which has no entry. The cluster 0 stands for it like in `..` entries.
+    pub const ROOT: EntryLocation = EntryLocation {
+        directory: 0,
+        slot: 0,
+    };
+}
+
+impl DirectoryEntry {
+    /// Returns the index of the slot holding the short entry, with the cluster and size.
+    pub(crate) fn short_slot(&self) -> usize {
+        *self.slots.last().expect("A directory entry without slots")
+    }
+
+    /// Returns whether the entry is one of the `.` and `..` entries of a subdirectory.
+    pub(crate) fn is_dot_entry(&self) -> bool {
+        self.short_name == *b".          " || self.short_name == *b"..         "
+    }
+}
+
+/// Parses the entries of a directory, skipping free slots and the volume label.
+///
+/// Long names whose checksum doesn't match the short entry are ignored, the short name is used instead.
+pub(crate) fn parse_entries(data: &[u8]) -> Vec<DirectoryEntry> {
+    let mut entries = Vec::new();
+    let mut long_name: Vec<u16> = Vec::new();
+    let mut long_name_slots: Vec<usize> = Vec::new();
+    let mut long_name_checksum = 0;
+    let mut next_ordinal = 0;
+
+    for (slot, bytes) in data.chunks_exact(ENTRY_SIZE).enumerate() {
+        match bytes[0] {
+            ENTRY_END => break,
+            ENTRY_FREE => {
+                long_name_slots.clear();
+                continue;
+            }
+            _ => {}
+        }
+        let attributes = bytes[11];
+        if attributes & ATTRIBUTE_LONG_NAME == ATTRIBUTE_LONG_NAME {
+            let ordinal = bytes[0] & !LAST_LONG_NAME_ENTRY;
+            if bytes[0] & LAST_LONG_NAME_ENTRY != 0 {
+                long_name = alloc::vec![0xFFFF; ordinal as usize * LONG_NAME_UNITS_PER_ENTRY];
+                long_name_slots.clear();
+                long_name_checksum = bytes[13];
+            } else if ordinal != next_ordinal || bytes[13] != long_name_checksum {
+                long_name_slots.clear();
+                continue;
+            }
+            if ordinal == 0 || long_name.len() < ordinal as usize * LONG_NAME_UNITS_PER_ENTRY {
+                long_name_slots.clear();
+                continue;
+            }
+            let start = (ordinal as usize - 1) * LONG_NAME_UNITS_PER_ENTRY;
+            for (index, unit) in long_name_units(bytes).enumerate() {
+                long_name[start + index] = unit;
+            }
+            long_name_slots.push(slot);
+            next_ordinal = ordinal - 1;
+            continue;
+        }
+        if attributes & ATTRIBUTE_VOLUME_ID != 0 {
+            long_name_slots.clear();
+            continue;
+        }
+
+        let mut short_name = [0u8; 11];
+        short_name.copy_from_slice(&bytes[0..11]);
+        let has_long_name = !long_name_slots.is_empty()
+            && next_ordinal == 0
+            && checksum(&short_name) == long_name_checksum;
+        let name = if has_long_name {
+            let units = long_name
+                .iter()
+                .copied()
+                .take_while(|&unit| unit != 0x0000 && unit != 0xFFFF);
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        } else {
+            long_name_slots.clear();
+            format_short_name(&short_name)
+        };
+        let mut slots = core::mem::take(&mut long_name_slots);
+        slots.push(slot);
+        let is_directory = attributes & ATTRIBUTE_DIRECTORY != 0;
+        entries.push(DirectoryEntry {
+            name,
+            is_directory,
+            size: if is_directory { 0 } else { read_u32(bytes, 28) },
+            attributes,
+            first_cluster: (read_u16(bytes, 20) as u32) << 16 | read_u16(bytes, 26) as u32,
+            short_name,
+            slots,
+        });
+    }
+    entries
+}
+
+/// Builds the slots of a new entry, the long name ones followed by the short one.
+///
+/// No long name entries are needed if the name is its own short name.
+pub(crate) fn build_entry(
+    name: &str,
+    short_name: [u8; 11],
+    attributes: u8,
+    first_cluster: u32,
+    size: u32,
+) -> Vec<[u8; ENTRY_SIZE]> {
+    let mut slots = Vec::new();
+    if format_short_name(&short_name) != name {
+        let mut units: Vec<u16> = name.encode_utf16().collect();
+        let entry_count = (units.len() + LONG_NAME_UNITS_PER_ENTRY - 1) / LONG_NAME_UNITS_PER_ENTRY;
+        // the name ends with a null unless it fills the entries, the rest is padded with 0xFFFF
+        if units.len() % LONG_NAME_UNITS_PER_ENTRY != 0 {
+            units.push(0x0000);
+        }
+        units.resize(entry_count * LONG_NAME_UNITS_PER_ENTRY, 0xFFFF);
+        let checksum = checksum(&short_name);
+        for ordinal in (1..=entry_count).rev() {
+            let mut slot = [0u8; ENTRY_SIZE];
+            slot[0] = ordinal as u8;
+            if ordinal == entry_count {
+                slot[0] |= LAST_LONG_NAME_ENTRY;
+            }
+            slot[11] = ATTRIBUTE_LONG_NAME;
+            slot[13] = checksum;
+            let part = &units
+                [(ordinal - 1) * LONG_NAME_UNITS_PER_ENTRY..ordinal * LONG_NAME_UNITS_PER_ENTRY];
+            for (unit, offset) in part.iter().zip(LONG_NAME_OFFSETS) {
+                slot[offset..offset + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            slots.push(slot);
+        }
+    }
+    let mut slot = [0u8; ENTRY_SIZE];
+    slot[0..11].copy_from_slice(&short_name);
+    slot[11] = attributes;
+    // creation, last access and modification dates
+    for offset in [16, 18, 24] {
+        slot[offset..offset + 2].copy_from_slice(&DEFAULT_DATE.to_le_bytes());
+    }
+    set_first_cluster(&mut slot, first_cluster);
+    slot[28..32].copy_from_slice(&size.to_le_bytes());
+    slots.push(slot);
+    slots
+}
+
+/// Stores the first cluster in a short entry.
+pub(crate) fn set_first_cluster(slot: &mut [u8], cluster: u32) {
+    slot[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    slot[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+}
+
+/// Checks that the name can be stored as a long name.
+pub(crate) fn validate_name(name: &str) -> Result<(), FatError> {
+    let invalid = name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.encode_utf16().count() > MAX_LONG_NAME_UNITS
+        || name.ends_with(' ')
+        || name.ends_with('.')
+        || name.chars().any(|c| c < ' ' || "\"*/:<>?\\|".contains(c));
+    if invalid {
+        Err(FatError::InvalidName)
+    } else {
+        Ok(())
+    }
+}
+
+/// Generates the 8.3 name of a long name, unique among the names the `exists` function knows.
+///
+/// A name that is a valid upper case 8.3 name is used as it is, other names get a numeric tail
+/// like `LONGNA~1.TXT`.
+pub(crate) fn generate_short_name(
+    name: &str,
+    exists: impl Fn(&[u8; 11]) -> bool,
+) -> Result<[u8; 11], FatError> {
+    if let Some(short_name) = as_short_name(name) {
+        if !exists(&short_name) {
+            return Ok(short_name);
+        }
+    }
+    let (base, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => (&name[..dot], &name[dot + 1..]),
+        _ => (name, ""),
+    };
+    let base: Vec<u8> = base.chars().filter_map(short_name_char).collect();
+    let extension: Vec<u8> = extension
+        .chars()
+        .filter_map(short_name_char)
+        .take(3)
+        .collect();
+    for number in 1..1_000_000u32 {
+        let tail = alloc::format!("~{}", number);
+        let base_length = base.len().min(8 - tail.len());
+        let mut short_name = [b' '; 11];
+        short_name[..base_length].copy_from_slice(&base[..base_length]);
+        short_name[base_length..base_length + tail.len()].copy_from_slice(tail.as_bytes());
+        short_name[8..8 + extension.len()].copy_from_slice(&extension);
+        if !exists(&short_name) {
+            return Ok(short_name);
+        }
+    }
+    Err(FatError::InvalidName)
+}
+
+/// Returns the 8.3 form of the name, if it is an upper case 8.3 name.
+fn as_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = match name.split_once('.') {
+        Some((base, extension)) => (base, extension),
+        None => (name, ""),
+    };
+    let valid_part = |part: &str, length: usize| {
+        part.len() <= length && part.chars().all(|c| short_name_char(c) == Some(c as u8))
+    };
+    if base.is_empty() || !valid_part(base, 8) || !valid_part(extension, 3) {
+        return None;
+    }
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.as_bytes());
+    short_name[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+    Some(short_name)
+}
+
+/// Converts a character of a long name to one allowed in short names, spaces and dots are dropped.
+fn short_name_char(c: char) -> Option<u8> {
+    match c {
+        ' ' | '.' => None,
+        'a'..='z' => Some(c.to_ascii_uppercase() as u8),
+        'A'..='Z' | '0'..='9' => Some(c as u8),
+        '!' | '#' | '$' | '%' | '&' | '\'' | '(' | ')' | '-' | '@' | '^' | '_' | '`' | '{'
+        | '}' | '~' => Some(c as u8),
+        _ => Some(b'_'),
+    }
+}
+
+/// Formats a stored 8.3 name as `NAME.EXT`.
+fn format_short_name(short_name: &[u8; 11]) -> String {
+    let base = core::str::from_utf8(&short_name[..8])
+        .unwrap_or("")
+        .trim_end();
+    let extension = core::str::from_utf8(&short_name[8..])
+        .unwrap_or("")
+        .trim_end();
+    let mut name = String::from(base);
+    if !extension.is_empty() {
+        name.push('.');
+        name.push_str(extension);
+    }
+    // a first byte of 0x05 stands for 0xE5, which marks free entries
+    if short_name[0] == 0x05 {
+        name.replace_range(..1, "\u{E5}");
+    }
+    name
+}
+
+/// The checksum of the short name stored in its long name entries.
+fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+}
+
+/// The offsets of the 13 UTF-16 code units in a long name entry.
+const LONG_NAME_OFFSETS: [usize; LONG_NAME_UNITS_PER_ENTRY] =
+    [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+fn long_name_units(slot: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    LONG_NAME_OFFSETS
+        .iter()
+        .map(move |&offset| read_u16(slot, offset))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
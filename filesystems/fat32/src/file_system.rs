@@ -0,0 +1,994 @@
+use alloc::{vec, vec::Vec};
+use kernel::BlockDevice;
+
+use crate::{
+    boot_sector::BootSector,
+    constants::{
+        ATTRIBUTE_ARCHIVE, ATTRIBUTE_DIRECTORY, ENTRY_FREE, ENTRY_SIZE, FAT_BAD_CLUSTER,
+        FAT_END_OF_CHAIN, FAT_END_OF_CHAIN_MIN, FAT_ENTRY_MASK, FIRST_CLUSTER,
+        FS_INFO_LEAD_SIGNATURE, FS_INFO_STRUCT_SIGNATURE, FS_INFO_TRAIL_SIGNATURE, FS_INFO_UNKNOWN,
+    },
+    directory_entry::{
+        build_entry, generate_short_name, parse_entries, set_first_cluster, validate_name,
+    },
+    DirectoryEntry, EntryLocation, FatError,
+};
+
+/// A mounted FAT32 volume on a block device.
+///
+/// Paths are absolute or relative to the root directory, their components are separated by `/` and
+/// compared case-insensitively like FAT does. The methods ending in `_at` take the location of an entry
+/// instead, for callers resolving paths themselves. Changes may stay in the device's cache until `sync` is called.
+pub struct Fat32<D: BlockDevice> {
+    device: D,
+    boot_sector: BootSector,
+    cluster_size: usize,
+    /// The number of the last cluster, clusters are numbered from 2.
+    last_cluster: u32,
+    /// The free cluster count, if it's known.
+    free_clusters: Option<u32>,
+    /// Where the search for a free cluster starts.
+    next_free_cluster: u32,
+    /// Whether the FSInfo sector is out of date.
+    fs_info_dirty: bool,
+    /// The last read sector of the FAT, the cluster chains are mostly in the same sector.
+    fat_sector: Option<(u64, Vec<u8>)>,
+}
+
+/// A directory loaded into memory, with the clusters it is stored in.
+struct Directory {
+    clusters: Vec<u32>,
+    data: Vec<u8>,
+}
+
+impl Directory {
+    fn first_cluster(&self) -> u32 {
+        self.clusters[0]
+    }
+
+    fn entries(&self) -> Vec<DirectoryEntry> {
+        parse_entries(&self.data)
+    }
+
+    /// Returns the entry with the passed name, ignoring the case.
+    fn find(&self, name: &str) -> Result<DirectoryEntry, FatError> {
+        self.entries()
+            .into_iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+            .ok_or(FatError::NotFound)
+    }
+}
+
+impl<D: BlockDevice> Fat32<D> {
+    /// Mounts the FAT32 volume stored on the device.
+    ///
+    /// Returns `InvalidFileSystem` if the device doesn't hold one or its block size differs from the
+    /// volume's sector size.
+    pub fn mount(mut device: D) -> Result<Self, FatError> {
+        let mut sector = vec![0; device.block_size()];
+        device.read_blocks(0, &mut sector)?;
+        let boot_sector = BootSector::from_bytes(&sector)?;
+        if boot_sector.bytes_per_sector as usize != device.block_size()
+            || boot_sector.total_sectors as u64 > device.block_count()
+        {
+            return Err(FatError::InvalidFileSystem);
+        }
+        let last_cluster = boot_sector.cluster_count() + FIRST_CLUSTER - 1;
+        if boot_sector.root_cluster > last_cluster {
+            return Err(FatError::InvalidFileSystem);
+        }
+
+        // the FSInfo sector only holds hints, they are ignored if they don't make sense
+        let mut free_clusters = None;
+        let mut next_free_cluster = FIRST_CLUSTER;
+        device.read_blocks(boot_sector.fs_info_sector as u64, &mut sector)?;
+        if read_u32(&sector, 0) == FS_INFO_LEAD_SIGNATURE
+            && read_u32(&sector, 484) == FS_INFO_STRUCT_SIGNATURE
+        {
+            let free = read_u32(&sector, 488);
+            if free <= boot_sector.cluster_count() {
+                free_clusters = Some(free);
+            }
+            let next = read_u32(&sector, 492);
+            if (FIRST_CLUSTER..=last_cluster).contains(&next) {
+                next_free_cluster = next;
+            }
+        }
+
+        Ok(Fat32 {
+            device,
+            cluster_size: boot_sector.sectors_per_cluster as usize
+                * boot_sector.bytes_per_sector as usize,
+            boot_sector,
+            last_cluster,
+            free_clusters,
+            next_free_cluster,
+            fs_info_dirty: false,
+            fat_sector: None,
+        })
+    }
+
+    /// Returns the size of a cluster in bytes, the unit space is allocated in.
+    pub fn cluster_size(&self) -> usize {
+        self.cluster_size
+    }
+
+    /// Returns the free space of the volume in bytes.
+    ///
+    /// If the FSInfo sector didn't hold the free cluster count, the FAT is scanned once to count them.
+    pub fn free_space(&mut self) -> Result<u64, FatError> {
+        let free_clusters = match self.free_clusters {
+            Some(free_clusters) => free_clusters,
+            None => {
+                let mut free_clusters = 0;
+                for cluster in FIRST_CLUSTER..=self.last_cluster {
+                    if self.read_fat(cluster)? == 0 {
+                        free_clusters += 1;
+                    }
+                }
+                self.free_clusters = Some(free_clusters);
+                self.fs_info_dirty = true;
+                free_clusters
+            }
+        };
+        Ok(free_clusters as u64 * self.cluster_size as u64)
+    }
+
+    /// Returns the entries of the directory, without `.` and `..`.
+    pub fn read_dir(&mut self, path: &str) -> Result<Vec<DirectoryEntry>, FatError> {
+        let directory = self.find_directory(components(path))?;
+        Ok(directory_listing(&directory))
+    }
+
+    /// Returns the entries of the directory at the location, without `.` and `..`.
+    pub fn read_dir_at(
+        &mut self,
+        location: EntryLocation,
+    ) -> Result<Vec<DirectoryEntry>, FatError> {
+        let directory = self.open_directory(location)?;
+        Ok(directory_listing(&directory))
+    }
+
+    /// Returns the entry of the file or directory at the path.
+    ///
+    /// The root directory has no entry, so it's returned as a directory with an empty name.
+    pub fn metadata(&mut self, path: &str) -> Result<DirectoryEntry, FatError> {
+        if components(path).next().is_none() {
+            return Ok(self.root_entry());
+        }
+        Ok(self.lookup(path)?.1)
+    }
+
+    /// Returns the entry at the location, `NotFound` if it was removed.
+    pub fn metadata_at(&mut self, location: EntryLocation) -> Result<DirectoryEntry, FatError> {
+        if location == EntryLocation::ROOT {
+            return Ok(self.root_entry());
+        }
+        Ok(self.load_entry(location)?.1)
+    }
+
+    /// Returns the location of the entry with the name in the directory at the location.
+    pub fn lookup_at(
+        &mut self,
+        directory: EntryLocation,
+        name: &str,
+    ) -> Result<EntryLocation, FatError> {
+        let directory = self.open_directory(directory)?;
+        let entry = directory.find(name)?;
+        Ok(EntryLocation {
+            directory: directory.first_cluster(),
+            slot: entry.short_slot(),
+        })
+    }
+
+    /// Reads the file from `offset` into the buffer, returns the number of bytes read.
+    ///
+    /// Fewer bytes than the buffer holds are read at the end of the file, none at or after it.
+    pub fn read(
+        &mut self,
+        path: &str,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, FatError> {
+        let (_, entry) = self.lookup(path)?;
+        self.read_entry(&entry, offset, buffer)
+    }
+
+    /// Reads the file at the location like `read`.
+    pub fn read_at(
+        &mut self,
+        location: EntryLocation,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, FatError> {
+        let (_, entry) = self.load_entry(location)?;
+        self.read_entry(&entry, offset, buffer)
+    }
+
+    /// Reads the whole file.
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, FatError> {
+        let size = self.metadata(path)?.size as usize;
+        let mut data = vec![0; size];
+        let read = self.read(path, 0, &mut data)?;
+        data.truncate(read);
+        Ok(data)
+    }
+
+    /// Writes the data to the file at `offset`, returns the number of bytes written.
+    ///
+    /// Writing past the end extends the file, a gap between the old end and `offset` is filled with zeros.
+    pub fn write(&mut self, path: &str, offset: usize, data: &[u8]) -> Result<usize, FatError> {
+        let (mut directory, entry) = self.lookup(path)?;
+        self.write_entry(&mut directory, entry, offset, data)
+    }
+
+    /// Writes the data to the file at the location like `write`.
+    pub fn write_at(
+        &mut self,
+        location: EntryLocation,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<usize, FatError> {
+        let (mut directory, entry) = self.load_entry(location)?;
+        self.write_entry(&mut directory, entry, offset, data)
+    }
+
+    /// Writes the data to the end of the file, returns the number of bytes written.
+    pub fn append(&mut self, path: &str, data: &[u8]) -> Result<usize, FatError> {
+        let size = self.metadata(path)?.size as usize;
+        self.write(path, size, data)
+    }
+
+    /// Sets the size of the file, freeing the clusters past the new end or filling the new part with zeros.
+    pub fn truncate(&mut self, path: &str, size: usize) -> Result<(), FatError> {
+        let (mut directory, entry) = self.lookup(path)?;
+        self.truncate_entry(&mut directory, entry, size)
+    }
+
+    /// Sets the size of the file at the location like `truncate`.
+    pub fn truncate_at(&mut self, location: EntryLocation, size: usize) -> Result<(), FatError> {
+        let (mut directory, entry) = self.load_entry(location)?;
+        self.truncate_entry(&mut directory, entry, size)
+    }
+
+    /// Creates an empty file, its parent directory must exist.
+    pub fn create_file(&mut self, path: &str) -> Result<(), FatError> {
+        let (mut parent, name) = self.find_parent(path)?;
+        self.add_file(&mut parent, name).map(|_| ())
+    }
+
+    /// Creates an empty file in the directory at the location, returns the location of its entry.
+    pub fn create_file_at(
+        &mut self,
+        directory: EntryLocation,
+        name: &str,
+    ) -> Result<EntryLocation, FatError> {
+        let mut parent = self.open_directory(directory)?;
+        let slot = self.add_file(&mut parent, name)?;
+        Ok(EntryLocation {
+            directory: parent.first_cluster(),
+            slot,
+        })
+    }
+
+    /// Creates an empty directory, its parent directory must exist.
+    pub fn create_directory(&mut self, path: &str) -> Result<(), FatError> {
+        let (mut parent, name) = self.find_parent(path)?;
+        self.add_directory(&mut parent, name).map(|_| ())
+    }
+
+    /// Creates an empty directory in the directory at the location, returns the location of its entry.
+    pub fn create_directory_at(
+        &mut self,
+        directory: EntryLocation,
+        name: &str,
+    ) -> Result<EntryLocation, FatError> {
+        let mut parent = self.open_directory(directory)?;
+        let slot = self.add_directory(&mut parent, name)?;
+        Ok(EntryLocation {
+            directory: parent.first_cluster(),
+            slot,
+        })
+    }
+
+    /// Removes the file or the empty directory.
+    pub fn remove(&mut self, path: &str) -> Result<(), FatError> {
+        let (mut directory, entry) = self.lookup(path)?;
+        self.remove_entry(&mut directory, &entry)
+    }
+
+    /// Removes the entry with the name from the directory at the location like `remove`, returns the
+    /// location the entry had.
+    pub fn remove_at(
+        &mut self,
+        directory: EntryLocation,
+        name: &str,
+    ) -> Result<EntryLocation, FatError> {
+        let mut directory = self.open_directory(directory)?;
+        let entry = directory.find(name)?;
+        self.remove_entry(&mut directory, &entry)?;
+        Ok(EntryLocation {
+            directory: directory.first_cluster(),
+            slot: entry.short_slot(),
+        })
+    }
+
+    /// Renames or moves the file or directory, the destination must not exist.
+    ///
+    /// A directory can't be moved into itself or one of its subdirectories.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), FatError> {
+        let (mut source, entry) = self.lookup(from)?;
+        if entry.is_dot_entry() {
+            return Err(FatError::InvalidName);
+        }
+        let (mut destination, name) = self.find_parent(to)?;
+        validate_name(name)?;
+        let same_directory = source.first_cluster() == destination.first_cluster();
+        match destination.find(name) {
+            // only changes the case of the name
+            Ok(existing) if same_directory && existing.slots == entry.slots => {}
+            Ok(_) => return Err(FatError::AlreadyExists),
+            Err(FatError::NotFound) => {}
+            Err(error) => return Err(error),
+        }
+        if entry.is_directory && self.is_inside(&destination, entry.first_cluster)? {
+            return Err(FatError::InvalidName);
+        }
+
+        if same_directory {
+            self.free_slots(&mut source, &entry.slots)?;
+            return self
+                .add_entry(
+                    &mut source,
+                    name,
+                    entry.attributes,
+                    entry.first_cluster,
+                    entry.size,
+                )
+                .map(|_| ());
+        }
+        // the new entry is added first, so the data isn't lost if removing the old one fails
+        self.add_entry(
+            &mut destination,
+            name,
+            entry.attributes,
+            entry.first_cluster,
+            entry.size,
+        )?;
+        self.free_slots(&mut source, &entry.slots)?;
+        if entry.is_directory {
+            let mut moved = self.load_directory(entry.first_cluster)?;
+            let parent_reference = self.parent_reference(&destination);
+            if let Ok(dot_dot) = moved.find("..") {
+                let slot = dot_dot.short_slot();
+                set_first_cluster(
+                    &mut moved.data[slot * ENTRY_SIZE..(slot + 1) * ENTRY_SIZE],
+                    parent_reference,
+                );
+                self.write_slots(&moved, slot, 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the FSInfo sector if it changed and flushes the device.
+    pub fn sync(&mut self) -> Result<(), FatError> {
+        if self.fs_info_dirty {
+            let fs_info_sector = self.boot_sector.fs_info_sector as u64;
+            let mut sector = vec![0; self.device.block_size()];
+            self.device.read_blocks(fs_info_sector, &mut sector)?;
+            write_fs_info(
+                &mut sector,
+                self.free_clusters.unwrap_or(FS_INFO_UNKNOWN),
+                self.next_free_cluster,
+            );
+            self.device.write_blocks(fs_info_sector, &sector)?;
+            self.fs_info_dirty = false;
+        }
+        Ok(self.device.flush()?)
+    }
+
+    fn read_entry(
+        &mut self,
+        entry: &DirectoryEntry,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, FatError> {
+        if entry.is_directory {
+            return Err(FatError::IsADirectory);
+        }
+        let size = entry.size as usize;
+        if offset >= size || buffer.is_empty() {
+            return Ok(0);
+        }
+        let length = buffer.len().min(size - offset);
+        let chain = self.cluster_chain(entry.first_cluster)?;
+        self.read_data(&chain, offset, &mut buffer[..length])?;
+        Ok(length)
+    }
+
+    fn write_entry(
+        &mut self,
+        directory: &mut Directory,
+        mut entry: DirectoryEntry,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<usize, FatError> {
+        if entry.is_directory {
+            return Err(FatError::IsADirectory);
+        }
+        let end = match offset.checked_add(data.len()) {
+            Some(end) if end <= u32::MAX as usize => end,
+            _ => return Err(FatError::FileTooLarge),
+        };
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let size = entry.size as usize;
+        let mut chain = self.cluster_chain(entry.first_cluster)?;
+        self.extend_chain(&mut chain, end.max(size))?;
+        if offset > size {
+            self.zero_data(&chain, size, offset)?;
+        }
+        self.write_data(&chain, offset, data)?;
+
+        entry.first_cluster = chain[0];
+        entry.size = end.max(size) as u32;
+        self.update_entry(directory, &entry)?;
+        Ok(data.len())
+    }
+
+    fn truncate_entry(
+        &mut self,
+        directory: &mut Directory,
+        mut entry: DirectoryEntry,
+        size: usize,
+    ) -> Result<(), FatError> {
+        if entry.is_directory {
+            return Err(FatError::IsADirectory);
+        }
+        if size > u32::MAX as usize {
+            return Err(FatError::FileTooLarge);
+        }
+        let old_size = entry.size as usize;
+        let mut chain = self.cluster_chain(entry.first_cluster)?;
+        if size > old_size {
+            self.extend_chain(&mut chain, size)?;
+            self.zero_data(&chain, old_size, size)?;
+        } else {
+            let kept = self.clusters_for(size);
+            if kept < chain.len() {
+                if kept > 0 {
+                    self.write_fat(chain[kept - 1], FAT_END_OF_CHAIN)?;
+                }
+                self.free_clusters(&chain[kept..])?;
+                chain.truncate(kept);
+            }
+        }
+        entry.first_cluster = chain.first().copied().unwrap_or(0);
+        entry.size = size as u32;
+        self.update_entry(directory, &entry)
+    }
+
+    /// Adds an entry for an empty file to the directory, returns its short slot.
+    fn add_file(&mut self, parent: &mut Directory, name: &str) -> Result<usize, FatError> {
+        self.check_new_name(parent, name)?;
+        self.add_entry(parent, name, ATTRIBUTE_ARCHIVE, 0, 0)
+    }
+
+    /// Adds an entry for a new empty directory to the directory, returns its short slot.
+    fn add_directory(&mut self, parent: &mut Directory, name: &str) -> Result<usize, FatError> {
+        self.check_new_name(parent, name)?;
+
+        let cluster = self.allocate_cluster(None)?;
+        let mut data = vec![0; self.cluster_size];
+        let dot = build_entry(".", *b".          ", ATTRIBUTE_DIRECTORY, cluster, 0);
+        let dot_dot = build_entry(
+            "..",
+            *b"..         ",
+            ATTRIBUTE_DIRECTORY,
+            self.parent_reference(parent),
+            0,
+        );
+        data[..ENTRY_SIZE].copy_from_slice(&dot[0]);
+        data[ENTRY_SIZE..2 * ENTRY_SIZE].copy_from_slice(&dot_dot[0]);
+        let result = self
+            .write_cluster(cluster, &data)
+            .and_then(|_| self.add_entry(parent, name, ATTRIBUTE_DIRECTORY, cluster, 0));
+        if result.is_err() {
+            self.free_clusters(&[cluster])?;
+        }
+        result
+    }
+
+    /// Frees the slots and clusters of the file or empty directory.
+    fn remove_entry(
+        &mut self,
+        directory: &mut Directory,
+        entry: &DirectoryEntry,
+    ) -> Result<(), FatError> {
+        if entry.is_dot_entry() {
+            return Err(FatError::InvalidName);
+        }
+        if entry.is_directory {
+            let content = self.load_directory(entry.first_cluster)?;
+            if content.entries().iter().any(|entry| !entry.is_dot_entry()) {
+                return Err(FatError::DirectoryNotEmpty);
+            }
+        }
+        self.free_slots(directory, &entry.slots)?;
+        let chain = self.cluster_chain(entry.first_cluster)?;
+        self.free_clusters(&chain)
+    }
+
+    /// Returns the entry of the root directory, a directory with an empty name.
+    fn root_entry(&self) -> DirectoryEntry {
+        DirectoryEntry {
+            name: Default::default(),
+            is_directory: true,
+            size: 0,
+            attributes: ATTRIBUTE_DIRECTORY,
+            first_cluster: self.boot_sector.root_cluster,
+            short_name: [b' '; 11],
+            slots: Vec::new(),
+        }
+    }
+
+    /// Returns the directory holding the entry at the location and the entry.
+    ///
+    /// The root directory has no entry, it's a directory so file operations fail with `IsADirectory`.
+    fn load_entry(
+        &mut self,
+        location: EntryLocation,
+    ) -> Result<(Directory, DirectoryEntry), FatError> {
+        if location == EntryLocation::ROOT {
+            return Err(FatError::IsADirectory);
+        }
+        let directory = self.load_directory(location.directory)?;
+        let entry = directory
+            .entries()
+            .into_iter()
+            .find(|entry| entry.short_slot() == location.slot && !entry.is_dot_entry())
+            .ok_or(FatError::NotFound)?;
+        Ok((directory, entry))
+    }
+
+    /// Loads the directory at the location.
+    fn open_directory(&mut self, location: EntryLocation) -> Result<Directory, FatError> {
+        if location == EntryLocation::ROOT {
+            return self.load_directory(self.boot_sector.root_cluster);
+        }
+        let (_, entry) = self.load_entry(location)?;
+        if !entry.is_directory {
+            return Err(FatError::NotADirectory);
+        }
+        self.load_directory(entry.first_cluster)
+    }
+
+    /// Returns the directory holding the entry at the path and the entry.
+    fn lookup(&mut self, path: &str) -> Result<(Directory, DirectoryEntry), FatError> {
+        let (directory, name) = self.find_parent(path)?;
+        let entry = directory.find(name)?;
+        Ok((directory, entry))
+    }
+
+    /// Returns the parent directory of the path and the last component, which must not be empty.
+    fn find_parent<'a>(&mut self, path: &'a str) -> Result<(Directory, &'a str), FatError> {
+        let mut components: Vec<&str> = components(path).collect();
+        let name = components.pop().ok_or(FatError::InvalidName)?;
+        Ok((self.find_directory(components.into_iter())?, name))
+    }
+
+    fn find_directory<'a>(
+        &mut self,
+        components: impl Iterator<Item = &'a str>,
+    ) -> Result<Directory, FatError> {
+        let mut directory = self.load_directory(self.boot_sector.root_cluster)?;
+        for component in components {
+            let entry = directory.find(component)?;
+            if !entry.is_directory {
+                return Err(FatError::NotADirectory);
+            }
+            directory = self.load_directory(entry.first_cluster)?;
+        }
+        Ok(directory)
+    }
+
+    /// Loads the directory starting at the cluster, 0 stands for the root directory like in `..` entries.
+    fn load_directory(&mut self, cluster: u32) -> Result<Directory, FatError> {
+        let cluster = if cluster == 0 {
+            self.boot_sector.root_cluster
+        } else {
+            cluster
+        };
+        let clusters = self.cluster_chain(cluster)?;
+        if clusters.is_empty() {
+            return Err(FatError::InvalidFileSystem);
+        }
+        let mut data = vec![0; clusters.len() * self.cluster_size];
+        for (cluster, buffer) in clusters
+            .iter()
+            .zip(data.chunks_exact_mut(self.cluster_size))
+        {
+            self.read_cluster(*cluster, buffer)?;
+        }
+        Ok(Directory { clusters, data })
+    }
+
+    /// Returns the cluster the `..` entry of a subdirectory of the directory points to.
+    fn parent_reference(&self, directory: &Directory) -> u32 {
+        if directory.first_cluster() == self.boot_sector.root_cluster {
+            0
+        } else {
+            directory.first_cluster()
+        }
+    }
+
+    /// Returns whether the directory is the one starting at the cluster or inside it, by following the
+    /// `..` entries up to the root directory.
+    fn is_inside(&mut self, directory: &Directory, cluster: u32) -> Result<bool, FatError> {
+        let mut current = directory.first_cluster();
+        for _ in 0..=self.last_cluster {
+            if current == cluster {
+                return Ok(true);
+            }
+            if current == self.boot_sector.root_cluster {
+                return Ok(false);
+            }
+            let parent = self.load_directory(current)?.find("..")?;
+            current = if parent.first_cluster == 0 {
+                self.boot_sector.root_cluster
+            } else {
+                parent.first_cluster
+            };
+        }
+        Err(FatError::InvalidFileSystem)
+    }
+
+    fn check_new_name(&self, parent: &Directory, name: &str) -> Result<(), FatError> {
+        validate_name(name)?;
+        match parent.find(name) {
+            Ok(_) => Err(FatError::AlreadyExists),
+            Err(FatError::NotFound) => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Adds an entry to the directory, extending the directory by a cluster if there are too few free slots.
+    ///
+    /// Returns the slot of the short entry.
+    fn add_entry(
+        &mut self,
+        directory: &mut Directory,
+        name: &str,
+        attributes: u8,
+        first_cluster: u32,
+        size: u32,
+    ) -> Result<usize, FatError> {
+        let entries = directory.entries();
+        let short_name = generate_short_name(name, |short_name| {
+            entries.iter().any(|entry| entry.short_name == *short_name)
+        })?;
+        let slots = build_entry(name, short_name, attributes, first_cluster, size);
+
+        let slot_count = directory.data.len() / ENTRY_SIZE;
+        let mut run_start = 0;
+        let mut run_length = 0;
+        for slot in 0..slot_count {
+            if run_length == slots.len() {
+                break;
+            }
+            let first_byte = directory.data[slot * ENTRY_SIZE];
+            if first_byte == ENTRY_FREE || first_byte == 0 {
+                if run_length == 0 {
+                    run_start = slot;
+                }
+                run_length += 1;
+            } else {
+                run_length = 0;
+            }
+        }
+        if run_length < slots.len() {
+            if run_length == 0 {
+                run_start = slot_count;
+            }
+            // the free slots at the end are continued in new clusters
+            while directory.data.len() / ENTRY_SIZE < run_start + slots.len() {
+                let cluster = self.allocate_cluster(directory.clusters.last().copied())?;
+                directory.clusters.push(cluster);
+                directory
+                    .data
+                    .resize(directory.data.len() + self.cluster_size, 0);
+            }
+        }
+
+        for (index, slot) in slots.iter().enumerate() {
+            let offset = (run_start + index) * ENTRY_SIZE;
+            directory.data[offset..offset + ENTRY_SIZE].copy_from_slice(slot);
+        }
+        self.write_slots(directory, run_start, slots.len())?;
+        Ok(run_start + slots.len() - 1)
+    }
+
+    /// Stores the first cluster and size of the entry in its short slot.
+    fn update_entry(
+        &mut self,
+        directory: &mut Directory,
+        entry: &DirectoryEntry,
+    ) -> Result<(), FatError> {
+        let slot = entry.short_slot();
+        let bytes = &mut directory.data[slot * ENTRY_SIZE..(slot + 1) * ENTRY_SIZE];
+        set_first_cluster(bytes, entry.first_cluster);
+        bytes[28..32].copy_from_slice(&entry.size.to_le_bytes());
+        self.write_slots(directory, slot, 1)
+    }
+
+    /// Marks the slots of an entry as free.
+    fn free_slots(&mut self, directory: &mut Directory, slots: &[usize]) -> Result<(), FatError> {
+        for &slot in slots {
+            directory.data[slot * ENTRY_SIZE] = ENTRY_FREE;
+            self.write_slots(directory, slot, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the clusters of the directory holding the `count` slots from `first_slot` on.
+    fn write_slots(
+        &mut self,
+        directory: &Directory,
+        first_slot: usize,
+        count: usize,
+    ) -> Result<(), FatError> {
+        let start = first_slot * ENTRY_SIZE / self.cluster_size;
+        let end = ((first_slot + count) * ENTRY_SIZE - 1) / self.cluster_size;
+        for index in start..=end {
+            let data = &directory.data[index * self.cluster_size..(index + 1) * self.cluster_size];
+            self.write_cluster(directory.clusters[index], data)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the data from `offset` on of the file stored in the clusters.
+    fn read_data(
+        &mut self,
+        chain: &[u32],
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<(), FatError> {
+        let mut cluster_data = vec![0; self.cluster_size];
+        let mut position = offset;
+        let mut done = 0;
+        while done < buffer.len() {
+            let cluster = *chain
+                .get(position / self.cluster_size)
+                .ok_or(FatError::InvalidFileSystem)?;
+            let start = position % self.cluster_size;
+            let length = (self.cluster_size - start).min(buffer.len() - done);
+            if length == self.cluster_size {
+                self.read_cluster(cluster, &mut buffer[done..done + length])?;
+            } else {
+                self.read_cluster(cluster, &mut cluster_data)?;
+                buffer[done..done + length].copy_from_slice(&cluster_data[start..start + length]);
+            }
+            position += length;
+            done += length;
+        }
+        Ok(())
+    }
+
+    /// Writes the data from `offset` on to the file stored in the clusters, they must be enough.
+    fn write_data(&mut self, chain: &[u32], offset: usize, data: &[u8]) -> Result<(), FatError> {
+        let mut cluster_data = vec![0; self.cluster_size];
+        let mut position = offset;
+        let mut done = 0;
+        while done < data.len() {
+            let cluster = chain[position / self.cluster_size];
+            let start = position % self.cluster_size;
+            let length = (self.cluster_size - start).min(data.len() - done);
+            if length == self.cluster_size {
+                self.write_cluster(cluster, &data[done..done + length])?;
+            } else {
+                self.read_cluster(cluster, &mut cluster_data)?;
+                cluster_data[start..start + length].copy_from_slice(&data[done..done + length]);
+                self.write_cluster(cluster, &cluster_data)?;
+            }
+            position += length;
+            done += length;
+        }
+        Ok(())
+    }
+
+    /// Fills the bytes from `start` to `end` of the file with zeros.
+    ///
+    /// New clusters are already zeroed, but the last cluster may hold old data past the end of the file.
+    fn zero_data(&mut self, chain: &[u32], start: usize, end: usize) -> Result<(), FatError> {
+        let end = end.min(self.clusters_for(start) * self.cluster_size);
+        if start < end {
+            self.write_data(chain, start, &vec![0; end - start])?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of clusters needed for the bytes.
+    fn clusters_for(&self, bytes: usize) -> usize {
+        (bytes + self.cluster_size - 1) / self.cluster_size
+    }
+
+    /// Allocates clusters until the chain can hold `size` bytes.
+    ///
+    /// If the volume runs out of space, the clusters allocated so far are freed again.
+    fn extend_chain(&mut self, chain: &mut Vec<u32>, size: usize) -> Result<(), FatError> {
+        let old_length = chain.len();
+        while chain.len() < self.clusters_for(size) {
+            match self.allocate_cluster(chain.last().copied()) {
+                Ok(cluster) => chain.push(cluster),
+                Err(error) => {
+                    if old_length > 0 {
+                        self.write_fat(chain[old_length - 1], FAT_END_OF_CHAIN)?;
+                    }
+                    self.free_clusters(&chain[old_length..])?;
+                    chain.truncate(old_length);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the clusters of the chain starting at the cluster, none for 0.
+    fn cluster_chain(&mut self, first_cluster: u32) -> Result<Vec<u32>, FatError> {
+        let mut chain = Vec::new();
+        let mut cluster = first_cluster;
+        if cluster == 0 {
+            return Ok(chain);
+        }
+        loop {
+            // a loop in the chain would make it longer than the volume
+            if !(FIRST_CLUSTER..=self.last_cluster).contains(&cluster)
+                || chain.len() > self.last_cluster as usize
+            {
+                return Err(FatError::InvalidFileSystem);
+            }
+            chain.push(cluster);
+            match self.read_fat(cluster)? {
+                next if next >= FAT_END_OF_CHAIN_MIN => return Ok(chain),
+                FAT_BAD_CLUSTER | 0 => return Err(FatError::InvalidFileSystem),
+                next => cluster = next,
+            }
+        }
+    }
+
+    /// Finds a free cluster, marks it as the end of a chain and zeroes it.
+    ///
+    /// If there's a previous cluster, the new one is linked after it.
+    fn allocate_cluster(&mut self, previous: Option<u32>) -> Result<u32, FatError> {
+        if self.free_clusters == Some(0) {
+            return Err(FatError::NoSpace);
+        }
+        let cluster_count = self.last_cluster - FIRST_CLUSTER + 1;
+        let start = self.next_free_cluster - FIRST_CLUSTER;
+        let mut found = None;
+        for index in 0..cluster_count {
+            let cluster = FIRST_CLUSTER + (start + index) % cluster_count;
+            if self.read_fat(cluster)? == 0 {
+                found = Some(cluster);
+                break;
+            }
+        }
+        let cluster = found.ok_or(FatError::NoSpace)?;
+
+        self.write_cluster(cluster, &vec![0; self.cluster_size])?;
+        self.write_fat(cluster, FAT_END_OF_CHAIN)?;
+        if let Some(previous) = previous {
+            self.write_fat(previous, cluster)?;
+        }
+        self.free_clusters = self.free_clusters.map(|free| free.saturating_sub(1));
+        self.next_free_cluster = if cluster == self.last_cluster {
+            FIRST_CLUSTER
+        } else {
+            cluster + 1
+        };
+        self.fs_info_dirty = true;
+        Ok(cluster)
+    }
+
+    fn free_clusters(&mut self, clusters: &[u32]) -> Result<(), FatError> {
+        for &cluster in clusters {
+            self.write_fat(cluster, 0)?;
+        }
+        if !clusters.is_empty() {
+            self.free_clusters = self.free_clusters.map(|free| free + clusters.len() as u32);
+            self.fs_info_dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Returns the sector of the first FAT holding the entry of the cluster and the entry's offset in it.
+    fn fat_position(&self, cluster: u32) -> (u64, usize) {
+        let bytes_per_sector = self.boot_sector.bytes_per_sector as usize;
+        let offset = cluster as usize * 4;
+        (
+            self.boot_sector.reserved_sectors as u64 + (offset / bytes_per_sector) as u64,
+            offset % bytes_per_sector,
+        )
+    }
+
+    fn read_fat(&mut self, cluster: u32) -> Result<u32, FatError> {
+        let (sector, offset) = self.fat_position(cluster);
+        if !matches!(&self.fat_sector, Some((cached, _)) if *cached == sector) {
+            let mut data = vec![0; self.device.block_size()];
+            self.device.read_blocks(sector, &mut data)?;
+            self.fat_sector = Some((sector, data));
+        }
+        let (_, data) = self
+            .fat_sector
+            .as_ref()
+            .expect("The FAT sector was just read");
+        Ok(read_u32(data, offset) & FAT_ENTRY_MASK)
+    }
+
+    /// Sets the entry of the cluster in all FATs, keeping the reserved upper 4 bits.
+    fn write_fat(&mut self, cluster: u32, value: u32) -> Result<(), FatError> {
+        let (sector, offset) = self.fat_position(cluster);
+        let mut data = vec![0; self.device.block_size()];
+        for fat in 0..self.boot_sector.fat_count as u64 {
+            let fat_sector = sector + fat * self.boot_sector.fat_size as u64;
+            self.device.read_blocks(fat_sector, &mut data)?;
+            let entry = (read_u32(&data, offset) & !FAT_ENTRY_MASK) | (value & FAT_ENTRY_MASK);
+            data[offset..offset + 4].copy_from_slice(&entry.to_le_bytes());
+            self.device.write_blocks(fat_sector, &data)?;
+            if fat == 0 {
+                self.fat_sector = Some((sector, data.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn cluster_sector(&self, cluster: u32) -> u64 {
+        self.boot_sector.first_data_sector()
+            + (cluster - FIRST_CLUSTER) as u64 * self.boot_sector.sectors_per_cluster as u64
+    }
+
+    fn read_cluster(&mut self, cluster: u32, buffer: &mut [u8]) -> Result<(), FatError> {
+        let sector = self.cluster_sector(cluster);
+        Ok(self.device.read_blocks(sector, buffer)?)
+    }
+
+    fn write_cluster(&mut self, cluster: u32, data: &[u8]) -> Result<(), FatError> {
+        let sector = self.cluster_sector(cluster);
+        Ok(self.device.write_blocks(sector, data)?)
+    }
+}
+
+/// Returns the non-empty components of the path.
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+}
+
+/// Returns the entries of the directory without `.` and `..`.
+fn directory_listing(directory: &Directory) -> Vec<DirectoryEntry> {
+    directory
+        .entries()
+        .into_iter()
+        .filter(|entry| !entry.is_dot_entry())
+        .collect()
+}
+
+/// Fills the fields of the FSInfo sector.
+pub(crate) fn write_fs_info(sector: &mut [u8], free_clusters: u32, next_free_cluster: u32) {
+    sector[0..4].copy_from_slice(&FS_INFO_LEAD_SIGNATURE.to_le_bytes());
+    sector[484..488].copy_from_slice(&FS_INFO_STRUCT_SIGNATURE.to_le_bytes());
+    sector[488..492].copy_from_slice(&free_clusters.to_le_bytes());
+    sector[492..496].copy_from_slice(&next_free_cluster.to_le_bytes());
+    sector[508..512].copy_from_slice(&FS_INFO_TRAIL_SIGNATURE.to_le_bytes());
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
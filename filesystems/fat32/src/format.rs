@@ -0,0 +1,139 @@
+use alloc::vec;
+use kernel::BlockDevice;
+
+use crate::{
+    boot_sector::BootSector,
+    constants::{
+        ATTRIBUTE_VOLUME_ID, DEFAULT_DATE, FAT_END_OF_CHAIN, FAT_ENTRY_MASK, FIRST_CLUSTER,
+        MEDIA_FIXED_DISK, MIN_CLUSTERS,
+    },
+    file_system::write_fs_info,
+    Fat32, FatError,
+};
+
+/// The reserved sectors before the FATs, holding the boot sector, FSInfo and their backups.
+const RESERVED_SECTORS: u16 = 32;
+const FS_INFO_SECTOR: u16 = 1;
+const BACKUP_BOOT_SECTOR: u16 = 6;
+const FAT_COUNT: u8 = 2;
+/// The number of sectors zeroed by a single write while clearing the FATs.
+const ZERO_CHUNK_SECTORS: usize = 64;
+
+/// The cluster sizes used by Microsoft's format tool, as the largest volume in 512 byte sectors
+/// using each number of 512 byte sectors per cluster.
+const CLUSTER_SIZES: [(u64, u64); 5] = [
+    (532_480, 1),
+    (16_777_216, 8),
+    (33_554_432, 16),
+    (67_108_864, 32),
+    (u64::MAX, 64),
+];
+
+impl<D: BlockDevice> Fat32<D> {
+    /// Creates an empty FAT32 volume on the whole device and mounts it.
+    ///
+    /// The label is stored in the boot sector and the root directory, it must be at most 11 upper case
+    /// characters allowed in short names. Returns `VolumeTooSmall` if the device can't hold the
+    /// 65525 clusters a FAT32 volume needs at least.
+    pub fn format(mut device: D, label: &str) -> Result<Self, FatError> {
+        let bytes_per_sector = device.block_size();
+        if !bytes_per_sector.is_power_of_two() || !(512..=4096).contains(&bytes_per_sector) {
+            return Err(FatError::InvalidFileSystem);
+        }
+        let volume_label = parse_label(label)?;
+        let total_sectors = device.block_count().min(u32::MAX as u64);
+
+        let size_in_512_byte_sectors = total_sectors * (bytes_per_sector / 512) as u64;
+        let (_, sectors_per_cluster_512) = CLUSTER_SIZES
+            .iter()
+            .find(|(max_sectors, _)| size_in_512_byte_sectors <= *max_sectors)
+            .expect("The last cluster size covers every volume");
+        let sectors_per_cluster = (sectors_per_cluster_512 * 512 / bytes_per_sector as u64).max(1);
+
+        // the FAT size formula of the FAT specification, it errs on the side of a slightly too large FAT
+        let data_and_fat_sectors = total_sectors.saturating_sub(RESERVED_SECTORS as u64);
+        let sectors_per_fat_divisor =
+            ((bytes_per_sector as u64 / 2) * sectors_per_cluster + FAT_COUNT as u64) / 2;
+        let fat_size =
+            (data_and_fat_sectors + sectors_per_fat_divisor - 1) / sectors_per_fat_divisor;
+
+        let boot_sector = BootSector {
+            bytes_per_sector: bytes_per_sector as u16,
+            sectors_per_cluster: sectors_per_cluster as u8,
+            reserved_sectors: RESERVED_SECTORS,
+            fat_count: FAT_COUNT,
+            total_sectors: total_sectors as u32,
+            fat_size: fat_size as u32,
+            root_cluster: FIRST_CLUSTER,
+            fs_info_sector: FS_INFO_SECTOR,
+            backup_boot_sector: BACKUP_BOOT_SECTOR,
+            volume_id: utils::get_current_tick() as u32,
+            volume_label,
+        };
+        let cluster_count = boot_sector.cluster_count();
+        if cluster_count < MIN_CLUSTERS {
+            return Err(FatError::VolumeTooSmall);
+        }
+
+        // the boot sector and FSInfo, followed by their backups
+        let mut sector = vec![0; bytes_per_sector];
+        sector[..512].copy_from_slice(&boot_sector.to_bytes());
+        device.write_blocks(0, &sector)?;
+        device.write_blocks(BACKUP_BOOT_SECTOR as u64, &sector)?;
+        sector.fill(0);
+        // the root directory is allocated already
+        write_fs_info(&mut sector, cluster_count - 1, FIRST_CLUSTER + 1);
+        device.write_blocks(FS_INFO_SECTOR as u64, &sector)?;
+        device.write_blocks((BACKUP_BOOT_SECTOR + FS_INFO_SECTOR) as u64, &sector)?;
+
+        // empty FATs, with the reserved entries and the root directory's single cluster
+        let zeros = vec![0; ZERO_CHUNK_SECTORS * bytes_per_sector];
+        let mut first_sector = vec![0; bytes_per_sector];
+        let reserved_entries = [
+            (FAT_ENTRY_MASK & !0xFF) | MEDIA_FIXED_DISK as u32,
+            FAT_END_OF_CHAIN,
+            FAT_END_OF_CHAIN,
+        ];
+        for (index, entry) in reserved_entries.iter().enumerate() {
+            first_sector[index * 4..index * 4 + 4].copy_from_slice(&entry.to_le_bytes());
+        }
+        for fat in 0..FAT_COUNT as u64 {
+            let fat_start = RESERVED_SECTORS as u64 + fat * fat_size;
+            let mut written = 0;
+            while written < fat_size {
+                let count = (fat_size - written).min(ZERO_CHUNK_SECTORS as u64) as usize;
+                device.write_blocks(fat_start + written, &zeros[..count * bytes_per_sector])?;
+                written += count as u64;
+            }
+            device.write_blocks(fat_start, &first_sector)?;
+        }
+
+        // the root directory, holding only the volume label
+        let cluster_size = sectors_per_cluster as usize * bytes_per_sector;
+        let mut root = vec![0; cluster_size];
+        if !label.is_empty() {
+            root[..11].copy_from_slice(&volume_label);
+            root[11] = ATTRIBUTE_VOLUME_ID;
+            root[24..26].copy_from_slice(&DEFAULT_DATE.to_le_bytes());
+        }
+        device.write_blocks(boot_sector.first_data_sector(), &root)?;
+
+        device.flush()?;
+        Fat32::mount(device)
+    }
+}
+
+/// Converts the label to the padded form stored on the volume, "NO NAME" for an empty label.
+fn parse_label(label: &str) -> Result<[u8; 11], FatError> {
+    let valid = label.len() <= 11
+        && label
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || " _-".contains(c));
+    if !valid {
+        return Err(FatError::InvalidName);
+    }
+    let label = if label.is_empty() { "NO NAME" } else { label };
+    let mut volume_label = [b' '; 11];
+    volume_label[..label.len()].copy_from_slice(label.as_bytes());
+    Ok(volume_label)
+}
@@ -0,0 +1,19 @@
+#![no_std] // no standard library
+#![no_main]
+extern crate alloc;
+
+mod constants;
+pub use constants::FatError;
+
+mod boot_sector;
+
+mod directory_entry;
+pub use directory_entry::{DirectoryEntry, EntryLocation};
+
+mod file_system;
+pub use file_system::Fat32;
+
+mod format;
+
+mod vfs;
+pub use vfs::Fat32FileSystem;
@@ -0,0 +1,202 @@
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+use kernel::{
+    processes::SleepMutex,
+    vfs::{DirectoryEntry, FileStat, FileSystem, FileType, Inode, VfsError},
+    BlockDevice,
+};
+
+use crate::{EntryLocation, Fat32, FatError};
+
+/// The permissions reported for files, FAT doesn't store any.
+const FILE_PERMISSIONS: u16 = 0o644;
+const DIRECTORY_PERMISSIONS: u16 = 0o755;
+
+impl From<FatError> for VfsError {
+    fn from(error: FatError) -> Self {
+        match error {
+            FatError::Device(_) | FatError::InvalidFileSystem => VfsError::IoError,
+            FatError::VolumeTooSmall => VfsError::InvalidArgument,
+            FatError::NotFound => VfsError::NotFound,
+            FatError::AlreadyExists => VfsError::AlreadyExists,
+            FatError::NotADirectory => VfsError::NotADirectory,
+            FatError::IsADirectory => VfsError::IsADirectory,
+            FatError::DirectoryNotEmpty => VfsError::DirectoryNotEmpty,
+            FatError::InvalidName => VfsError::InvalidName,
+            FatError::NoSpace => VfsError::NoSpace,
+            FatError::FileTooLarge => VfsError::FileTooLarge,
+        }
+    }
+}
+
+/// A FAT32 volume that can be mounted in the virtual file system.
+pub struct Fat32FileSystem<D: BlockDevice> {
+    volume: Arc<SleepMutex<Volume<D>>>,
+}
+
+impl<D: BlockDevice> Fat32FileSystem<D> {
+    pub fn new(fat: Fat32<D>) -> Self {
+        Fat32FileSystem {
+            volume: Arc::new(SleepMutex::new(Volume {
+                fat,
+                inodes: BTreeMap::new(),
+            })),
+        }
+    }
+}
+
+impl<D: BlockDevice + 'static> FileSystem for Fat32FileSystem<D> {
+    fn name(&self) -> &str {
+        "fat32"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(Fat32Inode {
+            volume: self.volume.clone(),
+            location: EntryLocation::ROOT,
+            removed: AtomicBool::new(false),
+        })
+    }
+
+    fn sync(&self) -> Result<(), VfsError> {
+        Ok(self.volume.lock().fat.sync()?)
+    }
+}
+
+/// A mounted volume and the inodes handed out for it, behind a sleeping lock since it's held across disk I/O.
+struct Volume<D: BlockDevice> {
+    fat: Fat32<D>,
+    /// The inodes in use by the location of their entry.
+    inodes: BTreeMap<EntryLocation, Weak<Fat32Inode<D>>>,
+}
+
+impl<D: BlockDevice> Volume<D> {
+    /// Marks the inode in use for the location as removed, since the slot may be reused from now on.
+    fn detach(&mut self, location: EntryLocation) {
+        if let Some(inode) = self
+            .inodes
+            .remove(&location)
+            .and_then(|inode| inode.upgrade())
+        {
+            inode.removed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A file or directory of a mounted volume, identified by the location of its entry.
+///
+/// There's one per entry in use, so an open file doesn't turn into a new file whose entry reuses the
+/// slot after being removed. It fails with `NotFound` once its entry was removed.
+struct Fat32Inode<D: BlockDevice> {
+    volume: Arc<SleepMutex<Volume<D>>>,
+    location: EntryLocation,
+    /// Only changed with the volume locked.
+    removed: AtomicBool,
+}
+
+impl<D: BlockDevice + 'static> Fat32Inode<D> {
+    /// Returns the location of the entry, unless it was removed. The volume must be locked.
+    fn location(&self) -> Result<EntryLocation, VfsError> {
+        if self.removed.load(Ordering::Relaxed) {
+            Err(VfsError::NotFound)
+        } else {
+            Ok(self.location)
+        }
+    }
+
+    /// Returns the inode in use for the location, or a new one.
+    fn inode(&self, volume: &mut Volume<D>, location: EntryLocation) -> Arc<dyn Inode> {
+        if let Some(inode) = volume.inodes.get(&location).and_then(Weak::upgrade) {
+            return inode;
+        }
+        let inode = Arc::new(Fat32Inode {
+            volume: self.volume.clone(),
+            location,
+            removed: AtomicBool::new(false),
+        });
+        volume.inodes.retain(|_, inode| inode.strong_count() > 0);
+        volume.inodes.insert(location, Arc::downgrade(&inode));
+        inode
+    }
+}
+
+impl<D: BlockDevice + 'static> Inode for Fat32Inode<D> {
+    fn stat(&self) -> Result<FileStat, VfsError> {
+        let mut volume = self.volume.lock();
+        let entry = volume.fat.metadata_at(self.location()?)?;
+        Ok(if entry.is_directory {
+            FileStat::new(0, FileType::Directory, DIRECTORY_PERMISSIONS, 0)
+        } else {
+            FileStat::new(0, FileType::File, FILE_PERMISSIONS, entry.size as u64)
+        })
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        let mut volume = self.volume.lock();
+        Ok(volume
+            .fat
+            .read_at(self.location()?, offset as usize, buffer)?)
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        let mut volume = self.volume.lock();
+        Ok(volume
+            .fat
+            .write_at(self.location()?, offset as usize, data)?)
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), VfsError> {
+        let mut volume = self.volume.lock();
+        Ok(volume.fat.truncate_at(self.location()?, size as usize)?)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, VfsError> {
+        let mut volume = self.volume.lock();
+        let location = volume.fat.lookup_at(self.location()?, name)?;
+        Ok(self.inode(&mut volume, location))
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirectoryEntry>, VfsError> {
+        let mut volume = self.volume.lock();
+        let entries = volume.fat.read_dir_at(self.location()?)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| DirectoryEntry {
+                name: entry.name,
+                inode: 0,
+                file_type: if entry.is_directory {
+                    FileType::Directory
+                } else {
+                    FileType::File
+                },
+            })
+            .collect())
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: FileType,
+        _permissions: u16,
+    ) -> Result<Arc<dyn Inode>, VfsError> {
+        let mut volume = self.volume.lock();
+        let directory = self.location()?;
+        let location = match file_type {
+            FileType::File => volume.fat.create_file_at(directory, name)?,
+            FileType::Directory => volume.fat.create_directory_at(directory, name)?,
+            _ => return Err(VfsError::NotSupported),
+        };
+        Ok(self.inode(&mut volume, location))
+    }
+
+    fn remove(&self, name: &str) -> Result<(), VfsError> {
+        let mut volume = self.volume.lock();
+        let location = volume.fat.remove_at(self.location()?, name)?;
+        volume.detach(location);
+        Ok(())
+    }
+}
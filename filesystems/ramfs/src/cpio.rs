@@ -0,0 +1,168 @@
+//! Unpacks CPIO archives in the `newc` format, the format of Linux initramfs images.
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use core::str;
+use kernel::vfs::{self, OpenFlags, VfsError};
+
+const MAGIC: &[u8] = b"070701";
+/// The magic of archives with checksums, which are ignored.
+const MAGIC_CHECKSUM: &[u8] = b"070702";
+/// The magic followed by 13 fields of 8 hexadecimal digits.
+const HEADER_SIZE: usize = 110;
+/// The name of the entry ending the archive.
+const TRAILER: &str = "TRAILER!!!";
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_FILE: u32 = 0o100000;
+const MODE_SYMLINK: u32 = 0o120000;
+const MODE_PERMISSIONS_MASK: u32 = 0o7777;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpioError {
+    /// A header has the wrong magic or a field that's not hexadecimal.
+    InvalidHeader,
+    /// The archive ends in the middle of an entry or before the trailer.
+    Truncated,
+    /// A name or symlink target is not UTF-8, or a name has a `..` component.
+    InvalidName,
+    /// Creating an entry in the VFS failed.
+    Vfs(VfsError),
+}
+
+impl From<VfsError> for CpioError {
+    fn from(error: VfsError) -> Self {
+        CpioError::Vfs(error)
+    }
+}
+
+/// An entry of the archive.
+struct Entry<'a> {
+    name: &'a str,
+    mode: u32,
+    /// The device and inode number, which hard links of the same file share.
+    inode: (u32, u32, u32),
+    links: u32,
+    data: &'a [u8],
+}
+
+/// Unpacks the archive into the directory at `target`, returns the number of unpacked entries.
+///
+/// Directories, regular files and symlinks are created, existing directories are kept and existing
+/// files are overwritten. Other entries, like device nodes, are skipped. Names with a `..` component
+/// are rejected, so nothing is created outside of `target`.
+///
+/// The VFS has no hard links, so they're unpacked as copies. Only the last entry of a hard linked file
+/// holds the data, the earlier ones are created empty and filled once it's reached.
+pub fn unpack(archive: &[u8], target: &str) -> Result<usize, CpioError> {
+    let target = target.trim_end_matches('/');
+    let mut offset = 0;
+    let mut unpacked = 0;
+    // the paths of the hard links waiting for the data of their file
+    let mut hard_links: BTreeMap<(u32, u32, u32), Vec<String>> = BTreeMap::new();
+    loop {
+        let (entry, next) = parse_entry(archive, offset)?;
+        if entry.name == TRAILER {
+            return Ok(unpacked);
+        }
+        offset = next;
+
+        let name = entry.name.trim_start_matches("./").trim_matches('/');
+        if name.is_empty() || name == "." {
+            continue;
+        }
+        if name.split('/').any(|component| component == "..") {
+            return Err(CpioError::InvalidName);
+        }
+        let path = format!("{}/{}", target, name);
+        let permissions = (entry.mode & MODE_PERMISSIONS_MASK) as u16;
+        match entry.mode & MODE_TYPE_MASK {
+            MODE_DIRECTORY => match vfs::create_directory(&path, permissions) {
+                Ok(()) | Err(VfsError::AlreadyExists) => {}
+                Err(error) => return Err(error.into()),
+            },
+            MODE_FILE if entry.links > 1 => {
+                write_file(&path, permissions, entry.data)?;
+                let paths = hard_links.entry(entry.inode).or_default();
+                if entry.data.is_empty() {
+                    paths.push(path);
+                } else {
+                    for path in paths.drain(..) {
+                        write_file(&path, permissions, entry.data)?;
+                    }
+                }
+            }
+            MODE_FILE => write_file(&path, permissions, entry.data)?,
+            MODE_SYMLINK => {
+                let target = str::from_utf8(entry.data).map_err(|_| CpioError::InvalidName)?;
+                vfs::create_symlink(&path, target)?;
+            }
+            _ => continue,
+        }
+        unpacked += 1;
+    }
+}
+
+/// Creates or truncates the file and writes the data to it.
+fn write_file(path: &str, permissions: u16, mut data: &[u8]) -> Result<(), CpioError> {
+    let file = vfs::open(
+        path,
+        OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+        permissions,
+    )?;
+    while !data.is_empty() {
+        match file.write(data)? {
+            0 => return Err(CpioError::Vfs(VfsError::NoSpace)),
+            written => data = &data[written..],
+        }
+    }
+    Ok(())
+}
+
+/// Parses the entry at the offset, returns it and the offset of the next one.
+fn parse_entry(archive: &[u8], offset: usize) -> Result<(Entry, usize), CpioError> {
+    let header = archive
+        .get(offset..offset + HEADER_SIZE)
+        .ok_or(CpioError::Truncated)?;
+    if &header[..6] != MAGIC && &header[..6] != MAGIC_CHECKSUM {
+        return Err(CpioError::InvalidHeader);
+    }
+    // the fields after the magic: inode, mode, uid, gid, links, modification time, file size,
+    // device major and minor, represented device major and minor, name size and checksum
+    let field = |index: usize| parse_hex(&header[6 + index * 8..14 + index * 8]);
+    let mode = field(1)?;
+    let links = field(4)?;
+    let file_size = field(6)? as usize;
+    let inode = (field(7)?, field(8)?, field(0)?);
+    let name_size = field(11)? as usize;
+
+    let name_start = offset + HEADER_SIZE;
+    // the name size includes the terminating null byte
+    let name = archive
+        .get(name_start..name_start + name_size)
+        .ok_or(CpioError::Truncated)?;
+    let name = name.strip_suffix(&[0]).ok_or(CpioError::InvalidHeader)?;
+    let name = str::from_utf8(name).map_err(|_| CpioError::InvalidName)?;
+
+    // the name and the data are padded to multiples of 4 bytes
+    let data_start = align(name_start + name_size);
+    let data = archive
+        .get(data_start..data_start + file_size)
+        .ok_or(CpioError::Truncated)?;
+    let entry = Entry {
+        name,
+        mode,
+        inode,
+        links,
+        data,
+    };
+    Ok((entry, align(data_start + file_size)))
+}
+
+fn parse_hex(digits: &[u8]) -> Result<u32, CpioError> {
+    let digits = str::from_utf8(digits).map_err(|_| CpioError::InvalidHeader)?;
+    u32::from_str_radix(digits, 16).map_err(|_| CpioError::InvalidHeader)
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
@@ -0,0 +1,104 @@
+use alloc::sync::Arc;
+use kernel::vfs::{FileSystem, Inode, VfsError};
+use spin::Mutex;
+
+use crate::inode::RamInode;
+
+/// The permissions of the root directory.
+const ROOT_PERMISSIONS: u16 = 0o755;
+
+/// A file system keeping its files in memory, they are lost when it's dropped.
+///
+/// Without a capacity it's a ramfs, growing as long as the heap has space. With a capacity it's a tmpfs,
+/// failing with `NoSpace` once its files hold that many bytes.
+pub struct RamFs {
+    root: Arc<RamInode>,
+    shared: Arc<Shared>,
+}
+
+/// The state shared by all inodes of a file system.
+pub(crate) struct Shared {
+    next_inode: Mutex<u64>,
+    /// The bytes held by the files.
+    used: Mutex<u64>,
+    capacity: Option<u64>,
+}
+
+impl Shared {
+    pub(crate) fn allocate_inode(&self) -> u64 {
+        let mut next_inode = self.next_inode.lock();
+        *next_inode += 1;
+        *next_inode
+    }
+
+    /// Accounts for a file growing by the bytes, fails if the file system would exceed its capacity.
+    pub(crate) fn reserve(&self, bytes: u64) -> Result<(), VfsError> {
+        let mut used = self.used.lock();
+        let new_used = used.checked_add(bytes).ok_or(VfsError::NoSpace)?;
+        if self.capacity.map_or(false, |capacity| new_used > capacity) {
+            return Err(VfsError::NoSpace);
+        }
+        *used = new_used;
+        Ok(())
+    }
+
+    /// Accounts for a file shrinking by the bytes.
+    pub(crate) fn release(&self, bytes: u64) {
+        let mut used = self.used.lock();
+        *used = used.saturating_sub(bytes);
+    }
+}
+
+impl RamFs {
+    /// Creates an empty ramfs.
+    pub fn new() -> Self {
+        RamFs::create(None)
+    }
+
+    /// Creates an empty tmpfs holding at most `capacity` bytes.
+    pub fn with_capacity(capacity: u64) -> Self {
+        RamFs::create(Some(capacity))
+    }
+
+    fn create(capacity: Option<u64>) -> Self {
+        let shared = Arc::new(Shared {
+            next_inode: Mutex::new(0),
+            used: Mutex::new(0),
+            capacity,
+        });
+        RamFs {
+            root: RamInode::new_directory(shared.clone(), ROOT_PERMISSIONS),
+            shared,
+        }
+    }
+
+    /// Returns the number of bytes the files hold.
+    pub fn used_space(&self) -> u64 {
+        *self.shared.used.lock()
+    }
+
+    /// Returns the maximum number of bytes the files can hold, `None` for a ramfs.
+    pub fn capacity(&self) -> Option<u64> {
+        self.shared.capacity
+    }
+}
+
+impl Default for RamFs {
+    fn default() -> Self {
+        RamFs::new()
+    }
+}
+
+impl FileSystem for RamFs {
+    fn name(&self) -> &str {
+        if self.shared.capacity.is_some() {
+            "tmpfs"
+        } else {
+            "ramfs"
+        }
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
@@ -0,0 +1,250 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use kernel::vfs::{DirectoryEntry, FileStat, FileType, Inode, VfsError};
+use spin::Mutex;
+
+use crate::file_system::Shared;
+
+/// The longest name an entry can have, like most disk file systems.
+const MAX_NAME_LENGTH: usize = 255;
+const SYMLINK_PERMISSIONS: u16 = 0o777;
+
+/// A file, directory or symlink held in memory.
+pub(crate) struct RamInode {
+    number: u64,
+    permissions: u16,
+    shared: Arc<Shared>,
+    node: Mutex<Node>,
+}
+
+enum Node {
+    File(Vec<u8>),
+    Directory(BTreeMap<String, Arc<RamInode>>),
+    Symlink(String),
+}
+
+impl RamInode {
+    fn new(shared: Arc<Shared>, permissions: u16, node: Node) -> Arc<Self> {
+        Arc::new(RamInode {
+            number: shared.allocate_inode(),
+            permissions,
+            shared,
+            node: Mutex::new(node),
+        })
+    }
+
+    pub(crate) fn new_directory(shared: Arc<Shared>, permissions: u16) -> Arc<Self> {
+        RamInode::new(shared, permissions, Node::Directory(BTreeMap::new()))
+    }
+
+    /// Adds a new inode to the directory, built by `create` once the name was checked.
+    fn add_entry(
+        &self,
+        name: &str,
+        create: impl FnOnce() -> Arc<RamInode>,
+    ) -> Result<Arc<RamInode>, VfsError> {
+        if name.is_empty()
+            || name.len() > MAX_NAME_LENGTH
+            || name == "."
+            || name == ".."
+            || name.contains(&['/', '\0'][..])
+        {
+            return Err(VfsError::InvalidName);
+        }
+        match &mut *self.node.lock() {
+            Node::Directory(entries) => {
+                if entries.contains_key(name) {
+                    return Err(VfsError::AlreadyExists);
+                }
+                let inode = create();
+                entries.insert(name.to_string(), inode.clone());
+                Ok(inode)
+            }
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    /// Resizes the file, reserving or releasing the difference in the file system's usage.
+    fn resize(&self, data: &mut Vec<u8>, size: usize) -> Result<(), VfsError> {
+        if size > data.len() {
+            let growth = size - data.len();
+            self.shared.reserve(growth as u64)?;
+            if data.try_reserve(growth).is_err() {
+                self.shared.release(growth as u64);
+                return Err(VfsError::NoSpace);
+            }
+        } else {
+            self.shared.release((data.len() - size) as u64);
+        }
+        data.resize(size, 0);
+        Ok(())
+    }
+}
+
+impl Inode for RamInode {
+    fn stat(&self) -> Result<FileStat, VfsError> {
+        Ok(match &*self.node.lock() {
+            Node::File(data) => FileStat::new(
+                self.number,
+                FileType::File,
+                self.permissions,
+                data.len() as u64,
+            ),
+            Node::Directory(entries) => {
+                let mut stat = FileStat::new(self.number, FileType::Directory, self.permissions, 0);
+                // the entry in the parent, `.` and the `..` of every subdirectory
+                stat.links = 2 + entries
+                    .values()
+                    .filter(|entry| matches!(*entry.node.lock(), Node::Directory(_)))
+                    .count() as u32;
+                stat
+            }
+            Node::Symlink(target) => FileStat::new(
+                self.number,
+                FileType::Symlink,
+                SYMLINK_PERMISSIONS,
+                target.len() as u64,
+            ),
+        })
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        match &*self.node.lock() {
+            Node::File(data) => {
+                if offset >= data.len() as u64 {
+                    return Ok(0);
+                }
+                let offset = offset as usize;
+                let length = buffer.len().min(data.len() - offset);
+                buffer[..length].copy_from_slice(&data[offset..offset + length]);
+                Ok(length)
+            }
+            Node::Directory(_) => Err(VfsError::IsADirectory),
+            Node::Symlink(_) => Err(VfsError::InvalidArgument),
+        }
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        match &mut *self.node.lock() {
+            Node::File(_) if data.is_empty() => Ok(0),
+            Node::File(contents) => {
+                let end = offset
+                    .checked_add(data.len() as u64)
+                    .filter(|end| *end <= isize::MAX as u64)
+                    .ok_or(VfsError::FileTooLarge)? as usize;
+                if end > contents.len() {
+                    self.resize(contents, end)?;
+                }
+                contents[offset as usize..end].copy_from_slice(data);
+                Ok(data.len())
+            }
+            Node::Directory(_) => Err(VfsError::IsADirectory),
+            Node::Symlink(_) => Err(VfsError::InvalidArgument),
+        }
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), VfsError> {
+        match &mut *self.node.lock() {
+            Node::File(contents) => {
+                if size > isize::MAX as u64 {
+                    return Err(VfsError::FileTooLarge);
+                }
+                self.resize(contents, size as usize)
+            }
+            Node::Directory(_) => Err(VfsError::IsADirectory),
+            Node::Symlink(_) => Err(VfsError::InvalidArgument),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, VfsError> {
+        match &*self.node.lock() {
+            Node::Directory(entries) => entries
+                .get(name)
+                .map(|inode| inode.clone() as Arc<dyn Inode>)
+                .ok_or(VfsError::NotFound),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirectoryEntry>, VfsError> {
+        match &*self.node.lock() {
+            Node::Directory(entries) => Ok(entries
+                .iter()
+                .map(|(name, inode)| DirectoryEntry {
+                    name: name.clone(),
+                    inode: inode.number,
+                    file_type: match &*inode.node.lock() {
+                        Node::File(_) => FileType::File,
+                        Node::Directory(_) => FileType::Directory,
+                        Node::Symlink(_) => FileType::Symlink,
+                    },
+                })
+                .collect()),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: FileType,
+        permissions: u16,
+    ) -> Result<Arc<dyn Inode>, VfsError> {
+        let node = match file_type {
+            FileType::File => Node::File(Vec::new()),
+            FileType::Directory => Node::Directory(BTreeMap::new()),
+            _ => return Err(VfsError::NotSupported),
+        };
+        let inode = self.add_entry(name, || {
+            RamInode::new(self.shared.clone(), permissions, node)
+        })?;
+        Ok(inode)
+    }
+
+    fn create_symlink(&self, name: &str, target: &str) -> Result<(), VfsError> {
+        self.add_entry(name, || {
+            RamInode::new(
+                self.shared.clone(),
+                SYMLINK_PERMISSIONS,
+                Node::Symlink(target.to_string()),
+            )
+        })?;
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<(), VfsError> {
+        match &mut *self.node.lock() {
+            Node::Directory(entries) => {
+                let inode = entries.get(name).ok_or(VfsError::NotFound)?;
+                if let Node::Directory(children) = &*inode.node.lock() {
+                    if !children.is_empty() {
+                        return Err(VfsError::DirectoryNotEmpty);
+                    }
+                }
+                // open files keep the inode, and its contents, until they are closed
+                entries.remove(name);
+                Ok(())
+            }
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn read_link(&self) -> Result<String, VfsError> {
+        match &*self.node.lock() {
+            Node::Symlink(target) => Ok(target.clone()),
+            _ => Err(VfsError::InvalidArgument),
+        }
+    }
+}
+
+impl Drop for RamInode {
+    fn drop(&mut self) {
+        if let Node::File(data) = self.node.get_mut() {
+            self.shared.release(data.len() as u64);
+        }
+    }
+}
@@ -0,0 +1,10 @@
+#![no_std] // no standard library
+#![no_main]
+extern crate alloc;
+
+mod file_system;
+pub use file_system::RamFs;
+
+mod inode;
+
+pub mod cpio;
@@ -0,0 +1,37 @@
+mod ram_disk;
+pub use ram_disk::RamDisk;
+
+/// A device storing data in blocks of a fixed size, like a disk or a partition.
+///
+/// Filesystems are written against this trait, so they work on any driver implementing it.
+/// Blocks are addressed by their index, starting at 0.
+pub trait BlockDevice: Send {
+    /// Returns the size of a block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// Returns the number of blocks of the device.
+    fn block_count(&self) -> u64;
+
+    /// Reads the blocks starting at `block` into the buffer, its length must be a multiple of the block size.
+    fn read_blocks(&mut self, block: u64, buffer: &mut [u8]) -> Result<(), BlockDeviceError>;
+
+    /// Writes the buffer to the blocks starting at `block`, its length must be a multiple of the block size.
+    ///
+    /// The blocks may be cached until `flush` is called.
+    fn write_blocks(&mut self, block: u64, buffer: &[u8]) -> Result<(), BlockDeviceError>;
+
+    /// Makes sure the written blocks are stored on the device.
+    fn flush(&mut self) -> Result<(), BlockDeviceError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockDeviceError {
+    /// The buffer doesn't hold a whole number of blocks.
+    InvalidBufferSize,
+    /// The blocks are beyond the end of the device.
+    OutOfRange,
+    /// The device didn't respond in time.
+    Timeout,
+    /// The device failed to transfer the blocks.
+    DeviceError,
+}
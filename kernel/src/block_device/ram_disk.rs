@@ -0,0 +1,66 @@
+use alloc::{vec, vec::Vec};
+
+use super::{BlockDevice, BlockDeviceError};
+
+/// A block device keeping its blocks in memory, its content is lost when it's dropped.
+pub struct RamDisk {
+    block_size: usize,
+    data: Vec<u8>,
+}
+
+impl RamDisk {
+    /// Creates a zeroed RAM disk of `block_count` blocks of `block_size` bytes.
+    pub fn new(block_size: usize, block_count: u64) -> Self {
+        RamDisk {
+            block_size,
+            data: vec![0; block_size * block_count as usize],
+        }
+    }
+
+    /// Creates a RAM disk holding the passed data, padded with zeros to whole blocks.
+    pub fn from_bytes(block_size: usize, mut data: Vec<u8>) -> Self {
+        let padded_size = (data.len() + block_size - 1) / block_size * block_size;
+        data.resize(padded_size, 0);
+        RamDisk { block_size, data }
+    }
+
+    /// Returns the byte range of the blocks starting at `block` covered by a buffer of the passed length.
+    fn range(&self, block: u64, length: usize) -> Result<(usize, usize), BlockDeviceError> {
+        if length % self.block_size != 0 {
+            return Err(BlockDeviceError::InvalidBufferSize);
+        }
+        let start = (block as usize)
+            .checked_mul(self.block_size)
+            .ok_or(BlockDeviceError::OutOfRange)?;
+        match start.checked_add(length) {
+            Some(end) if end <= self.data.len() => Ok((start, end)),
+            _ => Err(BlockDeviceError::OutOfRange),
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        (self.data.len() / self.block_size) as u64
+    }
+
+    fn read_blocks(&mut self, block: u64, buffer: &mut [u8]) -> Result<(), BlockDeviceError> {
+        let (start, end) = self.range(block, buffer.len())?;
+        buffer.copy_from_slice(&self.data[start..end]);
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, block: u64, buffer: &[u8]) -> Result<(), BlockDeviceError> {
+        let (start, end) = self.range(block, buffer.len())?;
+        self.data[start..end].copy_from_slice(buffer);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockDeviceError> {
+        Ok(())
+    }
+}
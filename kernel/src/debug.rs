@@ -39,8 +39,8 @@ pub fn print_memory_map(memory_map: &MemoryRegions) {
     }
 }
 
-#[cfg(debug_assertions)]
-fn decode_memory_kind(kind: MemoryRegionKind) -> &'static str {
+/// Returns the name of the kind of a region of the memory map.
+pub(crate) fn decode_memory_kind(kind: MemoryRegionKind) -> &'static str {
     match kind {
         MemoryRegionKind::Usable => "usable",
         MemoryRegionKind::Bootloader => "bootloader",
@@ -0,0 +1,93 @@
+//! The device file system, publishing the devices of the drivers as files, usually mounted at `/dev`.
+//!
+//! Drivers register their devices by name, the operations on the files are passed on to the devices.
+mod block;
+pub use block::BlockDeviceFile;
+mod device;
+pub use device::Device;
+mod file_system;
+pub use file_system::DevFs;
+mod memory;
+mod serial;
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::vfs::VfsError;
+
+/// The inode number of the root directory, the devices get the following ones.
+const ROOT_INODE: u64 = 1;
+
+lazy_static! {
+    static ref DEVICES: Mutex<Registry> = Mutex::new(Registry {
+        devices: BTreeMap::new(),
+        next_inode: ROOT_INODE + 1,
+    });
+}
+
+struct Registry {
+    devices: BTreeMap<String, RegisteredDevice>,
+    next_inode: u64,
+}
+
+#[derive(Clone)]
+struct RegisteredDevice {
+    inode: u64,
+    permissions: u16,
+    device: Arc<dyn Device>,
+}
+
+/// Publishes the device as `name` with the permissions, which are only reported by `stat`.
+///
+/// A device already registered with the name is replaced, so drivers can register their devices again
+/// when they are reloaded. Files opened before keep using the old device.
+pub fn register_device(
+    name: &str,
+    permissions: u16,
+    device: Arc<dyn Device>,
+) -> Result<(), VfsError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(&['/', '\0'][..]) {
+        return Err(VfsError::InvalidName);
+    }
+    let mut registry = DEVICES.lock();
+    let inode = registry.next_inode;
+    registry.next_inode += 1;
+    registry.devices.insert(
+        String::from(name),
+        RegisteredDevice {
+            inode,
+            permissions,
+            device,
+        },
+    );
+    Ok(())
+}
+
+/// Removes the device from the devfs, files opened before keep using it until they are closed.
+pub fn unregister_device(name: &str) -> Result<(), VfsError> {
+    DEVICES
+        .lock()
+        .devices
+        .remove(name)
+        .map(|_| ())
+        .ok_or(VfsError::NotFound)
+}
+
+/// Returns the names of the registered devices, sorted by name.
+pub fn devices() -> Vec<String> {
+    DEVICES.lock().devices.keys().cloned().collect()
+}
+
+/// Registers the devices of the kernel itself: `null`, `zero`, `random` and the serial port `ttyS0`.
+pub(crate) fn register_kernel_devices() {
+    let devices: [(&str, u16, Arc<dyn Device>); 4] = [
+        ("null", 0o666, Arc::new(memory::Null)),
+        ("zero", 0o666, Arc::new(memory::Zero)),
+        ("random", 0o666, Arc::new(memory::Random::new())),
+        ("ttyS0", 0o620, Arc::new(serial::Serial::new())),
+    ];
+    for (name, permissions, device) in devices {
+        register_device(name, permissions, device).expect("Invalid device name");
+    }
+}
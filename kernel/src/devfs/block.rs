@@ -0,0 +1,114 @@
+use alloc::vec;
+use utils::ioctl;
+
+use crate::{
+    processes::SleepMutex,
+    vfs::{FileType, VfsError},
+    BlockDevice,
+};
+
+use super::Device;
+
+/// The most bytes transferred by a single read or write, larger transfers are shortened.
+const MAX_TRANSFER_SIZE: usize = 64 * 1024;
+
+/// Publishes a block device, like a disk or a partition, as a file of its bytes.
+///
+/// Transfers that don't cover whole blocks read the partially written blocks first. The device's
+/// cache is only flushed with the `BLOCK_FLUSH` command.
+pub struct BlockDeviceFile<D: BlockDevice> {
+    /// Locked across the transfers, which may sleep until the device is done.
+    device: SleepMutex<D>,
+}
+
+impl<D: BlockDevice> BlockDeviceFile<D> {
+    pub fn new(device: D) -> Self {
+        BlockDeviceFile {
+            device: SleepMutex::new(device),
+        }
+    }
+}
+
+/// Returns the first block and the number of blocks covering the bytes.
+fn covering_blocks(offset: u64, length: usize, block_size: u64) -> (u64, u64) {
+    let first = offset / block_size;
+    let end = (offset + length as u64 + block_size - 1) / block_size;
+    (first, end - first)
+}
+
+impl<D: BlockDevice> Device for BlockDeviceFile<D> {
+    fn file_type(&self) -> FileType {
+        FileType::BlockDevice
+    }
+
+    fn size(&self) -> u64 {
+        let device = self.device.lock();
+        device.block_size() as u64 * device.block_count()
+    }
+
+    fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        let mut device = self.device.lock();
+        let block_size = device.block_size() as u64;
+        let size = block_size * device.block_count();
+        if offset >= size {
+            return Ok(0);
+        }
+        let length = buffer
+            .len()
+            .min((size - offset) as usize)
+            .min(MAX_TRANSFER_SIZE);
+        if length == 0 {
+            return Ok(0);
+        }
+        let (first, count) = covering_blocks(offset, length, block_size);
+        let mut blocks = vec![0; (count * block_size) as usize];
+        device.read_blocks(first, &mut blocks)?;
+        let start = (offset % block_size) as usize;
+        buffer[..length].copy_from_slice(&blocks[start..start + length]);
+        Ok(length)
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let mut device = self.device.lock();
+        let block_size = device.block_size() as u64;
+        let size = block_size * device.block_count();
+        if offset >= size {
+            return Err(VfsError::NoSpace);
+        }
+        let length = data
+            .len()
+            .min((size - offset) as usize)
+            .min(MAX_TRANSFER_SIZE);
+        let (first, count) = covering_blocks(offset, length, block_size);
+        let mut blocks = vec![0; (count * block_size) as usize];
+        let block_size = block_size as usize;
+        let start = (offset % block_size as u64) as usize;
+        // the parts of the first and last block that aren't written are kept
+        if start != 0 {
+            device.read_blocks(first, &mut blocks[..block_size])?;
+        }
+        if (start + length) % block_size != 0 {
+            let last_start = blocks.len() - block_size;
+            device.read_blocks(first + count - 1, &mut blocks[last_start..])?;
+        }
+        blocks[start..start + length].copy_from_slice(&data[..length]);
+        device.write_blocks(first, &blocks)?;
+        Ok(length)
+    }
+
+    fn ioctl(&self, command: u64, _argument: u64) -> Result<u64, VfsError> {
+        let mut device = self.device.lock();
+        match command {
+            ioctl::BLOCK_SIZE => Ok(device.block_size() as u64),
+            ioctl::BLOCK_COUNT => Ok(device.block_count()),
+            ioctl::BLOCK_FLUSH => {
+                device.flush()?;
+                Ok(0)
+            }
+            _ => Err(VfsError::NotSupported),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::vfs::{FileType, VfsError};
+
+/// A device published in the devfs, the file operations on its file are passed on to it.
+///
+/// Devices implement the operations they support, the others keep their default implementation
+/// returning `NotSupported`.
+pub trait Device: Send + Sync {
+    /// Returns `CharacterDevice` for devices transferring streams of bytes, `BlockDevice` for storage.
+    fn file_type(&self) -> FileType {
+        FileType::CharacterDevice
+    }
+
+    /// Returns the size in bytes, `0` for devices without a size.
+    fn size(&self) -> u64 {
+        0
+    }
+
+    /// Reads from `offset` into the buffer, returns the number of bytes read, `0` at the end.
+    ///
+    /// Devices without a size, like a serial port, ignore the offset.
+    fn read(&self, _offset: u64, _buffer: &mut [u8]) -> Result<usize, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Writes the data at `offset`, returns the number of bytes written.
+    fn write(&self, _offset: u64, _data: &[u8]) -> Result<usize, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Handles a device specific command, one of `utils::ioctl`'s constants.
+    ///
+    /// The argument is a plain value, devices can't access the memory of the calling process.
+    fn ioctl(&self, _command: u64, _argument: u64) -> Result<u64, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+}
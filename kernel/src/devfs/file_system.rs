@@ -0,0 +1,124 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::vfs::{DirectoryEntry, FileStat, FileSystem, FileType, Inode, VfsError};
+
+use super::{Device, RegisteredDevice, DEVICES, ROOT_INODE};
+
+/// The permissions of the root directory.
+const ROOT_PERMISSIONS: u16 = 0o755;
+
+/// The device file system, a flat directory with a file for every registered device.
+///
+/// All mounts show the same devices, files can't be created or removed through the VFS.
+#[derive(Default)]
+pub struct DevFs;
+
+impl DevFs {
+    pub fn new() -> Self {
+        DevFs
+    }
+}
+
+impl FileSystem for DevFs {
+    fn name(&self) -> &str {
+        "devfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(RootDirectory)
+    }
+}
+
+struct RootDirectory;
+
+impl Inode for RootDirectory {
+    fn stat(&self) -> Result<FileStat, VfsError> {
+        Ok(FileStat::new(
+            ROOT_INODE,
+            FileType::Directory,
+            ROOT_PERMISSIONS,
+            0,
+        ))
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, VfsError> {
+        let device = DEVICES
+            .lock()
+            .devices
+            .get(name)
+            .cloned()
+            .ok_or(VfsError::NotFound)?;
+        Ok(Arc::new(DeviceInode(device)))
+    }
+
+    fn cache_entries(&self) -> bool {
+        false
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirectoryEntry>, VfsError> {
+        let devices: Vec<(String, RegisteredDevice)> = DEVICES
+            .lock()
+            .devices
+            .iter()
+            .map(|(name, device)| (name.clone(), device.clone()))
+            .collect();
+        // the devices are asked for their type without holding the lock
+        Ok(devices
+            .into_iter()
+            .map(|(name, device)| DirectoryEntry {
+                name,
+                inode: device.inode,
+                file_type: device.device.file_type(),
+            })
+            .collect())
+    }
+
+    fn create(
+        &self,
+        _name: &str,
+        _file_type: FileType,
+        _permissions: u16,
+    ) -> Result<Arc<dyn Inode>, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    fn remove(&self, name: &str) -> Result<(), VfsError> {
+        if DEVICES.lock().devices.contains_key(name) {
+            Err(VfsError::NotSupported)
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+}
+
+/// The file of a device, passing the operations on to it.
+struct DeviceInode(RegisteredDevice);
+
+impl DeviceInode {
+    fn device(&self) -> &dyn Device {
+        &*self.0.device
+    }
+}
+
+impl Inode for DeviceInode {
+    fn stat(&self) -> Result<FileStat, VfsError> {
+        Ok(FileStat::new(
+            self.0.inode,
+            self.device().file_type(),
+            self.0.permissions,
+            self.device().size(),
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        self.device().read(offset, buffer)
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        self.device().write(offset, data)
+    }
+
+    fn ioctl(&self, command: u64, argument: u64) -> Result<u64, VfsError> {
+        self.device().ioctl(command, argument)
+    }
+}
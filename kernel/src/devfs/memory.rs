@@ -0,0 +1,78 @@
+use spin::Mutex;
+use utils::get_current_tick;
+use x86_64::instructions::random::RdRand;
+
+use crate::vfs::VfsError;
+
+use super::Device;
+
+/// Discards everything written to it, reading from it returns the end of the file.
+pub(super) struct Null;
+
+impl Device for Null {
+    fn read(&self, _offset: u64, _buffer: &mut [u8]) -> Result<usize, VfsError> {
+        Ok(0)
+    }
+
+    fn write(&self, _offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        Ok(data.len())
+    }
+}
+
+/// Returns zeros when it's read and discards everything written to it.
+pub(super) struct Zero;
+
+impl Device for Zero {
+    fn read(&self, _offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        buffer.fill(0);
+        Ok(buffer.len())
+    }
+
+    fn write(&self, _offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        Ok(data.len())
+    }
+}
+
+/// Returns random bytes from the CPU's random number generator, if it has one.
+///
+/// Without one, the bytes come from a xorshift generator seeded with the time stamp counter, which is
+/// not suitable for cryptography. Written data is discarded.
+pub(super) struct Random {
+    generator: Option<RdRand>,
+    state: Mutex<u64>,
+}
+
+impl Random {
+    pub(super) fn new() -> Self {
+        Random {
+            generator: RdRand::new(),
+            // xorshift never leaves a state of 0
+            state: Mutex::new(get_current_tick() | 1),
+        }
+    }
+
+    fn next(&self) -> u64 {
+        if let Some(value) = self.generator.and_then(RdRand::get_u64) {
+            return value;
+        }
+        let mut state = self.state.lock();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+}
+
+impl Device for Random {
+    fn read(&self, _offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        for chunk in buffer.chunks_mut(8) {
+            let length = chunk.len();
+            chunk.copy_from_slice(&self.next().to_le_bytes()[..length]);
+        }
+        Ok(buffer.len())
+    }
+
+    fn write(&self, _offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        Ok(data.len())
+    }
+}
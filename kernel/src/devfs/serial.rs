@@ -0,0 +1,90 @@
+use spin::Mutex;
+use test_framework::serial;
+use x86_64::instructions::port::Port;
+
+use crate::{processes::sleep, vfs::VfsError};
+
+use super::Device;
+
+/// The I/O port of the first serial port, the one the kernel logs to.
+const COM1: u16 = 0x3F8;
+/// The offset of the line status register from the data register.
+const LINE_STATUS_OFFSET: u16 = 5;
+/// Set in the line status when a received byte can be read.
+const DATA_READY: u8 = 1 << 0;
+/// Set in the line status when a byte can be sent.
+const TRANSMITTER_EMPTY: u8 = 1 << 5;
+/// The most bytes sent with the interrupts disabled, about 1.4 ms at 115200 baud.
+const MAX_CHUNK_SIZE: usize = 16;
+
+/// The first serial port, already initialised by the kernel's logger.
+///
+/// Reading waits until a byte was received, then returns the received bytes that fit into the buffer.
+/// Writing sends the data in small chunks with the logger's lock held, so log messages only appear
+/// between the chunks.
+pub(super) struct Serial {
+    /// The ports used for reading, writing uses its own ones.
+    ports: Mutex<SerialPorts>,
+}
+
+struct SerialPorts {
+    data: Port<u8>,
+    line_status: Port<u8>,
+}
+
+impl Serial {
+    pub(super) fn new() -> Self {
+        Serial {
+            ports: Mutex::new(SerialPorts::new()),
+        }
+    }
+}
+
+impl SerialPorts {
+    fn new() -> Self {
+        SerialPorts {
+            data: Port::new(COM1),
+            line_status: Port::new(COM1 + LINE_STATUS_OFFSET),
+        }
+    }
+
+    fn line_status(&mut self) -> u8 {
+        unsafe { self.line_status.read() }
+    }
+}
+
+impl Device for Serial {
+    fn read(&self, _offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        // the port has no interrupt set up, so it's polled every tick until something arrives
+        while self.ports.lock().line_status() & DATA_READY == 0 {
+            sleep(1);
+        }
+        let mut ports = self.ports.lock();
+        let mut read = 0;
+        while read < buffer.len() && ports.line_status() & DATA_READY != 0 {
+            buffer[read] = unsafe { ports.data.read() };
+            read += 1;
+        }
+        Ok(read)
+    }
+
+    fn write(&self, _offset: u64, data: &[u8]) -> Result<usize, VfsError> {
+        // the logger's lock is held with interrupts disabled, so the reader's lock mustn't be taken
+        // and the data is sent in chunks, letting the timer interrupt in between
+        for chunk in data.chunks(MAX_CHUNK_SIZE) {
+            serial::with_lock(|| {
+                let mut ports = SerialPorts::new();
+                for &byte in chunk {
+                    while ports.line_status() & TRANSMITTER_EMPTY == 0 {
+                        core::hint::spin_loop();
+                    }
+                    unsafe { ports.data.write(byte) };
+                }
+            });
+        }
+        Ok(data.len())
+    }
+}
@@ -0,0 +1,100 @@
+mod elf_header;
+pub use elf_header::{ElfHeader, ElfType};
+mod program_header;
+pub use program_header::{ProgramHeader, ProgramHeaderFlags, ProgramHeaderType};
+
+use alloc::vec::Vec;
+
+use crate::memory::MappingError;
+
+use self::{elf_header::ELF_HEADER_SIZE, program_header::PROGRAM_HEADER_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ElfError {
+    /// The file doesn't start with `\x7FELF`.
+    InvalidMagic,
+    /// The file is not a 64-bit ELF file.
+    UnsupportedClass,
+    /// The file is not a little-endian ELF file.
+    UnsupportedEndianness,
+    /// The file wasn't built for x86_64.
+    UnsupportedMachine,
+    /// The file is not a statically linked executable.
+    UnsupportedType,
+    /// The file is shorter than its headers claim.
+    Truncated,
+    /// A segment is malformed or can't be loaded where it asks to.
+    InvalidSegment,
+    /// There is not enough memory to load the file.
+    OutOfMemory,
+}
+
+impl From<MappingError> for ElfError {
+    fn from(error: MappingError) -> Self {
+        match error {
+            MappingError::OutOfMemory => ElfError::OutOfMemory,
+            MappingError::NotUserPage | MappingError::NotMapped | MappingError::AccessViolation => {
+                ElfError::InvalidSegment
+            }
+        }
+    }
+}
+
+/// A parsed ELF64 file.
+pub struct ElfFile<'a> {
+    pub bytes: &'a [u8],
+    pub header: ElfHeader,
+    pub program_headers: Vec<ProgramHeader>,
+}
+
+impl<'a> ElfFile<'a> {
+    /// Parses the file header and the program header table of an ELF64 file.
+    pub fn parse(bytes: &'a [u8]) -> Result<ElfFile<'a>, ElfError> {
+        let header = ElfHeader::from_bytes(bytes)?;
+        if header.program_header_count > 0
+            && (header.program_header_entry_size as usize) < PROGRAM_HEADER_SIZE
+        {
+            return Err(ElfError::Truncated);
+        }
+
+        let mut program_headers = Vec::with_capacity(header.program_header_count as usize);
+        for index in 0..header.program_header_count as u64 {
+            let start = header
+                .program_header_offset
+                .saturating_add(index * header.program_header_entry_size as u64);
+            let end = start.saturating_add(PROGRAM_HEADER_SIZE as u64);
+            if start < ELF_HEADER_SIZE as u64 || end > bytes.len() as u64 {
+                return Err(ElfError::Truncated);
+            }
+            program_headers.push(ProgramHeader::from_bytes(
+                &bytes[start as usize..end as usize],
+            )?);
+        }
+
+        Ok(ElfFile {
+            bytes,
+            header,
+            program_headers,
+        })
+    }
+
+    /// Returns the segments that have to be loaded into memory.
+    pub fn loadable_segments(&self) -> impl Iterator<Item = &ProgramHeader> {
+        self.program_headers
+            .iter()
+            .filter(|header| header.header_type == ProgramHeaderType::Load)
+    }
+
+    /// Returns the data of a segment stored in the file.
+    pub fn segment_data(&self, header: &ProgramHeader) -> Result<&'a [u8], ElfError> {
+        let end = header
+            .offset
+            .checked_add(header.file_size)
+            .ok_or(ElfError::Truncated)?;
+        if end > self.bytes.len() as u64 {
+            return Err(ElfError::Truncated);
+        }
+        Ok(&self.bytes[header.offset as usize..end as usize])
+    }
+}
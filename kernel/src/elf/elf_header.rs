@@ -0,0 +1,102 @@
+use utils::byte_reader::ByteReader;
+
+use super::ElfError;
+
+/// The size of the ELF64 file header in bytes.
+pub const ELF_HEADER_SIZE: usize = 64;
+
+const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+const ELF_MACHINE_X86_64: u16 = 0x3E;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ElfType {
+    None = 0,
+    Relocatable = 1,
+    Executable = 2,
+    SharedObject = 3,
+    Core = 4,
+    Unknown = 0xFFFF,
+}
+
+impl From<u16> for ElfType {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => ElfType::None,
+            1 => ElfType::Relocatable,
+            2 => ElfType::Executable,
+            3 => ElfType::SharedObject,
+            4 => ElfType::Core,
+            _ => ElfType::Unknown,
+        }
+    }
+}
+
+/// The file header of an ELF64 little-endian x86_64 file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfHeader {
+    pub elf_type: ElfType,
+    /// The virtual address of the entry point.
+    pub entry: u64,
+    /// The offset of the program header table in the file.
+    pub program_header_offset: u64,
+    /// The offset of the section header table in the file.
+    pub section_header_offset: u64,
+    pub flags: u32,
+    pub program_header_entry_size: u16,
+    pub program_header_count: u16,
+    pub section_header_entry_size: u16,
+    pub section_header_count: u16,
+    pub section_names_index: u16,
+}
+
+impl ElfHeader {
+    pub fn from_bytes(bytes: &[u8]) -> Result<ElfHeader, ElfError> {
+        if bytes.len() < ELF_HEADER_SIZE {
+            return Err(ElfError::Truncated);
+        }
+        let mut reader = ByteReader::of(bytes);
+        if reader.read_slice::<4>() != ELF_MAGIC {
+            return Err(ElfError::InvalidMagic);
+        }
+        if reader.read_u8() != ELF_CLASS_64 {
+            return Err(ElfError::UnsupportedClass);
+        }
+        if reader.read_u8() != ELF_DATA_LITTLE_ENDIAN {
+            return Err(ElfError::UnsupportedEndianness);
+        }
+        // version, OS ABI, ABI version and padding
+        reader.read_slice::<10>();
+
+        let elf_type = reader.read_enum_u16::<ElfType>();
+        if reader.read_u16() != ELF_MACHINE_X86_64 {
+            return Err(ElfError::UnsupportedMachine);
+        }
+        let _version = reader.read_u32();
+        let entry = reader.read_u64();
+        let program_header_offset = reader.read_u64();
+        let section_header_offset = reader.read_u64();
+        let flags = reader.read_u32();
+        let _header_size = reader.read_u16();
+        let program_header_entry_size = reader.read_u16();
+        let program_header_count = reader.read_u16();
+        let section_header_entry_size = reader.read_u16();
+        let section_header_count = reader.read_u16();
+        let section_names_index = reader.read_u16();
+
+        Ok(ElfHeader {
+            elf_type,
+            entry,
+            program_header_offset,
+            section_header_offset,
+            flags,
+            program_header_entry_size,
+            program_header_count,
+            section_header_entry_size,
+            section_header_count,
+            section_names_index,
+        })
+    }
+}
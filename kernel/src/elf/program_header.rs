@@ -0,0 +1,95 @@
+use bitflags::bitflags;
+use utils::byte_reader::ByteReader;
+
+use super::ElfError;
+
+/// The size of an ELF64 program header in bytes.
+pub const PROGRAM_HEADER_SIZE: usize = 56;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ProgramHeaderType {
+    Null = 0,
+    /// A segment that has to be loaded into memory.
+    Load = 1,
+    Dynamic = 2,
+    Interpreter = 3,
+    Note = 4,
+    ProgramHeaderTable = 6,
+    ThreadLocalStorage = 7,
+    Unknown = 0xFFFF_FFFF,
+}
+
+impl From<u32> for ProgramHeaderType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => ProgramHeaderType::Null,
+            1 => ProgramHeaderType::Load,
+            2 => ProgramHeaderType::Dynamic,
+            3 => ProgramHeaderType::Interpreter,
+            4 => ProgramHeaderType::Note,
+            6 => ProgramHeaderType::ProgramHeaderTable,
+            7 => ProgramHeaderType::ThreadLocalStorage,
+            _ => ProgramHeaderType::Unknown,
+        }
+    }
+}
+
+bitflags! {
+    #[repr(C)]
+    pub struct ProgramHeaderFlags: u32 {
+        /// Executable
+        const EXECUTE = 0b001;
+        /// Writable
+        const WRITE = 0b010;
+        /// Readable
+        const READ = 0b100;
+    }
+}
+
+/// An entry of the program header table, describing a segment of the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramHeader {
+    pub header_type: ProgramHeaderType,
+    pub flags: ProgramHeaderFlags,
+    /// The offset of the segment's data in the file.
+    pub offset: u64,
+    /// The virtual address the segment is loaded at.
+    pub virtual_address: u64,
+    /// The number of bytes of the segment stored in the file.
+    pub file_size: u64,
+    /// The number of bytes of the segment in memory, the bytes not stored in the file are zeroed.
+    pub memory_size: u64,
+    pub alignment: u64,
+}
+
+impl ProgramHeader {
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProgramHeader, ElfError> {
+        if bytes.len() < PROGRAM_HEADER_SIZE {
+            return Err(ElfError::Truncated);
+        }
+        let mut reader = ByteReader::of(bytes);
+        let header_type = reader.read_enum_u32::<ProgramHeaderType>();
+        let flags = ProgramHeaderFlags::from_bits_truncate(reader.read_u32());
+        let offset = reader.read_u64();
+        let virtual_address = reader.read_u64();
+        let _physical_address = reader.read_u64();
+        let file_size = reader.read_u64();
+        let memory_size = reader.read_u64();
+        let alignment = reader.read_u64();
+
+        if file_size > memory_size {
+            return Err(ElfError::InvalidSegment);
+        }
+
+        Ok(ProgramHeader {
+            header_type,
+            flags,
+            offset,
+            virtual_address,
+            file_size,
+            memory_size,
+            alignment,
+        })
+    }
+}
@@ -2,13 +2,15 @@ use alloc::vec::Vec;
 use bootloader::BootInfo;
 use lazy_static::lazy_static;
 use spin::Mutex;
+use utils::{syscall_error::SysCallError, syscall_name::SysCallName};
 
 use crate::{
-    interrupts, memory,
+    devfs, interrupts, logger, memory, processes, procfs,
     structures::{
         driver::{Driver, Registrator},
         kernel_information::KernelInformation,
     },
+    vfs,
 };
 
 use crate::debug;
@@ -16,6 +18,7 @@ use crate::debug;
 lazy_static! {
     static ref REGISTERED_DRIVERS: Mutex<Vec<Registrator>> = Mutex::new(Vec::new());
     static ref INITIALIZED_DRIVERS: Mutex<Vec<Driver>> = Mutex::new(Vec::new());
+    static ref SHUTDOWN_HOOKS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
 }
 
 /// Initialises the components of the OS, **must** be called before any other functions.
@@ -25,8 +28,18 @@ pub fn init(boot_info: &'static BootInfo) -> KernelInformation {
     memory::init(boot_info);
     let kernel_info = KernelInformation::new(boot_info);
     interrupts::reload_gdt();
+    interrupts::init_per_cpu();
     interrupts::init_idt();
     interrupts::syscalls::setup_syscalls();
+    logger::register_syscalls();
+    processes::syscalls::register_syscalls();
+    vfs::syscalls::register_syscalls();
+    interrupts::syscalls::register_syscall(SysCallName::Shutdown, shutdown_syscall);
+    register_shutdown_hook(vfs::sync);
+    devfs::register_kernel_devices();
+    procfs::init(&kernel_info);
+    processes::adopt_boot_context();
+    processes::spawn_idle_thread();
     interrupts::enable();
 
     kernel_info
@@ -44,11 +57,37 @@ pub fn reload_drivers(kernel_info: KernelInformation) {
     );
 }
 
+/// Returns the drivers initialized by the last `reload_drivers`, in the order they were registered.
+pub fn drivers() -> Vec<Driver> {
+    INITIALIZED_DRIVERS.lock().clone()
+}
+
 /// Registers a driver. After registering drivers call reload_drivers to initialize them.
 pub fn register_driver(registrator: Registrator) {
     REGISTERED_DRIVERS.lock().push(registrator);
 }
 
+/// Registers a function called by `shutdown`, e.g. to write cached data back to a disk.
+pub fn register_shutdown_hook(hook: fn()) {
+    SHUTDOWN_HOOKS.lock().push(hook);
+}
+
+/// Runs the registered shutdown hooks, then halts the machine.
+pub fn shutdown() -> ! {
+    // the hooks may sleep, so they run with interrupts enabled and without holding the lock
+    let hooks = SHUTDOWN_HOOKS.lock().clone();
+    for hook in hooks {
+        hook();
+    }
+    x86_64::instructions::interrupts::disable();
+    hlt_loop();
+}
+
+/// Shuts the machine down on behalf of a process.
+fn shutdown_syscall(_: u64, _: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    shutdown()
+}
+
 /// Endless loop calling halt continuously.
 pub fn hlt_loop() -> ! {
     loop {
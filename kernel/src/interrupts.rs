@@ -2,14 +2,24 @@
 
 // TODO: implement all remaining interrupt handlers for CPU interrupts
 
+mod counters;
+pub use counters::{interrupt_counts, InterruptCount};
 mod cpu_handlers;
 mod interrupt_register;
 pub use interrupt_register::init_idt;
 mod gdt;
 mod pic_handlers;
 pub use gdt::{reload_gdt, GDT};
+pub use pic_handlers::{
+    milliseconds_to_ticks, ATA_PRIMARY_INTERRUPT, ATA_SECONDARY_INTERRUPT,
+    TIMER_FREQUENCY_MILLIHERTZ,
+};
+mod per_cpu;
+pub(crate) use per_cpu::{init as init_per_cpu, set_kernel_stack};
 mod pic;
 pub mod syscalls;
+mod yield_handler;
+pub(crate) use yield_handler::yield_now;
 
 use crate::debug;
 
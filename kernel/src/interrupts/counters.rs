@@ -0,0 +1,74 @@
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::{pic::InterruptIndex, yield_handler::YIELD_INTERRUPT_INDEX};
+
+/// Counts how often an interrupt occurred, it's incremented by the interrupt's handler.
+///
+/// The count is atomic, so handlers can increment it without taking a lock.
+pub(crate) struct InterruptCounter {
+    vector: u8,
+    name: &'static str,
+    count: AtomicU64,
+}
+
+impl InterruptCounter {
+    const fn new(vector: u8, name: &'static str) -> Self {
+        InterruptCounter {
+            vector,
+            name,
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn increment(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) static BREAKPOINT: InterruptCounter = InterruptCounter::new(3, "breakpoint");
+pub(crate) static GENERAL_PROTECTION_FAULT: InterruptCounter =
+    InterruptCounter::new(13, "general protection fault");
+pub(crate) static PAGE_FAULT: InterruptCounter = InterruptCounter::new(14, "page fault");
+pub(crate) static TIMER: InterruptCounter =
+    InterruptCounter::new(InterruptIndex::Timer as u8, "timer");
+pub(crate) static KEYBOARD: InterruptCounter =
+    InterruptCounter::new(InterruptIndex::Keyboard as u8, "keyboard");
+pub(crate) static ATA_PRIMARY: InterruptCounter =
+    InterruptCounter::new(InterruptIndex::AtaPrimary as u8, "ATA primary");
+pub(crate) static ATA_SECONDARY: InterruptCounter =
+    InterruptCounter::new(InterruptIndex::AtaSecondary as u8, "ATA secondary");
+pub(crate) static YIELD: InterruptCounter = InterruptCounter::new(YIELD_INTERRUPT_INDEX, "yield");
+
+/// The counters of the handled interrupts, ordered by vector. The fatal ones aren't counted.
+static COUNTERS: [&InterruptCounter; 8] = [
+    &BREAKPOINT,
+    &GENERAL_PROTECTION_FAULT,
+    &PAGE_FAULT,
+    &TIMER,
+    &KEYBOARD,
+    &ATA_PRIMARY,
+    &ATA_SECONDARY,
+    &YIELD,
+];
+
+/// How often an interrupt occurred since boot, as returned by `interrupt_counts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptCount {
+    /// The index of the interrupt in the IDT.
+    pub vector: u8,
+    pub name: &'static str,
+    pub count: u64,
+}
+
+/// Returns how often each of the handled interrupts occurred since boot, ordered by vector.
+pub fn interrupt_counts() -> Vec<InterruptCount> {
+    COUNTERS
+        .iter()
+        .map(|counter| InterruptCount {
+            vector: counter.vector,
+            name: counter.name,
+            count: counter.count.load(Ordering::Relaxed),
+        })
+        .collect()
+}
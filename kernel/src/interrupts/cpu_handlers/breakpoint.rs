@@ -1,7 +1,8 @@
-use crate::log_println;
+use crate::{interrupts::counters, log_println};
 use x86_64::structures::idt::InterruptStackFrame;
 
 /// Handles a breakpoint interrupt (like `int3`).
 pub extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    counters::BREAKPOINT.increment();
     log_println!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame);
 }
@@ -1,20 +1,62 @@
+use alloc::format;
+use core::arch::asm;
 use test_framework::serial_println;
 use x86_64::structures::idt::InterruptStackFrame;
 use x86_64::structures::idt::PageFaultErrorCode;
+use x86_64::VirtAddr;
 
-use crate::hlt_loop;
+use crate::{
+    debug, hlt_loop,
+    interrupts::counters,
+    processes::scheduler::{exit_running_process, with_running_process},
+};
+
+/// The exit code of a process killed because of a page fault it caused.
+const PAGE_FAULT_EXIT_CODE: i32 = 139;
 
 /// Handles a page fault.
+///
+/// Faults caused by user mode are resolved in the address space of the running process: pages of its
+/// memory regions are mapped on their first access and copy-on-write pages are copied on their first write.
+/// If the access isn't allowed, only the process is killed. Faults caused by the kernel itself are fatal.
 pub extern "x86-interrupt" fn page_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
     use x86_64::registers::control::Cr2;
-    x86_64::instructions::interrupts::disable();
+    counters::PAGE_FAULT.increment();
+    let address = VirtAddr::new(Cr2::read_raw());
 
+    if stack_frame.code_segment & 3 == 3 {
+        let result = with_running_process(|process| {
+            let resolved = process.address_space.handle_page_fault(
+                address,
+                error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE),
+                error_code.contains(PageFaultErrorCode::INSTRUCTION_FETCH),
+            );
+            (process.id, resolved)
+        });
+        match result {
+            Some((_, Ok(()))) => return,
+            Some((id, Err(error))) => {
+                debug::log(&format!(
+                    "Process {} caused a page fault at {:?} ({:?}, {:?})",
+                    id, address, error_code, error
+                ));
+                unsafe {
+                    // the process never returns to user mode, so the kernel GS base is restored for good
+                    asm!("swapgs");
+                }
+                exit_running_process(PAGE_FAULT_EXIT_CODE);
+            }
+            None => {}
+        }
+    }
+
+    x86_64::instructions::interrupts::disable();
     serial_println!("EXCEPTION: PAGE FAULT");
     serial_println!("{:?}", error_code);
-    serial_println!("Page: {:?}", Cr2::read_raw());
+    serial_println!("Page: {:?}", address);
     serial_println!("{:#?}", stack_frame);
     hlt_loop();
 }
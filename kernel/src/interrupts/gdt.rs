@@ -20,7 +20,8 @@ lazy_static! {
         #[repr(align(16))]
         struct Stack([u8; STACK_SIZE]);
 
-        // Stack used when an exception happens in user mode
+        // Stack used when an exception happens in user mode before any process runs,
+        // it's replaced by the kernel stack of the running process
         tss.privilege_stack_table[0] = {
 
             static mut STACK: Stack = Stack([0; STACK_SIZE]);
@@ -120,3 +121,13 @@ pub fn reload_gdt() {
     }
     debug::log("Segment registers loaded");
 }
+
+/// Sets the stack the CPU switches to when an interrupt happens in user mode.
+///
+/// This function is unsafe because the stack must stay allocated as long as it is in use.
+pub(crate) unsafe fn set_privilege_stack(stack_top: VirtAddr) {
+    // The TSS is only read by the CPU when it switches stacks, which can't happen while interrupts
+    // are handled by the kernel on the same stack
+    let tss = &*TSS as *const TaskStateSegment as *mut TaskStateSegment;
+    (*tss).privilege_stack_table[0] = stack_top;
+}
@@ -1,5 +1,5 @@
 use lazy_static::lazy_static;
-use x86_64::structures::idt::InterruptDescriptorTable;
+use x86_64::{structures::idt::InterruptDescriptorTable, VirtAddr};
 
 use crate::{
     debug,
@@ -13,6 +13,7 @@ use crate::{
             ata_primary_interrupt_handler, ata_secondary_interrupt_handler,
             keyboard_interrupt_handler, timer_interrupt_handler,
         },
+        yield_handler::{yield_interrupt_handler, YIELD_INTERRUPT_INDEX},
     },
 };
 
@@ -43,8 +44,12 @@ lazy_static! {
         // ##################
         // # PIC interrupts #
         // ##################
-        idt[InterruptIndex::Timer.as_usize()]
-            .set_handler_fn(timer_interrupt_handler);
+        unsafe {
+            // the timer handler switches processes, so it needs a stack that isn't owned by any of them
+            idt[InterruptIndex::Timer.as_usize()]
+                .set_handler_addr(VirtAddr::from_ptr(timer_interrupt_handler as *const ()))
+                .set_stack_index(crate::interrupts::gdt::TIMER_IST_INDEX);
+        }
 
         idt[InterruptIndex::Keyboard.as_usize()]
             .set_handler_fn(keyboard_interrupt_handler);
@@ -55,6 +60,16 @@ lazy_static! {
         idt[InterruptIndex::AtaSecondary.as_usize()]
             .set_handler_fn(ata_secondary_interrupt_handler);
 
+        // #######################
+        // # Software interrupts #
+        // #######################
+        unsafe {
+            // it switches processes like the timer handler, so it uses the same stack
+            idt[YIELD_INTERRUPT_INDEX as usize]
+                .set_handler_addr(VirtAddr::from_ptr(yield_interrupt_handler as *const ()))
+                .set_stack_index(crate::interrupts::gdt::TIMER_IST_INDEX);
+        }
+
         idt
     };
 }
@@ -0,0 +1,54 @@
+use x86_64::{
+    registers::model_specific::{GsBase, KernelGsBase},
+    VirtAddr,
+};
+
+use crate::debug;
+
+use super::gdt::{self, GDT};
+
+/// The data of a CPU that the assembly entry points need before they can use a stack.
+///
+/// While the CPU runs kernel code, the GS base points to this area. While it runs user code,
+/// the area is stored in the kernel GS base and `swapgs` makes it available again on entry.
+///
+/// The field offsets are used by the assembly code, so the layout must not change.
+#[repr(C)]
+pub(crate) struct PerCpu {
+    /// `gs:[0]`: the top of the kernel stack of the running process.
+    kernel_stack_top: u64,
+    /// `gs:[8]`: the user mode stack pointer, saved while a system call switches stacks.
+    user_stack: u64,
+    /// `gs:[16]`: the code segment selector of user mode.
+    user_code_selector: u64,
+    /// `gs:[24]`: the stack segment selector of user mode.
+    user_data_selector: u64,
+}
+
+// There's only a single CPU for now.
+static mut PER_CPU: PerCpu = PerCpu {
+    kernel_stack_top: 0,
+    user_stack: 0,
+    user_code_selector: 0,
+    user_data_selector: 0,
+};
+
+/// Initializes the per-CPU area and loads it into the GS base, **must** be called after the GDT is loaded.
+pub(crate) fn init() {
+    debug::log("Loading per-CPU area");
+    unsafe {
+        PER_CPU.user_code_selector = ((GDT.1.user_code_selector.index() * 8) | 3) as u64;
+        PER_CPU.user_data_selector = ((GDT.1.user_data_selector.index() * 8) | 3) as u64;
+        GsBase::write(VirtAddr::from_ptr(&PER_CPU));
+        KernelGsBase::write(VirtAddr::zero());
+    }
+}
+
+/// Sets the stack used by system calls and interrupts coming from user mode.
+///
+/// This function is unsafe because the stack must stay allocated as long as it is in use
+/// and must only be used by the running process.
+pub(crate) unsafe fn set_kernel_stack(stack_top: VirtAddr) {
+    PER_CPU.kernel_stack_top = stack_top.as_u64();
+    gdt::set_privilege_stack(stack_top);
+}
@@ -1,7 +1,10 @@
 mod timer;
-pub use timer::timer_interrupt_handler;
+pub use timer::{milliseconds_to_ticks, timer_interrupt_handler, TIMER_FREQUENCY_MILLIHERTZ};
 mod keyboard;
 pub use keyboard::keyboard_interrupt_handler;
 mod ata;
-pub use ata::{ata_primary_interrupt_handler, ata_secondary_interrupt_handler};
+pub use ata::{
+    ata_primary_interrupt_handler, ata_secondary_interrupt_handler, ATA_PRIMARY_INTERRUPT,
+    ATA_SECONDARY_INTERRUPT,
+};
 mod addresses;
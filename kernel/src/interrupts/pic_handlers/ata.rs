@@ -1,8 +1,21 @@
 use x86_64::structures::idt::InterruptStackFrame;
 
-use crate::interrupts::pic::{InterruptIndex, PICS};
+use crate::{
+    interrupts::{
+        counters,
+        pic::{InterruptIndex, PICS},
+    },
+    processes::InterruptEvent,
+};
+
+/// Signalled when the device on the primary ATA bus raises IRQ 14.
+pub static ATA_PRIMARY_INTERRUPT: InterruptEvent = InterruptEvent::new();
+/// Signalled when the device on the secondary ATA bus raises IRQ 15.
+pub static ATA_SECONDARY_INTERRUPT: InterruptEvent = InterruptEvent::new();
 
 pub extern "x86-interrupt" fn ata_primary_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    counters::ATA_PRIMARY.increment();
+    ATA_PRIMARY_INTERRUPT.signal();
     unsafe {
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::AtaPrimary.as_u8());
@@ -10,6 +23,8 @@ pub extern "x86-interrupt" fn ata_primary_interrupt_handler(_stack_frame: Interr
 }
 
 pub extern "x86-interrupt" fn ata_secondary_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    counters::ATA_SECONDARY.increment();
+    ATA_SECONDARY_INTERRUPT.signal();
     unsafe {
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::AtaSecondary.as_u8());
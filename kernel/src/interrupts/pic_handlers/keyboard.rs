@@ -5,7 +5,7 @@ use x86_64::structures::idt::InterruptStackFrame;
 
 use crate::interrupts::pic::PICS;
 use crate::interrupts::{
-    pic::InterruptIndex, pic_handlers::addresses::PS2_INTERRUPT_CONTROLLER_SCAN_CODE_PORT,
+    counters, pic::InterruptIndex, pic_handlers::addresses::PS2_INTERRUPT_CONTROLLER_SCAN_CODE_PORT,
 };
 use crate::log_print;
 
@@ -18,6 +18,7 @@ lazy_static! {
 /// Handles a keyboard interrupt.
 pub extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
     use x86_64::instructions::port::Port;
+    counters::KEYBOARD.increment();
 
     let mut keyboard = KEYBOARD.lock();
     let mut port = Port::new(PS2_INTERRUPT_CONTROLLER_SCAN_CODE_PORT);
@@ -1,9 +1,58 @@
-use x86_64::structures::idt::InterruptStackFrame;
+use core::arch::asm;
+use utils::{pop_all, push_all};
 
-use crate::interrupts::pic::{InterruptIndex, PICS};
+use crate::{
+    interrupts::{
+        counters,
+        pic::{InterruptIndex, PICS},
+    },
+    processes::{scheduler::SCHEDULER, RegistersState},
+};
+
+/// The frequency of the PIT's input clock in Hz.
+const PIT_INPUT_FREQUENCY: u64 = 1_193_182;
+/// The PIT isn't reprogrammed, so it divides its input clock by the maximum of 65536.
+const PIT_DIVISOR: u64 = 65536;
+/// The number of timer interrupts per 1000 seconds, the PIT ticks at about 18.2 Hz.
+pub const TIMER_FREQUENCY_MILLIHERTZ: u64 = PIT_INPUT_FREQUENCY * 1000 / PIT_DIVISOR;
+
+/// Returns the number of timer ticks lasting at least the passed number of milliseconds.
+pub const fn milliseconds_to_ticks(milliseconds: u64) -> u64 {
+    (milliseconds * TIMER_FREQUENCY_MILLIHERTZ + 999_999) / 1_000_000
+}
 
 /// Handles a timer interrupt.
-pub extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+///
+/// Pushes all the general purpose registers on top of the interrupt stack frame, so together
+/// they form a `RegistersState`. The scheduler can then replace it with the state of the next
+/// process, which is restored on return.
+///
+/// The interrupted and the restored contexts can each be in user or kernel mode (e.g. in the middle
+/// of a system call), so the GS base is swapped based on the code segment of each frame.
+#[naked]
+pub unsafe extern "C" fn timer_interrupt_handler() -> ! {
+    asm!(
+        "test qword ptr [rsp + 8], 3", // interrupted in user mode if the CS has RPL 3
+        "jz 2f",
+        "swapgs",
+        "2:",
+        push_all!(),
+        "mov rdi, rsp", // Pointer to the RegistersState
+        "call timer_handler",
+        pop_all!(),
+        "test qword ptr [rsp + 8], 3", // returning to user mode
+        "jz 3f",
+        "swapgs",
+        "3:",
+        "iretq",
+        options(noreturn)
+    );
+}
+
+#[no_mangle]
+extern "C" fn timer_handler(state: &mut RegistersState) {
+    counters::TIMER.increment();
+    SCHEDULER.lock().tick(state);
     unsafe {
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
@@ -1,19 +1,25 @@
 use lazy_static::lazy_static;
 use spin::Mutex;
-use test_framework::serial_println;
-use utils::syscall_name::SysCallName;
+use utils::{
+    pop_all, push_all,
+    syscall_error::{encode_result, SysCallError},
+    syscall_name::SysCallName,
+};
 use x86_64::VirtAddr;
 
-use crate::debug;
+use crate::{debug, processes::RegistersState};
 
 use super::gdt::GDT;
-use core::{arch::asm, mem};
+use core::arch::asm;
 
-pub type SysCallHandlerFunc = extern "C" fn(u64, u64);
+/// A system call handler, taking the six arguments of the call and returning its result.
+pub type SysCallHandlerFunc = fn(u64, u64, u64, u64, u64, u64) -> Result<u64, SysCallError>;
+
+const MAX_SYSCALLS: usize = 1024;
 
 lazy_static! {
-    static ref SYSCALLS: Mutex<[SysCallHandlerFunc; 1024]> =
-        Mutex::new([unsafe { mem::transmute(0u8 as *const ()) }; 1024]);
+    static ref SYSCALLS: Mutex<[Option<SysCallHandlerFunc>; MAX_SYSCALLS]> =
+        Mutex::new([None; MAX_SYSCALLS]);
 }
 
 /// Sets up the LSTAR, FSTAR and STAR model-specific registers so it's possible to use `syscall`.
@@ -45,64 +51,77 @@ pub(crate) fn setup_syscalls() {
     debug::log("Syscalls active");
 }
 
-pub fn register_syscall(syscall_number: u16, handler: SysCallHandlerFunc) {
-    SYSCALLS.lock()[syscall_number as usize] = handler;
+/// Registers the handler of a system call, replacing the previous one.
+pub fn register_syscall(name: SysCallName, handler: SysCallHandlerFunc) {
+    SYSCALLS.lock()[name as usize] = Some(handler);
 }
 
-fn call_syscall(syscall_number: u16, arg1: u64, arg2: u64) {
-    SYSCALLS.lock()[syscall_number as usize](arg1, arg2);
+fn call_syscall(
+    syscall_number: u64,
+    arg1: u64,
+    arg2: u64,
+    arg3: u64,
+    arg4: u64,
+    arg5: u64,
+    arg6: u64,
+) -> Result<u64, SysCallError> {
+    // the lock is released before calling, so handlers can take as long as they need
+    let handler = SYSCALLS
+        .lock()
+        .get(syscall_number as usize)
+        .copied()
+        .flatten()
+        .ok_or(SysCallError::UnknownSysCall)?;
+    handler(arg1, arg2, arg3, arg4, arg5, arg6)
 }
 
 /// Handles a system call.
 /// On entry to this function:
+/// - the system call number is stored in RAX
+/// - the arguments are stored in RDI, RSI, RDX, R10, R8 and R9
 /// - the instruction pointer is stored in RCX
 /// - the flags are stored in R11
 /// - the stack pointer is still targeting the user mode stack
+/// - the GS base is still the one of user mode
 ///
 /// To properly handle this, we need to:
-/// 1. save the user mode stack pointer
-/// 2. set the syscall stack pointer
-/// 3. save all the registers we need to preserve on the stack
-/// 4. do our thing with the values we got from the user
-/// 5. restore the registers from the stack
-/// 6. restore the user mode stack pointer
-/// 7. sysretq (maybe setting the flags back?)
+/// 1. swap the GS base to get the per-CPU area
+/// 2. save the user mode stack pointer and switch to the kernel stack of the process
+/// 3. save all the registers on the stack, in the layout of a `RegistersState`
+/// 4. enable interrupts, so the process can be preempted or blocked while in the kernel
+/// 5. do our thing with the values we got from the user
+/// 6. disable interrupts, since an interrupt after `swapgs` would use the user GS base
+/// 7. restore the registers from the stack, with the result in RAX
+/// 8. swap the GS base back and return with iretq, which restores the flags and the stack
 #[no_mangle]
 #[naked]
 unsafe extern "C" fn _syscall() -> ! {
     asm!(
-        "mov r10, rsp",
-        "mov rsp, 0x007F80014000", // User stack saved in R10, start of kernel stack loaded
-        "push r10",
-        "push rcx",
-        "push r11",
-        "push rbp",
-        "push rbx", // save callee-saved registers
-        "push r12",
-        "push r13",
-        "push r14",
-        "push r15",
-        // We didn't touch RDI, RSI and RDX so we can just call the function with them.
+        "swapgs",
+        "mov gs:[8], rsp",        // user stack
+        "mov rsp, gs:[0]",        // kernel stack of the running process
+        "push qword ptr gs:[24]", // SS
+        "push qword ptr gs:[8]",  // RSP
+        "push r11",               // RFLAGS
+        "push qword ptr gs:[16]", // CS
+        "push rcx",               // RIP
+        push_all!(),
+        "sti",
+        "mov rdi, rsp", // Pointer to the RegistersState
         "call handler",
-        // TODO: Returning using iret so we can return to kernel processes
-        "pop r15", // restore callee-saved registers
-        "pop r14",
-        "pop r13",
-        "pop r12",
-        "pop rbx",
-        "pop rbp",
-        "pop r11",
-        "pop rcx",
-        "pop r10",
-        "mov rsp, r10",
-        "sysretq",
+        "cli",
+        pop_all!(),
+        "swapgs",
+        "iretq",
         options(noreturn)
     );
 }
 
 #[no_mangle]
-extern "C" fn handler(name: u64, arg1: u64, arg2: u64) {
+extern "C" fn handler(state: &mut RegistersState) {
     // This block executes after saving the user state and before returning back
-    serial_println!("syscall {:#?}", name);
-    //call_syscall(name as u64 as u16, arg1, arg2);
+    let result = call_syscall(
+        state.rax, state.rdi, state.rsi, state.rdx, state.r10, state.r8, state.r9,
+    );
+    state.rax = encode_result(result);
 }
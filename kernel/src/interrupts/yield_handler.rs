@@ -0,0 +1,45 @@
+use core::arch::asm;
+use utils::{pop_all, push_all};
+
+use crate::{
+    interrupts::counters,
+    processes::{scheduler::SCHEDULER, RegistersState},
+};
+
+/// The interrupt vector raised by the kernel to switch to the next process, right after the PIC interrupts.
+pub(crate) const YIELD_INTERRUPT_INDEX: u8 = 0x30;
+
+/// Gives the rest of the time slice of the running process to the next process in the ready queue.
+///
+/// A process that blocked or exited is switched away from for good, the others are queued again.
+pub(crate) fn yield_now() {
+    unsafe {
+        asm!("int {}", const YIELD_INTERRUPT_INDEX);
+    }
+}
+
+/// Handles the interrupt raised by `yield_now`.
+///
+/// It switches processes exactly like the timer interrupt does, only the interrupted context is
+/// always in kernel mode and there is no PIC to notify.
+#[naked]
+pub unsafe extern "C" fn yield_interrupt_handler() -> ! {
+    asm!(
+        push_all!(),
+        "mov rdi, rsp", // Pointer to the RegistersState
+        "call yield_handler",
+        pop_all!(),
+        "test qword ptr [rsp + 8], 3", // returning to user mode
+        "jz 2f",
+        "swapgs",
+        "2:",
+        "iretq",
+        options(noreturn)
+    );
+}
+
+#[no_mangle]
+extern "C" fn yield_handler(state: &mut RegistersState) {
+    counters::YIELD.increment();
+    SCHEDULER.lock().schedule(state);
+}
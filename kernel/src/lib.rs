@@ -22,18 +22,34 @@ use test_framework::{
 };
 
 mod init;
-pub use init::{hlt_loop, init, register_driver, reload_drivers};
+pub use init::{
+    drivers, hlt_loop, init, register_driver, register_shutdown_hook, reload_drivers, shutdown,
+};
 
 use crate::logger::Logger;
 
 mod interrupts;
+pub use interrupts::{
+    interrupt_counts, milliseconds_to_ticks, InterruptCount, ATA_PRIMARY_INTERRUPT,
+    ATA_SECONDARY_INTERRUPT, TIMER_FREQUENCY_MILLIHERTZ,
+};
 mod user_mode;
 pub use user_mode::run_in_user_mode;
+mod block_device;
+pub use block_device::{BlockDevice, BlockDeviceError, RamDisk};
 mod debug;
+pub mod devfs;
+pub mod elf;
 pub mod logger;
 mod memory;
+pub use memory::{
+    heap_stats, slab_stats, DmaBuffer, FrameAllocatorStats, HeapStats, MemoryRegion, RegionKind,
+    SlabCacheStats,
+};
 pub mod processes;
+pub mod procfs;
 pub mod structures;
+pub mod vfs;
 
 lazy_static! {
     pub static ref LOGGER: Arc<Mutex<Option<Box<dyn Logger>>>> = Arc::from(Mutex::new(None));
@@ -1,6 +1,8 @@
 use core::fmt::{self, Write};
 
-use crate::LOGGER;
+use utils::{syscall_error::SysCallError, syscall_name::SysCallName};
+
+use crate::{interrupts::syscalls::register_syscall, memory::user_memory::user_str, LOGGER};
 
 pub trait Logger: Write + Send {
     fn log(&mut self, message: &str);
@@ -26,3 +28,22 @@ macro_rules! log_println {
     () => ($crate::log_print!("\n"));
     ($($arg:tt)*) => ($crate::log_print!("{}\n", format_args!($($arg)*)));
 }
+
+/// Registers the system calls writing to the console.
+pub(crate) fn register_syscalls() {
+    register_syscall(SysCallName::ConsoleWrite, console_write);
+}
+
+/// Writes the string passed by the running process to the logger and returns its length.
+fn console_write(
+    buffer: u64,
+    length: u64,
+    _: u64,
+    _: u64,
+    _: u64,
+    _: u64,
+) -> Result<u64, SysCallError> {
+    let message = unsafe { user_str(buffer, length)? };
+    __print(format_args!("{}", message));
+    Ok(length)
+}
@@ -1,8 +1,16 @@
+mod address_space;
+pub use address_space::{AddressSpace, MappingError, MemoryRegion, RegionKind};
 mod allocator;
+mod dma;
+pub use allocator::{heap_stats, slab_stats, HeapStats};
+pub use dma::DmaBuffer;
 mod frame_allocator;
 mod heap;
 mod memory_init;
 mod page_table;
-pub use frame_allocator::FullFrameAllocator;
+mod slab;
+pub use slab::SlabCacheStats;
+pub mod user_memory;
+pub use frame_allocator::{FrameAllocatorStats, FullFrameAllocator};
 pub use memory_init::init;
-pub use page_table::{create_mapping, MEMORY_MAPPER};
+pub use page_table::{create_mapping, physical_memory_offset, MEMORY_MAPPER};
@@ -0,0 +1,534 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use spin::Mutex;
+use utils::syscall_error::SysCallError;
+use x86_64::{
+    instructions::{interrupts::without_interrupts, tlb},
+    registers::control::Cr3,
+    structures::paging::{
+        mapper::{MapToError, MappedFrame, TranslateResult},
+        FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page, PageSize, PageTable,
+        PageTableFlags, PageTableIndex, PhysFrame, Size4KiB, Translate,
+    },
+    VirtAddr,
+};
+
+use super::{physical_memory_offset, FullFrameAllocator};
+
+/// The index of the level-3 entry mapping the kernel stack, the user space ends right before it.
+const KERNEL_STACK_LEVEL_3_INDEX: usize = 510;
+/// The index of the level-3 entry mapping the kernel data.
+const KERNEL_DATA_LEVEL_3_INDEX: usize = 511;
+/// A page table flag marking read-only pages that are copied when they are written to.
+pub const COPY_ON_WRITE: PageTableFlags = PageTableFlags::BIT_9;
+
+lazy_static::lazy_static! {
+    /// The number of address spaces mapping each frame shared by more than one,
+    /// the other user frames belong to a single address space.
+    ///
+    /// It's also locked by the page fault handler, so it **must** only be locked with interrupts disabled.
+    static ref SHARED_FRAMES: Mutex<BTreeMap<PhysFrame, usize>> = Mutex::new(BTreeMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingError {
+    /// There are no free frames left.
+    OutOfMemory,
+    /// The page is outside of the user part of the address space.
+    NotUserPage,
+    /// The page is not mapped.
+    NotMapped,
+    /// The address isn't part of a memory region or the region doesn't allow the access.
+    AccessViolation,
+}
+
+impl From<MapToError<Size4KiB>> for MappingError {
+    fn from(error: MapToError<Size4KiB>) -> Self {
+        match error {
+            MapToError::FrameAllocationFailed => MappingError::OutOfMemory,
+            // user pages are always 4 KiB, so this only happens outside of the user part
+            MapToError::ParentEntryHugePage | MapToError::PageAlreadyMapped(_) => {
+                MappingError::NotUserPage
+            }
+        }
+    }
+}
+
+impl From<MappingError> for SysCallError {
+    fn from(error: MappingError) -> Self {
+        match error {
+            MappingError::OutOfMemory => SysCallError::OutOfMemory,
+            _ => SysCallError::InvalidAddress,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// A segment of the program file.
+    Program,
+    /// The heap, moved with the `Brk` system call.
+    Heap,
+    /// The stack, growing downwards as it's used.
+    Stack,
+}
+
+/// A range of the user address space a process may use.
+///
+/// The pages of a region are only mapped when they are first accessed, except for the program
+/// segments, which are loaded with the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub start: VirtAddr,
+    pub end: VirtAddr,
+    /// The flags the pages of the region are mapped with.
+    pub flags: PageTableFlags,
+    pub kind: RegionKind,
+}
+
+/// The virtual address space of a user mode process, owning its page tables and user frames.
+///
+/// The first 510 GiB of the first level-4 entry belong to the process, the rest of the address
+/// space maps the kernel exactly like the kernel's page table does, so interrupt handlers and
+/// system calls can run with the page table of any process.
+/// All the user frames and page tables are freed when the address space is dropped.
+pub struct AddressSpace {
+    level_4_frame: PhysFrame,
+    allocator: FullFrameAllocator,
+    regions: Vec<MemoryRegion>,
+}
+
+impl AddressSpace {
+    /// Creates an address space that maps only the kernel.
+    pub fn new() -> Result<Self, MappingError> {
+        let mut allocator = FullFrameAllocator::get().ok_or(MappingError::OutOfMemory)?;
+        let level_4_frame = allocate_table(&mut allocator)?;
+        // from here on, dropping the address space frees the tables on error
+        let address_space = AddressSpace {
+            level_4_frame,
+            allocator,
+            regions: Vec::new(),
+        };
+        let level_3_frame = allocate_table(&mut allocator)?;
+
+        unsafe {
+            let kernel_level_4 = table(Cr3::read().0);
+            let level_4 = table(level_4_frame);
+            // The interrupt handlers run with the page table of the interrupted process,
+            // so the heap, the physical memory mapping and the rest of the kernel have to be mapped too
+            for (entry, kernel_entry) in level_4.iter_mut().zip(kernel_level_4.iter()).skip(1) {
+                if !kernel_entry.is_unused() {
+                    entry.set_addr(kernel_entry.addr(), kernel_entry.flags());
+                }
+            }
+            level_4[0].set_addr(
+                level_3_frame.start_address(),
+                PageTableFlags::PRESENT
+                    | PageTableFlags::WRITABLE
+                    | PageTableFlags::USER_ACCESSIBLE,
+            );
+
+            // Just take the mapping of the kernel stack and data from the bootloader's page tables
+            let kernel_level_3 = table(
+                kernel_level_4[0]
+                    .frame()
+                    .expect("The kernel is not mapped in the first level-4 entry"),
+            );
+            let level_3 = table(level_3_frame);
+            for index in [KERNEL_STACK_LEVEL_3_INDEX, KERNEL_DATA_LEVEL_3_INDEX] {
+                level_3[index].set_addr(
+                    kernel_level_3[index].addr(),
+                    PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+                );
+            }
+        }
+        Ok(address_space)
+    }
+
+    /// Returns the level-4 page table to load into CR3.
+    pub fn page_table(&self) -> PhysFrame {
+        self.level_4_frame
+    }
+
+    /// Adds a memory region, its pages are mapped when they are accessed.
+    pub fn add_region(&mut self, region: MemoryRegion) {
+        self.regions.push(region);
+    }
+
+    /// Returns the memory regions, in the order they were added.
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    /// Returns the first region of the passed kind.
+    pub fn region_mut(&mut self, kind: RegionKind) -> Option<&mut MemoryRegion> {
+        self.regions.iter_mut().find(|region| region.kind == kind)
+    }
+
+    /// Resolves a fault on the passed address, by mapping the page if it's part of a region
+    /// or by copying the page if it's a copy-on-write page that's written to.
+    ///
+    /// Returns `AccessViolation` if the access isn't allowed, the process then can't continue.
+    pub fn handle_page_fault(
+        &mut self,
+        address: VirtAddr,
+        write: bool,
+        execute: bool,
+    ) -> Result<(), MappingError> {
+        let region = *self
+            .regions
+            .iter()
+            .find(|region| region.start <= address && address < region.end)
+            .ok_or(MappingError::AccessViolation)?;
+        if (write && !region.flags.contains(PageTableFlags::WRITABLE))
+            || (execute && region.flags.contains(PageTableFlags::NO_EXECUTE))
+        {
+            return Err(MappingError::AccessViolation);
+        }
+
+        let page = Page::containing_address(address);
+        match self.translate(page) {
+            None => self.map_user_page(page, region.flags).map(|_| ()),
+            Some((frame, flags)) if write && !flags.contains(PageTableFlags::WRITABLE) => {
+                if flags.contains(COPY_ON_WRITE) {
+                    self.copy_on_write(page, frame, flags)
+                } else {
+                    Err(MappingError::AccessViolation)
+                }
+            }
+            // another access already resolved it
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Makes a copy-on-write page writable, copying its frame if other address spaces still use it.
+    fn copy_on_write(
+        &mut self,
+        page: Page<Size4KiB>,
+        frame: PhysFrame,
+        flags: PageTableFlags,
+    ) -> Result<(), MappingError> {
+        let flags = (flags - COPY_ON_WRITE) | PageTableFlags::WRITABLE;
+        let mut allocator = self.allocator;
+        let mut mapper = self.mapper();
+
+        if !is_shared(frame) {
+            unsafe {
+                mapper
+                    .update_flags(page, flags)
+                    .map_err(|_| MappingError::NotMapped)?
+                    .flush();
+            }
+            return Ok(());
+        }
+
+        let copy: PhysFrame = allocator
+            .allocate_frame()
+            .ok_or(MappingError::OutOfMemory)?;
+        unsafe {
+            frame_pointer(copy)
+                .copy_from_nonoverlapping(frame_pointer(frame), Size4KiB::SIZE as usize);
+            let (_, flush) = mapper.unmap(page).map_err(|_| MappingError::NotMapped)?;
+            flush.flush();
+            // the parent tables exist already
+            mapper.map_to(page, copy, flags, &mut allocator)?.flush();
+            release_frame(frame, &mut allocator);
+        }
+        Ok(())
+    }
+
+    /// Creates a copy of the address space for a forked process.
+    ///
+    /// Both address spaces share the frames, the writable pages become copy-on-write in both of them,
+    /// so a frame is only copied when one of the processes writes to it.
+    /// The address space has to be the loaded one, its TLB entries are flushed.
+    pub fn fork(&mut self) -> Result<AddressSpace, MappingError> {
+        let mut child = AddressSpace::new()?;
+        child.regions = self.regions.clone();
+
+        let result = unsafe { self.share_user_pages(&mut child) };
+        // some pages may have become read-only even if the copy failed
+        tlb::flush_all();
+        result.map(|_| child)
+    }
+
+    /// Maps all the user pages of the address space into the child, copy-on-write if they are writable.
+    ///
+    /// This function is unsafe because the TLB has to be flushed afterwards.
+    unsafe fn share_user_pages(&mut self, child: &mut AddressSpace) -> Result<(), MappingError> {
+        let level_3 = match table(self.level_4_frame)[0].frame() {
+            Ok(frame) => table(frame),
+            Err(_) => return Ok(()),
+        };
+        for (level_3_index, level_3_entry) in
+            level_3.iter().enumerate().take(KERNEL_STACK_LEVEL_3_INDEX)
+        {
+            let level_2 = match level_3_entry.frame() {
+                Ok(frame) => table(frame),
+                Err(_) => continue,
+            };
+            for (level_2_index, level_2_entry) in level_2.iter().enumerate() {
+                let level_1 = match level_2_entry.frame() {
+                    Ok(frame) => table(frame),
+                    Err(_) => continue,
+                };
+                for (level_1_index, entry) in level_1.iter_mut().enumerate() {
+                    let frame = match entry.frame() {
+                        Ok(frame) => frame,
+                        Err(_) => continue,
+                    };
+                    let mut flags = entry.flags();
+                    if flags.contains(PageTableFlags::WRITABLE) {
+                        flags = (flags - PageTableFlags::WRITABLE) | COPY_ON_WRITE;
+                        entry.set_flags(flags);
+                    }
+                    let page = Page::from_page_table_indices(
+                        PageTableIndex::new(0),
+                        PageTableIndex::new(level_3_index as u16),
+                        PageTableIndex::new(level_2_index as u16),
+                        PageTableIndex::new(level_1_index as u16),
+                    );
+                    child.map_shared_page(page, frame, flags)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps a user page to a frame that's mapped by another address space too.
+    fn map_shared_page(
+        &mut self,
+        page: Page<Size4KiB>,
+        frame: PhysFrame,
+        flags: PageTableFlags,
+    ) -> Result<(), MappingError> {
+        let mut allocator = self.allocator;
+        let parent_flags =
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+        share_frame(frame);
+        unsafe {
+            match self.mapper().map_to_with_table_flags(
+                page,
+                frame,
+                flags,
+                parent_flags,
+                &mut allocator,
+            ) {
+                // the address space isn't loaded yet
+                Ok(flush) => flush.ignore(),
+                Err(error) => {
+                    release_frame(frame, &mut allocator);
+                    return Err(error.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a mapper for the page tables of the address space.
+    fn mapper(&mut self) -> OffsetPageTable {
+        unsafe { OffsetPageTable::new(table(self.level_4_frame), physical_memory_offset()) }
+    }
+
+    /// Maps a user accessible page to a zeroed frame and returns the frame.
+    ///
+    /// If the page is already mapped, e.g. because two segments share it, the frame is kept
+    /// and the page gets the permissions of both mappings.
+    pub fn map_user_page(
+        &mut self,
+        page: Page<Size4KiB>,
+        flags: PageTableFlags,
+    ) -> Result<PhysFrame, MappingError> {
+        if !is_user_page(page) {
+            return Err(MappingError::NotUserPage);
+        }
+        let flags = flags | PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        let mut allocator = self.allocator;
+        let mut mapper = self.mapper();
+
+        if let TranslateResult::Mapped {
+            frame: MappedFrame::Size4KiB(frame),
+            flags: current_flags,
+            ..
+        } = mapper.translate(page.start_address())
+        {
+            let no_execute = current_flags & flags & PageTableFlags::NO_EXECUTE;
+            let merged_flags = ((current_flags | flags) - PageTableFlags::NO_EXECUTE) | no_execute;
+            unsafe {
+                mapper
+                    .update_flags(page, merged_flags)
+                    .map_err(|_| MappingError::NotUserPage)?
+                    .flush();
+            }
+            return Ok(frame);
+        }
+
+        let frame = allocator
+            .allocate_frame()
+            .ok_or(MappingError::OutOfMemory)?;
+        unsafe {
+            zero_frame(frame);
+            // the parent tables get all the permissions, the pages restrict them
+            let parent_flags = PageTableFlags::PRESENT
+                | PageTableFlags::WRITABLE
+                | PageTableFlags::USER_ACCESSIBLE;
+            match mapper.map_to_with_table_flags(page, frame, flags, parent_flags, &mut allocator) {
+                // the page isn't in use yet, so the TLB can't have a stale entry for it
+                Ok(flush) => flush.ignore(),
+                Err(error) => {
+                    allocator.deallocate_frame(frame);
+                    return Err(error.into());
+                }
+            }
+        }
+        Ok(frame)
+    }
+
+    /// Maps all the user pages between `start` and `end` with the passed flags,
+    /// the pages already mapped are kept as they are.
+    pub fn map_user_range(
+        &mut self,
+        start: VirtAddr,
+        end: VirtAddr,
+        flags: PageTableFlags,
+    ) -> Result<(), MappingError> {
+        if start >= end {
+            return Ok(());
+        }
+        let pages = Page::<Size4KiB>::range_inclusive(
+            Page::containing_address(start),
+            Page::containing_address(end - 1u64),
+        );
+        for page in pages {
+            self.map_user_page(page, flags)?;
+        }
+        Ok(())
+    }
+
+    /// Unmaps a user page and frees its frame.
+    pub fn unmap_user_page(&mut self, page: Page<Size4KiB>) -> Result<(), MappingError> {
+        if !is_user_page(page) {
+            return Err(MappingError::NotUserPage);
+        }
+        let mut allocator = self.allocator;
+        let (frame, flush) = self
+            .mapper()
+            .unmap(page)
+            .map_err(|_| MappingError::NotMapped)?;
+        flush.flush();
+        unsafe { release_frame(frame, &mut allocator) };
+        Ok(())
+    }
+
+    /// Returns the frame a user page is mapped to and the flags it's mapped with.
+    fn translate(&mut self, page: Page<Size4KiB>) -> Option<(PhysFrame, PageTableFlags)> {
+        match self.mapper().translate(page.start_address()) {
+            TranslateResult::Mapped {
+                frame: MappedFrame::Size4KiB(frame),
+                flags,
+                ..
+            } => Some((frame, flags)),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for AddressSpace {
+    /// Frees all the user frames and the page tables of the address space.
+    ///
+    /// The address space must not be loaded anymore.
+    fn drop(&mut self) {
+        debug_assert_ne!(Cr3::read().0, self.level_4_frame);
+        unsafe {
+            let level_4 = table(self.level_4_frame);
+            if let Ok(level_3_frame) = level_4[0].frame() {
+                let level_3 = table(level_3_frame);
+                // the kernel stack and data entries are shared with the kernel
+                for entry in level_3.iter().take(KERNEL_STACK_LEVEL_3_INDEX) {
+                    if let Ok(level_2_frame) = entry.frame() {
+                        free_table(level_2_frame, 2, &mut self.allocator);
+                    }
+                }
+                self.allocator.deallocate_frame(level_3_frame);
+            }
+            self.allocator.deallocate_frame(self.level_4_frame);
+        }
+    }
+}
+
+/// Returns whether the page belongs to the user part of the address space.
+fn is_user_page(page: Page<Size4KiB>) -> bool {
+    u16::from(page.p4_index()) == 0
+        && (u16::from(page.p3_index()) as usize) < KERNEL_STACK_LEVEL_3_INDEX
+}
+
+/// Frees a page table of the passed level, the tables it points to and the frames they map.
+unsafe fn free_table(frame: PhysFrame, level: u8, allocator: &mut FullFrameAllocator) {
+    for entry in table(frame).iter() {
+        // user pages are always 4 KiB, so there are no huge pages to free
+        if let Ok(child) = entry.frame() {
+            if level > 1 {
+                free_table(child, level - 1, allocator);
+            } else {
+                release_frame(child, allocator);
+            }
+        }
+    }
+    allocator.deallocate_frame(frame);
+}
+
+/// Runs the function on the locked reference counts with interrupts disabled.
+fn with_shared_frames<R>(function: impl FnOnce(&mut BTreeMap<PhysFrame, usize>) -> R) -> R {
+    without_interrupts(|| function(&mut SHARED_FRAMES.lock()))
+}
+
+/// Returns whether more than one address space maps the frame.
+fn is_shared(frame: PhysFrame) -> bool {
+    with_shared_frames(|shared_frames| shared_frames.contains_key(&frame))
+}
+
+/// Adds a reference to a user frame that gets mapped by one more address space.
+fn share_frame(frame: PhysFrame) {
+    // a frame that isn't shared yet has a single owner
+    with_shared_frames(|shared_frames| *shared_frames.entry(frame).or_insert(1) += 1);
+}
+
+/// Drops a reference to a user frame, freeing it if no other address space maps it.
+///
+/// This function is unsafe because the frame must not be used by the caller anymore.
+unsafe fn release_frame(frame: PhysFrame, allocator: &mut FullFrameAllocator) {
+    with_shared_frames(|shared_frames| match shared_frames.get_mut(&frame) {
+        Some(references) if *references > 2 => *references -= 1,
+        // the last remaining address space owns the frame alone
+        Some(_) => {
+            shared_frames.remove(&frame);
+        }
+        None => allocator.deallocate_frame(frame),
+    })
+}
+
+/// Allocates a frame for a page table and clears it.
+fn allocate_table(allocator: &mut FullFrameAllocator) -> Result<PhysFrame, MappingError> {
+    let frame = allocator
+        .allocate_frame()
+        .ok_or(MappingError::OutOfMemory)?;
+    unsafe { table(frame).zero() };
+    Ok(frame)
+}
+
+/// Fills the frame with zeros.
+unsafe fn zero_frame(frame: PhysFrame) {
+    frame_pointer(frame).write_bytes(0, Size4KiB::SIZE as usize);
+}
+
+/// Returns a pointer to the contents of the frame, through the physical memory mapping.
+fn frame_pointer(frame: PhysFrame) -> *mut u8 {
+    (physical_memory_offset() + frame.start_address().as_u64()).as_mut_ptr()
+}
+
+/// Returns the page table stored in the frame, accessed through the physical memory mapping.
+///
+/// This function is unsafe because the caller must guarantee that the frame holds a page table
+/// and that no other reference to it is alive.
+unsafe fn table<'a>(frame: PhysFrame) -> &'a mut PageTable {
+    &mut *(physical_memory_offset() + frame.start_address().as_u64()).as_mut_ptr()
+}
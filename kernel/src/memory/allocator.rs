@@ -1,5 +1,174 @@
-use linked_list_allocator::LockedHeap;
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{null_mut, NonNull},
+};
+use linked_list_allocator::Heap;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::{
+    heap::grow_heap,
+    slab::{size_class, SlabCache, SlabCacheStats, SIZE_CLASSES},
+};
 
 /// The global memory allocator
 #[global_allocator]
-pub static ALLOCATOR: LockedHeap = LockedHeap::empty();
+pub static ALLOCATOR: KernelHeap = KernelHeap::empty();
+
+/// The usage of the kernel heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    /// The number of bytes currently mapped for the heap.
+    pub size: usize,
+    /// The number of bytes currently allocated.
+    pub used: usize,
+    /// The number of bytes that can be allocated without growing the heap.
+    pub free: usize,
+    /// The number of allocations that weren't freed yet.
+    pub allocations: usize,
+    /// The number of allocations since boot.
+    pub total_allocations: u64,
+}
+
+/// Returns the usage of the kernel heap.
+pub fn heap_stats() -> HeapStats {
+    ALLOCATOR.stats()
+}
+
+/// Returns the usage of the slab caches, ordered by object size.
+pub fn slab_stats() -> [SlabCacheStats; SIZE_CLASSES.len()] {
+    ALLOCATOR.slab_stats()
+}
+
+/// A linked list heap that maps more memory when it's full.
+///
+/// Small allocations are served by slab caches of fixed size objects taken from the heap,
+/// which keeps the linked list short and avoids fragmenting it.
+///
+/// The lock is only held with interrupts disabled. Code holding the scheduler lock allocates with
+/// interrupts disabled, so a process preempted while holding the heap would block it forever.
+pub struct KernelHeap {
+    inner: Mutex<KernelHeapInner>,
+}
+
+struct KernelHeapInner {
+    heap: Heap,
+    caches: [SlabCache; SIZE_CLASSES.len()],
+    allocations: usize,
+    total_allocations: u64,
+}
+
+impl KernelHeapInner {
+    /// Allocates from the linked list heap, growing it if it's full.
+    fn allocate_from_heap(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if let Ok(pointer) = self.heap.allocate_first_fit(layout) {
+            return Some(pointer);
+        }
+        // the padding needed to align the allocation can be up to its alignment
+        let grown = grow_heap(self.heap.top(), layout.size() + layout.align())?;
+        unsafe { self.heap.extend(grown) };
+        self.heap.allocate_first_fit(layout).ok()
+    }
+
+    /// Allocates from the slab cache with the passed index, giving it a new slab if it's empty.
+    fn allocate_from_cache(&mut self, index: usize) -> Option<NonNull<u8>> {
+        if let Some(object) = self.caches[index].allocate() {
+            return Some(object);
+        }
+        let slab = self.allocate_from_heap(SlabCache::slab_layout())?;
+        unsafe { self.caches[index].add_slab(slab) };
+        self.caches[index].allocate()
+    }
+}
+
+impl KernelHeap {
+    /// Runs the function on the locked heap with interrupts disabled.
+    fn with_inner<R>(&self, function: impl FnOnce(&mut KernelHeapInner) -> R) -> R {
+        without_interrupts(|| function(&mut self.inner.lock()))
+    }
+
+    const fn empty() -> Self {
+        KernelHeap {
+            inner: Mutex::new(KernelHeapInner {
+                heap: Heap::empty(),
+                caches: [
+                    SlabCache::new(SIZE_CLASSES[0]),
+                    SlabCache::new(SIZE_CLASSES[1]),
+                    SlabCache::new(SIZE_CLASSES[2]),
+                    SlabCache::new(SIZE_CLASSES[3]),
+                    SlabCache::new(SIZE_CLASSES[4]),
+                    SlabCache::new(SIZE_CLASSES[5]),
+                    SlabCache::new(SIZE_CLASSES[6]),
+                    SlabCache::new(SIZE_CLASSES[7]),
+                ],
+                allocations: 0,
+                total_allocations: 0,
+            }),
+        }
+    }
+
+    /// Initializes the heap with the passed memory.
+    ///
+    /// This function is unsafe because the memory must be mapped and unused.
+    pub unsafe fn init(&self, start: usize, size: usize) {
+        self.with_inner(|inner| inner.heap.init(start, size));
+    }
+
+    /// Returns the usage of the slab caches, ordered by object size.
+    pub fn slab_stats(&self) -> [SlabCacheStats; SIZE_CLASSES.len()] {
+        let mut stats = [SlabCacheStats {
+            object_size: 0,
+            slabs: 0,
+            used_objects: 0,
+            free_objects: 0,
+            total_allocations: 0,
+        }; SIZE_CLASSES.len()];
+        self.with_inner(|inner| {
+            for (stats, cache) in stats.iter_mut().zip(inner.caches.iter()) {
+                *stats = cache.stats();
+            }
+        });
+        stats
+    }
+
+    /// Returns the usage of the heap, the slabs count as used memory.
+    pub fn stats(&self) -> HeapStats {
+        self.with_inner(|inner| HeapStats {
+            size: inner.heap.size(),
+            used: inner.heap.used(),
+            free: inner.heap.free(),
+            allocations: inner.allocations,
+            total_allocations: inner.total_allocations,
+        })
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.with_inner(|inner| {
+            let pointer = match size_class(&layout) {
+                Some(index) => inner.allocate_from_cache(index),
+                None => inner.allocate_from_heap(layout),
+            };
+            let pointer = match pointer {
+                Some(pointer) => pointer,
+                None => return null_mut(),
+            };
+            inner.allocations += 1;
+            inner.total_allocations += 1;
+            pointer.as_ptr()
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(pointer) = NonNull::new(ptr) {
+            self.with_inner(|inner| {
+                match size_class(&layout) {
+                    Some(index) => inner.caches[index].deallocate(pointer),
+                    None => inner.heap.deallocate(pointer, layout),
+                }
+                inner.allocations -= 1;
+            });
+        }
+    }
+}
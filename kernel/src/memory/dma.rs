@@ -0,0 +1,62 @@
+use core::slice;
+use x86_64::{
+    structures::paging::{PageSize, PhysFrame, Size4KiB},
+    PhysAddr,
+};
+
+use super::{frame_allocator::FullFrameAllocator, page_table::physical_memory_offset};
+
+/// The end of the memory devices with 32 bit addresses can reach.
+const DMA_LIMIT: u64 = 1 << 32;
+
+/// Physically contiguous memory below 4 GiB, for devices that access the memory directly.
+///
+/// The memory is zeroed when allocated and accessed by the kernel through the physical memory mapping.
+pub struct DmaBuffer {
+    start: PhysFrame<Size4KiB>,
+    frames: usize,
+}
+
+impl DmaBuffer {
+    /// Allocates a buffer of at least `size` bytes, rounded up to whole frames.
+    ///
+    /// Returns `None` if the frame allocator isn't initialized or no large enough free range is left.
+    pub fn new(size: usize) -> Option<Self> {
+        let frames = (size + Size4KiB::SIZE as usize - 1) / Size4KiB::SIZE as usize;
+        let start =
+            FullFrameAllocator::get()?.allocate_contiguous(frames, PhysAddr::new(DMA_LIMIT))?;
+        let mut buffer = DmaBuffer { start, frames };
+        buffer.as_mut_slice().fill(0);
+        Some(buffer)
+    }
+
+    /// Returns the physical address of the buffer, the one to pass to the device.
+    pub fn physical_address(&self) -> PhysAddr {
+        self.start.start_address()
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn size(&self) -> usize {
+        self.frames * Size4KiB::SIZE as usize
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.pointer(), self.size()) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.pointer(), self.size()) }
+    }
+
+    fn pointer(&self) -> *mut u8 {
+        (physical_memory_offset() + self.physical_address().as_u64()).as_mut_ptr()
+    }
+}
+
+impl Drop for DmaBuffer {
+    fn drop(&mut self) {
+        if let Some(mut allocator) = FullFrameAllocator::get() {
+            unsafe { allocator.deallocate_contiguous(self.start, self.frames) };
+        }
+    }
+}
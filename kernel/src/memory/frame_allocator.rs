@@ -1,70 +1,294 @@
 use bootloader::boot_info::{MemoryRegionKind, MemoryRegions};
+use core::slice;
 use spin::Mutex;
 use x86_64::{
-    structures::paging::{FrameAllocator, PageSize, PhysFrame},
+    instructions::interrupts::without_interrupts,
+    structures::paging::{
+        FrameAllocator, FrameDeallocator, PageSize, PhysFrame, Size2MiB, Size4KiB,
+    },
     PhysAddr,
 };
 
+use super::page_table::physical_memory_offset;
+
+/// The number of 4 KiB frames in a 2 MiB frame.
+const FRAMES_PER_HUGE_FRAME: usize = (Size2MiB::SIZE / Size4KiB::SIZE) as usize;
+/// The number of words of the bitmap covering a 2 MiB frame.
+const WORDS_PER_HUGE_FRAME: usize = FRAMES_PER_HUGE_FRAME / 64;
+
 /// A FrameAllocator that returns usable frames from the bootloader's memory map.
+///
+/// All the allocators share a single bitmap of the physical memory, so they can be copied freely.
+/// 4 KiB and 2 MiB frames are taken from the same pool and can be given back with `deallocate_frame`.
 #[derive(Clone, Copy)]
 #[repr(C)]
-pub struct FullFrameAllocator {
-    memory_map: &'static MemoryRegions,
+pub struct FullFrameAllocator;
+
+/// The usage of the physical memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameAllocatorStats {
+    /// The number of bytes of usable memory.
+    pub total_memory: u64,
+    /// The number of bytes currently allocated.
+    pub used_memory: u64,
+    /// The number of bytes that can still be allocated.
+    pub free_memory: u64,
+}
+
+/// Only locked with interrupts disabled through `with_bitmap`, like the kernel heap's lock.
+static FRAME_BITMAP: Mutex<Option<FrameBitmap>> = Mutex::new(None);
+
+/// Runs the function on the bitmap with interrupts disabled, returns `None` if it's not initialized.
+fn with_bitmap<R>(function: impl FnOnce(&mut FrameBitmap) -> R) -> Option<R> {
+    without_interrupts(|| FRAME_BITMAP.lock().as_mut().map(function))
+}
+
+/// A bitmap with a bit per 4 KiB frame of the physical memory, set if the frame isn't free.
+///
+/// The bitmap itself is stored in the first usable region that is large enough
+/// and accessed through the physical memory mapping.
+struct FrameBitmap {
+    words: &'static mut [u64],
+    /// The number of usable frames.
+    usable_frames: usize,
+    /// The number of usable frames currently allocated.
+    used_frames: usize,
+    /// The word where the search for a free 4 KiB frame starts.
+    next_word: usize,
 }
 
-static FRAME_NEXT: Mutex<usize> = Mutex::new(0);
-static FRAME_REGION: Mutex<usize> = Mutex::new(usize::MAX);
+impl FrameBitmap {
+    /// Builds the bitmap of the passed memory map, only the frames marked as `Usable` are free.
+    ///
+    /// This function is unsafe because the physical memory mapping has to be set up.
+    unsafe fn new(memory_map: &MemoryRegions) -> Self {
+        let usable_regions = || {
+            memory_map
+                .iter()
+                .filter(|region| region.kind == MemoryRegionKind::Usable)
+        };
+        let memory_end = usable_regions()
+            .map(|region| region.end)
+            .max()
+            .expect("No usable memory found");
+        // whole 2 MiB frames, so they can be searched word by word
+        let frame_count = memory_end as usize / Size4KiB::SIZE as usize;
+        let word_count = (frame_count + FRAMES_PER_HUGE_FRAME - 1) / FRAMES_PER_HUGE_FRAME
+            * WORDS_PER_HUGE_FRAME;
+        let bitmap_size = (word_count * 8) as u64;
+
+        let bitmap_start = usable_regions()
+            .map(|region| (align_up(region.start, Size4KiB::SIZE), region.end))
+            .find(|(start, end)| start + bitmap_size <= *end)
+            .expect("No usable region can hold the frame bitmap")
+            .0;
+        let words = slice::from_raw_parts_mut(
+            (physical_memory_offset() + bitmap_start).as_mut_ptr::<u64>(),
+            word_count,
+        );
+        words.fill(u64::MAX);
+
+        let mut bitmap = FrameBitmap {
+            words,
+            usable_frames: 0,
+            used_frames: 0,
+            next_word: 0,
+        };
+        for region in usable_regions() {
+            let start = align_up(region.start, Size4KiB::SIZE);
+            let end = region.end & !(Size4KiB::SIZE - 1);
+            if start < end {
+                bitmap.set_range(frame_index(start), frame_index(end), false);
+                bitmap.usable_frames += frame_index(end) - frame_index(start);
+            }
+        }
+        // the first frame is never handed out, so a physical address of 0 is never valid
+        if bitmap.words[0] & 1 == 0 {
+            bitmap.words[0] |= 1;
+            bitmap.usable_frames -= 1;
+        }
+        // the bitmap's own frames are never handed out
+        let bitmap_frames = align_up(bitmap_size, Size4KiB::SIZE) / Size4KiB::SIZE;
+        let bitmap_index = frame_index(bitmap_start);
+        bitmap.set_range(bitmap_index, bitmap_index + bitmap_frames as usize, true);
+        bitmap.used_frames += bitmap_frames as usize;
+        bitmap
+    }
+
+    fn set_range(&mut self, start: usize, end: usize, used: bool) {
+        for index in start..end {
+            if used {
+                self.words[index / 64] |= 1 << (index % 64);
+            } else {
+                self.words[index / 64] &= !(1 << (index % 64));
+            }
+        }
+    }
+
+    fn allocate_4kib(&mut self) -> Option<usize> {
+        let word_count = self.words.len();
+        let word_index = (0..word_count)
+            .map(|offset| (self.next_word + offset) % word_count)
+            .find(|&index| self.words[index] != u64::MAX)?;
+        let bit = (!self.words[word_index]).trailing_zeros() as usize;
+        self.words[word_index] |= 1 << bit;
+        self.used_frames += 1;
+        self.next_word = word_index;
+        Some(word_index * 64 + bit)
+    }
+
+    fn allocate_2mib(&mut self) -> Option<usize> {
+        let first_word = self
+            .words
+            .chunks_exact(WORDS_PER_HUGE_FRAME)
+            .position(|words| words.iter().all(|&word| word == 0))?
+            * WORDS_PER_HUGE_FRAME;
+        self.words[first_word..first_word + WORDS_PER_HUGE_FRAME].fill(u64::MAX);
+        self.used_frames += FRAMES_PER_HUGE_FRAME;
+        Some(first_word * 64)
+    }
+
+    /// Allocates `count` consecutive frames, all of them below the frame with the index `limit`.
+    fn allocate_contiguous(&mut self, count: usize, limit: usize) -> Option<usize> {
+        let limit = limit.min(self.words.len() * 64);
+        let mut start = 0;
+        for index in 0..limit {
+            if self.words[index / 64] & (1 << (index % 64)) != 0 {
+                start = index + 1;
+            } else if index + 1 - start == count {
+                self.set_range(start, index + 1, true);
+                self.used_frames += count;
+                return Some(start);
+            }
+        }
+        None
+    }
+
+    fn deallocate(&mut self, start: usize, count: usize) {
+        for index in start..start + count {
+            debug_assert!(
+                self.words[index / 64] & (1 << (index % 64)) != 0,
+                "Freeing a frame that isn't allocated"
+            );
+        }
+        self.set_range(start, start + count, false);
+        self.used_frames -= count;
+    }
+
+    fn stats(&self) -> FrameAllocatorStats {
+        let total_memory = self.usable_frames as u64 * Size4KiB::SIZE;
+        let used_memory = self.used_frames as u64 * Size4KiB::SIZE;
+        FrameAllocatorStats {
+            total_memory,
+            used_memory,
+            free_memory: total_memory - used_memory,
+        }
+    }
+}
 
 impl FullFrameAllocator {
     /// Create a FrameAllocator from the passed memory map.
     ///
     /// This function is unsafe because the caller must guarantee that the passed
     /// memory map is valid. The main requirement is that all frames that are marked
-    /// as `USABLE` in it are really unused.
+    /// as `USABLE` in it are really unused. The physical memory mapping has to be set up.
     pub unsafe fn init(memory_map: &'static MemoryRegions) -> Self {
-        let mut region_index = FRAME_REGION.lock();
-        if *region_index == usize::MAX {
-            *region_index = memory_map
-                .iter()
-                .enumerate()
-                .find(|(_, region)| region.kind == MemoryRegionKind::Usable)
-                .expect("No usable memory found")
-                .0;
+        without_interrupts(|| {
+            let mut bitmap = FRAME_BITMAP.lock();
+            if bitmap.is_none() {
+                *bitmap = Some(FrameBitmap::new(memory_map));
+            }
+        });
+        FullFrameAllocator
+    }
+
+    /// Returns an allocator sharing the state of the one created by `init`.
+    ///
+    /// Returns `None` if `init` wasn't called yet.
+    pub fn get() -> Option<Self> {
+        with_bitmap(|_| FullFrameAllocator)
+    }
+
+    /// Returns `count` physically consecutive 4 KiB frames ending below the passed address.
+    pub(crate) fn allocate_contiguous(
+        &mut self,
+        count: usize,
+        limit: PhysAddr,
+    ) -> Option<PhysFrame<Size4KiB>> {
+        if count == 0 {
+            return None;
         }
-        FullFrameAllocator { memory_map }
+        let index =
+            with_bitmap(|bitmap| bitmap.allocate_contiguous(count, frame_index(limit.as_u64())))??;
+        Some(frame_at(index))
+    }
+
+    /// Gives back frames returned by `allocate_contiguous`.
+    ///
+    /// The caller must guarantee that the frames are unused.
+    pub(crate) unsafe fn deallocate_contiguous(
+        &mut self,
+        start: PhysFrame<Size4KiB>,
+        count: usize,
+    ) {
+        with_bitmap(|bitmap| bitmap.deallocate(frame_index(start.start_address().as_u64()), count));
+    }
+
+    /// Returns the usage of the physical memory.
+    pub fn stats(&self) -> FrameAllocatorStats {
+        with_bitmap(|bitmap| bitmap.stats()).expect("The frame allocator is not initialized")
     }
 }
 
-unsafe impl<S> FrameAllocator<S> for FullFrameAllocator
-where
-    S: PageSize,
-{
-    /// Returns the next usable frame
-    fn allocate_frame(&mut self) -> Option<PhysFrame<S>> {
-        let mut next = FRAME_NEXT.lock();
-        let mut region_index = FRAME_REGION.lock();
-        // Get the current region
-        let mut region = self.memory_map.get(*region_index)?;
-        // If we don't have any more pages left in the region...
-        if region.end - region.start < ((*next as u64) + 1) * S::SIZE {
-            // Find the index of a next Usable region
-            *region_index = self
-                .memory_map
-                .iter()
-                .enumerate()
-                .filter(|(i, _)| i > &region_index)
-                .find(|(_, region)| region.kind == MemoryRegionKind::Usable)
-                .map(|(i, _)| i)?;
-            // Use the new region
-            *next = 0;
-            region = self.memory_map.get(*region_index)?;
-        }
-        // According to the Rust community, step_by+nth should be O(1)
-        let phys_frame = (region.start..region.end)
-            .step_by(S::SIZE as usize)
-            .nth(*next)
-            .map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))?;
-        *next += 1;
-        Some(phys_frame)
+unsafe impl FrameAllocator<Size4KiB> for FullFrameAllocator {
+    /// Returns a free 4 KiB frame
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        let index = with_bitmap(FrameBitmap::allocate_4kib)??;
+        Some(frame_at(index))
     }
 }
+
+unsafe impl FrameAllocator<Size2MiB> for FullFrameAllocator {
+    /// Returns a free 2 MiB aligned frame
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size2MiB>> {
+        let index = with_bitmap(FrameBitmap::allocate_2mib)??;
+        Some(frame_at(index))
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for FullFrameAllocator {
+    /// Gives the frame back, so it's returned by one of the next allocations.
+    ///
+    /// The caller must guarantee that the frame is unused.
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        with_bitmap(|bitmap| bitmap.deallocate(frame_index(frame.start_address().as_u64()), 1));
+    }
+}
+
+impl FrameDeallocator<Size2MiB> for FullFrameAllocator {
+    /// Gives the frame back, its 4 KiB parts can be allocated again too.
+    ///
+    /// The caller must guarantee that the frame is unused.
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size2MiB>) {
+        with_bitmap(|bitmap| {
+            bitmap.deallocate(
+                frame_index(frame.start_address().as_u64()),
+                FRAMES_PER_HUGE_FRAME,
+            )
+        });
+    }
+}
+
+/// Returns the index of the 4 KiB frame containing the physical address.
+fn frame_index(address: u64) -> usize {
+    (address / Size4KiB::SIZE) as usize
+}
+
+/// Returns the frame starting at the 4 KiB frame with the passed index.
+fn frame_at<S: PageSize>(index: usize) -> PhysFrame<S> {
+    PhysFrame::containing_address(PhysAddr::new(index as u64 * Size4KiB::SIZE))
+}
+
+fn align_up(address: u64, alignment: u64) -> u64 {
+    (address + alignment - 1) & !(alignment - 1)
+}
@@ -1,27 +1,65 @@
 /// Where the kernel heap starts
 const HEAP_START: usize = 0x_5555_AAAA_0000;
-/// Size of the kernel heap
+/// Initial size of the kernel heap
 const HEAP_SIZE: usize = 16 * 1024 * 1024; // 16 MiB
+/// Size the kernel heap can grow to, it has to stay within a single level-4 entry
+/// because the user address spaces copy the kernel's level-4 entries when they are created
+const HEAP_MAX_SIZE: usize = 1024 * 1024 * 1024; // 1 GiB
 
 use x86_64::{
     structures::paging::{
-        mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size2MiB,
+        mapper::MapToError, FrameAllocator, Mapper, Page, PageSize, PageTableFlags, Size2MiB,
     },
     VirtAddr,
 };
 
-use super::{allocator::ALLOCATOR, frame_allocator::FullFrameAllocator};
+use super::{allocator::ALLOCATOR, frame_allocator::FullFrameAllocator, page_table::MEMORY_MAPPER};
 
 /// maps the kernels heap memory area to physical addresses
 pub fn init_heap(
     mapper: &mut impl Mapper<Size2MiB>,
     frame_allocator: &mut FullFrameAllocator,
 ) -> Result<(), MapToError<Size2MiB>> {
+    let heap_start = VirtAddr::new(HEAP_START as u64);
+    map_heap_pages(heap_start, heap_start + HEAP_SIZE, mapper, frame_allocator)?;
+
+    unsafe {
+        ALLOCATOR.init(HEAP_START, HEAP_SIZE);
+    }
+
+    Ok(())
+}
+
+/// Maps the pages after the end of the heap so it can grow by at least `min_size` bytes.
+///
+/// Returns the number of bytes the heap can grow by, `None` if it can't grow anymore.
+pub(super) fn grow_heap(heap_end: usize, min_size: usize) -> Option<usize> {
+    // the page containing the end is already mapped
+    let mapped_end = VirtAddr::new(heap_end as u64).align_up(Size2MiB::SIZE);
+    let new_end = VirtAddr::new(heap_end.checked_add(min_size)? as u64).align_up(Size2MiB::SIZE);
+    if new_end.as_u64() > (HEAP_START + HEAP_MAX_SIZE) as u64 {
+        return None;
+    }
+
+    let mut frame_allocator = FullFrameAllocator::get()?;
+    let mut mapper = MEMORY_MAPPER.lock();
+    map_heap_pages(mapped_end, new_end, mapper.as_mut()?, &mut frame_allocator).ok()?;
+    Some((new_end.as_u64() as usize) - heap_end)
+}
+
+/// Maps the heap pages between `start` and `end` to newly allocated frames.
+fn map_heap_pages(
+    start: VirtAddr,
+    end: VirtAddr,
+    mapper: &mut impl Mapper<Size2MiB>,
+    frame_allocator: &mut FullFrameAllocator,
+) -> Result<(), MapToError<Size2MiB>> {
+    if start >= end {
+        return Ok(());
+    }
     let page_range = {
-        let heap_start = VirtAddr::new(HEAP_START as u64);
-        let heap_end = heap_start + HEAP_SIZE - 1u64;
-        let heap_start_page = Page::containing_address(heap_start);
-        let heap_end_page = Page::containing_address(heap_end);
+        let heap_start_page = Page::containing_address(start);
+        let heap_end_page = Page::containing_address(end - 1u64);
         Page::range_inclusive(heap_start_page, heap_end_page)
     };
 
@@ -33,10 +71,5 @@ pub fn init_heap(
         let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
         unsafe { mapper.map_to(page, frame, flags, frame_allocator)?.flush() };
     }
-
-    unsafe {
-        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
-    }
-
     Ok(())
 }
@@ -1,5 +1,7 @@
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
 use x86_64::registers::control::Cr3;
 use x86_64::structures::paging::{Mapper, Page, PageTableFlags, Size2MiB};
 use x86_64::{
@@ -12,9 +14,13 @@ use crate::structures::kernel_information::KernelInformation;
 use super::frame_allocator::FullFrameAllocator;
 
 lazy_static! {
+    /// Locked with interrupts disabled, the kernel heap locks it when it grows.
     pub static ref MEMORY_MAPPER: Mutex<Option<OffsetPageTable<'static>>> = Mutex::new(None);
 }
 
+/// The virtual address at which the complete physical memory is mapped.
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
 /// Initialize a new OffsetPageTable.
 ///
 /// This function is unsafe because the caller must guarantee that the
@@ -22,6 +28,7 @@ lazy_static! {
 /// `physical_memory_offset`. Also, this function must be only called once
 /// to avoid aliasing `&mut` references (which is undefined behavior).
 pub unsafe fn init(physical_memory_offset: VirtAddr) {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::Relaxed);
     let level_4_table = active_level_4_table(physical_memory_offset);
     let _ = MEMORY_MAPPER
         .lock()
@@ -44,6 +51,11 @@ unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut
     &mut *page_table_ptr // unsafe
 }
 
+/// Returns the virtual address at which the complete physical memory is mapped.
+pub fn physical_memory_offset() -> VirtAddr {
+    VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed))
+}
+
 /// Maps a given virtual page to a given physical address. If a physical address is not given, a frame will be allocated from the FrameAllocator.
 pub fn create_mapping(
     page: Page<Size2MiB>,
@@ -52,20 +64,22 @@ pub fn create_mapping(
     kernel_info: KernelInformation,
 ) {
     let mut frame_allocator = unsafe { FullFrameAllocator::init(kernel_info.memory_regions) };
-    let mut mapper = MEMORY_MAPPER.lock();
-    let frame = if let Some(address) = address {
-        PhysFrame::<Size2MiB>::containing_address(address)
-    } else {
-        frame_allocator
-            .allocate_frame()
-            .expect("No more frames available")
-    };
+    without_interrupts(|| {
+        let mut mapper = MEMORY_MAPPER.lock();
+        let frame = if let Some(address) = address {
+            PhysFrame::<Size2MiB>::containing_address(address)
+        } else {
+            frame_allocator
+                .allocate_frame()
+                .expect("No more frames available")
+        };
 
-    let map_to_result = unsafe {
-        mapper
-            .as_mut()
-            .unwrap()
-            .map_to(page, frame, flags, &mut frame_allocator)
-    };
-    map_to_result.expect("map_to failed").flush();
+        let map_to_result = unsafe {
+            mapper
+                .as_mut()
+                .unwrap()
+                .map_to(page, frame, flags, &mut frame_allocator)
+        };
+        map_to_result.expect("map_to failed").flush();
+    });
 }
@@ -0,0 +1,112 @@
+use core::{alloc::Layout, ptr::NonNull};
+
+/// The object sizes of the slab caches, the allocations are rounded up to the next one.
+pub const SIZE_CLASSES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+/// The size of the memory blocks the caches take from the heap and split into objects.
+const SLAB_SIZE: usize = 16 * 1024;
+
+/// The usage of a slab cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlabCacheStats {
+    /// The size of the objects of the cache.
+    pub object_size: usize,
+    /// The number of slabs taken from the heap.
+    pub slabs: usize,
+    /// The number of objects currently allocated.
+    pub used_objects: usize,
+    /// The number of objects ready to be allocated.
+    pub free_objects: usize,
+    /// The number of allocations since boot.
+    pub total_allocations: u64,
+}
+
+/// A free object, linking to the next free object of the cache.
+struct FreeObject {
+    next: Option<NonNull<FreeObject>>,
+}
+
+/// A cache of objects of a single size, carved out of slabs taken from the heap.
+///
+/// The slabs are never given back, freed objects are kept for the next allocations of the same size.
+pub struct SlabCache {
+    object_size: usize,
+    free_list: Option<NonNull<FreeObject>>,
+    slabs: usize,
+    used_objects: usize,
+    free_objects: usize,
+    total_allocations: u64,
+}
+
+// The objects of the cache are only accessed through the cache, which is behind the heap's lock.
+unsafe impl Send for SlabCache {}
+
+impl SlabCache {
+    pub const fn new(object_size: usize) -> Self {
+        SlabCache {
+            object_size,
+            free_list: None,
+            slabs: 0,
+            used_objects: 0,
+            free_objects: 0,
+            total_allocations: 0,
+        }
+    }
+
+    /// Returns the layout of the slabs, aligned so every object is aligned to its size.
+    pub fn slab_layout() -> Layout {
+        Layout::from_size_align(SLAB_SIZE, 4096).unwrap()
+    }
+
+    /// Takes an object from the free list, returns `None` if the cache needs a new slab.
+    pub fn allocate(&mut self) -> Option<NonNull<u8>> {
+        let object = self.free_list?;
+        self.free_list = unsafe { object.as_ref().next };
+        self.free_objects -= 1;
+        self.used_objects += 1;
+        self.total_allocations += 1;
+        Some(object.cast())
+    }
+
+    /// Gives an object back to the cache.
+    ///
+    /// This function is unsafe because the object must have been allocated from this cache.
+    pub unsafe fn deallocate(&mut self, object: NonNull<u8>) {
+        let mut object = object.cast::<FreeObject>();
+        object.as_mut().next = self.free_list;
+        self.free_list = Some(object);
+        self.free_objects += 1;
+        self.used_objects -= 1;
+    }
+
+    /// Splits a new slab into objects and adds them to the free list.
+    ///
+    /// This function is unsafe because the slab must be unused and have the layout of `slab_layout`.
+    pub unsafe fn add_slab(&mut self, slab: NonNull<u8>) {
+        for index in (0..SLAB_SIZE / self.object_size).rev() {
+            let mut object = NonNull::new_unchecked(slab.as_ptr().add(index * self.object_size))
+                .cast::<FreeObject>();
+            object.as_mut().next = self.free_list;
+            self.free_list = Some(object);
+            self.free_objects += 1;
+        }
+        self.slabs += 1;
+    }
+
+    pub fn stats(&self) -> SlabCacheStats {
+        SlabCacheStats {
+            object_size: self.object_size,
+            slabs: self.slabs,
+            used_objects: self.used_objects,
+            free_objects: self.free_objects,
+            total_allocations: self.total_allocations,
+        }
+    }
+}
+
+/// Returns the index of the cache serving the layout, `None` if it's too large for the caches.
+pub fn size_class(layout: &Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align());
+    SIZE_CLASSES
+        .iter()
+        .position(|&object_size| object_size >= size)
+}
@@ -0,0 +1,74 @@
+use core::{slice, str};
+
+use utils::syscall_error::SysCallError;
+use x86_64::{
+    structures::paging::{Page, Size4KiB},
+    VirtAddr,
+};
+
+use crate::{processes::scheduler::with_running_process, user_mode::USER_SPACE_END};
+
+/// Checks that the passed range is part of the running process' memory regions and allows the access.
+///
+/// The pages of the range that weren't accessed yet are mapped (or copied if they are copy-on-write
+/// and written to), so the kernel never faults on them.
+fn check_user_range(address: u64, length: u64, write: bool) -> Result<(), SysCallError> {
+    let end = address
+        .checked_add(length)
+        .ok_or(SysCallError::InvalidAddress)?;
+    if address == 0 || end > USER_SPACE_END {
+        return Err(SysCallError::InvalidAddress);
+    }
+    if length == 0 {
+        return Ok(());
+    }
+
+    let pages = Page::<Size4KiB>::range_inclusive(
+        Page::containing_address(VirtAddr::new(address)),
+        Page::containing_address(VirtAddr::new(end - 1)),
+    );
+    with_running_process(|process| {
+        pages
+            .into_iter()
+            .try_for_each(|page| {
+                process
+                    .address_space
+                    .handle_page_fault(page.start_address(), write, false)
+            })
+            .map_err(|_| SysCallError::InvalidAddress)
+    })
+    .ok_or(SysCallError::InvalidAddress)?
+}
+
+/// Returns the passed buffer of the running process' memory.
+///
+/// This function is unsafe because the slice is only valid as long as the process' page table
+/// is loaded and the memory stays mapped.
+pub unsafe fn user_slice<'a>(address: u64, length: u64) -> Result<&'a [u8], SysCallError> {
+    check_user_range(address, length, false)?;
+    if length == 0 {
+        return Ok(&[]);
+    }
+    Ok(slice::from_raw_parts(address as *const u8, length as usize))
+}
+
+/// Returns the passed buffer of the running process' memory for writing.
+///
+/// This function is unsafe for the same reasons as `user_slice`.
+pub unsafe fn user_slice_mut<'a>(address: u64, length: u64) -> Result<&'a mut [u8], SysCallError> {
+    check_user_range(address, length, true)?;
+    if length == 0 {
+        return Ok(&mut []);
+    }
+    Ok(slice::from_raw_parts_mut(
+        address as *mut u8,
+        length as usize,
+    ))
+}
+
+/// Returns the passed UTF-8 string of the running process' memory.
+///
+/// This function is unsafe for the same reasons as `user_slice`.
+pub unsafe fn user_str<'a>(address: u64, length: u64) -> Result<&'a str, SysCallError> {
+    str::from_utf8(user_slice(address, length)?).map_err(|_| SysCallError::InvalidArgument)
+}
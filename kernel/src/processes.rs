@@ -1,2 +1,20 @@
 mod registers_state;
 pub use registers_state::RegistersState;
+mod executables;
+mod interrupt_event;
+pub use executables::{register_executable_loader, ExecutableLoader};
+pub use interrupt_event::InterruptEvent;
+mod kernel_stack;
+pub(crate) use kernel_stack::KernelStack;
+mod kernel_thread;
+pub use kernel_thread::spawn_kernel_thread;
+pub(crate) use kernel_thread::{adopt_boot_context, spawn_idle_thread};
+mod process;
+pub use process::{Process, ProcessId, ProcessInfo, ProcessState};
+pub(crate) mod scheduler;
+mod sleep_mutex;
+pub use scheduler::{process, processes, running_process, sleep, ticks};
+pub use sleep_mutex::{SleepMutex, SleepMutexGuard};
+pub(crate) mod syscalls;
+mod wait_queue;
+pub use wait_queue::WaitQueue;
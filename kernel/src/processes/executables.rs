@@ -0,0 +1,30 @@
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::vfs;
+
+/// Looks up the executable at the passed path and returns its contents, `None` if it doesn't know it.
+pub type ExecutableLoader = fn(&str) -> Option<Vec<u8>>;
+
+lazy_static! {
+    static ref EXECUTABLE_LOADERS: Mutex<Vec<ExecutableLoader>> = Mutex::new(Vec::new());
+}
+
+/// Registers a source of the executables started with the `Exec` system call, e.g. a storage driver.
+pub fn register_executable_loader(loader: ExecutableLoader) {
+    EXECUTABLE_LOADERS.lock().push(loader);
+}
+
+/// Returns the contents of the executable at the passed path.
+///
+/// Files in the VFS come first, the loaders are asked in the order they were registered
+/// for paths the VFS has no file at.
+pub(crate) fn load_executable(path: &str) -> Option<Vec<u8>> {
+    if let Ok(executable) = vfs::read_file(path) {
+        return Some(executable);
+    }
+    // the loaders are copied, so they can take as long as they need
+    let loaders = EXECUTABLE_LOADERS.lock().clone();
+    loaders.iter().find_map(|loader| loader(path))
+}
@@ -0,0 +1,43 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::WaitQueue;
+
+/// An event signalled by an interrupt handler, which a driver waits for after starting an operation.
+///
+/// The event stays signalled until a waiter consumes it, so an interrupt arriving before the
+/// driver starts waiting isn't lost.
+pub struct InterruptEvent {
+    signalled: AtomicBool,
+    waiting: WaitQueue,
+}
+
+impl InterruptEvent {
+    pub const fn new() -> Self {
+        InterruptEvent {
+            signalled: AtomicBool::new(false),
+            waiting: WaitQueue::new(),
+        }
+    }
+
+    /// Signals the event and wakes up the processes waiting for it, called by the interrupt handler.
+    pub fn signal(&self) {
+        self.signalled.store(true, Ordering::Release);
+        self.waiting.wake_all();
+    }
+
+    /// Forgets an earlier signal, called before starting an operation that signals the event.
+    pub fn reset(&self) {
+        self.signalled.store(false, Ordering::Release);
+    }
+
+    /// Blocks the running process until the event is signalled and consumes the signal.
+    ///
+    /// Returns `false` if the event wasn't signalled within the passed number of timer ticks.
+    pub fn wait(&self, timeout: u64) -> bool {
+        self.waiting
+            .wait_for_timeout(timeout, || {
+                self.signalled.swap(false, Ordering::AcqRel).then(|| ())
+            })
+            .is_some()
+    }
+}
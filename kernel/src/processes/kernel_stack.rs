@@ -0,0 +1,54 @@
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use core::{mem::size_of, ptr::NonNull};
+
+use utils::constants::KIB;
+use x86_64::VirtAddr;
+
+use super::RegistersState;
+
+/// The size of the kernel stack of every process.
+const KERNEL_STACK_SIZE: usize = 16 * KIB as usize;
+
+/// The stack used while the kernel works on behalf of a process, e.g. in system calls.
+///
+/// Each process has its own, so a process can be preempted or blocked in the kernel without
+/// another process overwriting its state.
+pub(crate) struct KernelStack {
+    bottom: NonNull<u8>,
+}
+
+// The stack is only used by its process.
+unsafe impl Send for KernelStack {}
+
+impl KernelStack {
+    /// Allocates a new stack on the kernel heap, returns `None` if the heap is full.
+    pub(crate) fn new() -> Option<Self> {
+        let bottom = unsafe { alloc_zeroed(Self::layout()) };
+        NonNull::new(bottom).map(|bottom| KernelStack { bottom })
+    }
+
+    /// Returns the highest address of the stack because the stack grows downwards.
+    pub(crate) fn top(&self) -> VirtAddr {
+        VirtAddr::from_ptr(self.bottom.as_ptr()) + KERNEL_STACK_SIZE
+    }
+
+    /// Returns the user mode registers of the process, saved at the top of the stack.
+    ///
+    /// The system call entry saves them there, so this is only valid while the process is in a system call.
+    /// Writing to them changes where the process continues in user mode.
+    pub(crate) fn user_registers(&self) -> *mut RegistersState {
+        (self.top() - size_of::<RegistersState>()).as_mut_ptr()
+    }
+
+    fn layout() -> Layout {
+        Layout::from_size_align(KERNEL_STACK_SIZE, 16).unwrap()
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc(self.bottom.as_ptr(), Self::layout());
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use alloc::boxed::Box;
+use x86_64::{registers::rflags::RFlags, VirtAddr};
+
+use crate::{
+    interrupts::{yield_now, GDT},
+    memory::AddressSpace,
+};
+
+use super::{
+    scheduler::{self, exit_running_process},
+    KernelStack, Process, ProcessId, RegistersState,
+};
+
+/// The function run by a kernel thread.
+type ThreadFunction = Box<dyn FnOnce() + Send>;
+
+/// Starts a kernel thread running the passed function on its own kernel stack.
+///
+/// The thread is scheduled like the user mode processes, so it's preempted by the timer and can block
+/// in wait queues. It exits when the function returns.
+/// Returns `None` if there's not enough memory for the thread.
+pub fn spawn_kernel_thread(function: impl FnOnce() + Send + 'static) -> Option<ProcessId> {
+    let kernel_stack = KernelStack::new()?;
+    // the kernel is mapped in every address space, the thread just doesn't map any user pages
+    let address_space = AddressSpace::new().ok()?;
+    let function: Box<ThreadFunction> = Box::new(Box::new(function));
+
+    let mut registers = RegistersState::new(
+        VirtAddr::new(kernel_thread_entry as usize as u64),
+        RFlags::INTERRUPT_FLAG.bits(),
+        // the entry is entered as if it was called, with the return address on the stack
+        kernel_stack.top() - 8u64,
+    );
+    registers.rdi = Box::into_raw(function) as u64;
+    registers.cs = GDT.1.kernel_code_selector.0 as u64;
+    registers.ss = GDT.1.kernel_data_selector.0 as u64;
+
+    Some(scheduler::add_process(Process::new(
+        None,
+        registers,
+        address_space,
+        kernel_stack,
+        0,
+    )))
+}
+
+/// Turns the running boot context into a kernel thread, so the first switch away from it saves its
+/// context and it's resumed like any other process instead of being abandoned.
+///
+/// It must be called before the interrupts are enabled.
+pub(crate) fn adopt_boot_context() {
+    let kernel_stack = KernelStack::new().expect("Failed to create the boot kernel stack");
+    let address_space =
+        AddressSpace::new().expect("Failed to create the boot context's address space");
+    // the registers are saved by the first switch away from it
+    let registers = RegistersState::new(VirtAddr::zero(), 0, VirtAddr::zero());
+    scheduler::add_running_process(Process::new(
+        None,
+        registers,
+        address_space,
+        kernel_stack,
+        0,
+    ));
+}
+
+/// Starts a kernel thread that halts until the next interrupt, so there's always a process to switch to.
+///
+/// It yields after every interrupt, so a process woken up by an interrupt handler runs right away.
+pub(crate) fn spawn_idle_thread() {
+    spawn_kernel_thread(|| loop {
+        x86_64::instructions::hlt();
+        yield_now();
+    })
+    .expect("Failed to create the idle thread");
+}
+
+/// The first function of every kernel thread, it runs the thread's function and exits.
+extern "C" fn kernel_thread_entry(function: *mut ThreadFunction) -> ! {
+    let function = unsafe { Box::from_raw(function) };
+    function();
+    exit_running_process(0);
+}
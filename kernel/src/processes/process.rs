@@ -0,0 +1,122 @@
+use alloc::vec::Vec;
+
+use crate::{
+    memory::{AddressSpace, MemoryRegion},
+    vfs::FileTable,
+};
+
+use super::{KernelStack, RegistersState};
+
+/// The identifier of a process, unique for the whole lifetime of the kernel.
+pub type ProcessId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    /// The process waits in the ready queue for its time slice.
+    Ready,
+    /// The process is currently executing on the CPU.
+    Running,
+    /// The process waits in a wait queue or sleeps, it's queued again when it's woken up.
+    Blocked,
+    /// The process exited, it stays in the process table until its parent waits for it.
+    /// Processes without a parent are freed by the kernel.
+    Zombie,
+}
+
+/// A snapshot of a process, as returned by `processes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub id: ProcessId,
+    pub parent: Option<ProcessId>,
+    pub state: ProcessState,
+    pub exit_code: i32,
+    pub heap_start: u64,
+    pub program_break: u64,
+    /// The number of open file descriptors.
+    pub open_files: usize,
+    /// The memory regions of the address space, empty for kernel threads.
+    pub regions: Vec<MemoryRegion>,
+}
+
+impl ProcessInfo {
+    /// Creates an empty snapshot with room for the regions, to be filled by `Process::copy_info`.
+    pub(crate) fn with_capacity(regions: usize) -> Self {
+        ProcessInfo {
+            id: 0,
+            parent: None,
+            state: ProcessState::Ready,
+            exit_code: 0,
+            heap_start: 0,
+            program_break: 0,
+            open_files: 0,
+            regions: Vec::with_capacity(regions),
+        }
+    }
+}
+
+/// The process control block of a single user mode process or kernel thread.
+pub struct Process {
+    pub id: ProcessId,
+    /// The process that created this one, `None` if the kernel created it or the parent exited.
+    pub parent: Option<ProcessId>,
+    pub state: ProcessState,
+    /// The code passed to `Exit`, only meaningful once the process is a zombie.
+    pub exit_code: i32,
+    /// The registers of the process, saved when it was last interrupted.
+    pub(crate) registers: RegistersState,
+    /// The page tables of the process, freed with it.
+    pub(crate) address_space: AddressSpace,
+    /// The stack used by system calls and interrupts coming from the process.
+    pub(crate) kernel_stack: KernelStack,
+    /// The start of the heap, right after the program's segments.
+    pub(crate) heap_start: u64,
+    /// The end of the heap, moved with the `Brk` system call.
+    pub(crate) program_break: u64,
+    /// The files opened by the process, indexed by their file descriptors.
+    pub(crate) files: FileTable,
+}
+
+impl Process {
+    /// Creates a process that's ready to run, the scheduler assigns its ID when it's added.
+    pub(crate) fn new(
+        parent: Option<ProcessId>,
+        registers: RegistersState,
+        address_space: AddressSpace,
+        kernel_stack: KernelStack,
+        heap_start: u64,
+    ) -> Self {
+        Process {
+            id: 0,
+            parent,
+            state: ProcessState::Ready,
+            exit_code: 0,
+            registers,
+            address_space,
+            kernel_stack,
+            heap_start,
+            program_break: heap_start,
+            files: FileTable::new(),
+        }
+    }
+
+    /// Returns the number of memory regions, the room a snapshot needs for them.
+    pub(crate) fn region_count(&self) -> usize {
+        self.address_space.regions().len()
+    }
+
+    /// Copies a snapshot of the process into the info without allocating, so it can be taken with
+    /// the scheduler locked. The info must have room for `region_count` regions.
+    pub(crate) fn copy_info(&self, info: &mut ProcessInfo) {
+        let regions = self.address_space.regions();
+        debug_assert!(info.regions.capacity() >= regions.len());
+        info.id = self.id;
+        info.parent = self.parent;
+        info.state = self.state;
+        info.exit_code = self.exit_code;
+        info.heap_start = self.heap_start;
+        info.program_break = self.program_break;
+        info.open_files = self.files.open_files();
+        info.regions.clear();
+        info.regions.extend_from_slice(regions);
+    }
+}
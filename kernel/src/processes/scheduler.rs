@@ -0,0 +1,438 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    format,
+    vec::Vec,
+};
+use core::mem;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use utils::syscall_error::SysCallError;
+use x86_64::{
+    instructions::{hlt, interrupts, interrupts::without_interrupts},
+    registers::control::{Cr3, Cr3Flags},
+    structures::paging::PhysFrame,
+};
+
+use crate::{
+    debug,
+    interrupts::{set_kernel_stack, yield_now},
+    vfs::FileTable,
+};
+
+use super::{Process, ProcessId, ProcessInfo, ProcessState, RegistersState, WaitQueue};
+
+lazy_static! {
+    /// The scheduler of the OS.
+    ///
+    /// It is locked from inside the timer interrupt, so it **must** only be locked with interrupts disabled.
+    pub(crate) static ref SCHEDULER: Mutex<Scheduler> = Mutex::new(Scheduler::new());
+}
+
+/// The processes waiting for one of their children to exit.
+static CHILD_EXITED: WaitQueue = WaitQueue::new();
+
+/// A round-robin scheduler switching between user mode processes and kernel threads on every timer tick.
+pub(crate) struct Scheduler {
+    /// The process table.
+    processes: BTreeMap<ProcessId, Process>,
+    /// The processes waiting for their time slice, in the order they will run.
+    ready_queue: VecDeque<ProcessId>,
+    /// The process currently running, `None` before the boot context was adopted.
+    running: Option<ProcessId>,
+    next_id: ProcessId,
+    /// The number of timer ticks since the interrupts were enabled.
+    ticks: u64,
+    /// The sleeping processes and the tick they wake up at.
+    sleeping: Vec<(u64, ProcessId)>,
+}
+
+impl Scheduler {
+    fn new() -> Self {
+        Scheduler {
+            processes: BTreeMap::new(),
+            ready_queue: VecDeque::new(),
+            running: None,
+            next_id: 1,
+            ticks: 0,
+            sleeping: Vec::new(),
+        }
+    }
+
+    /// Adds a new process to the process table and queues it for execution.
+    pub(crate) fn add_process(&mut self, mut process: Process) -> ProcessId {
+        let id = self.next_id;
+        self.next_id += 1;
+        process.id = id;
+        self.processes.insert(id, process);
+        // a process is queued at most once, so queueing never allocates inside the timer interrupt
+        let missing = self.processes.len().saturating_sub(self.ready_queue.len());
+        self.ready_queue.reserve(missing);
+        self.ready_queue.push_back(id);
+        id
+    }
+
+    /// Adds the context calling this to the process table as the running process.
+    ///
+    /// Its registers are saved by the next switch, like the ones of a preempted process.
+    pub(crate) fn add_running_process(&mut self, mut process: Process) -> ProcessId {
+        let id = self.next_id;
+        self.next_id += 1;
+        process.id = id;
+        process.state = ProcessState::Running;
+        self.processes.insert(id, process);
+        let missing = self.processes.len().saturating_sub(self.ready_queue.len());
+        self.ready_queue.reserve(missing);
+        self.running = Some(id);
+        id
+    }
+
+    /// Returns the ID of the process currently running.
+    pub(crate) fn running_process(&self) -> Option<ProcessId> {
+        self.running
+    }
+
+    /// Returns the number of processes and the most regions one of them has, the size of a snapshot.
+    fn snapshot_size(&self) -> (usize, usize) {
+        let regions = self.processes.values().map(Process::region_count).max();
+        (self.processes.len(), regions.unwrap_or(0))
+    }
+
+    /// Returns the process control block of the process currently running.
+    pub(crate) fn running_process_mut(&mut self) -> Option<&mut Process> {
+        self.processes.get_mut(&self.running?)
+    }
+
+    /// Counts a timer tick, wakes up the processes whose sleep is over and switches to the next process.
+    pub(crate) fn tick(&mut self, state: &mut RegistersState) {
+        self.ticks += 1;
+        let ticks = self.ticks;
+        // nothing is allocated, the heap can't be used from interrupt handlers
+        while let Some(index) = self
+            .sleeping
+            .iter()
+            .position(|&(wake_tick, _)| wake_tick <= ticks)
+        {
+            let (_, id) = self.sleeping.swap_remove(index);
+            self.unblock(id);
+        }
+        self.schedule(state);
+    }
+
+    /// Saves the interrupted context and replaces it with the context of the next process in the ready queue.
+    ///
+    /// If there is no other process waiting, the interrupted context keeps running.
+    /// The boot context is adopted as a kernel thread before the interrupts are enabled, so every
+    /// interrupted context belongs to a process and is saved.
+    ///
+    /// The interrupted context may be in the middle of a system call, it is then resumed on
+    /// the kernel stack of its process. A process that exited or blocked isn't queued again, one
+    /// that was woken up before the switch away from it already is.
+    pub(crate) fn schedule(&mut self, state: &mut RegistersState) {
+        let next = match self.pop_ready() {
+            Some(next) => next,
+            None => return,
+        };
+
+        if let Some(id) = self.running.take() {
+            let process = self
+                .processes
+                .get_mut(&id)
+                .expect("Running process is not in the process table");
+            match process.state {
+                // a zombie stays in the table until it's removed outside of interrupts
+                ProcessState::Zombie => {}
+                ProcessState::Running => {
+                    process.registers = *state;
+                    process.state = ProcessState::Ready;
+                    self.ready_queue.push_back(id);
+                }
+                // a blocked process is queued again when it's woken up
+                ProcessState::Blocked | ProcessState::Ready => process.registers = *state,
+            }
+        }
+
+        let process = self
+            .processes
+            .get_mut(&next)
+            .expect("Ready process is not in the process table");
+        process.state = ProcessState::Running;
+        *state = process.registers;
+        self.running = Some(next);
+        unsafe {
+            switch_page_table(process.address_space.page_table());
+            set_kernel_stack(process.kernel_stack.top());
+        }
+    }
+
+    /// Returns the next process of the ready queue that is still ready to run.
+    ///
+    /// IDs of processes that were freed or aren't ready anymore are dropped.
+    fn pop_ready(&mut self) -> Option<ProcessId> {
+        while let Some(id) = self.ready_queue.pop_front() {
+            let ready = self
+                .processes
+                .get(&id)
+                .map_or(false, |process| process.state == ProcessState::Ready);
+            if ready {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    /// Marks the running process as blocked, it keeps running until the next switch and isn't queued again.
+    ///
+    /// Returns the ID of the process, `None` if no process is running.
+    pub(crate) fn block_running(&mut self) -> Option<ProcessId> {
+        let process = self.running_process_mut()?;
+        process.state = ProcessState::Blocked;
+        Some(process.id)
+    }
+
+    /// Returns the number of timer ticks since the interrupts were enabled.
+    pub(crate) fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Wakes up the passed process once the passed tick is reached, if it's blocked then.
+    pub(crate) fn wake_at(&mut self, tick: u64, id: ProcessId) {
+        if !self.sleeping.contains(&(tick, id)) {
+            self.sleeping.push((tick, id));
+        }
+    }
+
+    /// Drops the pending wake-ups of the process, e.g. the timeout of a wait that ended before it.
+    pub(crate) fn cancel_wake(&mut self, id: ProcessId) {
+        self.sleeping.retain(|&(_, sleeping)| sleeping != id);
+    }
+
+    /// Returns whether the process is blocked.
+    pub(crate) fn is_blocked(&self, id: ProcessId) -> bool {
+        self.processes
+            .get(&id)
+            .map_or(false, |process| process.state == ProcessState::Blocked)
+    }
+
+    /// Queues a blocked process again, the other processes are left as they are.
+    pub(crate) fn unblock(&mut self, id: ProcessId) {
+        if let Some(process) = self.processes.get_mut(&id) {
+            if process.state == ProcessState::Blocked {
+                process.state = ProcessState::Ready;
+                self.ready_queue.push_back(id);
+            }
+        }
+    }
+
+    /// Turns the running process into a zombie, its children lose their parent.
+    fn exit_running(&mut self, code: i32) -> Option<ProcessId> {
+        let id = self.running?;
+        for process in self.processes.values_mut() {
+            if process.parent == Some(id) {
+                process.parent = None;
+            }
+        }
+        let process = self.processes.get_mut(&id)?;
+        process.state = ProcessState::Zombie;
+        process.exit_code = code;
+        Some(id)
+    }
+
+    /// Removes the zombies without a parent that aren't running anymore from the process table.
+    fn remove_orphan_zombies(&mut self) -> Vec<Process> {
+        let running = self.running;
+        let orphans: Vec<ProcessId> = self
+            .processes
+            .values()
+            .filter(|process| {
+                process.state == ProcessState::Zombie
+                    && process.parent.is_none()
+                    && Some(process.id) != running
+            })
+            .map(|process| process.id)
+            .collect();
+        orphans
+            .iter()
+            .filter_map(|id| self.processes.remove(id))
+            .collect()
+    }
+
+    /// Removes a zombie child of the running process, `0` matches any child.
+    ///
+    /// Returns `Ok(None)` if the matching children are still alive.
+    fn remove_zombie_child(&mut self, id: ProcessId) -> Result<Option<Process>, SysCallError> {
+        let parent = self.running;
+        let mut children = self
+            .processes
+            .values()
+            .filter(|process| process.parent.is_some() && process.parent == parent)
+            .filter(|process| id == 0 || process.id == id)
+            .peekable();
+        if children.peek().is_none() {
+            return Err(SysCallError::NoChildProcess);
+        }
+        let zombie = children
+            .find(|process| process.state == ProcessState::Zombie)
+            .map(|process| process.id);
+        Ok(zombie.and_then(|zombie| self.processes.remove(&zombie)))
+    }
+}
+
+/// Loads the passed level-4 page table if it isn't already active.
+///
+/// This function is unsafe because the page table has to map the kernel the same way the current one does.
+pub(super) unsafe fn switch_page_table(page_table: PhysFrame) {
+    if Cr3::read().0 != page_table {
+        Cr3::write(page_table, Cr3Flags::empty());
+    }
+}
+
+/// Frees the zombies without a parent.
+///
+/// Freeing takes the locks of the heap and the frame allocator, so it can't happen in the
+/// timer interrupt, which might have interrupted their owner.
+pub(crate) fn reap_orphan_zombies() {
+    let orphans = without_interrupts(|| SCHEDULER.lock().remove_orphan_zombies());
+    drop(orphans);
+}
+
+/// Adds the context calling this to the scheduler as the running process, it must be called with
+/// interrupts disabled and before any other process was switched to.
+pub(crate) fn add_running_process(process: Process) -> ProcessId {
+    without_interrupts(|| {
+        let mut scheduler = SCHEDULER.lock();
+        assert!(scheduler.running.is_none(), "A process is already running");
+        scheduler.add_running_process(process)
+    })
+}
+
+/// Adds a new process to the scheduler, it will start running on one of the next timer ticks.
+pub(crate) fn add_process(process: Process) -> ProcessId {
+    reap_orphan_zombies();
+    without_interrupts(|| SCHEDULER.lock().add_process(process))
+}
+
+/// Waits until a child of the running process exited and frees it, `0` matches any child.
+///
+/// Returns the ID and the exit code of the child.
+pub(crate) fn wait_for_child(id: ProcessId) -> Result<(ProcessId, i32), SysCallError> {
+    let child = CHILD_EXITED.wait_for(|| SCHEDULER.lock().remove_zombie_child(id).transpose())?;
+    Ok((child.id, child.exit_code))
+}
+
+/// Blocks the running process for the passed number of timer ticks.
+///
+/// Without a running process, before the boot context was adopted, it halts until enough ticks passed instead.
+pub fn sleep(ticks: u64) {
+    let wake_tick = self::ticks() + ticks;
+    loop {
+        let blocked = without_interrupts(|| {
+            let mut scheduler = SCHEDULER.lock();
+            if scheduler.ticks >= wake_tick {
+                return None;
+            }
+            let id = scheduler.block_running();
+            if let Some(id) = id {
+                scheduler.wake_at(wake_tick, id);
+            }
+            Some(id.is_some())
+        });
+        match blocked {
+            None => return,
+            Some(true) => yield_now(),
+            Some(false) => hlt(),
+        }
+    }
+}
+
+/// Returns the number of timer ticks since the interrupts were enabled.
+pub fn ticks() -> u64 {
+    without_interrupts(|| SCHEDULER.lock().ticks)
+}
+
+/// Returns the ID of the process currently running, `None` before the boot context was adopted.
+pub fn running_process() -> Option<ProcessId> {
+    without_interrupts(|| SCHEDULER.lock().running_process())
+}
+
+/// Returns a snapshot of the processes in the process table, ordered by ID.
+pub fn processes() -> Vec<ProcessInfo> {
+    // the snapshot is allocated beforehand, the scheduler is only locked to copy into it
+    loop {
+        let (count, regions) = without_interrupts(|| SCHEDULER.lock().snapshot_size());
+        let mut infos: Vec<ProcessInfo> = (0..count)
+            .map(|_| ProcessInfo::with_capacity(regions))
+            .collect();
+        let copied = without_interrupts(|| {
+            let scheduler = SCHEDULER.lock();
+            let (new_count, new_regions) = scheduler.snapshot_size();
+            // processes were created or mapped more regions in between, the snapshot is too small
+            if new_count > count || new_regions > regions {
+                return None;
+            }
+            for (process, info) in scheduler.processes.values().zip(&mut infos) {
+                process.copy_info(info);
+            }
+            Some(new_count)
+        });
+        if let Some(count) = copied {
+            infos.truncate(count);
+            return infos;
+        }
+    }
+}
+
+/// Returns a snapshot of the process with the ID, `None` if it's not in the process table.
+pub fn process(id: ProcessId) -> Option<ProcessInfo> {
+    loop {
+        let regions = without_interrupts(|| {
+            SCHEDULER
+                .lock()
+                .processes
+                .get(&id)
+                .map(Process::region_count)
+        })?;
+        let mut info = ProcessInfo::with_capacity(regions);
+        let copied = without_interrupts(|| {
+            let scheduler = SCHEDULER.lock();
+            let process = scheduler.processes.get(&id)?;
+            if process.region_count() > regions {
+                return Some(false);
+            }
+            process.copy_info(&mut info);
+            Some(true)
+        })?;
+        if copied {
+            return Some(info);
+        }
+    }
+}
+
+/// Runs the passed function on the process control block of the running process.
+///
+/// Returns `None` before the boot context was adopted.
+pub(crate) fn with_running_process<T>(function: impl FnOnce(&mut Process) -> T) -> Option<T> {
+    without_interrupts(|| SCHEDULER.lock().running_process_mut().map(function))
+}
+
+/// Turns the running process into a zombie and waits for the scheduler to switch away from it.
+///
+/// It runs on the kernel stack of the process, so the process is only freed after the switch.
+/// The GS base must hold the per-CPU area, like it does in system calls.
+pub(crate) fn exit_running_process(code: i32) -> ! {
+    // the orphans that exited before are freed here, since this one can't free itself
+    reap_orphan_zombies();
+    // the open files are closed right away, not when the zombie is freed
+    let files = with_running_process(|process| mem::replace(&mut process.files, FileTable::new()));
+    drop(files);
+    if let Some(id) = without_interrupts(|| SCHEDULER.lock().exit_running(code)) {
+        debug::log(&format!("Process {} exited with code {}", id, code));
+    }
+    CHILD_EXITED.wake_all();
+
+    // the zombie is never switched back to, unless there's no other process to run yet
+    interrupts::enable();
+    loop {
+        yield_now();
+        hlt();
+    }
+}
@@ -0,0 +1,81 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::WaitQueue;
+
+/// A mutex whose waiters block instead of spinning, for data held across sleeping operations like disk I/O.
+///
+/// It can't be locked from interrupt handlers, since locking may block.
+pub struct SleepMutex<T: ?Sized> {
+    locked: AtomicBool,
+    waiting: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for SleepMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SleepMutex<T> {}
+
+impl<T> SleepMutex<T> {
+    pub const fn new(data: T) -> Self {
+        SleepMutex {
+            locked: AtomicBool::new(false),
+            waiting: WaitQueue::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> SleepMutex<T> {
+    /// Blocks the running process until the mutex is unlocked, then locks it.
+    pub fn lock(&self) -> SleepMutexGuard<T> {
+        self.waiting.wait_until(|| self.acquire());
+        SleepMutexGuard { mutex: self }
+    }
+
+    /// Locks the mutex if it's unlocked, without blocking.
+    pub fn try_lock(&self) -> Option<SleepMutexGuard<T>> {
+        self.acquire().then(|| SleepMutexGuard { mutex: self })
+    }
+
+    fn acquire(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+impl<T: Default> Default for SleepMutex<T> {
+    fn default() -> Self {
+        SleepMutex::new(T::default())
+    }
+}
+
+/// Gives access to the data of a locked `SleepMutex`, unlocking it when dropped.
+pub struct SleepMutexGuard<'a, T: ?Sized> {
+    mutex: &'a SleepMutex<T>,
+}
+
+impl<T: ?Sized> Deref for SleepMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SleepMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for SleepMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.locked.store(false, Ordering::Release);
+        // a woken process that finds it locked again just waits again
+        self.mutex.waiting.wake_one();
+    }
+}
@@ -0,0 +1,144 @@
+use core::{mem, mem::size_of};
+
+use utils::{syscall_error::SysCallError, syscall_name::SysCallName};
+use x86_64::{
+    structures::paging::{Page, PageSize, Size4KiB},
+    VirtAddr,
+};
+
+use crate::{
+    elf::ElfError,
+    interrupts::syscalls::register_syscall,
+    memory::{
+        user_memory::{user_slice_mut, user_str},
+        RegionKind,
+    },
+    user_mode::{load_program, USER_STACK_MAX_SIZE, USER_STACK_TOP},
+};
+
+use super::{
+    executables::load_executable,
+    running_process,
+    scheduler::{self, exit_running_process, switch_page_table, with_running_process},
+    KernelStack, Process,
+};
+
+/// Registers the system calls managing processes.
+pub(crate) fn register_syscalls() {
+    register_syscall(SysCallName::GetProcessId, get_process_id);
+    register_syscall(SysCallName::Exit, exit);
+    register_syscall(SysCallName::Brk, brk);
+    register_syscall(SysCallName::Fork, fork);
+    register_syscall(SysCallName::Exec, exec);
+    register_syscall(SysCallName::WaitPid, wait_pid);
+}
+
+/// Returns the ID of the running process.
+fn get_process_id(_: u64, _: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    running_process().ok_or(SysCallError::Unknown)
+}
+
+/// Ends the running process with the passed exit code.
+fn exit(code: u64, _: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    exit_running_process(code as i32)
+}
+
+/// Moves the end of the heap of the running process, `0` only returns the current end.
+///
+/// The heap pages are mapped when the process first accesses them, shrinking the heap unmaps
+/// the pages that are entirely above the new end.
+fn brk(address: u64, _: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    with_running_process(|process| {
+        if address == 0 {
+            return Ok(process.program_break);
+        }
+        if address < process.heap_start || address > USER_STACK_TOP - USER_STACK_MAX_SIZE {
+            return Err(SysCallError::InvalidArgument);
+        }
+        let old_end = VirtAddr::new(process.program_break);
+        let new_end = VirtAddr::new(address);
+        process
+            .address_space
+            .region_mut(RegionKind::Heap)
+            .ok_or(SysCallError::Unknown)?
+            .end = new_end;
+        if new_end < old_end {
+            let pages = Page::<Size4KiB>::range(
+                Page::containing_address(new_end.align_up(Size4KiB::SIZE)),
+                Page::containing_address(old_end.align_up(Size4KiB::SIZE)),
+            );
+            for page in pages {
+                // only the pages the process accessed are mapped
+                let _ = process.address_space.unmap_user_page(page);
+            }
+        }
+        process.program_break = address;
+        Ok(address)
+    })
+    .ok_or(SysCallError::Unknown)?
+}
+
+/// Creates a child process continuing from the same system call, with a copy-on-write copy of the memory.
+/// The child shares the open files of the parent.
+///
+/// Returns the ID of the child, the child itself returns `0`.
+fn fork(_: u64, _: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    let kernel_stack = KernelStack::new().ok_or(SysCallError::OutOfMemory)?;
+    let child = with_running_process(|parent| -> Result<Process, SysCallError> {
+        let address_space = parent.address_space.fork()?;
+        let mut registers = unsafe { *parent.kernel_stack.user_registers() };
+        registers.rax = 0;
+        let mut child = Process::new(
+            Some(parent.id),
+            registers,
+            address_space,
+            kernel_stack,
+            parent.heap_start,
+        );
+        child.program_break = parent.program_break;
+        child.files = parent.files.clone();
+        Ok(child)
+    })
+    .ok_or(SysCallError::Unknown)??;
+    Ok(scheduler::add_process(child))
+}
+
+/// Replaces the program of the running process with the executable at the passed path.
+///
+/// The old address space is freed, the process continues at the entry point of the new program.
+fn exec(path: u64, length: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    let path = unsafe { user_str(path, length)? };
+    let executable = load_executable(path).ok_or(SysCallError::NotFound)?;
+    let program = unsafe { load_program(&executable) }.map_err(|error| match error {
+        ElfError::OutOfMemory => SysCallError::OutOfMemory,
+        _ => SysCallError::InvalidExecutable,
+    })?;
+
+    let old_address_space = with_running_process(|process| unsafe {
+        switch_page_table(program.address_space.page_table());
+        // the system call returns to the entry point
+        *process.kernel_stack.user_registers() = program.registers;
+        process.heap_start = program.heap_start;
+        process.program_break = program.heap_start;
+        mem::replace(&mut process.address_space, program.address_space)
+    })
+    .ok_or(SysCallError::Unknown)?;
+    drop(old_address_space);
+    Ok(0)
+}
+
+/// Waits until the passed child process exited, `0` waits for any child.
+///
+/// Writes the exit code of the child to the passed pointer, if it's not null, and returns its ID.
+fn wait_pid(id: u64, exit_code: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    // checked before waiting, so the exit code of a reaped child is never lost
+    let buffer = match exit_code {
+        0 => None,
+        address => Some(unsafe { user_slice_mut(address, size_of::<i32>() as u64)? }),
+    };
+    let (child, code) = scheduler::wait_for_child(id)?;
+    if let Some(buffer) = buffer {
+        buffer.copy_from_slice(&code.to_ne_bytes());
+    }
+    Ok(child)
+}
@@ -0,0 +1,120 @@
+use alloc::vec::Vec;
+use spin::Mutex;
+use x86_64::instructions::{hlt, interrupts::without_interrupts};
+
+use crate::interrupts::yield_now;
+
+use super::{
+    scheduler::{ticks, SCHEDULER},
+    ProcessId,
+};
+
+/// A queue of processes blocked until an event happens, e.g. an interrupt of a device.
+///
+/// The waiting processes aren't scheduled until they are woken up, so nothing polls while they wait.
+/// Waking can happen from interrupt handlers, waiting can't.
+pub struct WaitQueue {
+    waiting: Mutex<Vec<ProcessId>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        WaitQueue {
+            waiting: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Blocks the running process until the passed function returns a value, and returns it.
+    ///
+    /// The function is called with interrupts disabled, once first and then again after every wake-up,
+    /// so a wake-up between the check and blocking isn't lost.
+    /// Without a running process, before the boot context was adopted, it halts until the next interrupt instead.
+    pub fn wait_for<T>(&self, condition: impl FnMut() -> Option<T>) -> T {
+        self.wait(None, condition)
+            .expect("Waiting without a timeout timed out")
+    }
+
+    /// Like `wait_for`, but gives up after the passed number of timer ticks and returns `None`.
+    pub fn wait_for_timeout<T>(
+        &self,
+        timeout: u64,
+        condition: impl FnMut() -> Option<T>,
+    ) -> Option<T> {
+        let deadline = ticks() + timeout;
+        self.wait(Some(deadline), condition)
+    }
+
+    fn wait<T>(
+        &self,
+        deadline: Option<u64>,
+        mut condition: impl FnMut() -> Option<T>,
+    ) -> Option<T> {
+        loop {
+            let blocked = without_interrupts(|| {
+                let value = condition();
+                let mut scheduler = SCHEDULER.lock();
+                let timed_out = deadline.map_or(false, |deadline| scheduler.ticks() >= deadline);
+                if value.is_some() || timed_out {
+                    // the queue and the timeout mustn't wake the process once it waits for something else
+                    if let Some(id) = scheduler.running_process() {
+                        self.waiting.lock().retain(|&waiting| waiting != id);
+                        scheduler.cancel_wake(id);
+                    }
+                    return Ok(value);
+                }
+                let id = scheduler.block_running();
+                if let Some(id) = id {
+                    let mut waiting = self.waiting.lock();
+                    if !waiting.contains(&id) {
+                        waiting.push(id);
+                    }
+                    if let Some(deadline) = deadline {
+                        scheduler.wake_at(deadline, id);
+                    }
+                }
+                Err(id.is_some())
+            });
+            match blocked {
+                Ok(value) => return value,
+                Err(true) => yield_now(),
+                Err(false) => hlt(),
+            }
+        }
+    }
+
+    /// Blocks the running process until the passed function returns `true`.
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        self.wait_for(|| condition().then(|| ()))
+    }
+
+    /// Wakes up the process that waits the longest, returns `false` if no process waits.
+    ///
+    /// Processes that were already woken up, e.g. by their timeout, are skipped, they check their
+    /// condition again anyway.
+    pub fn wake_one(&self) -> bool {
+        without_interrupts(|| {
+            let mut waiting = self.waiting.lock();
+            let mut scheduler = SCHEDULER.lock();
+            let index = waiting.iter().position(|&id| scheduler.is_blocked(id));
+            match index {
+                Some(index) => {
+                    scheduler.unblock(waiting.remove(index));
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    /// Wakes up all the waiting processes.
+    pub fn wake_all(&self) {
+        without_interrupts(|| {
+            // drained in place, the heap can't be used from interrupt handlers
+            let mut waiting = self.waiting.lock();
+            let mut scheduler = SCHEDULER.lock();
+            for id in waiting.drain(..) {
+                scheduler.unblock(id);
+            }
+        })
+    }
+}
@@ -0,0 +1,31 @@
+//! The process file system, describing the state of the kernel and its processes in text files,
+//! usually mounted at `/proc`.
+//!
+//! The files are generated whenever they are read, so they always show the current state and have
+//! a size of 0. Every process has a directory named after its ID, `self` links to the one of the
+//! reading process.
+mod file_system;
+pub use file_system::ProcFs;
+mod files;
+
+use alloc::vec::Vec;
+use bootloader::boot_info::MemoryRegion;
+use spin::Once;
+
+use crate::structures::kernel_information::KernelInformation;
+
+/// What the bootloader passed to the kernel, kept for the files describing it.
+struct BootInformation {
+    bootloader_version: [u16; 3],
+    memory_map: Vec<MemoryRegion>,
+}
+
+static BOOT_INFORMATION: Once<BootInformation> = Once::new();
+
+/// Keeps the bootloader's version and memory map, they are only passed at boot.
+pub(crate) fn init(kernel_info: &KernelInformation) {
+    BOOT_INFORMATION.call_once(|| BootInformation {
+        bootloader_version: kernel_info.bootloader_version,
+        memory_map: kernel_info.memory_regions.iter().copied().collect(),
+    });
+}
@@ -0,0 +1,221 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use crate::{
+    processes::{self, ProcessId, ProcessInfo},
+    vfs::{DirectoryEntry, FileStat, FileSystem, FileType, Inode, VfsError},
+};
+
+use super::files;
+
+const ROOT_INODE: u64 = 1;
+const FILE_PERMISSIONS: u16 = 0o444;
+const DIRECTORY_PERMISSIONS: u16 = 0o555;
+const SYMLINK_PERMISSIONS: u16 = 0o777;
+/// The link to the directory of the process reading it.
+const SELF_LINK: &str = "self";
+
+/// The files in the root directory, by name.
+static FILES: [(&str, fn() -> String); 7] = [
+    ("version", files::version),
+    ("meminfo", files::memory_info),
+    ("memmap", files::memory_map),
+    ("slabinfo", files::slab_info),
+    ("interrupts", files::interrupts),
+    ("drivers", files::drivers_list),
+    ("mounts", files::mounts),
+];
+
+/// The files in the directory of every process, by name.
+static PROCESS_FILES: [(&str, fn(&ProcessInfo) -> String); 2] = [
+    ("status", files::process_status),
+    ("maps", files::process_maps),
+];
+
+/// The process file system, generating its files from the state of the kernel when they are read.
+///
+/// All mounts show the same files, files can't be created, written or removed.
+#[derive(Default)]
+pub struct ProcFs;
+
+impl ProcFs {
+    pub fn new() -> Self {
+        ProcFs
+    }
+}
+
+impl FileSystem for ProcFs {
+    fn name(&self) -> &str {
+        "procfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcInode::Root)
+    }
+}
+
+/// A file or directory, identified by what it describes.
+#[derive(Clone, Copy)]
+enum ProcInode {
+    Root,
+    SelfLink,
+    /// A file in the root directory, by index into `FILES`.
+    File(usize),
+    ProcessDirectory(ProcessId),
+    /// A file in the directory of a process, by index into `PROCESS_FILES`.
+    ProcessFile(ProcessId, usize),
+}
+
+impl ProcInode {
+    /// Returns a unique number, the ones of a process's files are derived from its ID.
+    ///
+    /// The IDs are offset by one, so no number of a process collides with the root's ones.
+    fn number(self) -> u64 {
+        match self {
+            ProcInode::Root => ROOT_INODE,
+            ProcInode::SelfLink => ROOT_INODE + 1,
+            ProcInode::File(index) => ROOT_INODE + 2 + index as u64,
+            ProcInode::ProcessDirectory(id) => (id + 1) << 8,
+            ProcInode::ProcessFile(id, index) => ((id + 1) << 8) | (index as u64 + 1),
+        }
+    }
+
+    fn file_type(self) -> FileType {
+        match self {
+            ProcInode::Root | ProcInode::ProcessDirectory(_) => FileType::Directory,
+            ProcInode::SelfLink => FileType::Symlink,
+            ProcInode::File(_) | ProcInode::ProcessFile(..) => FileType::File,
+        }
+    }
+
+    fn entry(self, name: &str) -> DirectoryEntry {
+        DirectoryEntry {
+            name: name.to_string(),
+            inode: self.number(),
+            file_type: self.file_type(),
+        }
+    }
+
+    /// Generates the content of a file.
+    fn content(self) -> Result<String, VfsError> {
+        match self {
+            ProcInode::File(index) => Ok(FILES[index].1()),
+            ProcInode::ProcessFile(id, index) => {
+                let process = processes::process(id).ok_or(VfsError::NotFound)?;
+                Ok(PROCESS_FILES[index].1(&process))
+            }
+            ProcInode::Root | ProcInode::ProcessDirectory(_) => Err(VfsError::IsADirectory),
+            ProcInode::SelfLink => Err(VfsError::InvalidArgument),
+        }
+    }
+}
+
+impl Inode for ProcInode {
+    fn stat(&self) -> Result<FileStat, VfsError> {
+        let permissions = match self.file_type() {
+            FileType::Directory => DIRECTORY_PERMISSIONS,
+            FileType::Symlink => SYMLINK_PERMISSIONS,
+            _ => FILE_PERMISSIONS,
+        };
+        Ok(FileStat::new(
+            self.number(),
+            self.file_type(),
+            permissions,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        let content = self.content()?;
+        let data = content.as_bytes();
+        if offset >= data.len() as u64 {
+            return Ok(0);
+        }
+        let offset = offset as usize;
+        let length = buffer.len().min(data.len() - offset);
+        buffer[..length].copy_from_slice(&data[offset..offset + length]);
+        Ok(length)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, VfsError> {
+        let inode = match *self {
+            ProcInode::Root => {
+                if name == SELF_LINK {
+                    ProcInode::SelfLink
+                } else if let Some(index) = FILES.iter().position(|(file, _)| *file == name) {
+                    ProcInode::File(index)
+                } else {
+                    let id = name.parse().map_err(|_| VfsError::NotFound)?;
+                    processes::process(id).ok_or(VfsError::NotFound)?;
+                    ProcInode::ProcessDirectory(id)
+                }
+            }
+            ProcInode::ProcessDirectory(id) => {
+                processes::process(id).ok_or(VfsError::NotFound)?;
+                let index = PROCESS_FILES
+                    .iter()
+                    .position(|(file, _)| *file == name)
+                    .ok_or(VfsError::NotFound)?;
+                ProcInode::ProcessFile(id, index)
+            }
+            _ => return Err(VfsError::NotADirectory),
+        };
+        Ok(Arc::new(inode))
+    }
+
+    fn cache_entries(&self) -> bool {
+        // processes come and go, and `self` differs between them
+        false
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirectoryEntry>, VfsError> {
+        match *self {
+            ProcInode::Root => {
+                let mut entries: Vec<DirectoryEntry> = FILES
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, _))| ProcInode::File(index).entry(name))
+                    .collect();
+                entries.push(ProcInode::SelfLink.entry(SELF_LINK));
+                entries.extend(processes::processes().iter().map(|process| {
+                    ProcInode::ProcessDirectory(process.id).entry(&process.id.to_string())
+                }));
+                Ok(entries)
+            }
+            ProcInode::ProcessDirectory(id) => {
+                processes::process(id).ok_or(VfsError::NotFound)?;
+                Ok(PROCESS_FILES
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, _))| ProcInode::ProcessFile(id, index).entry(name))
+                    .collect())
+            }
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn create(
+        &self,
+        _name: &str,
+        _file_type: FileType,
+        _permissions: u16,
+    ) -> Result<Arc<dyn Inode>, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    fn remove(&self, _name: &str) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    fn read_link(&self) -> Result<String, VfsError> {
+        match self {
+            ProcInode::SelfLink => processes::running_process()
+                .map(|id| id.to_string())
+                .ok_or(VfsError::NotFound),
+            _ => Err(VfsError::InvalidArgument),
+        }
+    }
+}
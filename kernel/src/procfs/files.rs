@@ -0,0 +1,150 @@
+//! The generators of the files, returning their whole content.
+use alloc::{format, string::String};
+use core::fmt::Write;
+use utils::format_size;
+use x86_64::structures::paging::PageTableFlags;
+
+use crate::{
+    debug, drivers, heap_stats, interrupt_counts,
+    memory::{FullFrameAllocator, RegionKind},
+    processes::ProcessInfo,
+    slab_stats, vfs,
+};
+
+use super::BOOT_INFORMATION;
+
+/// The kernel's version and the bootloader's.
+pub(super) fn version() -> String {
+    let mut content = format!("rost {}", env!("CARGO_PKG_VERSION"));
+    if let Some(boot) = BOOT_INFORMATION.get() {
+        let [major, minor, patch] = boot.bootloader_version;
+        let _ = write!(content, " (bootloader {}.{}.{})", major, minor, patch);
+    }
+    content.push('\n');
+    content
+}
+
+/// The usage of the physical memory and the kernel heap, sizes in KiB.
+pub(super) fn memory_info() -> String {
+    let mut content = String::new();
+    let mut line = |name: &str, value: u64, unit: &str| {
+        let _ = writeln!(content, "{:<22}{:>12}{}", format!("{}:", name), value, unit);
+    };
+    if let Some(frames) = FullFrameAllocator::get().map(|allocator| allocator.stats()) {
+        line("MemTotal", frames.total_memory / 1024, " kB");
+        line("MemUsed", frames.used_memory / 1024, " kB");
+        line("MemFree", frames.free_memory / 1024, " kB");
+    }
+    let heap = heap_stats();
+    line("HeapSize", heap.size as u64 / 1024, " kB");
+    line("HeapUsed", heap.used as u64 / 1024, " kB");
+    line("HeapFree", heap.free as u64 / 1024, " kB");
+    line("HeapAllocations", heap.allocations as u64, "");
+    line("HeapTotalAllocations", heap.total_allocations, "");
+    content
+}
+
+/// The physical memory map passed by the bootloader, a region per line.
+pub(super) fn memory_map() -> String {
+    let mut content = String::new();
+    if let Some(boot) = BOOT_INFORMATION.get() {
+        for region in &boot.memory_map {
+            let _ = writeln!(
+                content,
+                "{:016x}-{:016x} {:>8} {}",
+                region.start,
+                region.end,
+                format_size(region.end - region.start),
+                debug::decode_memory_kind(region.kind)
+            );
+        }
+    }
+    content
+}
+
+/// The usage of the slab caches of the kernel heap, a cache per line.
+pub(super) fn slab_info() -> String {
+    let mut content = String::from("size   slabs    used    free  allocations\n");
+    for cache in slab_stats() {
+        let _ = writeln!(
+            content,
+            "{:<6} {:>5} {:>7} {:>7} {:>12}",
+            cache.object_size,
+            cache.slabs,
+            cache.used_objects,
+            cache.free_objects,
+            cache.total_allocations
+        );
+    }
+    content
+}
+
+/// How often each handled interrupt occurred, by vector.
+pub(super) fn interrupts() -> String {
+    let mut content = String::new();
+    for interrupt in interrupt_counts() {
+        let _ = writeln!(
+            content,
+            "{:>3}: {:>12} {}",
+            interrupt.vector, interrupt.count, interrupt.name
+        );
+    }
+    content
+}
+
+/// The signatures of the initialized drivers, in hexadecimal.
+pub(super) fn drivers_list() -> String {
+    let mut content = String::new();
+    for driver in drivers() {
+        for byte in driver.signature {
+            let _ = write!(content, "{:02x}", byte);
+        }
+        content.push('\n');
+    }
+    content
+}
+
+/// The mounted file systems, in the order they were mounted.
+pub(super) fn mounts() -> String {
+    let mut content = String::new();
+    for mount in vfs::mounts() {
+        let _ = writeln!(content, "{} {}", mount.path, mount.file_system);
+    }
+    content
+}
+
+/// The state of the process, a field per line.
+pub(super) fn process_status(process: &ProcessInfo) -> String {
+    let mut content = String::new();
+    let _ = writeln!(content, "Pid:\t{}", process.id);
+    let _ = writeln!(content, "PPid:\t{}", process.parent.unwrap_or(0));
+    let _ = writeln!(content, "State:\t{:?}", process.state);
+    let _ = writeln!(content, "ExitCode:\t{}", process.exit_code);
+    let _ = writeln!(content, "HeapStart:\t{:#x}", process.heap_start);
+    let _ = writeln!(content, "ProgramBreak:\t{:#x}", process.program_break);
+    let _ = writeln!(content, "OpenFiles:\t{}", process.open_files);
+    content
+}
+
+/// The memory regions of the process with their access rights, a region per line.
+pub(super) fn process_maps(process: &ProcessInfo) -> String {
+    let mut content = String::new();
+    for region in &process.regions {
+        let writable = region.flags.contains(PageTableFlags::WRITABLE);
+        let executable = !region.flags.contains(PageTableFlags::NO_EXECUTE);
+        let _ = writeln!(
+            content,
+            "{:016x}-{:016x} r{}{} {}",
+            region.start.as_u64(),
+            region.end.as_u64(),
+            if writable { 'w' } else { '-' },
+            if executable { 'x' } else { '-' },
+            match region.kind {
+                RegionKind::Program => "program",
+                RegionKind::Heap => "heap",
+                RegionKind::Stack => "stack",
+            }
+        );
+    }
+    content
+}
@@ -1,138 +1,176 @@
-use core::arch::asm;
+use core::cmp::{max, min};
 
-use utils::constants::MIB;
+use utils::constants::{KIB, MIB};
 use x86_64::{
-    structures::paging::{
-        FrameAllocator, PageTable, PageTableFlags, PhysFrame, Size2MiB, Size4KiB,
-    },
-    PhysAddr, VirtAddr,
+    registers::rflags::RFlags,
+    structures::paging::{Page, PageSize, PageTableFlags, Size4KiB},
+    VirtAddr,
 };
 
 use crate::{
-    debug, interrupts::GDT, memory::FullFrameAllocator,
-    structures::kernel_information::KernelInformation,
+    debug,
+    elf::{ElfError, ElfFile, ElfType, ProgramHeader, ProgramHeaderFlags},
+    interrupts::GDT,
+    memory::{physical_memory_offset, AddressSpace, MemoryRegion, RegionKind},
+    processes::{scheduler, KernelStack, Process, ProcessId, RegistersState},
 };
 
-/// Initializes and returns the level-4 page table that maps memory for a user-mode process.
-unsafe fn get_user_mode_mapping(
-    pmo: u64,
-    allocator: &mut FullFrameAllocator,
-) -> Option<(PhysFrame, PhysAddr)> {
-    let level_4_frame: PhysFrame<Size4KiB> = allocator.allocate_frame()?;
-    let level_3_frame: PhysFrame<Size4KiB> = allocator.allocate_frame()?;
-    let level_2_frame: PhysFrame<Size4KiB> = allocator.allocate_frame()?;
-
-    let page_table_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
-    let user_page_table_flags = page_table_flags | PageTableFlags::USER_ACCESSIBLE;
-
-    let level_4_table_address = level_4_frame.start_address();
-    let level_3_table_address = level_3_frame.start_address();
-    let level_2_table_address = level_2_frame.start_address();
-    // Just take the mapping from the bootloader's page tables
-    let (level_2_kernel_data_table_address, level_2_kernel_stack_table_address) =
-        get_kernel_data_and_stack_level_2_table_addresses(pmo);
-
-    let level_4_table = (level_4_table_address.as_u64() + pmo) as *mut PageTable;
-    let level_4_table = level_4_table.as_mut().unwrap();
-    // Mapping 0x0000_0000_0000 to level 3 table
-    level_4_table[0].set_addr(level_3_table_address, user_page_table_flags);
-
-    let level_3_table = (level_3_table_address.as_u64() + pmo) as *mut PageTable;
-    let level_3_table = level_3_table.as_mut().unwrap();
-    // Mapping 0x0000_0000_0000 to level 2 table
-    level_3_table[0].set_addr(level_2_table_address, user_page_table_flags);
-    // Mapping 0x007F_8000_0000 to kernel stack
-    level_3_table[510].set_addr(level_2_kernel_stack_table_address, page_table_flags);
-    // Mapping 0x007F_C000_0000 to kernel data
-    level_3_table[511].set_addr(level_2_kernel_data_table_address, page_table_flags);
-
-    let level_2_table = (level_2_table_address.as_u64() + pmo) as *mut PageTable;
-    // Mapping level 2 entries to 2mb frames
-    let level_2_table = level_2_table.as_mut().unwrap();
-    level_2_table
-        .iter_mut()
-        .take(8) // We're mapping 16mb for now, e.g. 0x0100_0000
-        .for_each(|entry| {
-            let frame: PhysFrame<Size2MiB> = allocator
-                .allocate_frame()
-                .expect("Failed to allocate user process frame");
-            entry.set_addr(
-                frame.start_address(),
-                PageTableFlags::HUGE_PAGE | user_page_table_flags,
-            );
-        });
-
-    Some((level_4_frame, level_2_table[0].addr()))
+/// The end of the addresses available to user mode processes, the kernel stack and data are mapped right after it.
+pub const USER_SPACE_END: u64 = 0x007F_8000_0000;
+/// The top of the stack of a user mode process.
+pub const USER_STACK_TOP: u64 = 0x007F_0000_0000;
+/// The size of the stack mapped when a user mode process starts.
+pub const USER_STACK_SIZE: u64 = 64 * KIB;
+/// The size the stack of a user mode process can grow to, the pages below the mapped part
+/// are mapped when the process first touches them.
+pub const USER_STACK_MAX_SIZE: u64 = 8 * MIB;
+
+/// Maps a loadable segment with the page flags it asks for and copies its data from the file.
+///
+/// The bytes of the segment that are not stored in the file (e.g. `.bss`) are left zeroed.
+unsafe fn load_segment(
+    address_space: &mut AddressSpace,
+    elf: &ElfFile,
+    header: &ProgramHeader,
+) -> Result<(), ElfError> {
+    if header.memory_size == 0 {
+        return Ok(());
+    }
+    let start = header.virtual_address;
+    let end = start
+        .checked_add(header.memory_size)
+        .ok_or(ElfError::InvalidSegment)?;
+    // The segments may not overlap the stack or anything above it
+    if end > USER_STACK_TOP - USER_STACK_MAX_SIZE {
+        return Err(ElfError::InvalidSegment);
+    }
+    let data = elf.segment_data(header)?;
+
+    let mut flags = PageTableFlags::empty();
+    if header.flags.contains(ProgramHeaderFlags::WRITE) {
+        flags |= PageTableFlags::WRITABLE;
+    }
+    if !header.flags.contains(ProgramHeaderFlags::EXECUTE) {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+    address_space.add_region(MemoryRegion {
+        start: VirtAddr::new(start),
+        end: VirtAddr::new(end),
+        flags,
+        kind: RegionKind::Program,
+    });
+
+    let pages = Page::<Size4KiB>::range_inclusive(
+        Page::containing_address(VirtAddr::new(start)),
+        Page::containing_address(VirtAddr::new(end - 1)),
+    );
+    for page in pages {
+        let frame = address_space.map_user_page(page, flags)?;
+
+        // Copying the part of the file data that lands in this page
+        let page_start = page.start_address().as_u64();
+        let copy_start = max(page_start, start);
+        let copy_end = min(page_start + Size4KiB::SIZE, start + header.file_size);
+        if copy_start < copy_end {
+            let source = &data[(copy_start - start) as usize..(copy_end - start) as usize];
+            let destination = (physical_memory_offset() + frame.start_address().as_u64())
+                .as_mut_ptr::<u8>()
+                .add((copy_start - page_start) as usize);
+            destination.copy_from_nonoverlapping(source.as_ptr(), source.len());
+        }
+    }
+    Ok(())
 }
 
-unsafe fn get_kernel_data_and_stack_level_2_table_addresses(pmo: u64) -> (PhysAddr, PhysAddr) {
-    use x86_64::registers::control::Cr3;
-    let level4 = (Cr3::read().0.start_address().as_u64() + pmo) as *const PageTable;
-    let level4 = level4.as_ref().unwrap();
-
-    let level3 = ((level4[0].addr().as_u64() + pmo) as *const PageTable)
-        .as_ref()
-        .unwrap();
-    (level3[511].addr(), level3[510].addr())
+/// A program loaded into a new address space, ready to be run by a process.
+pub(crate) struct LoadedProgram {
+    pub address_space: AddressSpace,
+    /// The registers the program starts with.
+    pub registers: RegistersState,
+    /// The start of the heap, right after the program's segments.
+    pub heap_start: u64,
 }
 
-type UserModeFunction = extern "C" fn();
-
-/// Jumps to the passed function, entering the user mode (Ring 3)
-pub unsafe fn run_in_user_mode(
-    function: UserModeFunction,
-    kernel_info: &mut KernelInformation,
-) -> ! {
-    let function_pointer = function as *const () as *const u8;
-    debug::log("Creating user mode mapping");
-    let (user_page_map, user_physical_address) = get_user_mode_mapping(
-        kernel_info.physical_memory_offset,
-        &mut kernel_info.allocator,
-    )
-    .expect("Error while creating user mode mapping");
-
-    let user_mode_code_address = 0x1000u64;
-
-    let virtual_address = VirtAddr::new(
-        user_physical_address.as_u64()
-            + user_mode_code_address
-            + kernel_info.physical_memory_offset,
-    )
-    .as_mut_ptr::<u8>();
-    debug::log("Loading program");
+/// Loads the passed ELF executable into a new address space.
+pub(crate) unsafe fn load_program(program: &[u8]) -> Result<LoadedProgram, ElfError> {
+    let elf = ElfFile::parse(program)?;
+    if elf.header.elf_type != ElfType::Executable {
+        return Err(ElfError::UnsupportedType);
+    }
+    let entry_is_executable = elf.loadable_segments().any(|header| {
+        header.flags.contains(ProgramHeaderFlags::EXECUTE)
+            && (header.virtual_address..header.virtual_address.saturating_add(header.memory_size))
+                .contains(&elf.header.entry)
+    });
+    if !entry_is_executable {
+        return Err(ElfError::InvalidSegment);
+    }
+
+    debug::log("Creating user mode address space");
+    let mut address_space = AddressSpace::new()?;
 
-    // TODO: loading the user mode function from e.g. an ELF file
-    virtual_address.copy_from_nonoverlapping(function_pointer, 1024);
-
-    x86_64::instructions::interrupts::disable();
-
-    let virtual_address = user_mode_code_address as *mut u8;
-    let code_selector = ((GDT.1.user_code_selector.index() * 8) | 3) as u64;
-    let data_selector = ((GDT.1.user_data_selector.index() * 8) | 3) as u64;
-
-    debug::log("Moving to user mode");
-
-    asm!(
-        "mov cr3, r10",
-        "mov rax, 0",
-        "push rax", // aligning the stack
-        "push r14", // data selector
-        "push r12", // user mode stack pointer
-        "pushfq",
-        "pop rax",
-        "or eax, 0x200",
-        "and eax, 0xffffbfff",
-        "push rax", // eflags
-        "push r13", // code selector (ring 3 code with bottom 2 bits set for ring 3)
-        "push r15", // instruction address to return to
-        // TODO: Should probably zero all the registers before running user program to avoid data leaks
-        "iretq",
-        in("r10") (user_page_map.start_address().as_u64()),
-        // TODO: better user mode stack pointer
-        in("r12") (virtual_address.add(2 * MIB as usize - 4096)), // For now we only use the first 2MiB page
-        in("r13") (code_selector),
-        in("r14") (data_selector),
-        in("r15") (virtual_address),
-        options(noreturn)
+    debug::log("Loading program");
+    for header in elf.loadable_segments() {
+        load_segment(&mut address_space, &elf, header)?;
+    }
+    // The heap starts on the first page after the program, it grows with the `Brk` system call
+    let program_end = elf
+        .loadable_segments()
+        .map(|header| header.virtual_address + header.memory_size)
+        .max()
+        .unwrap_or(0);
+    let heap_start = VirtAddr::new(program_end).align_up(Size4KiB::SIZE);
+    let data_flags = PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+    address_space.add_region(MemoryRegion {
+        start: heap_start,
+        end: heap_start,
+        flags: data_flags,
+        kind: RegionKind::Heap,
+    });
+
+    address_space.add_region(MemoryRegion {
+        start: VirtAddr::new(USER_STACK_TOP - USER_STACK_MAX_SIZE),
+        end: VirtAddr::new(USER_STACK_TOP),
+        flags: data_flags,
+        kind: RegionKind::Stack,
+    });
+    address_space.map_user_range(
+        VirtAddr::new(USER_STACK_TOP - USER_STACK_SIZE),
+        VirtAddr::new(USER_STACK_TOP),
+        data_flags,
+    )?;
+
+    let mut registers = RegistersState::new(
+        VirtAddr::new(elf.header.entry),
+        RFlags::INTERRUPT_FLAG.bits(),
+        // The entry point is entered as if it was called, with the return address on the stack
+        VirtAddr::new(USER_STACK_TOP - 8),
     );
+    // ring 3 selectors have the bottom 2 bits set
+    registers.cs = ((GDT.1.user_code_selector.index() * 8) | 3) as u64;
+    registers.ss = ((GDT.1.user_data_selector.index() * 8) | 3) as u64;
+
+    Ok(LoadedProgram {
+        address_space,
+        registers,
+        heap_start: heap_start.as_u64(),
+    })
+}
+
+/// Loads the passed ELF executable into a new address space and hands the resulting
+/// user mode (Ring 3) process to the scheduler.
+///
+/// The process starts running on one of the next timer interrupts, it has no parent.
+pub unsafe fn run_in_user_mode(program: &[u8]) -> Result<ProcessId, ElfError> {
+    let program = load_program(program)?;
+    let kernel_stack = KernelStack::new().ok_or(ElfError::OutOfMemory)?;
+
+    debug::log("Queueing user mode process");
+    Ok(scheduler::add_process(Process::new(
+        None,
+        program.registers,
+        program.address_space,
+        kernel_stack,
+        program.heap_start,
+    )))
 }
@@ -0,0 +1,165 @@
+mod dentry;
+pub use dentry::Dentry;
+mod file;
+pub(crate) use file::FileTable;
+pub use file::OpenFile;
+mod inode;
+pub use inode::{DirectoryEntry, FileSystem, Inode};
+mod mount;
+pub use mount::{mount, mounts, sync, unmount, MountInfo};
+mod path;
+pub use path::{resolve, resolve_parent};
+pub(crate) mod syscalls;
+
+pub use utils::file::{FileStat, FileType, OpenFlags, SeekOrigin};
+
+use alloc::{sync::Arc, vec::Vec};
+use utils::syscall_error::SysCallError;
+
+use crate::BlockDeviceError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VfsError {
+    /// Nothing exists at the path.
+    NotFound,
+    /// Something already exists at the path.
+    AlreadyExists,
+    /// A component of the path is not a directory.
+    NotADirectory,
+    /// The operation doesn't work on directories.
+    IsADirectory,
+    /// The directory still has entries.
+    DirectoryNotEmpty,
+    /// The name can't be stored by the file system.
+    InvalidName,
+    /// An argument is not valid for the operation, e.g. a seek before the start of a file.
+    InvalidArgument,
+    /// Resolving the path followed more than `MAX_SYMLINKS` symlinks, or it ends in a symlink opened
+    /// with `NO_FOLLOW`.
+    TooManySymlinks,
+    /// The file system has no space left.
+    NoSpace,
+    /// The file would grow beyond the size the file system supports.
+    FileTooLarge,
+    /// The file system is mounted read-only.
+    ReadOnly,
+    /// The inode or file system doesn't implement the operation.
+    NotSupported,
+    /// The file descriptor isn't open, or wasn't opened for the access.
+    BadFileDescriptor,
+    /// The process has `MAX_OPEN_FILES` open files.
+    TooManyOpenFiles,
+    /// The directory is a mount point, or the file system has other file systems mounted in it.
+    Busy,
+    /// The device holding the file system failed.
+    IoError,
+}
+
+impl From<VfsError> for SysCallError {
+    fn from(error: VfsError) -> Self {
+        match error {
+            VfsError::NotFound => SysCallError::NotFound,
+            VfsError::AlreadyExists => SysCallError::AlreadyExists,
+            VfsError::NotADirectory => SysCallError::NotADirectory,
+            VfsError::IsADirectory => SysCallError::IsADirectory,
+            VfsError::DirectoryNotEmpty => SysCallError::DirectoryNotEmpty,
+            VfsError::InvalidName | VfsError::InvalidArgument | VfsError::Busy => {
+                SysCallError::InvalidArgument
+            }
+            VfsError::TooManySymlinks => SysCallError::TooManySymlinks,
+            VfsError::NoSpace => SysCallError::NoSpace,
+            VfsError::FileTooLarge => SysCallError::FileTooLarge,
+            VfsError::ReadOnly => SysCallError::ReadOnly,
+            VfsError::NotSupported => SysCallError::NotSupported,
+            VfsError::BadFileDescriptor => SysCallError::BadFileDescriptor,
+            VfsError::TooManyOpenFiles => SysCallError::TooManyOpenFiles,
+            VfsError::IoError => SysCallError::IoError,
+        }
+    }
+}
+
+impl From<BlockDeviceError> for VfsError {
+    fn from(_: BlockDeviceError) -> Self {
+        VfsError::IoError
+    }
+}
+
+/// The maximum number of symlinks followed while resolving a single path.
+pub const MAX_SYMLINKS: usize = 8;
+
+/// Opens the file or directory at the path, creating a regular file with the permissions if the flags ask for it.
+pub fn open(path: &str, flags: OpenFlags, permissions: u16) -> Result<Arc<OpenFile>, VfsError> {
+    let follow = !flags.contains(OpenFlags::NO_FOLLOW);
+    let dentry = match resolve(path, follow) {
+        Ok(dentry) => {
+            if flags.contains(OpenFlags::CREATE | OpenFlags::EXCLUSIVE) {
+                return Err(VfsError::AlreadyExists);
+            }
+            dentry
+        }
+        Err(VfsError::NotFound) if flags.contains(OpenFlags::CREATE) => {
+            let (parent, name) = resolve_parent(path)?;
+            parent.create(name, FileType::File, permissions)?
+        }
+        Err(error) => return Err(error),
+    };
+
+    match dentry.file_type() {
+        // only reached with `NO_FOLLOW`, a symlink itself can't be read or written
+        FileType::Symlink => return Err(VfsError::TooManySymlinks),
+        FileType::Directory
+            if flags.intersects(OpenFlags::WRITE | OpenFlags::TRUNCATE | OpenFlags::APPEND) =>
+        {
+            return Err(VfsError::IsADirectory)
+        }
+        FileType::Directory => {}
+        _ if flags.contains(OpenFlags::DIRECTORY) => return Err(VfsError::NotADirectory),
+        FileType::File if flags.contains(OpenFlags::TRUNCATE | OpenFlags::WRITE) => {
+            dentry.inode().truncate(0)?;
+        }
+        _ => {}
+    }
+    Ok(Arc::new(OpenFile::new(dentry, flags)))
+}
+
+/// Returns the metadata of the file at the path, following symlinks.
+pub fn stat(path: &str) -> Result<FileStat, VfsError> {
+    resolve(path, true)?.inode().stat()
+}
+
+/// Reads the whole file at the path.
+pub fn read_file(path: &str) -> Result<Vec<u8>, VfsError> {
+    let file = open(path, OpenFlags::READ, 0)?;
+    let mut data = Vec::new();
+    let mut buffer = [0; 512];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(data),
+            read => data.extend_from_slice(&buffer[..read]),
+        }
+    }
+}
+
+/// Returns the entries of the directory at the path, without `.` and `..`.
+pub fn read_dir(path: &str) -> Result<Vec<DirectoryEntry>, VfsError> {
+    resolve(path, true)?.inode().read_dir()
+}
+
+/// Creates a directory with the permissions at the path.
+pub fn create_directory(path: &str, permissions: u16) -> Result<(), VfsError> {
+    let (parent, name) = resolve_parent(path)?;
+    parent.create(name, FileType::Directory, permissions)?;
+    Ok(())
+}
+
+/// Creates a symlink pointing to `target` at the path.
+pub fn create_symlink(path: &str, target: &str) -> Result<(), VfsError> {
+    let (parent, name) = resolve_parent(path)?;
+    parent.create_symlink(name, target)
+}
+
+/// Removes the file, symlink or empty directory at the path.
+pub fn remove(path: &str) -> Result<(), VfsError> {
+    let (parent, name) = resolve_parent(path)?;
+    parent.remove(name)
+}
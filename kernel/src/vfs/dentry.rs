@@ -0,0 +1,155 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use spin::Mutex;
+
+use super::{FileType, Inode, VfsError};
+
+/// A directory entry, binding a name in its parent directory to an inode.
+///
+/// Dentries form the tree the paths are resolved in. A dentry keeps its parent alive, so `..`
+/// always leads back the way the path came, and a file system mounted on a directory is attached
+/// to the directory's dentry. The children are cached as long as something else holds them,
+/// e.g. an open file or the mount table, unless the directory's inode doesn't `cache_entries`.
+pub struct Dentry {
+    name: String,
+    inode: Arc<dyn Inode>,
+    file_type: FileType,
+    /// `None` for the root of the first mounted file system.
+    parent: Option<Arc<Dentry>>,
+    children: Mutex<BTreeMap<String, Weak<Dentry>>>,
+    /// The root of the file system mounted on this directory.
+    mounted: Mutex<Option<Arc<Dentry>>>,
+}
+
+impl Dentry {
+    pub(crate) fn new(
+        name: String,
+        inode: Arc<dyn Inode>,
+        parent: Option<Arc<Dentry>>,
+    ) -> Result<Arc<Self>, VfsError> {
+        let file_type = inode.stat()?.file_type;
+        Ok(Arc::new(Dentry {
+            name,
+            inode,
+            file_type,
+            parent,
+            children: Mutex::new(BTreeMap::new()),
+            mounted: Mutex::new(None),
+        }))
+    }
+
+    /// Returns the name in the parent directory, empty for the root.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        &self.inode
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// Returns the parent directory, the root of a mounted file system has the parent of the mount point.
+    pub fn parent(&self) -> Option<&Arc<Dentry>> {
+        self.parent.as_ref()
+    }
+
+    /// Returns the absolute path the dentry was reached by, without symlinks.
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        let mut current = self;
+        while let Some(parent) = &current.parent {
+            names.push(current.name.as_str());
+            current = parent;
+        }
+        if names.is_empty() {
+            return "/".to_string();
+        }
+        names.iter().rev().fold(String::new(), |mut path, name| {
+            path.push('/');
+            path.push_str(name);
+            path
+        })
+    }
+
+    /// Returns the root of the file system mounted last on the dentry, the dentry itself if there's none.
+    pub(crate) fn top_mount(self: Arc<Self>) -> Arc<Dentry> {
+        let mut current = self;
+        loop {
+            let mounted = current.mounted.lock().clone();
+            match mounted {
+                Some(root) => current = root,
+                None => return current,
+            }
+        }
+    }
+
+    pub(crate) fn set_mounted(&self, root: Option<Arc<Dentry>>) {
+        *self.mounted.lock() = root;
+    }
+
+    pub(crate) fn is_mount_point(&self) -> bool {
+        self.mounted.lock().is_some()
+    }
+
+    /// Returns the child with the name, crossing into a file system mounted on it.
+    pub(crate) fn lookup(self: &Arc<Self>, name: &str) -> Result<Arc<Dentry>, VfsError> {
+        if !self.inode.cache_entries() {
+            let inode = self.inode.lookup(name)?;
+            return Dentry::new(name.to_string(), inode, Some(self.clone()));
+        }
+        let cached = self.children.lock().get(name).and_then(Weak::upgrade);
+        let child = match cached {
+            Some(child) => child,
+            None => {
+                let inode = self.inode.lookup(name)?;
+                self.insert_child(Dentry::new(name.to_string(), inode, Some(self.clone()))?)
+            }
+        };
+        Ok(child.top_mount())
+    }
+
+    /// Creates a regular file or directory in the directory and returns its dentry.
+    pub(crate) fn create(
+        self: &Arc<Self>,
+        name: &str,
+        file_type: FileType,
+        permissions: u16,
+    ) -> Result<Arc<Dentry>, VfsError> {
+        let inode = self.inode.create(name, file_type, permissions)?;
+        Ok(self.insert_child(Dentry::new(name.to_string(), inode, Some(self.clone()))?))
+    }
+
+    pub(crate) fn create_symlink(&self, name: &str, target: &str) -> Result<(), VfsError> {
+        self.inode.create_symlink(name, target)
+    }
+
+    /// Removes the child from the directory, fails with `Busy` if a file system is mounted on it.
+    pub(crate) fn remove(&self, name: &str) -> Result<(), VfsError> {
+        let child = self.children.lock().get(name).and_then(Weak::upgrade);
+        if child.map_or(false, |child| child.is_mount_point()) {
+            return Err(VfsError::Busy);
+        }
+        // the lock isn't held while the file system removes the entry, which may sleep on disk I/O
+        self.inode.remove(name)?;
+        self.children.lock().remove(name);
+        Ok(())
+    }
+
+    /// Caches the child, if another lookup cached one for the same name first, that one is kept and returned.
+    fn insert_child(&self, child: Arc<Dentry>) -> Arc<Dentry> {
+        let mut children = self.children.lock();
+        if let Some(existing) = children.get(&child.name).and_then(Weak::upgrade) {
+            return existing;
+        }
+        children.retain(|_, cached| cached.strong_count() > 0);
+        children.insert(child.name.clone(), Arc::downgrade(&child));
+        child
+    }
+}
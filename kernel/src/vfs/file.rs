@@ -0,0 +1,166 @@
+use alloc::{sync::Arc, vec::Vec};
+use spin::Mutex;
+use utils::file::DirectoryRecord;
+
+use super::{Dentry, FileStat, FileType, OpenFlags, SeekOrigin, VfsError};
+
+/// The maximum number of files a process can have open at the same time.
+pub const MAX_OPEN_FILES: usize = 256;
+
+/// A file or directory opened with `open`.
+///
+/// The file descriptors referring to it, e.g. the copies made by `fork`, share its offset.
+pub struct OpenFile {
+    dentry: Arc<Dentry>,
+    flags: OpenFlags,
+    /// The position in bytes for files, the index of the next entry for directories.
+    offset: Mutex<u64>,
+}
+
+impl OpenFile {
+    pub(crate) fn new(dentry: Arc<Dentry>, flags: OpenFlags) -> Self {
+        OpenFile {
+            dentry,
+            flags,
+            offset: Mutex::new(0),
+        }
+    }
+
+    pub fn dentry(&self) -> &Arc<Dentry> {
+        &self.dentry
+    }
+
+    pub fn flags(&self) -> OpenFlags {
+        self.flags
+    }
+
+    pub fn stat(&self) -> Result<FileStat, VfsError> {
+        self.dentry.inode().stat()
+    }
+
+    /// Reads from the offset into the buffer and advances the offset, returns `0` at the end of the file.
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        if !self.flags.contains(OpenFlags::READ) {
+            return Err(VfsError::BadFileDescriptor);
+        }
+        if self.dentry.file_type() == FileType::Directory {
+            return Err(VfsError::IsADirectory);
+        }
+        // the lock isn't held while the file system works, it may wait for the device
+        let offset = *self.offset.lock();
+        let read = self.dentry.inode().read_at(offset, buffer)?;
+        *self.offset.lock() = offset + read as u64;
+        Ok(read)
+    }
+
+    /// Writes the data at the offset, or at the end with `APPEND`, and advances the offset past it.
+    pub fn write(&self, data: &[u8]) -> Result<usize, VfsError> {
+        if !self.flags.contains(OpenFlags::WRITE) {
+            return Err(VfsError::BadFileDescriptor);
+        }
+        let offset = if self.flags.contains(OpenFlags::APPEND) {
+            self.stat()?.size
+        } else {
+            *self.offset.lock()
+        };
+        let written = self.dentry.inode().write_at(offset, data)?;
+        *self.offset.lock() = offset + written as u64;
+        Ok(written)
+    }
+
+    /// Moves the offset, returns the new offset. It can be moved past the end but not before the start.
+    pub fn seek(&self, offset: i64, origin: SeekOrigin) -> Result<u64, VfsError> {
+        let base = match origin {
+            SeekOrigin::Start => 0,
+            SeekOrigin::Current => *self.offset.lock(),
+            SeekOrigin::End => self.stat()?.size,
+        };
+        let new_offset = (base as i64)
+            .checked_add(offset)
+            .filter(|offset| *offset >= 0)
+            .ok_or(VfsError::InvalidArgument)? as u64;
+        *self.offset.lock() = new_offset;
+        Ok(new_offset)
+    }
+
+    /// Sends the device specific command to the inode, usually a device, and returns its result.
+    pub fn ioctl(&self, command: u64, argument: u64) -> Result<u64, VfsError> {
+        self.dentry.inode().ioctl(command, argument)
+    }
+
+    /// Writes the directory's entries from the offset into the buffer as `DirectoryRecord`s and advances
+    /// the offset past them. Returns the number of bytes written, `0` once all entries were read.
+    pub fn read_dir(&self, buffer: &mut [u8]) -> Result<usize, VfsError> {
+        if self.dentry.file_type() != FileType::Directory {
+            return Err(VfsError::NotADirectory);
+        }
+        let entries = self.dentry.inode().read_dir()?;
+        let mut offset = self.offset.lock();
+        let mut written = 0;
+        for entry in entries.iter().skip(*offset as usize) {
+            let record = DirectoryRecord {
+                inode: entry.inode,
+                file_type: entry.file_type,
+                name: &entry.name,
+            };
+            match record.write_to(&mut buffer[written..]) {
+                Some(length) => {
+                    written += length;
+                    *offset += 1;
+                }
+                // the buffer must fit at least one entry, or the caller would think it's the end
+                None if written == 0 => return Err(VfsError::InvalidArgument),
+                None => break,
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// The open files of a process, indexed by their file descriptors.
+///
+/// Cloning the table, as `fork` does, shares the open files, so the copies share their offsets.
+#[derive(Clone)]
+pub(crate) struct FileTable {
+    files: Vec<Option<Arc<OpenFile>>>,
+}
+
+impl FileTable {
+    pub(crate) const fn new() -> Self {
+        FileTable { files: Vec::new() }
+    }
+
+    /// Adds the file with the lowest free descriptor, returns the descriptor.
+    pub(crate) fn insert(&mut self, file: Arc<OpenFile>) -> Result<u64, VfsError> {
+        if let Some(descriptor) = self.files.iter().position(Option::is_none) {
+            self.files[descriptor] = Some(file);
+            return Ok(descriptor as u64);
+        }
+        if self.files.len() >= MAX_OPEN_FILES {
+            return Err(VfsError::TooManyOpenFiles);
+        }
+        self.files.push(Some(file));
+        Ok(self.files.len() as u64 - 1)
+    }
+
+    /// Returns the number of open file descriptors.
+    pub(crate) fn open_files(&self) -> usize {
+        self.files.iter().filter(|file| file.is_some()).count()
+    }
+
+    pub(crate) fn get(&self, descriptor: u64) -> Result<Arc<OpenFile>, VfsError> {
+        self.files
+            .get(descriptor as usize)
+            .cloned()
+            .flatten()
+            .ok_or(VfsError::BadFileDescriptor)
+    }
+
+    /// Removes the descriptor and returns its file, which is closed once no descriptor refers to it anymore.
+    pub(crate) fn remove(&mut self, descriptor: u64) -> Result<Arc<OpenFile>, VfsError> {
+        self.files
+            .get_mut(descriptor as usize)
+            .and_then(Option::take)
+            .ok_or(VfsError::BadFileDescriptor)
+    }
+}
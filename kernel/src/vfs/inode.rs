@@ -0,0 +1,97 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use super::{FileStat, FileType, VfsError};
+
+/// A file, directory, symlink or device of a mounted file system.
+///
+/// File systems implement the operations their objects support, the others keep their default
+/// implementation returning an error. Names passed to the directory operations are single
+/// components, never `.`, `..` or containing a `/`, the VFS resolves paths itself.
+pub trait Inode: Send + Sync {
+    /// Returns the metadata, the type must stay the same for the lifetime of the inode.
+    fn stat(&self) -> Result<FileStat, VfsError>;
+
+    /// Reads the contents from `offset` into the buffer, returns the number of bytes read, `0` at the end.
+    fn read_at(&self, _offset: u64, _buffer: &mut [u8]) -> Result<usize, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Writes the data at `offset`, returns the number of bytes written.
+    fn write_at(&self, _offset: u64, _data: &[u8]) -> Result<usize, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Shrinks or extends the contents to `size` bytes.
+    fn truncate(&self, _size: u64) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Returns the inode of the directory's entry with the name.
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, VfsError> {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Returns whether lookups in the directory can be cached, `false` for directories whose entries
+    /// change without going through the VFS, like the devices of the devfs.
+    fn cache_entries(&self) -> bool {
+        true
+    }
+
+    /// Returns the entries of the directory, without `.` and `..`.
+    fn read_dir(&self) -> Result<Vec<DirectoryEntry>, VfsError> {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Creates a regular file or directory in the directory.
+    fn create(
+        &self,
+        _name: &str,
+        _file_type: FileType,
+        _permissions: u16,
+    ) -> Result<Arc<dyn Inode>, VfsError> {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Creates a symlink pointing to `target` in the directory.
+    fn create_symlink(&self, _name: &str, _target: &str) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Removes the file, symlink or empty directory from the directory.
+    fn remove(&self, _name: &str) -> Result<(), VfsError> {
+        Err(VfsError::NotADirectory)
+    }
+
+    /// Returns the target of the symlink.
+    fn read_link(&self) -> Result<String, VfsError> {
+        Err(VfsError::InvalidArgument)
+    }
+
+    /// Handles a device specific command, one of `utils::ioctl`'s constants.
+    fn ioctl(&self, _command: u64, _argument: u64) -> Result<u64, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// An entry of a directory, as returned by `Inode::read_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    /// The inode number, 0 if the file system has none.
+    pub inode: u64,
+    pub file_type: FileType,
+}
+
+/// A mounted or mountable file system.
+pub trait FileSystem: Send + Sync {
+    /// Returns the name of the file system type, e.g. `ext2`.
+    fn name(&self) -> &str;
+
+    /// Returns the root directory.
+    fn root(&self) -> Arc<dyn Inode>;
+
+    /// Writes cached data back to the device, called when the file system is unmounted.
+    fn sync(&self) -> Result<(), VfsError> {
+        Ok(())
+    }
+}
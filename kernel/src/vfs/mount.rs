@@ -0,0 +1,168 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use spin::Mutex;
+
+use crate::debug;
+
+use super::{resolve, Dentry, FileSystem, FileType, VfsError};
+
+/// A file system attached to the directory tree.
+struct Mount {
+    /// The absolute path of the mount point when it was mounted.
+    path: String,
+    /// The directory the file system is mounted on, `None` for the first root.
+    mount_point: Option<Arc<Dentry>>,
+    root: Arc<Dentry>,
+    file_system: Arc<dyn FileSystem>,
+}
+
+/// A mounted file system, as listed by `mounts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub path: String,
+    /// The name of the file system type.
+    pub file_system: String,
+}
+
+/// The mounted file systems, in the order they were mounted.
+static MOUNTS: Mutex<Vec<Mount>> = Mutex::new(Vec::new());
+
+/// The root of the first file system mounted on `/`.
+static ROOT: Mutex<Option<Arc<Dentry>>> = Mutex::new(None);
+
+/// Returns the root directory, the root of the file system mounted last on `/`.
+pub(crate) fn root() -> Result<Arc<Dentry>, VfsError> {
+    let root = ROOT.lock().clone().ok_or(VfsError::NotFound)?;
+    Ok(root.top_mount())
+}
+
+/// Mounts the file system on the directory at the path, hiding the directory's entries until it's unmounted.
+///
+/// The first file system must be mounted on `/`, later ones can be mounted on any directory,
+/// including `/` and other mount points.
+pub fn mount(path: &str, file_system: Arc<dyn FileSystem>) -> Result<(), VfsError> {
+    {
+        let mut root = ROOT.lock();
+        if root.is_none() {
+            if path.split('/').any(|component| !component.is_empty()) {
+                return Err(VfsError::NotFound);
+            }
+            let dentry = Dentry::new(String::new(), file_system.root(), None)?;
+            if dentry.file_type() != FileType::Directory {
+                return Err(VfsError::NotADirectory);
+            }
+            *root = Some(dentry.clone());
+            add_mount("/".to_string(), None, dentry, file_system);
+            return Ok(());
+        }
+    }
+
+    let mount_point = resolve(path, true)?;
+    if mount_point.file_type() != FileType::Directory {
+        return Err(VfsError::NotADirectory);
+    }
+    // the entries of uncached directories get new dentries on every lookup, the mount would be lost
+    if let Some(parent) = mount_point.parent() {
+        if !parent.inode().cache_entries() {
+            return Err(VfsError::NotSupported);
+        }
+    }
+    // the root takes the place of the mount point, so `..` leaves the file system
+    let root = Dentry::new(
+        mount_point.name().to_string(),
+        file_system.root(),
+        mount_point.parent().cloned(),
+    )?;
+    if root.file_type() != FileType::Directory {
+        return Err(VfsError::NotADirectory);
+    }
+    mount_point.set_mounted(Some(root.clone()));
+    add_mount(mount_point.path(), Some(mount_point), root, file_system);
+    Ok(())
+}
+
+fn add_mount(
+    path: String,
+    mount_point: Option<Arc<Dentry>>,
+    root: Arc<Dentry>,
+    file_system: Arc<dyn FileSystem>,
+) {
+    debug::log(&format!("Mounted {} on {}", file_system.name(), path));
+    MOUNTS.lock().push(Mount {
+        path,
+        mount_point,
+        root,
+        file_system,
+    });
+}
+
+/// Unmounts the file system mounted last on the path and writes its cached data back.
+///
+/// The first root can't be unmounted, nor can a file system that has others mounted in it.
+pub fn unmount(path: &str) -> Result<(), VfsError> {
+    let root = resolve(path, true)?;
+    let mount = {
+        let mut mounts = MOUNTS.lock();
+        let index = mounts
+            .iter()
+            .position(|mount| Arc::ptr_eq(&mount.root, &root))
+            .ok_or(VfsError::InvalidArgument)?;
+        let mount_point = mounts[index].mount_point.clone().ok_or(VfsError::Busy)?;
+        let has_nested_mounts = mounts
+            .iter()
+            .filter_map(|mount| mount.mount_point.as_ref())
+            .any(|other| is_inside(other, &root));
+        if has_nested_mounts {
+            return Err(VfsError::Busy);
+        }
+        mount_point.set_mounted(None);
+        mounts.remove(index)
+    };
+    mount.file_system.sync()
+}
+
+/// Returns whether the dentry is part of the file system with the root.
+fn is_inside(dentry: &Arc<Dentry>, root: &Arc<Dentry>) -> bool {
+    let mut current = Some(dentry);
+    while let Some(dentry) = current {
+        if Arc::ptr_eq(dentry, root) {
+            return true;
+        }
+        current = dentry.parent();
+    }
+    false
+}
+
+/// Returns the mounted file systems, in the order they were mounted.
+pub fn mounts() -> Vec<MountInfo> {
+    MOUNTS
+        .lock()
+        .iter()
+        .map(|mount| MountInfo {
+            path: mount.path.clone(),
+            file_system: mount.file_system.name().to_string(),
+        })
+        .collect()
+}
+
+/// Writes the cached data of all mounted file systems back, registered as a shutdown hook.
+pub fn sync() {
+    let file_systems: Vec<_> = MOUNTS
+        .lock()
+        .iter()
+        .map(|mount| mount.file_system.clone())
+        .collect();
+    for file_system in file_systems {
+        if let Err(error) = file_system.sync() {
+            debug::log(&format!(
+                "Syncing {} failed: {:?}",
+                file_system.name(),
+                error
+            ));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use alloc::sync::Arc;
+
+use super::{mount::root, Dentry, FileType, VfsError, MAX_SYMLINKS};
+
+/// Resolves the path to its dentry, relative paths start at the root.
+///
+/// `.` and `..` are resolved on the directory tree, so `..` of a mounted root leads to the directory
+/// containing the mount point. Symlinks are followed, the last component only if `follow_last` is set.
+pub fn resolve(path: &str, follow_last: bool) -> Result<Arc<Dentry>, VfsError> {
+    let mut symlinks = 0;
+    walk(root()?, path, follow_last, &mut symlinks)
+}
+
+/// Resolves all components of the path but the last one, which is returned with the directory containing it.
+pub fn resolve_parent(path: &str) -> Result<(Arc<Dentry>, &str), VfsError> {
+    let path = path.trim_end_matches('/');
+    let (directory, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(VfsError::InvalidName);
+    }
+    let parent = resolve(directory, true)?;
+    if parent.file_type() != FileType::Directory {
+        return Err(VfsError::NotADirectory);
+    }
+    Ok((parent, name))
+}
+
+/// Resolves the path starting at `start`, counting the followed symlinks across the nested walks.
+fn walk(
+    start: Arc<Dentry>,
+    path: &str,
+    follow_last: bool,
+    symlinks: &mut usize,
+) -> Result<Arc<Dentry>, VfsError> {
+    let mut current = if path.starts_with('/') {
+        root()?
+    } else {
+        start
+    };
+    let mut components = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .peekable();
+    while let Some(component) = components.next() {
+        let last = components.peek().is_none();
+        current = match component {
+            "." | ".." if current.file_type() != FileType::Directory => {
+                return Err(VfsError::NotADirectory)
+            }
+            "." => current,
+            ".." => match current.parent() {
+                Some(parent) => parent.clone().top_mount(),
+                None => current,
+            },
+            name => {
+                let child = current.lookup(name)?;
+                if child.file_type() == FileType::Symlink && (follow_last || !last) {
+                    *symlinks += 1;
+                    if *symlinks > MAX_SYMLINKS {
+                        return Err(VfsError::TooManySymlinks);
+                    }
+                    let target = child.inode().read_link()?;
+                    // relative targets start in the directory containing the symlink
+                    walk(current, &target, true, symlinks)?
+                } else {
+                    child
+                }
+            }
+        };
+    }
+    Ok(current)
+}
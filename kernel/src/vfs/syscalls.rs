@@ -0,0 +1,133 @@
+use alloc::sync::Arc;
+use core::{mem::size_of, ptr};
+
+use utils::{syscall_error::SysCallError, syscall_name::SysCallName};
+
+use crate::{
+    interrupts::syscalls::register_syscall,
+    memory::user_memory::{user_slice, user_slice_mut, user_str},
+    processes::scheduler::with_running_process,
+};
+
+use super::{FileStat, FileTable, OpenFile, OpenFlags, SeekOrigin};
+
+/// Registers the system calls working on files.
+pub(crate) fn register_syscalls() {
+    register_syscall(SysCallName::Open, open);
+    register_syscall(SysCallName::Read, read);
+    register_syscall(SysCallName::Write, write);
+    register_syscall(SysCallName::Seek, seek);
+    register_syscall(SysCallName::Close, close);
+    register_syscall(SysCallName::Stat, stat);
+    register_syscall(SysCallName::ReadDir, read_dir);
+    register_syscall(SysCallName::Ioctl, ioctl);
+}
+
+/// Calls the function with the file table of the running process.
+fn with_files<T>(function: impl FnOnce(&mut FileTable) -> T) -> Result<T, SysCallError> {
+    with_running_process(|process| function(&mut process.files)).ok_or(SysCallError::Unknown)
+}
+
+/// Returns the file the descriptor of the running process refers to.
+fn file(descriptor: u64) -> Result<Arc<OpenFile>, SysCallError> {
+    Ok(with_files(|files| files.get(descriptor))??)
+}
+
+/// Opens the file at the passed path and returns the lowest free file descriptor for it.
+fn open(
+    path: u64,
+    length: u64,
+    flags: u64,
+    permissions: u64,
+    _: u64,
+    _: u64,
+) -> Result<u64, SysCallError> {
+    let path = unsafe { user_str(path, length)? };
+    let flags = OpenFlags::from_bits(flags).ok_or(SysCallError::InvalidArgument)?;
+    let file = super::open(path, flags, permissions as u16)?;
+    Ok(with_files(|files| files.insert(file))??)
+}
+
+/// Reads from the file at its offset into the passed buffer, returns the number of bytes read.
+fn read(
+    descriptor: u64,
+    buffer: u64,
+    length: u64,
+    _: u64,
+    _: u64,
+    _: u64,
+) -> Result<u64, SysCallError> {
+    let file = file(descriptor)?;
+    let buffer = unsafe { user_slice_mut(buffer, length)? };
+    Ok(file.read(buffer)? as u64)
+}
+
+/// Writes the passed buffer to the file at its offset, returns the number of bytes written.
+fn write(
+    descriptor: u64,
+    buffer: u64,
+    length: u64,
+    _: u64,
+    _: u64,
+    _: u64,
+) -> Result<u64, SysCallError> {
+    let file = file(descriptor)?;
+    let data = unsafe { user_slice(buffer, length)? };
+    Ok(file.write(data)? as u64)
+}
+
+/// Moves the offset of the file, returns the new offset.
+fn seek(
+    descriptor: u64,
+    offset: u64,
+    origin: u64,
+    _: u64,
+    _: u64,
+    _: u64,
+) -> Result<u64, SysCallError> {
+    let origin = SeekOrigin::try_from(origin).map_err(|_| SysCallError::InvalidArgument)?;
+    Ok(file(descriptor)?.seek(offset as i64, origin)?)
+}
+
+/// Closes the file descriptor.
+fn close(descriptor: u64, _: u64, _: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    // the file is dropped outside of the scheduler lock
+    let file = with_files(|files| files.remove(descriptor))??;
+    drop(file);
+    Ok(0)
+}
+
+/// Writes the metadata of the file at the passed path to the passed `FileStat`.
+fn stat(path: u64, length: u64, stat: u64, _: u64, _: u64, _: u64) -> Result<u64, SysCallError> {
+    let path = unsafe { user_str(path, length)? };
+    let buffer = unsafe { user_slice_mut(stat, size_of::<FileStat>() as u64)? };
+    let metadata = super::stat(path)?;
+    unsafe { ptr::write_unaligned(buffer.as_mut_ptr() as *mut FileStat, metadata) };
+    Ok(0)
+}
+
+/// Writes the next entries of the open directory into the passed buffer, returns the number of bytes written.
+fn read_dir(
+    descriptor: u64,
+    buffer: u64,
+    length: u64,
+    _: u64,
+    _: u64,
+    _: u64,
+) -> Result<u64, SysCallError> {
+    let file = file(descriptor)?;
+    let buffer = unsafe { user_slice_mut(buffer, length)? };
+    Ok(file.read_dir(buffer)? as u64)
+}
+
+/// Sends the passed command and argument to the device behind the file, returns the result of the command.
+fn ioctl(
+    descriptor: u64,
+    command: u64,
+    argument: u64,
+    _: u64,
+    _: u64,
+    _: u64,
+) -> Result<u64, SysCallError> {
+    Ok(file(descriptor)?.ioctl(command, argument)?)
+}
@@ -0,0 +1,63 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cmp::max,
+    ptr::{null_mut, NonNull},
+};
+
+use linked_list_allocator::Heap;
+use spin::Mutex;
+
+use crate::syscalls::brk;
+
+/// The minimal number of bytes the heap grows by.
+const HEAP_GROWTH: usize = 64 * 1024;
+
+/// A linked list heap that grows with the `Brk` system call when it's full.
+struct ProgramHeap {
+    heap: Mutex<Heap>,
+}
+
+/// The global memory allocator
+#[global_allocator]
+static ALLOCATOR: ProgramHeap = ProgramHeap {
+    heap: Mutex::new(Heap::empty()),
+};
+
+impl ProgramHeap {
+    /// Moves the end of the heap so at least `size` more bytes are available.
+    ///
+    /// Returns `None` if the kernel has no memory left.
+    unsafe fn grow(heap: &mut Heap, size: usize) -> Option<()> {
+        let size = max(size, HEAP_GROWTH);
+        if heap.size() == 0 {
+            let start = brk(0).ok()?;
+            brk(start + size as u64).ok()?;
+            heap.init(start as usize, size);
+        } else {
+            brk((heap.top() + size) as u64).ok()?;
+            heap.extend(size);
+        }
+        Some(())
+    }
+}
+
+unsafe impl GlobalAlloc for ProgramHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.heap.lock();
+        if let Ok(pointer) = heap.allocate_first_fit(layout) {
+            return pointer.as_ptr();
+        }
+        // the padding needed to align the allocation can be up to its alignment
+        if Self::grow(&mut heap, layout.size() + layout.align()).is_none() {
+            return null_mut();
+        }
+        heap.allocate_first_fit(layout)
+            .map_or(null_mut(), |pointer| pointer.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(pointer) = NonNull::new(ptr) {
+            self.heap.lock().deallocate(pointer, layout);
+        }
+    }
+}
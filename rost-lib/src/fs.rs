@@ -0,0 +1,131 @@
+use alloc::{string::String, vec::Vec};
+
+use utils::file::DirectoryRecord;
+pub use utils::{
+    file::{FileStat, FileType, OpenFlags, SeekOrigin},
+    ioctl,
+};
+
+use crate::syscalls::{self, SysCallError};
+
+/// A file or directory opened with the `Open` system call, closed when it's dropped.
+pub struct File {
+    descriptor: u64,
+}
+
+impl File {
+    /// Opens the file at the path for reading.
+    pub fn open(path: &str) -> Result<File, SysCallError> {
+        File::open_with(path, OpenFlags::READ, 0)
+    }
+
+    /// Creates a file at the path, or truncates the existing one, and opens it for writing.
+    pub fn create(path: &str) -> Result<File, SysCallError> {
+        File::open_with(
+            path,
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+            0o644,
+        )
+    }
+
+    /// Opens the file at the path with the flags, the permissions are used if it's created.
+    pub fn open_with(path: &str, flags: OpenFlags, permissions: u16) -> Result<File, SysCallError> {
+        let descriptor = syscalls::open(path, flags, permissions)?;
+        Ok(File { descriptor })
+    }
+
+    pub fn descriptor(&self) -> u64 {
+        self.descriptor
+    }
+
+    /// Reads into the buffer and returns the number of read bytes, `0` at the end of the file.
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, SysCallError> {
+        syscalls::read(self.descriptor, buffer)
+    }
+
+    /// Reads from the offset to the end of the file.
+    pub fn read_to_end(&self) -> Result<Vec<u8>, SysCallError> {
+        let mut data = Vec::new();
+        let mut buffer = [0; 512];
+        loop {
+            match self.read(&mut buffer)? {
+                0 => return Ok(data),
+                read => data.extend_from_slice(&buffer[..read]),
+            }
+        }
+    }
+
+    /// Writes the data and returns the number of written bytes.
+    pub fn write(&self, data: &[u8]) -> Result<usize, SysCallError> {
+        syscalls::write(self.descriptor, data)
+    }
+
+    /// Writes all of the data, retrying until the file system took all of it.
+    pub fn write_all(&self, mut data: &[u8]) -> Result<(), SysCallError> {
+        while !data.is_empty() {
+            match self.write(data)? {
+                0 => return Err(SysCallError::NoSpace),
+                written => data = &data[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the offset and returns the new one.
+    pub fn seek(&self, offset: i64, origin: SeekOrigin) -> Result<u64, SysCallError> {
+        syscalls::seek(self.descriptor, offset, origin)
+    }
+
+    /// Sends the device specific command, one of `ioctl`'s constants, to the device and returns its result.
+    pub fn ioctl(&self, command: u64, argument: u64) -> Result<u64, SysCallError> {
+        syscalls::ioctl(self.descriptor, command, argument)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        // the descriptor is valid until here, closing it can't fail
+        let _ = syscalls::close(self.descriptor);
+    }
+}
+
+/// An entry of a directory, as returned by `read_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    /// The inode number, 0 if the file system has none.
+    pub inode: u64,
+    pub file_type: FileType,
+}
+
+/// Returns the entries of the directory at the path, without `.` and `..`.
+pub fn read_dir(path: &str) -> Result<Vec<DirectoryEntry>, SysCallError> {
+    let directory = File::open_with(path, OpenFlags::READ | OpenFlags::DIRECTORY, 0)?;
+    let mut entries = Vec::new();
+    let mut buffer = [0; 1024];
+    loop {
+        let written = syscalls::read_dir(directory.descriptor, &mut buffer)?;
+        if written == 0 {
+            return Ok(entries);
+        }
+        let mut records = &buffer[..written];
+        while let Some((record, length)) = DirectoryRecord::read_from(records) {
+            entries.push(DirectoryEntry {
+                name: String::from(record.name),
+                inode: record.inode,
+                file_type: record.file_type,
+            });
+            records = &records[length..];
+        }
+    }
+}
+
+/// Returns the metadata of the file at the path, following symlinks.
+pub fn metadata(path: &str) -> Result<FileStat, SysCallError> {
+    syscalls::stat(path)
+}
+
+/// Reads the whole file at the path.
+pub fn read(path: &str) -> Result<Vec<u8>, SysCallError> {
+    File::open(path)?.read_to_end()
+}
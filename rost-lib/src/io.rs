@@ -0,0 +1,31 @@
+use core::fmt::{self, Write};
+
+use crate::syscalls::console_write;
+
+/// The console of the process, written with the `ConsoleWrite` system call.
+pub struct Console;
+
+impl Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        console_write(s).map(|_| ()).map_err(|_| fmt::Error)
+    }
+}
+
+#[doc(hidden)]
+pub fn __print(args: fmt::Arguments) {
+    // there's nowhere to report a failing console
+    let _ = Console.write_fmt(args);
+}
+
+#[macro_export]
+/// Prints a string to the console
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::io::__print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+/// Prints a string to the console and appends a newline
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
@@ -1,5 +1,17 @@
+//! The standard library of user mode programs.
+//!
+//! A program declares its `main` function with `entry_point!` and can then use the heap,
+//! `print!`/`println!`, the files in `fs` and the system call wrappers in `syscalls`.
 #![no_std] // no standard library
 #![no_main]
 #![allow(incomplete_features)]
 #![feature(generic_const_exprs, core_intrinsics, alloc_error_handler)]
 extern crate alloc;
+
+mod allocator;
+pub mod fs;
+pub mod io;
+mod runtime;
+#[doc(hidden)]
+pub use runtime::__run;
+pub mod syscalls;
@@ -0,0 +1,46 @@
+use core::{alloc::Layout, panic::PanicInfo};
+
+use crate::{println, syscalls::exit};
+
+/// The exit code of a process that panicked.
+const PANIC_EXIT_CODE: i32 = 101;
+
+/// Defines the entry point of a user mode program.
+///
+/// The passed function is called with an empty stack and the process exits when it returns.
+///
+/// ```ignore
+/// rost_lib::entry_point!(main);
+///
+/// fn main() {
+///     rost_lib::println!("Hello from user mode!");
+/// }
+/// ```
+#[macro_export]
+macro_rules! entry_point {
+    ($path:path) => {
+        #[export_name = "_start"]
+        pub extern "C" fn __impl_start() -> ! {
+            // validate the signature of the program entry point
+            let f: fn() = $path;
+            $crate::__run(f)
+        }
+    };
+}
+
+#[doc(hidden)]
+pub fn __run(main: fn()) -> ! {
+    main();
+    exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("[PANIC] {}", info);
+    exit(PANIC_EXIT_CODE);
+}
+
+#[alloc_error_handler]
+fn alloc_error_handler(layout: Layout) -> ! {
+    panic!("allocation error: {:?}", layout)
+}
@@ -0,0 +1,242 @@
+use core::{arch::asm, mem::MaybeUninit};
+
+use utils::syscall_error::decode_result;
+pub use utils::{
+    file::{FileStat, OpenFlags, SeekOrigin},
+    syscall_error::SysCallError,
+    syscall_name::SysCallName,
+};
+
+/// Calls the passed system call with its arguments, the unused ones can be anything.
+///
+/// This function is unsafe because the kernel may write to the memory the arguments point to.
+#[inline(always)]
+pub unsafe fn syscall(
+    name: SysCallName,
+    arg1: u64,
+    arg2: u64,
+    arg3: u64,
+    arg4: u64,
+    arg5: u64,
+    arg6: u64,
+) -> Result<u64, SysCallError> {
+    let result: u64;
+    asm!(
+        "syscall",
+        inlateout("rax") name as u64 => result,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        in("rdx") arg3,
+        in("r10") arg4,
+        in("r8") arg5,
+        in("r9") arg6,
+        // `syscall` stores the instruction pointer in RCX and the flags in R11
+        lateout("rcx") _,
+        lateout("r11") _,
+        options(nostack)
+    );
+    decode_result(result)
+}
+
+/// Writes the passed string to the console and returns the number of written bytes.
+pub fn console_write(message: &str) -> Result<usize, SysCallError> {
+    unsafe {
+        syscall(
+            SysCallName::ConsoleWrite,
+            message.as_ptr() as u64,
+            message.len() as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+        .map(|written| written as usize)
+    }
+}
+
+/// Returns the ID of the calling process.
+pub fn get_process_id() -> u64 {
+    unsafe { syscall(SysCallName::GetProcessId, 0, 0, 0, 0, 0, 0) }
+        .expect("Every process has an ID")
+}
+
+/// Terminates the calling process with the passed exit code.
+pub fn exit(code: i32) -> ! {
+    unsafe {
+        let _ = syscall(SysCallName::Exit, code as u64, 0, 0, 0, 0, 0);
+    }
+    unreachable!("Exit returned");
+}
+
+/// Writes the cached data back to the disks and halts the machine.
+pub fn shutdown() -> ! {
+    unsafe {
+        let _ = syscall(SysCallName::Shutdown, 0, 0, 0, 0, 0, 0);
+    }
+    unreachable!("Shutdown returned");
+}
+
+/// Moves the end of the heap to the passed address and returns the new end.
+///
+/// Passing `0` only returns the current end.
+///
+/// This function is unsafe because memory that is still in use must not be released.
+pub unsafe fn brk(address: u64) -> Result<u64, SysCallError> {
+    syscall(SysCallName::Brk, address, 0, 0, 0, 0, 0)
+}
+
+/// Creates a copy of the calling process.
+///
+/// Returns the ID of the child in the parent and `0` in the child.
+pub fn fork() -> Result<u64, SysCallError> {
+    unsafe { syscall(SysCallName::Fork, 0, 0, 0, 0, 0, 0) }
+}
+
+/// Replaces the program of the calling process with the executable at the passed path.
+///
+/// Only returns if the executable couldn't be started.
+pub fn exec(path: &str) -> SysCallError {
+    let result = unsafe {
+        syscall(
+            SysCallName::Exec,
+            path.as_ptr() as u64,
+            path.len() as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+    match result {
+        Ok(_) => unreachable!("Exec returned"),
+        Err(error) => error,
+    }
+}
+
+/// Waits until the child process with the passed ID exited, `0` waits for any child.
+///
+/// Returns the ID and the exit code of the child.
+pub fn wait_pid(id: u64) -> Result<(u64, i32), SysCallError> {
+    let mut exit_code = 0i32;
+    let child = unsafe {
+        syscall(
+            SysCallName::WaitPid,
+            id,
+            &mut exit_code as *mut i32 as u64,
+            0,
+            0,
+            0,
+            0,
+        )?
+    };
+    Ok((child, exit_code))
+}
+
+/// Opens the file or directory at the passed path and returns its file descriptor.
+///
+/// The permissions are used if the flags ask to create the file.
+pub fn open(path: &str, flags: OpenFlags, permissions: u16) -> Result<u64, SysCallError> {
+    unsafe {
+        syscall(
+            SysCallName::Open,
+            path.as_ptr() as u64,
+            path.len() as u64,
+            flags.bits(),
+            permissions as u64,
+            0,
+            0,
+        )
+    }
+}
+
+/// Reads from the file into the buffer and returns the number of read bytes, `0` at the end of the file.
+pub fn read(descriptor: u64, buffer: &mut [u8]) -> Result<usize, SysCallError> {
+    unsafe {
+        syscall(
+            SysCallName::Read,
+            descriptor,
+            buffer.as_mut_ptr() as u64,
+            buffer.len() as u64,
+            0,
+            0,
+            0,
+        )
+        .map(|read| read as usize)
+    }
+}
+
+/// Writes the data to the file and returns the number of written bytes.
+pub fn write(descriptor: u64, data: &[u8]) -> Result<usize, SysCallError> {
+    unsafe {
+        syscall(
+            SysCallName::Write,
+            descriptor,
+            data.as_ptr() as u64,
+            data.len() as u64,
+            0,
+            0,
+            0,
+        )
+        .map(|written| written as usize)
+    }
+}
+
+/// Moves the offset of the file and returns the new offset.
+pub fn seek(descriptor: u64, offset: i64, origin: SeekOrigin) -> Result<u64, SysCallError> {
+    unsafe {
+        syscall(
+            SysCallName::Seek,
+            descriptor,
+            offset as u64,
+            origin as u64,
+            0,
+            0,
+            0,
+        )
+    }
+}
+
+/// Closes the file descriptor.
+pub fn close(descriptor: u64) -> Result<(), SysCallError> {
+    unsafe { syscall(SysCallName::Close, descriptor, 0, 0, 0, 0, 0) }.map(|_| ())
+}
+
+/// Returns the metadata of the file at the passed path, following symlinks.
+pub fn stat(path: &str) -> Result<FileStat, SysCallError> {
+    let mut stat = MaybeUninit::<FileStat>::uninit();
+    unsafe {
+        syscall(
+            SysCallName::Stat,
+            path.as_ptr() as u64,
+            path.len() as u64,
+            stat.as_mut_ptr() as u64,
+            0,
+            0,
+            0,
+        )?;
+        Ok(stat.assume_init())
+    }
+}
+
+/// Reads the next entries of the open directory into the buffer as `DirectoryRecord`s.
+///
+/// Returns the number of written bytes, `0` once all entries were read.
+pub fn read_dir(descriptor: u64, buffer: &mut [u8]) -> Result<usize, SysCallError> {
+    unsafe {
+        syscall(
+            SysCallName::ReadDir,
+            descriptor,
+            buffer.as_mut_ptr() as u64,
+            buffer.len() as u64,
+            0,
+            0,
+            0,
+        )
+        .map(|written| written as usize)
+    }
+}
+
+/// Sends the device specific command, one of `ioctl`'s constants, to the device behind the open file.
+pub fn ioctl(descriptor: u64, command: u64, argument: u64) -> Result<u64, SysCallError> {
+    unsafe { syscall(SysCallName::Ioctl, descriptor, command, argument, 0, 0, 0) }
+}
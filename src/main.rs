@@ -13,13 +13,20 @@
 extern crate alloc;
 
 use bootloader::{entry_point, BootInfo};
-use core::{arch::asm, panic::PanicInfo};
-use kernel::structures::kernel_information::KernelInformation;
+use core::panic::PanicInfo;
+use kernel::{
+    devfs::DevFs, procfs::ProcFs, structures::kernel_information::KernelInformation, vfs,
+};
+use ramfs::RamFs;
 use tinytga::RawTga;
 use vga::vga_core::{Clearable, ImageDrawable};
 
+use alloc::sync::Arc;
 use core::alloc::Layout;
 
+#[cfg(test)]
+mod tests;
+
 entry_point!(kernel);
 pub fn kernel(boot_info: &'static mut BootInfo) -> ! {
     let mut kernel_info = kernel::init(boot_info);
@@ -49,17 +56,27 @@ fn bootup_sequence(kernel_info: KernelInformation) {
     );
 }
 
-#[no_mangle]
-extern "C" fn user_mode_check() {
-    unsafe {
-        asm!("mov rdi, 0", "syscall", "mov rdi, 1", "syscall");
-    }
-    loop {}
+/// The initramfs, a CPIO archive built by `build.rs` with the user programs and the files of `initramfs/`.
+const INITRAMFS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.cpio"));
+
+/// The maximum size of the files in `/tmp`.
+const TMP_CAPACITY: u64 = 16 * 1024 * 1024;
+
+/// Mounts a ramfs as the root file system, unpacks the initramfs into it, mounts the devices at `/dev`
+/// and the kernel state at `/proc`.
+fn mount_root() {
+    vfs::mount("/", Arc::new(RamFs::new())).expect("Failed to mount the root file system");
+    ramfs::cpio::unpack(INITRAMFS, "/").expect("Failed to unpack the initramfs");
+    vfs::mount("/tmp", Arc::new(RamFs::with_capacity(TMP_CAPACITY))).expect("Failed to mount /tmp");
+    vfs::mount("/dev", Arc::new(DevFs::new())).expect("Failed to mount /dev");
+    vfs::mount("/proc", Arc::new(ProcFs::new())).expect("Failed to mount /proc");
 }
 
-pub fn kernel_main(kernel_info: &mut KernelInformation) {
+pub fn kernel_main(_kernel_info: &mut KernelInformation) {
+    mount_root();
+    let init = vfs::read_file("/bin/init").expect("init is not in the initramfs");
     unsafe {
-        kernel::run_in_user_mode(user_mode_check, kernel_info);
+        kernel::run_in_user_mode(&init).expect("Failed to load init");
     }
     /*
         let test = Box::new(4);
@@ -0,0 +1,93 @@
+//! The kernel tests, run by `cargo ktest` after the kernel was initialized.
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+use kernel::{vfs, BlockDevice, BlockDeviceError};
+use ramfs::RamFs;
+use spin::Mutex;
+
+mod cpio;
+mod ext2;
+mod fat32;
+mod gpt;
+
+const SECTOR_SIZE: usize = 512;
+
+/// Creates an empty directory for a test and returns its path.
+///
+/// The tests run instead of `mount_root`, so a ramfs is mounted as the root file system first if there's none.
+pub fn test_directory(name: &str) -> String {
+    if vfs::mounts().is_empty() {
+        vfs::mount("/", Arc::new(RamFs::new())).expect("Failed to mount the root file system");
+    }
+    let path = format!("/{}", name);
+    vfs::create_directory(&path, 0o755).expect("Failed to create the test directory");
+    path
+}
+
+/// A disk storing only the sectors which aren't zeroed, so volumes larger than the kernel heap fit
+/// in it, like the smallest FAT32 one.
+///
+/// Clones share the sectors, so the volume can be mounted again after it was dropped.
+#[derive(Clone)]
+pub struct SparseDisk {
+    sectors: Arc<Mutex<BTreeMap<u64, Vec<u8>>>>,
+    sector_count: u64,
+}
+
+impl SparseDisk {
+    pub fn new(sector_count: u64) -> Self {
+        SparseDisk {
+            sectors: Arc::new(Mutex::new(BTreeMap::new())),
+            sector_count,
+        }
+    }
+
+    fn check_range(&self, block: u64, length: usize) -> Result<(), BlockDeviceError> {
+        if length % SECTOR_SIZE != 0 {
+            return Err(BlockDeviceError::InvalidBufferSize);
+        }
+        if block + (length / SECTOR_SIZE) as u64 > self.sector_count {
+            return Err(BlockDeviceError::OutOfRange);
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for SparseDisk {
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn read_blocks(&mut self, block: u64, buffer: &mut [u8]) -> Result<(), BlockDeviceError> {
+        self.check_range(block, buffer.len())?;
+        let sectors = self.sectors.lock();
+        for (index, chunk) in buffer.chunks_mut(SECTOR_SIZE).enumerate() {
+            match sectors.get(&(block + index as u64)) {
+                Some(sector) => chunk.copy_from_slice(sector),
+                None => chunk.fill(0),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, block: u64, buffer: &[u8]) -> Result<(), BlockDeviceError> {
+        self.check_range(block, buffer.len())?;
+        let mut sectors = self.sectors.lock();
+        for (index, chunk) in buffer.chunks(SECTOR_SIZE).enumerate() {
+            let sector = block + index as u64;
+            if chunk.iter().all(|&byte| byte == 0) {
+                sectors.remove(&sector);
+            } else {
+                sectors.insert(sector, chunk.to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockDeviceError> {
+        Ok(())
+    }
+}
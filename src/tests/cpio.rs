@@ -0,0 +1,118 @@
+use alloc::{format, vec::Vec};
+use kernel::vfs::{self, FileType, VfsError};
+use ramfs::cpio::{self, CpioError};
+
+use super::test_directory;
+
+const DIRECTORY: u32 = 0o040755;
+const FILE: u32 = 0o100644;
+const SYMLINK: u32 = 0o120777;
+
+/// Builds a `newc` entry with the device 0:1.
+fn entry(name: &str, mode: u32, inode: u32, links: u32, data: &[u8]) -> Vec<u8> {
+    let mut entry = format!(
+        "070701{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}",
+        inode,
+        mode,
+        0,
+        0,
+        links,
+        0,
+        data.len(),
+        0,
+        1,
+        0,
+        0,
+        name.len() + 1,
+        0
+    )
+    .into_bytes();
+    entry.extend_from_slice(name.as_bytes());
+    entry.push(0);
+    pad(&mut entry);
+    entry.extend_from_slice(data);
+    pad(&mut entry);
+    entry
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+    while bytes.len() % 4 != 0 {
+        bytes.push(0);
+    }
+}
+
+/// Builds an archive of the entries followed by the trailer.
+fn archive(entries: &[Vec<u8>]) -> Vec<u8> {
+    let mut archive = entries.concat();
+    archive.extend(entry("TRAILER!!!", 0, 0, 1, &[]));
+    archive
+}
+
+#[test_case]
+fn unpacks_directories_files_and_symlinks() {
+    let target = test_directory("cpio-unpack");
+    let archive = archive(&[
+        entry(".", DIRECTORY, 1, 2, &[]),
+        entry("bin", DIRECTORY, 2, 2, &[]),
+        entry("./bin/hello", FILE, 3, 1, b"Hello, World!"),
+        entry("link", SYMLINK, 4, 1, b"bin/hello"),
+    ]);
+    assert_eq!(cpio::unpack(&archive, &target), Ok(3));
+    let hello = format!("{}/bin/hello", target);
+    assert_eq!(vfs::read_file(&hello).unwrap(), b"Hello, World!");
+    let stat = vfs::stat(&hello).unwrap();
+    assert_eq!((stat.file_type, stat.permissions), (FileType::File, 0o644));
+    let link = format!("{}/link", target);
+    assert_eq!(vfs::read_file(&link).unwrap(), b"Hello, World!");
+}
+
+#[test_case]
+fn rejects_names_leaving_the_target() {
+    let target = test_directory("cpio-parent");
+    let inner = format!("{}/inner", target);
+    vfs::create_directory(&inner, 0o755).unwrap();
+    for name in ["../escaped", "bin/../../escaped", ".."] {
+        let archive = archive(&[entry(name, FILE, 1, 1, b"data")]);
+        assert_eq!(cpio::unpack(&archive, &inner), Err(CpioError::InvalidName));
+    }
+    let escaped = format!("{}/escaped", target);
+    assert_eq!(vfs::stat(&escaped).unwrap_err(), VfsError::NotFound);
+}
+
+#[test_case]
+fn unpacks_hard_links_as_copies() {
+    let target = test_directory("cpio-hard-links");
+    let archive = archive(&[
+        entry("first", FILE, 7, 3, &[]),
+        entry("second", FILE, 7, 3, &[]),
+        entry("other", FILE, 8, 1, b"other"),
+        entry("third", FILE, 7, 3, b"shared data"),
+    ]);
+    assert_eq!(cpio::unpack(&archive, &target), Ok(4));
+    for name in ["first", "second", "third"] {
+        let path = format!("{}/{}", target, name);
+        assert_eq!(vfs::read_file(&path).unwrap(), b"shared data");
+    }
+    let other = format!("{}/other", target);
+    assert_eq!(vfs::read_file(&other).unwrap(), b"other");
+}
+
+#[test_case]
+fn rejects_malformed_archives() {
+    let target = test_directory("cpio-malformed");
+    let mut invalid_magic = archive(&[]);
+    invalid_magic[5] = b'9';
+    assert_eq!(
+        cpio::unpack(&invalid_magic, &target),
+        Err(CpioError::InvalidHeader)
+    );
+    let without_trailer = entry("file", FILE, 1, 1, b"data");
+    assert_eq!(
+        cpio::unpack(&without_trailer, &target),
+        Err(CpioError::Truncated)
+    );
+    let mut truncated = archive(&[entry("file", FILE, 1, 1, b"data")]);
+    // ends in the middle of the first entry's name
+    truncated.truncate(112);
+    assert_eq!(cpio::unpack(&truncated, &target), Err(CpioError::Truncated));
+}
@@ -0,0 +1,345 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use ext2::{block_path, Ext2, Ext2Error, FileType};
+use kernel::BlockDevice;
+
+use super::SparseDisk;
+
+/// 256 block pointers fit in a 1 KiB block.
+const BLOCK_SIZE: usize = 1024;
+const SINGLY_INDIRECT: u64 = 12;
+const DOUBLY_INDIRECT: u64 = SINGLY_INDIRECT + 256;
+const TRIPLY_INDIRECT: u64 = DOUBLY_INDIRECT + 256 * 256;
+const END: u64 = TRIPLY_INDIRECT + 256 * 256 * 256;
+
+/// The volume the tests mount, a single group of 1 KiB blocks.
+const BLOCK_COUNT: u32 = 2048;
+const INODE_COUNT: u32 = 64;
+const BLOCK_BITMAP: u32 = 3;
+const INODE_BITMAP: u32 = 4;
+const INODE_TABLE: u32 = 5;
+const INODE_SIZE: usize = 128;
+/// The block after the inode table.
+const ROOT_DIRECTORY_BLOCK: u32 = INODE_TABLE + INODE_COUNT * INODE_SIZE as u32 / 1024;
+/// The blocks after the superblock up to the root directory are used, block 0 isn't part of a group.
+const FREE_BLOCKS: u32 = BLOCK_COUNT - 1 - ROOT_DIRECTORY_BLOCK;
+/// The inodes before the first usable one, 11 on revision 0, are reserved.
+const FREE_INODES: u32 = INODE_COUNT - 10;
+const BLOCKS_PER_GROUP: u32 = 8192;
+
+#[test_case]
+fn maps_direct_blocks() {
+    assert_eq!(block_path(BLOCK_SIZE, 0), Ok(vec![0]));
+    assert_eq!(block_path(BLOCK_SIZE, 11), Ok(vec![11]));
+}
+
+#[test_case]
+fn maps_indirect_blocks() {
+    assert_eq!(block_path(BLOCK_SIZE, SINGLY_INDIRECT), Ok(vec![12, 0]));
+    assert_eq!(
+        block_path(BLOCK_SIZE, DOUBLY_INDIRECT - 1),
+        Ok(vec![12, 255])
+    );
+    assert_eq!(block_path(BLOCK_SIZE, DOUBLY_INDIRECT), Ok(vec![13, 0, 0]));
+    assert_eq!(
+        block_path(BLOCK_SIZE, DOUBLY_INDIRECT + 256),
+        Ok(vec![13, 1, 0])
+    );
+    assert_eq!(
+        block_path(BLOCK_SIZE, TRIPLY_INDIRECT - 1),
+        Ok(vec![13, 255, 255])
+    );
+    assert_eq!(
+        block_path(BLOCK_SIZE, TRIPLY_INDIRECT),
+        Ok(vec![14, 0, 0, 0])
+    );
+    assert_eq!(
+        block_path(BLOCK_SIZE, TRIPLY_INDIRECT + 256 * 256 + 257),
+        Ok(vec![14, 1, 1, 1])
+    );
+    assert_eq!(block_path(BLOCK_SIZE, END - 1), Ok(vec![14, 255, 255, 255]));
+}
+
+#[test_case]
+fn rejects_blocks_beyond_the_triply_indirect_ones() {
+    assert_eq!(block_path(BLOCK_SIZE, END), Err(Ext2Error::FileTooLarge));
+    assert_eq!(
+        block_path(BLOCK_SIZE, u64::MAX),
+        Err(Ext2Error::FileTooLarge)
+    );
+}
+
+#[test_case]
+fn uses_the_pointers_per_block_of_the_block_size() {
+    assert_eq!(block_path(4096, 12 + 1023), Ok(vec![12, 1023]));
+    assert_eq!(block_path(4096, 12 + 1024), Ok(vec![13, 0, 0]));
+}
+
+fn put_u16(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Marks the first `used` entries of a bitmap as used, and the ones past the `count` entries of the
+/// group, which don't exist.
+fn bitmap(used: u32, count: u32) -> Vec<u8> {
+    let mut bitmap = vec![0; BLOCK_SIZE];
+    for bit in (0..used).chain(count..BLOCKS_PER_GROUP) {
+        bitmap[bit as usize / 8] |= 1 << (bit % 8);
+    }
+    bitmap
+}
+
+fn write_block(disk: &mut SparseDisk, block: u32, data: &[u8]) {
+    disk.write_blocks(block as u64 * 2, data).unwrap();
+}
+
+fn read_block(disk: &SparseDisk, block: u32) -> Vec<u8> {
+    let mut data = vec![0; BLOCK_SIZE];
+    disk.clone()
+        .read_blocks(block as u64 * 2, &mut data)
+        .unwrap();
+    data
+}
+
+/// Creates an empty revision 0 volume holding only the root directory, like `mke2fs -r 0` without
+/// `lost+found`.
+fn format() -> SparseDisk {
+    let mut disk = SparseDisk::new(BLOCK_COUNT as u64 * 2);
+
+    let mut superblock = vec![0; BLOCK_SIZE];
+    put_u32(&mut superblock, 0, INODE_COUNT);
+    put_u32(&mut superblock, 4, BLOCK_COUNT);
+    put_u32(&mut superblock, 12, FREE_BLOCKS);
+    put_u32(&mut superblock, 16, FREE_INODES);
+    put_u32(&mut superblock, 20, 1);
+    put_u32(&mut superblock, 32, BLOCKS_PER_GROUP);
+    put_u32(&mut superblock, 36, BLOCKS_PER_GROUP);
+    put_u32(&mut superblock, 40, INODE_COUNT);
+    put_u16(&mut superblock, 56, 0xEF53);
+    put_u16(&mut superblock, 58, 1);
+    put_u16(&mut superblock, 60, 1);
+    write_block(&mut disk, 1, &superblock);
+
+    let mut group_descriptors = vec![0; BLOCK_SIZE];
+    put_u32(&mut group_descriptors, 0, BLOCK_BITMAP);
+    put_u32(&mut group_descriptors, 4, INODE_BITMAP);
+    put_u32(&mut group_descriptors, 8, INODE_TABLE);
+    put_u16(&mut group_descriptors, 12, FREE_BLOCKS as u16);
+    put_u16(&mut group_descriptors, 14, FREE_INODES as u16);
+    put_u16(&mut group_descriptors, 16, 1);
+    write_block(&mut disk, 2, &group_descriptors);
+
+    write_block(
+        &mut disk,
+        BLOCK_BITMAP,
+        &bitmap(ROOT_DIRECTORY_BLOCK, BLOCK_COUNT - 1),
+    );
+    write_block(
+        &mut disk,
+        INODE_BITMAP,
+        &bitmap(INODE_COUNT - FREE_INODES, INODE_COUNT),
+    );
+
+    // the root directory is the second inode
+    let mut inodes = vec![0; BLOCK_SIZE];
+    let root = &mut inodes[INODE_SIZE..2 * INODE_SIZE];
+    put_u16(root, 0, 0o40755);
+    put_u32(root, 4, BLOCK_SIZE as u32);
+    put_u16(root, 26, 2);
+    put_u32(root, 28, 2);
+    put_u32(root, 40, ROOT_DIRECTORY_BLOCK);
+    write_block(&mut disk, INODE_TABLE, &inodes);
+
+    let mut directory = vec![0; BLOCK_SIZE];
+    for (offset, name, length) in [(0, ".", 12), (12, "..", BLOCK_SIZE - 12)] {
+        put_u32(&mut directory, offset, 2);
+        put_u16(&mut directory, offset + 4, length as u16);
+        put_u16(&mut directory, offset + 6, name.len() as u16);
+        directory[offset + 8..offset + 8 + name.len()].copy_from_slice(name.as_bytes());
+    }
+    write_block(&mut disk, ROOT_DIRECTORY_BLOCK, &directory);
+    disk
+}
+
+fn mount() -> (SparseDisk, Ext2<SparseDisk>) {
+    let disk = format();
+    let ext2 = Ext2::mount(disk.clone()).expect("Failed to mount the volume");
+    (disk, ext2)
+}
+
+/// Syncs and drops the volume, then mounts the disk again.
+fn remount(disk: &SparseDisk, mut ext2: Ext2<SparseDisk>) -> Ext2<SparseDisk> {
+    ext2.sync().unwrap();
+    drop(ext2);
+    Ext2::mount(disk.clone()).expect("Failed to mount the volume again")
+}
+
+/// Returns data which differs in every block, so misplaced blocks are noticed.
+fn pattern(length: usize) -> Vec<u8> {
+    (0..length)
+        .map(|index| (index * 7 + index / 1021) as u8)
+        .collect()
+}
+
+/// Returns the free block and inode counts of the superblock and the group descriptor, followed by
+/// the group's directory count.
+fn counters(disk: &SparseDisk) -> [u32; 5] {
+    let superblock = read_block(disk, 1);
+    let group_descriptors = read_block(disk, 2);
+    let u32_at = |bytes: &[u8], offset: usize| {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    };
+    let u16_at =
+        |bytes: &[u8], offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as u32;
+    [
+        u32_at(&superblock, 12),
+        u32_at(&superblock, 16),
+        u16_at(&group_descriptors, 12),
+        u16_at(&group_descriptors, 14),
+        u16_at(&group_descriptors, 16),
+    ]
+}
+
+#[test_case]
+fn mounts_an_empty_volume() {
+    let (_, mut ext2) = mount();
+    assert!(!ext2.is_read_only());
+    assert_eq!(ext2.block_size(), BLOCK_SIZE);
+    assert_eq!(ext2.free_space(), FREE_BLOCKS as u64 * BLOCK_SIZE as u64);
+    assert!(ext2.read_dir("/").unwrap().is_empty());
+    let root = ext2.metadata("/").unwrap();
+    assert_eq!((root.file_type, root.links), (FileType::Directory, 2));
+}
+
+#[test_case]
+fn reads_and_writes_across_indirect_blocks() {
+    let (disk, mut ext2) = mount();
+    let free_space = ext2.free_space();
+    // the last 10 blocks are mapped through the doubly indirect block
+    let mut data = pattern((DOUBLY_INDIRECT + 10) as usize * BLOCK_SIZE);
+    ext2.create_file("/big", 0o644).unwrap();
+    assert_eq!(ext2.write("/big", 0, &data), Ok(data.len()));
+    assert_eq!(ext2.read_file("/big"), Ok(data.clone()));
+    // the singly and doubly indirect blocks and one block of pointers below the latter
+    let blocks = DOUBLY_INDIRECT + 10 + 3;
+    assert_eq!(ext2.free_space(), free_space - blocks * BLOCK_SIZE as u64);
+
+    // writes and reads over the first block of each level
+    for block in [SINGLY_INDIRECT, DOUBLY_INDIRECT] {
+        let offset = block as usize * BLOCK_SIZE - 10;
+        ext2.write("/big", offset, &[0xAB; 20]).unwrap();
+        data[offset..offset + 20].fill(0xAB);
+        let mut buffer = [0; 40];
+        assert_eq!(ext2.read("/big", offset - 10, &mut buffer), Ok(40));
+        assert_eq!(buffer[..], data[offset - 10..offset + 30]);
+    }
+
+    let mut ext2 = remount(&disk, ext2);
+    assert_eq!(ext2.read_file("/big"), Ok(data.clone()));
+    assert_eq!(ext2.metadata("/big").unwrap().size, data.len() as u64);
+
+    // truncating into the direct blocks frees the indirect ones too
+    ext2.truncate("/big", 5 * BLOCK_SIZE + 1).unwrap();
+    assert_eq!(
+        ext2.read_file("/big"),
+        Ok(data[..5 * BLOCK_SIZE + 1].to_vec())
+    );
+    assert_eq!(ext2.free_space(), free_space - 6 * BLOCK_SIZE as u64);
+    ext2.remove("/big").unwrap();
+    assert_eq!(ext2.free_space(), free_space);
+}
+
+#[test_case]
+fn stores_fast_and_slow_symlinks() {
+    let (disk, mut ext2) = mount();
+    ext2.create_file("/target", 0o644).unwrap();
+    ext2.write("/target", 0, b"hello").unwrap();
+    let free_space = ext2.free_space();
+
+    // short targets are stored in the inode, longer ones in a block
+    ext2.create_symlink("/fast", "target").unwrap();
+    assert_eq!(ext2.free_space(), free_space);
+    let long_target = format!("{}target", "./".repeat(40));
+    ext2.create_symlink("/slow", &long_target).unwrap();
+    assert_eq!(ext2.free_space(), free_space - BLOCK_SIZE as u64);
+
+    let mut ext2 = remount(&disk, ext2);
+    assert_eq!(ext2.read_link("/fast"), Ok(String::from("target")));
+    assert_eq!(ext2.read_link("/slow"), Ok(long_target.clone()));
+    let slow = ext2.metadata("/slow").unwrap();
+    assert_eq!(
+        (slow.file_type, slow.size),
+        (FileType::Symlink, long_target.len() as u64)
+    );
+    assert_eq!(ext2.read_file("/fast"), Ok(b"hello".to_vec()));
+    assert_eq!(ext2.read_file("/slow"), Ok(b"hello".to_vec()));
+    assert_eq!(ext2.read_link("/target"), Err(Ext2Error::NotASymlink));
+
+    ext2.remove("/slow").unwrap();
+    ext2.remove("/fast").unwrap();
+    assert_eq!(ext2.free_space(), free_space);
+    assert_eq!(ext2.read_file("/target"), Ok(b"hello".to_vec()));
+}
+
+#[test_case]
+fn keeps_the_allocation_counters() {
+    let (disk, mut ext2) = mount();
+    let initial = [FREE_BLOCKS, FREE_INODES, FREE_BLOCKS, FREE_INODES, 1];
+    assert_eq!(counters(&disk), initial);
+
+    ext2.create_directory("/directory", 0o755).unwrap();
+    ext2.create_file("/directory/file", 0o644).unwrap();
+    ext2.write("/directory/file", 0, &pattern(3 * BLOCK_SIZE))
+        .unwrap();
+    ext2.sync().unwrap();
+    // a block for the directory's entries and three for the file
+    assert_eq!(
+        counters(&disk),
+        [
+            FREE_BLOCKS - 4,
+            FREE_INODES - 2,
+            FREE_BLOCKS - 4,
+            FREE_INODES - 2,
+            2
+        ]
+    );
+    assert_eq!(ext2.metadata("/").unwrap().links, 3);
+
+    ext2.remove("/directory/file").unwrap();
+    assert_eq!(ext2.remove("/"), Err(Ext2Error::InvalidName));
+    ext2.remove("/directory").unwrap();
+    ext2.sync().unwrap();
+    assert_eq!(counters(&disk), initial);
+    assert_eq!(ext2.metadata("/").unwrap().links, 2);
+}
+
+#[test_case]
+fn runs_out_of_inodes() {
+    let (disk, mut ext2) = mount();
+    for index in 0..FREE_INODES {
+        ext2.create_file(&format!("/file{}", index), 0o644).unwrap();
+    }
+    assert_eq!(
+        ext2.create_file("/one too many", 0o644),
+        Err(Ext2Error::NoSpace)
+    );
+    ext2.sync().unwrap();
+    assert_eq!(counters(&disk)[1], 0);
+
+    // the freed inodes are used again, the root directory's block never had to grow
+    let mut ext2 = remount(&disk, ext2);
+    ext2.remove("/file7").unwrap();
+    ext2.create_file("/reused", 0o644).unwrap();
+    for index in (0..FREE_INODES).filter(|&index| index != 7) {
+        ext2.remove(&format!("/file{}", index)).unwrap();
+    }
+    ext2.remove("/reused").unwrap();
+    ext2.sync().unwrap();
+    assert_eq!(
+        counters(&disk),
+        [FREE_BLOCKS, FREE_INODES, FREE_BLOCKS, FREE_INODES, 1]
+    );
+}
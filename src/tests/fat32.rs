@@ -0,0 +1,241 @@
+use alloc::{string::String, vec, vec::Vec};
+use fat32::{Fat32, FatError};
+use kernel::BlockDevice;
+
+use super::SparseDisk;
+
+const SECTOR_SIZE: usize = 512;
+/// A little more than the 65525 clusters of the smallest FAT32 volume, a cluster is a sector.
+const SECTOR_COUNT: u64 = 68_000;
+const END_OF_CHAIN_MIN: u32 = 0x0FFF_FFF8;
+
+fn format() -> (SparseDisk, Fat32<SparseDisk>) {
+    let disk = SparseDisk::new(SECTOR_COUNT);
+    let fat = Fat32::format(disk.clone(), "TEST").expect("Failed to format the volume");
+    (disk, fat)
+}
+
+/// Syncs and drops the volume, then mounts the disk again.
+fn remount(disk: &SparseDisk, mut fat: Fat32<SparseDisk>) -> Fat32<SparseDisk> {
+    fat.sync().unwrap();
+    drop(fat);
+    Fat32::mount(disk.clone()).expect("Failed to mount the volume again")
+}
+
+/// Returns data which differs in every cluster, so misplaced clusters are noticed.
+fn pattern(length: usize) -> Vec<u8> {
+    (0..length)
+        .map(|index| (index * 7 + index / 509) as u8)
+        .collect()
+}
+
+fn names(fat: &mut Fat32<SparseDisk>, path: &str) -> Vec<String> {
+    let mut names: Vec<String> = fat
+        .read_dir(path)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    names.sort();
+    names
+}
+
+fn read_sector(disk: &SparseDisk, lba: u64) -> Vec<u8> {
+    let mut sector = vec![0; SECTOR_SIZE];
+    disk.clone().read_blocks(lba, &mut sector).unwrap();
+    sector
+}
+
+/// Returns the first sectors of the FATs and of the data region from the boot sector.
+fn layout(disk: &SparseDisk) -> (u64, u64, u64) {
+    let boot_sector = read_sector(disk, 0);
+    let reserved_sectors = u16::from_le_bytes([boot_sector[14], boot_sector[15]]) as u64;
+    let fat_size = u32::from_le_bytes(boot_sector[36..40].try_into().unwrap()) as u64;
+    (
+        reserved_sectors,
+        reserved_sectors + fat_size,
+        reserved_sectors + 2 * fat_size,
+    )
+}
+
+/// Returns the short entry with the 8.3 name from the first cluster of the root directory.
+fn root_short_entry(disk: &SparseDisk, short_name: &[u8; 11]) -> Option<Vec<u8>> {
+    let (_, _, data_start) = layout(disk);
+    read_sector(disk, data_start)
+        .chunks(32)
+        .find(|entry| entry[..11] == *short_name)
+        .map(|entry| entry.to_vec())
+}
+
+/// Follows the cluster chain in the FAT starting at `fat_start`.
+fn cluster_chain(disk: &SparseDisk, fat_start: u64, first_cluster: u32) -> Vec<u32> {
+    let mut chain = vec![first_cluster];
+    loop {
+        let cluster = *chain.last().unwrap() as u64;
+        let sector = read_sector(disk, fat_start + cluster * 4 / SECTOR_SIZE as u64);
+        let offset = (cluster * 4) as usize % SECTOR_SIZE;
+        let next = u32::from_le_bytes(sector[offset..offset + 4].try_into().unwrap()) & 0x0FFF_FFFF;
+        if next >= END_OF_CHAIN_MIN {
+            return chain;
+        }
+        assert!(chain.len() < 1024, "The cluster chain has a loop");
+        chain.push(next);
+    }
+}
+
+#[test_case]
+fn formats_an_empty_volume() {
+    let (_, mut fat) = format();
+    assert_eq!(fat.cluster_size(), SECTOR_SIZE);
+    assert!(fat.read_dir("/").unwrap().is_empty());
+    let free_space = fat.free_space().unwrap();
+    assert!(free_space >= 65524 * SECTOR_SIZE as u64);
+
+    assert_eq!(
+        Fat32::format(SparseDisk::new(60_000), "TEST").err(),
+        Some(FatError::VolumeTooSmall)
+    );
+}
+
+#[test_case]
+fn writes_files_across_clusters() {
+    let (disk, mut fat) = format();
+    let free_space = fat.free_space().unwrap();
+    let data = pattern(3 * SECTOR_SIZE + 100);
+    fat.create_file("/Long File Name.txt").unwrap();
+    assert_eq!(fat.write("/Long File Name.txt", 0, &data), Ok(data.len()));
+    assert_eq!(fat.read_file("/long file name.TXT"), Ok(data.clone()));
+    assert_eq!(
+        fat.free_space().unwrap(),
+        free_space - 4 * SECTOR_SIZE as u64
+    );
+
+    // a write over the boundary of the first two clusters
+    let patch = [0xAB; 20];
+    let offset = SECTOR_SIZE - 10;
+    fat.write("/Long File Name.txt", offset, &patch).unwrap();
+    let mut buffer = [0; 40];
+    assert_eq!(
+        fat.read("/Long File Name.txt", offset - 10, &mut buffer),
+        Ok(40)
+    );
+    assert_eq!(buffer[..10], data[offset - 10..offset]);
+    assert_eq!(buffer[10..30], patch);
+    assert_eq!(buffer[30..], data[offset + 20..offset + 30]);
+
+    // appending past the end fills the gap with zeros
+    fat.write("/Long File Name.txt", data.len() + SECTOR_SIZE, b"end")
+        .unwrap();
+    let file = fat.read_file("/Long File Name.txt").unwrap();
+    assert_eq!(file.len(), data.len() + SECTOR_SIZE + 3);
+    assert!(file[data.len()..data.len() + SECTOR_SIZE]
+        .iter()
+        .all(|&byte| byte == 0));
+
+    let mut fat = remount(&disk, fat);
+    assert_eq!(fat.read_file("/Long File Name.txt"), Ok(file));
+}
+
+#[test_case]
+fn keeps_chains_and_long_names_after_remounting() {
+    let (disk, mut fat) = format();
+    fat.create_directory("/Some Directory").unwrap();
+    fat.create_file("/Some Directory/notes about things.txt")
+        .unwrap();
+    fat.create_file("/README.TXT").unwrap();
+    let data = pattern(5 * SECTOR_SIZE);
+    fat.write("/README.TXT", 0, &data).unwrap();
+    fat.write("/Some Directory/notes about things.txt", 0, &data[..700])
+        .unwrap();
+    let free_space = fat.free_space().unwrap();
+
+    let mut fat = remount(&disk, fat);
+    assert_eq!(names(&mut fat, "/"), ["README.TXT", "Some Directory"]);
+    assert_eq!(
+        names(&mut fat, "/some directory"),
+        ["notes about things.txt"]
+    );
+    assert!(fat.metadata("/Some Directory").unwrap().is_directory);
+    assert_eq!(fat.read_file("/README.TXT"), Ok(data.clone()));
+    assert_eq!(
+        fat.read_file("/Some Directory/notes about things.txt"),
+        Ok(data[..700].to_vec())
+    );
+    // the free cluster count is kept in the FSInfo sector
+    assert_eq!(fat.free_space(), Ok(free_space));
+
+    // the file's chain holds a cluster per sector of data and is the same in both FATs
+    let entry = root_short_entry(&disk, b"README  TXT").unwrap();
+    let first_cluster = u16::from_le_bytes([entry[26], entry[27]]) as u32
+        | (u16::from_le_bytes([entry[20], entry[21]]) as u32) << 16;
+    let (first_fat, second_fat, _) = layout(&disk);
+    let chain = cluster_chain(&disk, first_fat, first_cluster);
+    assert_eq!(chain.len(), 5);
+    assert_eq!(cluster_chain(&disk, second_fat, first_cluster), chain);
+}
+
+#[test_case]
+fn generates_short_names() {
+    let (disk, mut fat) = format();
+    for name in [
+        "README.TXT",
+        "readme.txt.bak",
+        "Long File Name.txt",
+        "long file name 2.txt",
+    ] {
+        fat.create_file(name).unwrap();
+    }
+    // names which fit 8.3 in upper case are only stored as short names
+    assert!(root_short_entry(&disk, b"README  TXT").is_some());
+    assert!(root_short_entry(&disk, b"README~1BAK").is_some());
+    assert!(root_short_entry(&disk, b"LONGFI~1TXT").is_some());
+    assert!(root_short_entry(&disk, b"LONGFI~2TXT").is_some());
+
+    // the case is only ignored when looking names up
+    assert_eq!(fat.create_file("/readme.txt"), Err(FatError::AlreadyExists));
+    assert_eq!(fat.create_file("/a:b"), Err(FatError::InvalidName));
+}
+
+#[test_case]
+fn renames_and_removes_files() {
+    let (disk, mut fat) = format();
+    let free_space = fat.free_space().unwrap();
+    let data = pattern(2 * SECTOR_SIZE + 1);
+    fat.create_directory("/archive").unwrap();
+    fat.create_file("/draft.txt").unwrap();
+    fat.write("/draft.txt", 0, &data).unwrap();
+
+    fat.rename("/draft.txt", "/A Much Longer Name.txt").unwrap();
+    assert_eq!(names(&mut fat, "/"), ["A Much Longer Name.txt", "archive"]);
+    fat.rename("/A Much Longer Name.txt", "/archive/final.txt")
+        .unwrap();
+    assert_eq!(
+        fat.metadata("/A Much Longer Name.txt").err(),
+        Some(FatError::NotFound)
+    );
+    assert_eq!(fat.read_file("/archive/final.txt"), Ok(data.clone()));
+
+    // directories move with their content
+    fat.create_directory("/old").unwrap();
+    fat.rename("/archive", "/old/archive").unwrap();
+    assert_eq!(
+        fat.rename("/old", "/old/archive/old"),
+        Err(FatError::InvalidName)
+    );
+    assert_eq!(fat.read_file("/old/archive/final.txt"), Ok(data));
+    assert_eq!(fat.read_dir("/old/archive/..").unwrap().len(), 1);
+
+    assert_eq!(fat.remove("/old"), Err(FatError::DirectoryNotEmpty));
+    fat.truncate("/old/archive/final.txt", 10).unwrap();
+    assert_eq!(fat.metadata("/old/archive/final.txt").unwrap().size, 10);
+    fat.remove("/old/archive/final.txt").unwrap();
+    fat.remove("/old/archive").unwrap();
+    fat.remove("/old").unwrap();
+    assert!(fat.read_dir("/").unwrap().is_empty());
+    // every cluster was freed
+    assert_eq!(fat.free_space(), Ok(free_space));
+
+    let mut fat = remount(&disk, fat);
+    assert!(fat.read_dir("/").unwrap().is_empty());
+    assert_eq!(fat.free_space(), Ok(free_space));
+}
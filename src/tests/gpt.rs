@@ -0,0 +1,142 @@
+use alloc::vec;
+use ata::{gpt, Guid, PartitionIOError, SECTOR_SIZE};
+use kernel::{BlockDevice, RamDisk};
+use utils::crc32::crc32;
+
+/// A 2 MiB disk, room for the tables and a partition at the first aligned sector.
+const SECTOR_COUNT: u64 = 4096;
+const ENTRY_COUNT: usize = 128;
+const ENTRY_SIZE: usize = 128;
+
+fn disk_with_partition() -> RamDisk {
+    let mut disk = RamDisk::new(SECTOR_SIZE, SECTOR_COUNT);
+    gpt::create_table(&mut disk).unwrap();
+    gpt::create_partition(&mut disk, 1024, Guid::LINUX_FILESYSTEM, "root").unwrap();
+    disk
+}
+
+fn read_sector(disk: &mut RamDisk, lba: u64) -> [u8; SECTOR_SIZE] {
+    let mut sector = [0; SECTOR_SIZE];
+    disk.read_blocks(lba, &mut sector).unwrap();
+    sector
+}
+
+/// Changes the first entry of the primary table and updates the checksums.
+fn patch_primary_entry(disk: &mut RamDisk, patch: impl FnOnce(&mut [u8])) {
+    let mut header = read_sector(disk, 1);
+    let entries_lba = u64::from_le_bytes(header[72..80].try_into().unwrap());
+    let mut entries = vec![0; ENTRY_COUNT * ENTRY_SIZE];
+    disk.read_blocks(entries_lba, &mut entries).unwrap();
+    patch(&mut entries[..ENTRY_SIZE]);
+    disk.write_blocks(entries_lba, &entries).unwrap();
+
+    header[88..92].copy_from_slice(&crc32(&entries).to_le_bytes());
+    header[16..20].fill(0);
+    let header_crc = crc32(&header[..92]);
+    header[16..20].copy_from_slice(&header_crc.to_le_bytes());
+    disk.write_blocks(1, &header).unwrap();
+}
+
+#[test_case]
+fn crc32_matches_the_check_value() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test_case]
+fn reads_created_partitions() {
+    let mut disk = disk_with_partition();
+    let mbr = read_sector(&mut disk, 0);
+    assert_eq!((mbr[450], mbr[510], mbr[511]), (0xEE, 0x55, 0xAA));
+    let partitions = gpt::read_partitions(&mut disk).unwrap();
+    assert_eq!(partitions.len(), 1);
+    assert_eq!(
+        (partitions[0].start_lba, partitions[0].sectors),
+        (2048, 1024)
+    );
+    let info = partitions[0].gpt.as_ref().unwrap();
+    assert_eq!(info.type_guid, Guid::LINUX_FILESYSTEM);
+    assert_eq!(info.name, "root");
+    assert_eq!(
+        gpt::create_partition(&mut disk, SECTOR_COUNT, Guid::BASIC_DATA, "big"),
+        Err(PartitionIOError::NotEnoughSpace)
+    );
+}
+
+#[test_case]
+fn falls_back_to_the_backup_table() {
+    let mut disk = disk_with_partition();
+    let mut header = read_sector(&mut disk, 1);
+    header[40] ^= 0xFF;
+    disk.write_blocks(1, &header).unwrap();
+    assert_eq!(gpt::read_partitions(&mut disk).unwrap().len(), 1);
+
+    let mut backup = read_sector(&mut disk, SECTOR_COUNT - 1);
+    backup[0] = 0;
+    disk.write_blocks(SECTOR_COUNT - 1, &backup).unwrap();
+    assert_eq!(
+        gpt::read_partitions(&mut disk),
+        Err(PartitionIOError::InvalidPartitionTable)
+    );
+}
+
+#[test_case]
+fn rejects_entries_with_a_corrupted_checksum() {
+    let mut disk = disk_with_partition();
+    // the backup table stays intact
+    let mut entries = read_sector(&mut disk, 2);
+    entries[ENTRY_SIZE - 1] ^= 0xFF;
+    disk.write_blocks(2, &entries).unwrap();
+    assert_eq!(gpt::read_partitions(&mut disk).unwrap().len(), 1);
+}
+
+#[test_case]
+fn skips_entries_with_an_invalid_range() {
+    let mut disk = disk_with_partition();
+    patch_primary_entry(&mut disk, |entry| {
+        entry[40..48].copy_from_slice(&u64::MAX.to_le_bytes())
+    });
+    assert!(gpt::read_partitions(&mut disk).unwrap().is_empty());
+
+    let mut disk = disk_with_partition();
+    patch_primary_entry(&mut disk, |entry| {
+        entry[40..48].copy_from_slice(&2000u64.to_le_bytes())
+    });
+    assert!(gpt::read_partitions(&mut disk).unwrap().is_empty());
+}
+
+#[test_case]
+fn skips_entries_outside_the_usable_sectors() {
+    // the first sector of the primary entry array
+    let mut disk = disk_with_partition();
+    patch_primary_entry(&mut disk, |entry| {
+        entry[32..40].copy_from_slice(&2u64.to_le_bytes())
+    });
+    assert!(gpt::read_partitions(&mut disk).unwrap().is_empty());
+
+    // the backup header
+    let mut disk = disk_with_partition();
+    patch_primary_entry(&mut disk, |entry| {
+        entry[40..48].copy_from_slice(&(SECTOR_COUNT - 1).to_le_bytes())
+    });
+    assert!(gpt::read_partitions(&mut disk).unwrap().is_empty());
+}
+
+#[test_case]
+fn refuses_to_write_tables_that_dont_fit() {
+    assert_eq!(
+        gpt::create_table(&mut RamDisk::new(SECTOR_SIZE, 0)),
+        Err(PartitionIOError::NotEnoughSpace)
+    );
+    // a copy of the first half of the disk, whose table still describes the whole disk
+    let mut disk = RamDisk::new(SECTOR_SIZE, SECTOR_COUNT);
+    gpt::create_table(&mut disk).unwrap();
+    let mut data = vec![0; SECTOR_SIZE * SECTOR_COUNT as usize / 2];
+    disk.read_blocks(0, &mut data).unwrap();
+    let mut shrunk = RamDisk::new(SECTOR_SIZE, SECTOR_COUNT / 2);
+    shrunk.write_blocks(0, &data).unwrap();
+    assert_eq!(
+        gpt::create_partition(&mut shrunk, 1, Guid::BASIC_DATA, "small"),
+        Err(PartitionIOError::InvalidPartitionTable)
+    );
+}
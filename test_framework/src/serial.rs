@@ -32,6 +32,17 @@ pub fn __print(args: ::core::fmt::Arguments) {
     });
 }
 
+/// Runs the function while holding the lock of the serial port `serial_print` writes to, with
+/// interrupts disabled, so nothing is printed in between what the function sends to the port.
+pub fn with_lock<R>(f: impl FnOnce() -> R) -> R {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let _serial = SERIAL1.lock();
+        f()
+    })
+}
+
 lazy_static! {
     static ref SERIAL1: Mutex<SerialPort> = {
         let mut serial_port = unsafe { SerialPort::new(0x3F8) };
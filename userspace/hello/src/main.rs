@@ -0,0 +1,17 @@
+#![no_std] // no standard library
+#![no_main]
+extern crate alloc;
+
+use alloc::vec::Vec;
+use rost_lib::{entry_point, println, syscalls::get_process_id};
+
+entry_point!(main);
+
+fn main() {
+    let squares: Vec<u64> = (1..=5).map(|n| n * n).collect();
+    println!(
+        "Hello from process {}! Squares from the heap: {:?}",
+        get_process_id(),
+        squares
+    );
+}
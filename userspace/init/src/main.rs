@@ -0,0 +1,40 @@
+#![no_std] // no standard library
+#![no_main]
+
+use rost_lib::{
+    entry_point, fs, print, println,
+    syscalls::{exec, exit, fork, get_process_id, shutdown, wait_pid},
+};
+
+entry_point!(main);
+
+/// The programs started by init, one after the other.
+const PROGRAMS: &[&str] = &["/bin/hello"];
+
+/// The greeting printed when the system is up.
+const MOTD: &str = "/etc/motd";
+
+fn main() {
+    println!("init running as process {}", get_process_id());
+    if let Ok(motd) = fs::read(MOTD) {
+        print!("{}", core::str::from_utf8(&motd).unwrap_or_default());
+    }
+    for program in PROGRAMS {
+        match fork() {
+            Ok(0) => {
+                let error = exec(program);
+                println!("Failed to start {}: {:?}", program, error);
+                exit(1);
+            }
+            Ok(child) => match wait_pid(child) {
+                Ok((child, code)) => {
+                    println!("{} (process {}) exited with code {}", program, child, code)
+                }
+                Err(error) => println!("Failed to wait for {}: {:?}", program, error),
+            },
+            Err(error) => println!("Failed to fork: {:?}", error),
+        }
+    }
+    println!("Shutting down");
+    shutdown();
+}
@@ -35,6 +35,10 @@ impl<'a> ByteReader<'a> {
         value
     }
 
+    pub fn read_u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.read_slice::<8>())
+    }
+
     pub fn read_enum_u8<T>(&mut self) -> T
     where
         T: From<u8>,
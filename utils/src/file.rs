@@ -0,0 +1,177 @@
+//! The types of the file system calls, shared by the kernel and user mode programs.
+use bitflags::bitflags;
+
+bitflags! {
+    /// The flags passed to the `Open` system call.
+    pub struct OpenFlags: u64 {
+        /// The file can be read.
+        const READ = 1 << 0;
+        /// The file can be written.
+        const WRITE = 1 << 1;
+        /// Creates a regular file if nothing exists at the path.
+        const CREATE = 1 << 2;
+        /// Together with `CREATE`, fails if something already exists at the path.
+        const EXCLUSIVE = 1 << 3;
+        /// Truncates a regular file to 0 bytes, needs `WRITE`.
+        const TRUNCATE = 1 << 4;
+        /// Every write appends to the end of the file, regardless of the offset.
+        const APPEND = 1 << 5;
+        /// Fails if the path doesn't lead to a directory.
+        const DIRECTORY = 1 << 6;
+        /// Fails if the path ends in a symlink instead of following it.
+        const NO_FOLLOW = 1 << 7;
+    }
+}
+
+/// The kind of a file system object.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Unknown = 0,
+    File = 1,
+    Directory = 2,
+    Symlink = 3,
+    CharacterDevice = 4,
+    BlockDevice = 5,
+    Fifo = 6,
+    Socket = 7,
+}
+
+impl From<u8> for FileType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => FileType::File,
+            2 => FileType::Directory,
+            3 => FileType::Symlink,
+            4 => FileType::CharacterDevice,
+            5 => FileType::BlockDevice,
+            6 => FileType::Fifo,
+            7 => FileType::Socket,
+            _ => FileType::Unknown,
+        }
+    }
+}
+
+/// The metadata of a file, written by the `Stat` system call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    /// The number of the inode, unique within its file system. 0 if the file system has no inode numbers.
+    pub inode: u64,
+    pub file_type: FileType,
+    /// The permission bits, including setuid, setgid and sticky.
+    pub permissions: u16,
+    /// The number of directory entries referring to the inode.
+    pub links: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// The access, modification and inode change times in seconds since the Unix epoch.
+    pub accessed: u64,
+    pub modified: u64,
+    pub changed: u64,
+}
+
+impl FileStat {
+    /// Returns the metadata of an object without owner and times, file systems fill in what they know.
+    pub fn new(inode: u64, file_type: FileType, permissions: u16, size: u64) -> Self {
+        FileStat {
+            inode,
+            file_type,
+            permissions,
+            links: 1,
+            uid: 0,
+            gid: 0,
+            size,
+            accessed: 0,
+            modified: 0,
+            changed: 0,
+        }
+    }
+}
+
+/// The position the offset passed to the `Seek` system call is relative to.
+#[repr(u64)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekOrigin {
+    Start = 0,
+    Current = 1,
+    End = 2,
+}
+
+impl TryFrom<u64> for SeekOrigin {
+    type Error = ();
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SeekOrigin::Start),
+            1 => Ok(SeekOrigin::Current),
+            2 => Ok(SeekOrigin::End),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The size of the header of a record written by the `ReadDir` system call: the inode (`u64`),
+/// the length of the record (`u16`), the length of the name (`u16`) and the file type (`u8`).
+const RECORD_HEADER_SIZE: usize = 13;
+
+/// Records start at multiples of this alignment.
+const RECORD_ALIGNMENT: usize = 8;
+
+/// An entry of a directory, as written by the `ReadDir` system call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirectoryRecord<'a> {
+    pub inode: u64,
+    pub file_type: FileType,
+    pub name: &'a str,
+}
+
+impl<'a> DirectoryRecord<'a> {
+    /// Returns the number of bytes the record takes in a buffer.
+    pub fn length(&self) -> usize {
+        (RECORD_HEADER_SIZE + self.name.len() + RECORD_ALIGNMENT - 1) & !(RECORD_ALIGNMENT - 1)
+    }
+
+    /// Writes the record to the start of the buffer, returns the number of bytes written
+    /// or `None` if the buffer is too small.
+    pub fn write_to(&self, buffer: &mut [u8]) -> Option<usize> {
+        let length = self.length();
+        if buffer.len() < length || length > u16::MAX as usize {
+            return None;
+        }
+        buffer[0..8].copy_from_slice(&self.inode.to_le_bytes());
+        buffer[8..10].copy_from_slice(&(length as u16).to_le_bytes());
+        buffer[10..12].copy_from_slice(&(self.name.len() as u16).to_le_bytes());
+        buffer[12] = self.file_type as u8;
+        buffer[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + self.name.len()]
+            .copy_from_slice(self.name.as_bytes());
+        buffer[RECORD_HEADER_SIZE + self.name.len()..length].fill(0);
+        Some(length)
+    }
+
+    /// Reads the record at the start of the buffer, returns it and its length
+    /// or `None` if the buffer doesn't start with a valid record.
+    pub fn read_from(buffer: &'a [u8]) -> Option<(Self, usize)> {
+        if buffer.len() < RECORD_HEADER_SIZE {
+            return None;
+        }
+        let inode = u64::from_le_bytes(buffer[0..8].try_into().ok()?);
+        let length = u16::from_le_bytes([buffer[8], buffer[9]]) as usize;
+        let name_length = u16::from_le_bytes([buffer[10], buffer[11]]) as usize;
+        if length > buffer.len() || RECORD_HEADER_SIZE + name_length > length {
+            return None;
+        }
+        let name =
+            core::str::from_utf8(&buffer[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + name_length])
+                .ok()?;
+        Some((
+            DirectoryRecord {
+                inode,
+                file_type: FileType::from(buffer[12]),
+                name,
+            },
+            length,
+        ))
+    }
+}
@@ -0,0 +1,28 @@
+//! The commands of the `Ioctl` system call, grouped by the kind of device understanding them.
+//!
+//! Devices answer commands they don't understand with `NotSupported`.
+
+/// Returns the size of a block of a block device in bytes.
+pub const BLOCK_SIZE: u64 = 0x100;
+/// Returns the number of blocks of a block device.
+pub const BLOCK_COUNT: u64 = 0x101;
+/// Makes a block device store the written blocks, returns `0`.
+pub const BLOCK_FLUSH: u64 = 0x102;
+
+/// Returns the width of a framebuffer in pixels.
+pub const FRAMEBUFFER_WIDTH: u64 = 0x200;
+/// Returns the height of a framebuffer in pixels.
+pub const FRAMEBUFFER_HEIGHT: u64 = 0x201;
+/// Returns the number of pixels between the starts of two lines of a framebuffer, at least its width.
+pub const FRAMEBUFFER_STRIDE: u64 = 0x202;
+/// Returns the size of a pixel of a framebuffer in bytes.
+pub const FRAMEBUFFER_BYTES_PER_PIXEL: u64 = 0x203;
+/// Returns the pixel format of a framebuffer, one of the `PIXEL_FORMAT` constants.
+pub const FRAMEBUFFER_PIXEL_FORMAT: u64 = 0x204;
+
+/// Red, green and blue bytes, followed by padding up to the size of a pixel.
+pub const PIXEL_FORMAT_RGB: u64 = 0;
+/// Blue, green and red bytes, followed by padding up to the size of a pixel.
+pub const PIXEL_FORMAT_BGR: u64 = 1;
+/// A single grayscale byte, followed by padding up to the size of a pixel.
+pub const PIXEL_FORMAT_U8: u64 = 2;
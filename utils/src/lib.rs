@@ -10,9 +10,13 @@ extern crate alloc;
 pub mod array_combiner;
 pub mod byte_reader;
 pub mod constants;
+pub mod crc32;
+pub mod file;
+pub mod ioctl;
 use crate::constants::{GIB, KIB, MIB};
 pub mod port_extensions;
 pub mod static_stack;
+pub mod syscall_error;
 pub mod syscall_name;
 
 /// Formats the size in bytes to a human readable string.
@@ -0,0 +1,94 @@
+/// The errors returned by system calls.
+///
+/// They are returned in RAX as their negated value, so the values from `-4095` to `-1` are errors
+/// and all the others are successful results.
+#[repr(u64)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysCallError {
+    /// There is no system call with the passed number.
+    UnknownSysCall = 1,
+    /// One of the arguments is not valid for this system call.
+    InvalidArgument = 2,
+    /// A passed pointer doesn't point to memory owned by the process.
+    InvalidAddress = 3,
+    /// The kernel ran out of memory while handling the system call.
+    OutOfMemory = 4,
+    /// There is no file or executable at the passed path.
+    NotFound = 5,
+    /// The process has no child process matching the passed ID.
+    NoChildProcess = 6,
+    /// The passed file is not a valid executable.
+    InvalidExecutable = 7,
+    /// The passed file descriptor isn't open, or wasn't opened for the requested access.
+    BadFileDescriptor = 8,
+    /// Something already exists at the passed path.
+    AlreadyExists = 9,
+    /// A component of the passed path is not a directory.
+    NotADirectory = 10,
+    /// The operation doesn't work on directories.
+    IsADirectory = 11,
+    /// The directory can't be removed because it still has entries.
+    DirectoryNotEmpty = 12,
+    /// The file system has no space left.
+    NoSpace = 13,
+    /// The file would grow beyond the size the file system supports.
+    FileTooLarge = 14,
+    /// The file system is mounted read-only.
+    ReadOnly = 15,
+    /// The file or file system doesn't support the operation.
+    NotSupported = 16,
+    /// Resolving the passed path followed too many symlinks.
+    TooManySymlinks = 17,
+    /// The process has no free file descriptor left.
+    TooManyOpenFiles = 18,
+    /// The device holding the file failed.
+    IoError = 19,
+    Unknown = 4095,
+}
+
+/// The lowest value of RAX that represents an error.
+const FIRST_ERROR_VALUE: u64 = -4095i64 as u64;
+
+impl From<u64> for SysCallError {
+    fn from(value: u64) -> Self {
+        match value {
+            1 => SysCallError::UnknownSysCall,
+            2 => SysCallError::InvalidArgument,
+            3 => SysCallError::InvalidAddress,
+            4 => SysCallError::OutOfMemory,
+            5 => SysCallError::NotFound,
+            6 => SysCallError::NoChildProcess,
+            7 => SysCallError::InvalidExecutable,
+            8 => SysCallError::BadFileDescriptor,
+            9 => SysCallError::AlreadyExists,
+            10 => SysCallError::NotADirectory,
+            11 => SysCallError::IsADirectory,
+            12 => SysCallError::DirectoryNotEmpty,
+            13 => SysCallError::NoSpace,
+            14 => SysCallError::FileTooLarge,
+            15 => SysCallError::ReadOnly,
+            16 => SysCallError::NotSupported,
+            17 => SysCallError::TooManySymlinks,
+            18 => SysCallError::TooManyOpenFiles,
+            19 => SysCallError::IoError,
+            _ => SysCallError::Unknown,
+        }
+    }
+}
+
+/// Encodes the result of a system call into the value returned in RAX.
+pub fn encode_result(result: Result<u64, SysCallError>) -> u64 {
+    match result {
+        Ok(value) => value,
+        Err(error) => (error as u64).wrapping_neg(),
+    }
+}
+
+/// Decodes the value returned in RAX by a system call.
+pub fn decode_result(value: u64) -> Result<u64, SysCallError> {
+    if value >= FIRST_ERROR_VALUE {
+        Err(SysCallError::from(value.wrapping_neg()))
+    } else {
+        Ok(value)
+    }
+}
@@ -1,6 +1,77 @@
+/// The numbers of the system calls, passed in RAX.
+///
+/// The arguments are passed in RDI, RSI, RDX, R10, R8 and R9 and the result is returned in RAX,
+/// encoded with `syscall_error::encode_result`.
 #[repr(u64)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SysCallName {
-    FirstProcess = 0,
-    SecondProcess = 1,
+    /// Writes a UTF-8 string to the console.
+    ///
+    /// `(buffer: *const u8, length: usize) -> written bytes`
+    ConsoleWrite = 0,
+    /// Returns the ID of the calling process.
+    ///
+    /// `() -> process ID`
+    GetProcessId = 1,
+    /// Terminates the calling process, never returns.
+    ///
+    /// `(exit code: i32) -> !`
+    Exit = 2,
+    /// Moves the end of the heap of the calling process, the new pages are mapped when they are accessed.
+    /// Passing `0` only returns the current end.
+    ///
+    /// `(new end: *const u8) -> end of the heap`
+    Brk = 3,
+    /// Creates a copy of the calling process, sharing its memory copy-on-write.
+    /// The parent gets the ID of the child and the child gets `0`.
+    ///
+    /// `() -> process ID of the child or 0`
+    Fork = 4,
+    /// Replaces the program of the calling process with the passed executable, only returns on error.
+    ///
+    /// `(path: *const u8, length: usize) -> !`
+    Exec = 5,
+    /// Waits until the passed child process, or any child process if `0` is passed, exited
+    /// and frees it. The exit code of the child is written to the passed pointer, if it's not null.
+    ///
+    /// `(process ID: u64, exit code: *mut i32) -> process ID of the child`
+    WaitPid = 6,
+    /// Opens the file or directory at the passed path, `permissions` are used if the file is created.
+    ///
+    /// `(path: *const u8, length: usize, flags: OpenFlags, permissions: u16) -> file descriptor`
+    Open = 7,
+    /// Reads from the file at its offset and advances the offset, returns `0` at the end of the file.
+    ///
+    /// `(file descriptor: u64, buffer: *mut u8, length: usize) -> read bytes`
+    Read = 8,
+    /// Writes to the file at its offset, or at its end if it was opened with `APPEND`, and advances the offset.
+    ///
+    /// `(file descriptor: u64, buffer: *const u8, length: usize) -> written bytes`
+    Write = 9,
+    /// Moves the offset of the file, relative to its start, the current offset or its end.
+    ///
+    /// `(file descriptor: u64, offset: i64, origin: SeekOrigin) -> new offset`
+    Seek = 10,
+    /// Closes the file descriptor, the file stays open while other descriptors refer to it.
+    ///
+    /// `(file descriptor: u64) -> 0`
+    Close = 11,
+    /// Writes the metadata of the file at the passed path, following symlinks.
+    ///
+    /// `(path: *const u8, length: usize, stat: *mut FileStat) -> 0`
+    Stat = 12,
+    /// Writes as many entries of the open directory as fit into the buffer, encoded as `DirectoryRecord`s,
+    /// and advances the offset past them. Returns `0` once all entries were read.
+    ///
+    /// `(file descriptor: u64, buffer: *mut u8, length: usize) -> written bytes`
+    ReadDir = 13,
+    /// Sends a device specific command, one of `ioctl`'s constants, to the device behind the open file.
+    /// The argument is a plain value, the meaning of the result depends on the command.
+    ///
+    /// `(file descriptor: u64, command: u64, argument: u64) -> result`
+    Ioctl = 14,
+    /// Writes the cached data back to the disks and halts the machine, never returns.
+    ///
+    /// `() -> !`
+    Shutdown = 15,
 }